@@ -0,0 +1,81 @@
+/*
+ * C API of irpc-schema, built with the `ffi` feature.
+ *
+ * Build the library with
+ *
+ *     cargo rustc --release --no-default-features --features ffi --crate-type cdylib
+ *
+ * or `--crate-type staticlib` for a static library, and link against
+ * `target/release/libirpc_schema.so` (or `.dylib`, `.dll`, `.a`).
+ *
+ * Handles returned by `irpc_schema_parse` and `irpc_manifest_decode` are
+ * owned by the caller and must be released exactly once with
+ * `irpc_schema_free` and `irpc_manifest_free`, not with `free`. Text is
+ * written into caller provided buffers in the style of `snprintf`. Functions
+ * return a negative value or NULL on invalid arguments.
+ *
+ * Keep in sync with src/ffi.rs.
+ */
+#ifndef IRPC_SCHEMA_H
+#define IRPC_SCHEMA_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An opaque handle to a schema. */
+typedef struct IrpcSchema IrpcSchema;
+
+/* An opaque handle to a manifest. */
+typedef struct IrpcSchemaManifest IrpcSchemaManifest;
+
+/* Parses a schema from its text form, returning NULL on failure. */
+IrpcSchema *irpc_schema_parse(const char *text);
+
+/* Releases a schema. NULL is ignored. */
+void irpc_schema_free(IrpcSchema *schema);
+
+/* Writes the 32 byte stable hash of a schema to `out`. Returns 0 on success. */
+int irpc_schema_hash(const IrpcSchema *schema, uint8_t *out);
+
+/*
+ * Pretty prints a schema into `buf`, writing nodes of up to
+ * `max_inline_width` characters on a single line.
+ */
+ptrdiff_t irpc_schema_pretty_print(const IrpcSchema *schema, size_t max_inline_width,
+                                   char *buf, size_t len);
+
+/* Writes the changes from `old` to `new` into `buf`, one per line. */
+ptrdiff_t irpc_schema_diff(const IrpcSchema *old, const IrpcSchema *new_, char *buf,
+                           size_t len);
+
+/*
+ * Classifies the changes from `old` to `new`: 0 for compatible, 1 for
+ * migratable and 2 for breaking.
+ */
+int irpc_schema_compat(const IrpcSchema *old, const IrpcSchema *new_);
+
+/* Decodes a postcard encoded manifest, returning NULL on failure. */
+IrpcSchemaManifest *irpc_manifest_decode(const uint8_t *bytes, size_t len);
+
+/* Releases a manifest. NULL is ignored. */
+void irpc_manifest_free(IrpcSchemaManifest *manifest);
+
+/* Writes the 32 byte hash of the whole manifest to `out`. Returns 0 on success. */
+int irpc_manifest_hash(const IrpcSchemaManifest *manifest, uint8_t *out);
+
+/*
+ * Looks up a message by its 32 byte schema hash, and writes its name into
+ * `buf`. Returns -1 if the hash is not part of the manifest.
+ */
+ptrdiff_t irpc_manifest_lookup(const IrpcSchemaManifest *manifest, const uint8_t *hash,
+                               char *buf, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IRPC_SCHEMA_H */
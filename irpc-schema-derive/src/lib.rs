@@ -7,59 +7,484 @@ use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemEnum, Meta};
 // The attribute macro for schema generation
 #[proc_macro_attribute]
 pub fn schema(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(item as DeriveInput);
+    let mut input = parse_macro_input!(item as DeriveInput);
     let name = &input.ident;
 
     // Parse the attribute to extract schema type and optional name
     let attr_meta = parse_macro_input!(attr as Meta);
-    let (schema_type, explicit_name) = match attr_meta {
-        Meta::Path(path) => {
-            let schema_type = path.get_ident().unwrap().to_string();
-            (schema_type, None)
-        }
-        Meta::List(list) => {
-            let schema_type = list.path.get_ident().unwrap().to_string();
-            let mut explicit_name = None;
-
-            // Parse the nested meta items
-            for nested in list.nested.iter() {
-                match nested {
-                    syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => {
-                        if let syn::Lit::Str(lit_str) = &nv.lit {
-                            explicit_name = Some(lit_str.value());
-                        } else {
-                            panic!("Expected string literal for name parameter");
+    let (schema_type, explicit_name, pinned_hash, legacy_enum, enumeration, naming) =
+        match attr_meta {
+            Meta::Path(path) => {
+                let schema_type = path.get_ident().unwrap().to_string();
+                (schema_type, None, None, false, false, None)
+            }
+            Meta::List(list) => {
+                let schema_type = list.path.get_ident().unwrap().to_string();
+                let mut explicit_name = None;
+                let mut pinned_hash = None;
+                let mut legacy_enum = false;
+                let mut enumeration = false;
+                let mut naming = None;
+
+                // Parse the nested meta items
+                for nested in list.nested.iter() {
+                    match nested {
+                        syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => {
+                            if let syn::Lit::Str(lit_str) = &nv.lit {
+                                explicit_name = Some(lit_str.value());
+                            } else {
+                                panic!("Expected string literal for name parameter");
+                            }
+                        }
+                        syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("hash") => {
+                            if let syn::Lit::Str(lit_str) = &nv.lit {
+                                pinned_hash = Some(parse_hash(&lit_str.value()));
+                            } else {
+                                panic!("Expected string literal for hash parameter");
+                            }
+                        }
+                        syn::NestedMeta::Meta(Meta::NameValue(nv))
+                            if nv.path.is_ident("naming") =>
+                        {
+                            if let syn::Lit::Str(lit_str) = &nv.lit {
+                                naming = Some(lit_str.value());
+                            } else {
+                                panic!("Expected string literal for naming parameter");
+                            }
+                        }
+                        syn::NestedMeta::Meta(Meta::Path(path)) if path.is_ident("legacy_enum") => {
+                            legacy_enum = true;
                         }
+                        syn::NestedMeta::Meta(Meta::Path(path)) if path.is_ident("enumeration") => {
+                            enumeration = true;
+                        }
+                        _ => panic!("Unsupported parameter in schema attribute"),
                     }
-                    _ => panic!("Unsupported parameter in schema attribute"),
                 }
+
+                (
+                    schema_type,
+                    explicit_name,
+                    pinned_hash,
+                    legacy_enum,
+                    enumeration,
+                    naming,
+                )
             }
+            _ => panic!("Unsupported attribute format"),
+        };
 
-            (schema_type, explicit_name)
+    if let Some(explicit_name) = &explicit_name {
+        if !is_valid_name(explicit_name) {
+            panic!(
+                "Invalid name {:?}: names must not be empty, start or end with whitespace, \
+                 or contain control characters, whitespace other than spaces, '\"' or '\\'",
+                explicit_name
+            );
         }
-        _ => panic!("Unsupported attribute format"),
-    };
-
+    }
+    let mut field_types = FieldTypes::default();
+    let tagging = serde_tagging(&input.attrs);
     let schema_impl = match schema_type.as_str() {
         "Atom" => generate_atom_schema(name, explicit_name.as_deref()),
-        "Structural" => generate_structural_schema(&input.data),
-        "Nominal" => generate_nominal_schema(name, &input.data, explicit_name.as_deref()),
+        "Structural" => generate_structural_schema(&input.data, tagging, &mut field_types),
+        "Nominal" if enumeration => {
+            generate_enumeration_schema(name, &input.data, explicit_name.as_deref(), tagging)
+        }
+        "Nominal" => generate_nominal_schema(
+            name,
+            &input.data,
+            explicit_name.as_deref(),
+            legacy_enum,
+            tagging,
+            &mut field_types,
+        ),
         _ => panic!("Unsupported schema type"),
     };
+    if legacy_enum && schema_type != "Nominal" {
+        panic!("legacy_enum is only supported for Nominal schemas");
+    }
+    if enumeration && (schema_type != "Nominal" || legacy_enum) {
+        panic!("enumeration is only supported for Nominal schemas without legacy_enum");
+    }
+    let naming_check = match naming {
+        Some(_) if schema_type == "Structural" => {
+            panic!("naming is only supported for Nominal and Atom schemas")
+        }
+        Some(pattern) => {
+            let name_text = explicit_name.clone().unwrap_or_else(|| name.to_string());
+            let invalid = format!("invalid naming pattern `{}`", pattern);
+            let mismatch = format!(
+                "type name `{}` does not match the naming pattern `{}`",
+                name_text, pattern
+            );
+            // the messages are format strings
+            let invalid = invalid.replace('{', "{{").replace('}', "}}");
+            let mismatch = mismatch.replace('{', "{{").replace('}', "}}");
+            quote! {
+                const _: () = {
+                    assert!(::irpc_schema::naming::is_valid_pattern(#pattern), #invalid);
+                    assert!(::irpc_schema::naming::matches(#pattern, #name_text), #mismatch);
+                };
+            }
+        }
+        None => quote! {},
+    };
+    let locals = field_types.locals();
+    let collect_docs = field_types.collect_docs();
+    let collect_docs = match schema_type.as_str() {
+        // structural types have no name to attach docs to
+        "Structural" => collect_docs,
+        _ => {
+            let name_text = explicit_name.clone().unwrap_or_else(|| name.to_string());
+            let item_docs = match schema_type.as_str() {
+                "Atom" => item_docs(&input.attrs, vec![]),
+                _ => item_docs(&input.attrs, member_docs(&input.data)),
+            };
+            quote! {
+                if docs.insert(#name_text, #item_docs) {
+                    #collect_docs
+                }
+            }
+        }
+    };
+    strip_field_attrs(&mut input.data);
+
+    // With a pinned hash, the hash is available as a constant, and checked
+    // against the schema when it is first built
+    let (init, const_hash_impl) = match pinned_hash {
+        Some(bytes) => {
+            let type_name = name.to_string();
+            (
+                quote! {
+                    let schema = #schema_impl;
+                    ::irpc_schema::const_hash::check_pinned(#type_name, &schema, &[#(#bytes),*]);
+                    schema
+                },
+                quote! {
+                    #[allow(deprecated)]
+                    impl ::irpc_schema::const_hash::ConstSchemaHash for #name {
+                        const SCHEMA_HASH: [u8; 32] = [#(#bytes),*];
+                    }
+                },
+            )
+        }
+        None => (schema_impl, quote! {}),
+    };
 
     let expanded = quote! {
         #input
 
+        #[allow(deprecated)]
         impl ::irpc_schema::HasSchema for #name {
             fn schema() -> ::irpc_schema::Schema {
-                #schema_impl
+                Self::static_schema().clone()
+            }
+
+            fn static_schema() -> &'static ::irpc_schema::Schema {
+                static SCHEMA: ::std::sync::OnceLock<::irpc_schema::Schema> =
+                    ::std::sync::OnceLock::new();
+                SCHEMA.get_or_init(|| {
+                    #locals
+                    #init
+                })
+            }
+
+            fn collect_docs(docs: &mut ::irpc_schema::docs::DocTable) {
+                #collect_docs
             }
         }
+
+        #const_hash_impl
+        #naming_check
     };
 
     TokenStream::from(expanded)
 }
 
+// Same as `irpc_schema::check::is_valid_name`
+fn is_valid_name(name: &str) -> bool {
+    let trimmed = name.trim();
+    !trimmed.is_empty()
+        && trimmed.len() == name.len()
+        && name
+            .chars()
+            .all(|c| !c.is_control() && (c == ' ' || !c.is_whitespace()) && c != '"' && c != '\\')
+}
+
+// The serde representation of an enum, from `#[serde(tag = "..")]`,
+// `#[serde(tag = "..", content = "..")]` or `#[serde(untagged)]`. `None` for
+// the default, externally tagged representation
+fn serde_tagging(attrs: &[syn::Attribute]) -> Option<proc_macro2::TokenStream> {
+    let mut tag = None;
+    let mut content = None;
+    let mut untagged = false;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            continue;
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("tag") => {
+                    if let syn::Lit::Str(lit_str) = &nv.lit {
+                        tag = Some(lit_str.value());
+                    }
+                }
+                syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("content") => {
+                    if let syn::Lit::Str(lit_str) = &nv.lit {
+                        content = Some(lit_str.value());
+                    }
+                }
+                syn::NestedMeta::Meta(Meta::Path(path)) if path.is_ident("untagged") => {
+                    untagged = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    match (tag, content) {
+        _ if untagged => Some(quote! { ::irpc_schema::Tagging::Untagged }),
+        (Some(tag), Some(content)) => Some(quote! {
+            ::irpc_schema::Tagging::Adjacent(#tag.to_string(), #content.to_string())
+        }),
+        (Some(tag), None) => Some(quote! { ::irpc_schema::Tagging::Internal(#tag.to_string()) }),
+        (None, _) => None,
+    }
+}
+
+// Wraps the schema of an enum in `Schema::Tagged`, unless it is externally
+// tagged
+fn with_tagging(
+    schema: proc_macro2::TokenStream,
+    tagging: Option<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    match tagging {
+        Some(tagging) => quote! { ::irpc_schema::Schema::Tagged(#tagging, Box::new(#schema)) },
+        None => schema,
+    }
+}
+
+// Parses a pinned schema hash given as 64 hex digits
+fn parse_hash(hex: &str) -> [u8; 32] {
+    if hex.len() != 64 || !hex.is_ascii() {
+        panic!("Expected 64 hex digits for hash parameter");
+    }
+    let mut res = [0u8; 32];
+    for (i, byte) in res.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+            .expect("Expected 64 hex digits for hash parameter");
+    }
+    res
+}
+
+// The field types of a schema, so each distinct type is looked up only once,
+// even if many fields share it
+#[derive(Default)]
+struct FieldTypes {
+    // the types, with their tokens as text for comparison
+    types: Vec<(String, syn::Type)>,
+}
+
+impl FieldTypes {
+    // An expression for the schema of a field, using a local shared by all
+    // fields of the same type. With `#[schema(with = "Type")]`, the schema of
+    // the given type is used instead of the schema of the field type
+    fn schema(&mut self, field: &syn::Field) -> proc_macro2::TokenStream {
+        let with = field_with(field);
+        let ty = with.as_ref().unwrap_or(&field.ty);
+        let key = quote!(#ty).to_string();
+        let index = match self.types.iter().position(|(k, _)| k == &key) {
+            Some(index) => index,
+            None => {
+                self.types.push((key, ty.clone()));
+                self.types.len() - 1
+            }
+        };
+        let local = Self::local(index);
+        quote! { #local.clone() }
+    }
+
+    // The definitions of the locals
+    fn locals(&self) -> proc_macro2::TokenStream {
+        let defs = self.types.iter().enumerate().map(|(index, (_, ty))| {
+            let local = Self::local(index);
+            quote! {
+                let #local: &'static ::irpc_schema::Schema =
+                    <#ty as ::irpc_schema::HasSchema>::static_schema();
+            }
+        });
+        quote! { #(#defs)* }
+    }
+
+    // Statements adding the docs of all field types to `docs`
+    fn collect_docs(&self) -> proc_macro2::TokenStream {
+        let types = self.types.iter().map(|(_, ty)| ty);
+        quote! {
+            #(<#types as ::irpc_schema::HasSchema>::collect_docs(docs);)*
+        }
+    }
+
+    fn local(index: usize) -> syn::Ident {
+        syn::Ident::new(
+            &format!("__field_schema_{}", index),
+            proc_macro2::Span::call_site(),
+        )
+    }
+}
+
+// The type from `#[schema(with = "Type")]` on a field, if any
+fn field_with(field: &syn::Field) -> Option<syn::Type> {
+    let mut res = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("schema"))
+    {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            panic!("Expected #[schema(with = \"Type\")] on field");
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("with") => {
+                    let syn::Lit::Str(lit_str) = &nv.lit else {
+                        panic!("Expected string literal for with parameter");
+                    };
+                    res = Some(lit_str.parse().expect("Expected a type for with parameter"));
+                }
+                _ => panic!("Unsupported parameter in field schema attribute"),
+            }
+        }
+    }
+    res
+}
+
+// The doc comment from `///` or `#[doc = ".."]` attributes, without the
+// comment markers
+fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(lit_str),
+                ..
+            })) => Some(lit_str.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+        .collect::<Vec<_>>();
+    let doc = lines.join("\n").trim().to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+// An expression for the `Deprecation` from a `#[deprecated]` attribute
+fn deprecation(attrs: &[syn::Attribute]) -> proc_macro2::TokenStream {
+    let Some(attr) = attrs.iter().find(|attr| attr.path.is_ident("deprecated")) else {
+        return quote! { None };
+    };
+    let (mut since, mut note) = (None, None);
+    match attr.parse_meta() {
+        Ok(Meta::Path(_)) => {}
+        Ok(Meta::NameValue(syn::MetaNameValue {
+            lit: syn::Lit::Str(lit_str),
+            ..
+        })) => note = Some(lit_str.value()),
+        Ok(Meta::List(list)) => {
+            for nested in list.nested {
+                if let syn::NestedMeta::Meta(Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit_str),
+                    ..
+                })) = nested
+                {
+                    if path.is_ident("since") {
+                        since = Some(lit_str.value());
+                    } else if path.is_ident("note") {
+                        note = Some(lit_str.value());
+                    }
+                }
+            }
+        }
+        _ => panic!("Unsupported deprecated attribute"),
+    }
+    let since = optional_string(since);
+    let note = optional_string(note);
+    quote! {
+        Some(::irpc_schema::docs::Deprecation { since: #since, note: #note })
+    }
+}
+
+fn optional_string(value: Option<String>) -> proc_macro2::TokenStream {
+    match value {
+        Some(value) => quote! { Some(#value.to_string()) },
+        None => quote! { None },
+    }
+}
+
+// An expression for the `ItemDocs` of an item with the given members
+fn item_docs(
+    attrs: &[syn::Attribute],
+    members: Vec<(String, proc_macro2::TokenStream)>,
+) -> proc_macro2::TokenStream {
+    let doc = optional_string(doc_comment(attrs));
+    let deprecated = deprecation(attrs);
+    let members = members
+        .into_iter()
+        .map(|(name, docs)| quote! { (#name.to_string(), #docs) });
+    quote! {
+        ::irpc_schema::docs::ItemDocs {
+            docs: ::irpc_schema::docs::Docs { doc: #doc, deprecated: #deprecated },
+            members: [#(#members),*].into_iter().collect(),
+        }
+    }
+}
+
+// The docs of fields, keyed by name or index
+fn field_docs(fields: &Fields) -> Vec<(String, proc_macro2::TokenStream)> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let name = match &field.ident {
+                Some(ident) => ident.to_string(),
+                None => index.to_string(),
+            };
+            (name, item_docs(&field.attrs, vec![]))
+        })
+        .collect()
+}
+
+// The docs of the fields of a struct or the variants of an enum
+fn member_docs(data: &syn::Data) -> Vec<(String, proc_macro2::TokenStream)> {
+    match data {
+        Data::Struct(data) => field_docs(&data.fields),
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|v| {
+                (
+                    v.ident.to_string(),
+                    item_docs(&v.attrs, field_docs(&v.fields)),
+                )
+            })
+            .collect(),
+        Data::Union(_) => vec![],
+    }
+}
+
+// Removes the `#[schema(..)]` attributes of fields, which are not attributes
+// of their own
+fn strip_field_attrs(data: &mut syn::Data) {
+    let fields: Vec<&mut syn::Fields> = match data {
+        Data::Struct(data) => vec![&mut data.fields],
+        Data::Enum(data) => data.variants.iter_mut().map(|v| &mut v.fields).collect(),
+        Data::Union(_) => vec![],
+    };
+    for field in fields.into_iter().flat_map(|fields| fields.iter_mut()) {
+        field.attrs.retain(|attr| !attr.path.is_ident("schema"));
+    }
+}
+
 // Generates an Atom schema (just the type name)
 fn generate_atom_schema(
     name: &syn::Ident,
@@ -75,20 +500,16 @@ fn generate_atom_schema(
 }
 
 // Generates a Structural schema (tuples or unnamed structs)
-fn generate_structural_schema(data: &syn::Data) -> proc_macro2::TokenStream {
+fn generate_structural_schema(
+    data: &syn::Data,
+    tagging: Option<proc_macro2::TokenStream>,
+    field_types: &mut FieldTypes,
+) -> proc_macro2::TokenStream {
     match data {
         Data::Struct(data_struct) => match &data_struct.fields {
             Fields::Named(fields) => {
-                let types: Vec<proc_macro2::TokenStream> = fields
-                    .named
-                    .iter()
-                    .map(|f| {
-                        let ty = &f.ty;
-                        quote! {
-                            <#ty as ::irpc_schema::HasSchema>::schema()
-                        }
-                    })
-                    .collect();
+                let types: Vec<proc_macro2::TokenStream> =
+                    fields.named.iter().map(|f| field_types.schema(f)).collect();
                 if types.is_empty() {
                     quote! {
                         ::irpc_schema::Schema::Unit
@@ -103,12 +524,7 @@ fn generate_structural_schema(data: &syn::Data) -> proc_macro2::TokenStream {
                 let types: Vec<proc_macro2::TokenStream> = fields
                     .unnamed
                     .iter()
-                    .map(|f| {
-                        let ty = &f.ty;
-                        quote! {
-                            <#ty as ::irpc_schema::HasSchema>::schema()
-                        }
-                    })
+                    .map(|f| field_types.schema(f))
                     .collect();
                 if types.is_empty() {
                     quote! {
@@ -130,25 +546,13 @@ fn generate_structural_schema(data: &syn::Data) -> proc_macro2::TokenStream {
                 .iter()
                 .map(|v| {
                     let variant_fields = match &v.fields {
-                        Fields::Named(fields) => fields
-                            .named
-                            .iter()
-                            .map(|f| {
-                                let ty = &f.ty;
-                                quote! {
-                                    <#ty as ::irpc_schema::HasSchema>::schema()
-                                }
-                            })
-                            .collect(),
+                        Fields::Named(fields) => {
+                            fields.named.iter().map(|f| field_types.schema(f)).collect()
+                        }
                         Fields::Unnamed(fields) => fields
                             .unnamed
                             .iter()
-                            .map(|f| {
-                                let ty = &f.ty;
-                                quote! {
-                                    <#ty as ::irpc_schema::HasSchema>::schema()
-                                }
-                            })
+                            .map(|f| field_types.schema(f))
                             .collect(),
                         Fields::Unit => vec![],
                     };
@@ -168,19 +572,32 @@ fn generate_structural_schema(data: &syn::Data) -> proc_macro2::TokenStream {
                     ::irpc_schema::Schema::Bottom
                 };
             }
-            quote! {
-                ::irpc_schema::Schema::Sum(vec![#(#variant_schemas),*])
-            }
+            with_tagging(
+                quote! {
+                    ::irpc_schema::Schema::Sum(vec![#(#variant_schemas),*])
+                },
+                tagging,
+            )
         }
         _ => panic!("Unsupported type for Structural schema"),
     }
 }
 
 // Generates a Nominal schema (Struct or Enum with names)
+//
+// Enums are always mapped to Enum, variants with named fields to Struct and
+// variants with unnamed fields to Product. With `legacy_enum`, the mapping of
+// earlier versions is used instead, where a single variant enum is a Struct,
+// and variants with several fields are an Enum or a Sum. Enums with a serde
+// representation other than the default are wrapped in Tagged, inside the
+// Named.
 fn generate_nominal_schema(
     name: &syn::Ident,
     data: &syn::Data,
     explicit_name: Option<&str>,
+    legacy_enum: bool,
+    tagging: Option<proc_macro2::TokenStream>,
+    field_types: &mut FieldTypes,
 ) -> proc_macro2::TokenStream {
     let name_text = explicit_name.unwrap_or(&name.to_string()).to_string();
     match data {
@@ -191,9 +608,9 @@ fn generate_nominal_schema(
                     .iter()
                     .map(|f| {
                         let field_name = f.ident.as_ref().unwrap().to_string();
-                        let field_type = &f.ty;
+                        let field_schema = field_types.schema(f);
                         quote! {
-                            ::irpc_schema::Named(#field_name.to_string(), <#field_type as ::irpc_schema::HasSchema>::schema())
+                            ::irpc_schema::Named(#field_name.to_string(), #field_schema)
                         }
                     })
                     .collect();
@@ -212,12 +629,7 @@ fn generate_nominal_schema(
                 let field_schemas: Vec<proc_macro2::TokenStream> = fields
                     .unnamed
                     .iter()
-                    .map(|f| {
-                        let field_type = &f.ty;
-                        quote! {
-                            <#field_type as ::irpc_schema::HasSchema>::schema()
-                        }
-                    })
+                    .map(|f| field_types.schema(f))
                     .collect();
                 let schema = if field_schemas.is_empty() {
                     quote! { ::irpc_schema::Schema::Unit }
@@ -249,16 +661,16 @@ fn generate_nominal_schema(
                                 .named
                                 .iter()
                                 .map(|f| {
-                                    let field_type = &f.ty;
+                                    let field_schema = field_types.schema(f);
                                     let field_name = f.ident.as_ref().unwrap().to_string();
                                     quote! {
-                                        ::irpc_schema::Named(#field_name.to_string(),<#field_type as ::irpc_schema::HasSchema>::schema())
+                                        ::irpc_schema::Named(#field_name.to_string(), #field_schema)
                                     }
                                 })
                                 .collect::<Vec<_>>();
                             let schema_type = if named.is_empty() {
                                 quote! { ::irpc_schema::Schema::Unit }
-                            } else if named.len() == 1 {
+                            } else if named.len() == 1 || !legacy_enum {
                                 quote! { ::irpc_schema::Schema::Struct(vec![#(#named),*]) }
                             } else {
                                 quote! { ::irpc_schema::Schema::Enum(vec![#(#named),*]) }
@@ -274,16 +686,11 @@ fn generate_nominal_schema(
                             let unnamed = fields
                                 .unnamed
                                 .iter()
-                                .map(|f| {
-                                    let field_type = &f.ty;
-                                    quote! {
-                                        <#field_type as ::irpc_schema::HasSchema>::schema()
-                                    }
-                                })
+                                .map(|f| field_types.schema(f))
                                 .collect::<Vec<_>>();
                             let schema_type = if unnamed.is_empty() {
                                 quote! { ::irpc_schema::Schema::Unit }
-                            } else if unnamed.len() == 1 {
+                            } else if unnamed.len() == 1 || !legacy_enum {
                                 quote! { ::irpc_schema::Schema::Product(vec![#(#unnamed),*]) }
                             } else {
                                 quote! { ::irpc_schema::Schema::Sum(vec![#(#unnamed),*]) }
@@ -309,11 +716,17 @@ fn generate_nominal_schema(
 
             let schema = if variants.is_empty() {
                 quote! { ::irpc_schema::Schema::Bottom }
-            } else if variants.len() == 1 {
+            } else if variants.len() == 1 && legacy_enum {
                 quote! { ::irpc_schema::Schema::Struct(vec![#(#variants),*]) }
             } else {
                 quote! { ::irpc_schema::Schema::Enum(vec![#(#variants),*]) }
             };
+            // an empty enum has no values to represent
+            let schema = if variants.is_empty() {
+                schema
+            } else {
+                with_tagging(schema, tagging)
+            };
             quote! {
                 ::irpc_schema::Schema::Named(
                     Box::new(::irpc_schema::Named(#name_text.to_string(), #schema))
@@ -324,27 +737,247 @@ fn generate_nominal_schema(
     }
 }
 
+// Generates a Nominal schema for an enum of unit variants as an Enumeration,
+// with the variant indices as discriminants, since that is what serde sends
+fn generate_enumeration_schema(
+    name: &syn::Ident,
+    data: &syn::Data,
+    explicit_name: Option<&str>,
+    tagging: Option<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    let name_text = explicit_name.unwrap_or(&name.to_string()).to_string();
+    let Data::Enum(data_enum) = data else {
+        panic!("enumeration is only supported for enums");
+    };
+    if tagging.is_some() {
+        panic!("enumeration is only supported for externally tagged enums");
+    }
+    let cases = data_enum
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(index, v)| {
+            if !matches!(v.fields, Fields::Unit) {
+                panic!(
+                    "enumeration requires unit variants, but {} has fields",
+                    v.ident
+                );
+            }
+            let variant_name_text = v.ident.to_string();
+            let index = index as u32;
+            quote! { (#variant_name_text.to_string(), #index) }
+        })
+        .collect::<Vec<_>>();
+    let schema = if cases.is_empty() {
+        quote! { ::irpc_schema::Schema::Bottom }
+    } else {
+        quote! { ::irpc_schema::Schema::Enumeration(vec![#(#cases),*]) }
+    };
+    quote! {
+        ::irpc_schema::Schema::Named(
+            Box::new(::irpc_schema::Named(#name_text.to_string(), #schema))
+        )
+    }
+}
+
 /// Implements stable serialization and deserialization for an enum with
 /// a number of distinct variants.
 ///
 /// Each variant must have a single unnamed field of distinct type. Each type
 /// must implement `HasSchema`.
+///
+/// With `#[serialize_stable(short)]`, only the first 8 bytes of the hash are
+/// sent. See `irpc_schema::short`.
 #[proc_macro_attribute]
-pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn serialize_stable(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(
+        attr with syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated
+    );
+    let mut short = false;
+    for arg in args {
+        match arg.to_string().as_str() {
+            "short" => short = true,
+            other => panic!("Unsupported option {} in serialize_stable attribute", other),
+        }
+    }
+
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(item as ItemEnum);
 
-    // Get the original enum
-    let original_enum = input.clone();
+    TokenStream::from(generate_stable_impls(&input, short, |field_type| {
+        quote! { <#field_type as ::irpc_schema::HasSchema>::static_schema().clone() }
+    }))
+}
 
-    // Get the name of the enum
+/// This is identical to `serialize_stable`, but for a specific service.
+///
+/// The schema hashes for each variant will include not just the message type itself,
+/// but also the kind (none, oneshot, spsc) and type of tx and rx channels it uses.
+///
+/// That way any change to channel kinds and types will result in a different hash,
+/// just like a change to the message type itself.
+///
+/// Usage:
+/// ```rust
+/// use irpc::channel::{none::NoReceiver, oneshot};
+/// use irpc_schema::{schema, serialize_service};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct MyService;
+///
+/// impl irpc::Service for MyService {
+///     type Message = MyServiceProto;
+/// }
+///
+/// #[schema(Nominal)]
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Ping;
+///
+/// impl irpc::Channels<MyService> for Ping {
+///     type Rx = NoReceiver;
+///     type Tx = oneshot::Sender<()>;
+/// }
+///
+/// #[serialize_service(MyService)]
+/// #[derive(Debug)]
+/// enum MyServiceProto {
+///     Ping(Ping),
+/// }
+/// ```
+///
+/// This macro requires that `irpc::Channels` is implemented for the given service type
+/// for each variant of the enum. It also requires that HasSchema is implemented for
+/// all channlels payload types.
+///
+/// With `#[serialize_service(MyService, client)]`, a `MyServiceClient` wrapping
+/// `irpc::Client<MyService>` is generated as well, with one method per variant named
+/// after the variant in snake case. See `irpc_schema::client` for the requirements.
+///
+/// With `#[serialize_service(MyService, router)]`, a `MyServiceRouter<T>` is generated,
+/// that decodes messages and dispatches them to handlers registered with `on_<variant>`.
+/// See `irpc_schema::router`.
+///
+/// With `#[serialize_service(MyService, short)]`, only the first 8 bytes of the hash
+/// are sent. This can not be combined with `router`. See `irpc_schema::short`.
+#[proc_macro_attribute]
+pub fn serialize_service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // Service for which this macro is applied, and options
+    let args = parse_macro_input!(
+        attr with syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated
+    );
+    let mut args = args.into_iter();
+    let service = args.next().expect("expected a service type");
+    let mut client = false;
+    let mut router = false;
+    let mut short = false;
+    for arg in args {
+        match arg.to_string().as_str() {
+            "client" => client = true,
+            "router" => router = true,
+            "short" => short = true,
+            other => panic!(
+                "Unsupported option {} in serialize_service attribute",
+                other
+            ),
+        }
+    }
+    if short && router {
+        panic!("The short option can not be combined with router in serialize_service attribute");
+    }
+
+    // Parse the input tokens into a syntax tree
+    let input = parse_macro_input!(item as ItemEnum);
     let enum_name = &input.ident;
+    let (variant_names, field_types) = message_variants(&input);
 
-    // Generate names for our hash struct
-    let schema_struct_name = syn::Ident::new(&format!("{}Schemas", enum_name), enum_name.span());
-    let schema_struct_static_name =
-        syn::Ident::new(&format!("__{}_SCHEMAS", enum_name), enum_name.span());
+    let generated_impls = generate_stable_impls(&input, short, |field_type| {
+        quote! { <#field_type as ::irpc_schema::ChannelsSchema<#service>>::schema() }
+    });
+
+    let client_impls = if client {
+        let vis = &input.vis;
+        let client_name = syn::Ident::new(&format!("{}Client", service), service.span());
+        let methods = variant_names
+            .iter()
+            .zip(field_types.iter())
+            .map(|(variant_name, field_type)| {
+                let method = syn::Ident::new(&snake_case(&variant_name.to_string()), variant_name.span());
+                quote! {
+                    pub fn #method(
+                        &self,
+                        msg: #field_type,
+                    ) -> impl ::std::future::Future<
+                        Output = ::irpc::Result<::irpc_schema::client::CallOutput<#service, #field_type>>,
+                    > + Send + 'static {
+                        ::irpc_schema::client::call(&self.inner, msg, self.local_capacity)
+                    }
+                }
+            });
+        quote! {
+            /// Typed client with one method per message.
+            #[derive(Debug, Clone)]
+            #vis struct #client_name {
+                inner: ::irpc::Client<#service>,
+                local_capacity: usize,
+            }
 
+            impl #client_name {
+                pub fn new(inner: ::irpc::Client<#service>) -> Self {
+                    Self {
+                        inner,
+                        local_capacity: ::irpc_schema::client::DEFAULT_LOCAL_CAPACITY,
+                    }
+                }
+
+                /// Sets the capacity of channels created for local services.
+                pub fn with_local_capacity(mut self, local_capacity: usize) -> Self {
+                    self.local_capacity = local_capacity;
+                    self
+                }
+
+                /// The wrapped irpc client.
+                pub fn inner(&self) -> &::irpc::Client<#service> {
+                    &self.inner
+                }
+
+                #(#methods)*
+            }
+
+            impl From<::irpc::Client<#service>> for #client_name {
+                fn from(inner: ::irpc::Client<#service>) -> Self {
+                    Self::new(inner)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let router_impls = if router {
+        let router_name = syn::Ident::new(&format!("{}Router", service), service.span());
+        generate_router(
+            &input.vis,
+            &router_name,
+            enum_name,
+            &variant_names,
+            &field_types,
+        )
+    } else {
+        quote! {}
+    };
+
+    // Return the generated code
+    TokenStream::from(quote! {
+        #generated_impls
+        #client_impls
+        #router_impls
+    })
+}
+
+// The variant names and field types of a `serialize_stable` or
+// `serialize_service` enum, whose variants must all have a single unnamed field
+fn message_variants(input: &ItemEnum) -> (Vec<&syn::Ident>, Vec<&syn::Type>) {
     // Collect all variants
     let variants = &input.variants;
 
@@ -374,20 +1007,47 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
         field_types.push(field_type);
     }
 
-    // Define fields for our SchemaHashes struct
-    let schema_struct_fields = variant_names.iter().map(|variant_name| {
-        quote! { pub #variant_name: ::irpc_schema::SchemaAndHash }
-    });
+    (variant_names, field_types)
+}
 
-    // Generate initialization for our SchemaHashes struct
-    let schema_struct_inits =
-        variant_names
-            .iter()
-            .zip(field_types.iter())
-            .map(|(variant_name, field_type)| {
-                quote! {
-                    #variant_name: ::irpc_schema::SchemaAndHash::from(<#field_type as ::irpc_schema::HasSchema>::schema())
-                }
+// Generates what `serialize_stable` and `serialize_service` have in common:
+// the enum itself, the struct with the schemas and the dispatch table,
+// `schemas`, `to_postcard`, test vectors and the serde impls, with tracing,
+// telemetry and short mode. `variant_schema` gives the schema of the field of
+// a variant.
+fn generate_stable_impls(
+    input: &ItemEnum,
+    short: bool,
+    variant_schema: impl Fn(&syn::Type) -> proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    // Get the original enum
+    let original_enum = input.clone();
+
+    // Get the name of the enum
+    let enum_name = &input.ident;
+
+    // Generate names for our hash struct
+    let schema_struct_name = syn::Ident::new(&format!("{}Schemas", enum_name), enum_name.span());
+    let schema_struct_static_name =
+        syn::Ident::new(&format!("__{}_SCHEMAS", enum_name), enum_name.span());
+
+    let (variant_names, field_types) = message_variants(input);
+
+    // Define fields for our SchemaHashes struct
+    let schema_struct_fields = variant_names.iter().map(|variant_name| {
+        quote! { pub #variant_name: ::irpc_schema::SchemaAndHash }
+    });
+
+    // Generate initialization for our SchemaHashes struct
+    let schema_struct_inits =
+        variant_names
+            .iter()
+            .zip(field_types.iter())
+            .map(|(variant_name, field_type)| {
+                let schema = variant_schema(field_type);
+                quote! {
+                    #variant_name: ::irpc_schema::SchemaAndHash::from(#schema)
+                }
             });
 
     let schema_struct_to_tuples = variant_names.iter().map(|variant_name| {
@@ -397,37 +1057,84 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     });
 
+    let ShortMode {
+        field: short_field,
+        init: short_init,
+        check: short_check,
+        hash_type,
+        table,
+        unknown_hash,
+    } = ShortMode::new(short, &variant_names);
+    let wire_hash = if short {
+        quote! { &::irpc_schema::short::short_hash(&hash) }
+    } else {
+        quote! { &hash }
+    };
+
+    // Generate arms serializing into a single buffer
+    let to_postcard_arms = variant_names.iter().map(|variant_name| {
+        let ident = variant_name.to_string();
+        quote! {
+            #enum_name::#variant_name(payload) => {
+                let hash = schema_struct_value.#variant_name.hash;
+                ::irpc_schema::telemetry::record_message(
+                    ::irpc_schema::telemetry::Direction::Serialize,
+                    #ident,
+                    &hash,
+                    payload,
+                );
+                ::irpc_schema::wire::to_vec(#wire_hash, payload)
+            }
+        }
+    });
+
     // Generate serialization arms using the static hashes
     let serialize_arms = variant_names.iter().map(|variant_name| {
+        let ident = variant_name.to_string();
         quote! {
             #enum_name::#variant_name(payload) => {
                 let hash = schema_struct_value.#variant_name.hash;
+                ::irpc_schema::telemetry::record_message(
+                    ::irpc_schema::telemetry::Direction::Serialize,
+                    #ident,
+                    &hash,
+                    payload,
+                );
 
                 let mut tup = serializer.serialize_tuple(2)?;
-                tup.serialize_element(&hash)?;
+                tup.serialize_element(#wire_hash)?;
                 tup.serialize_element(payload)?;
                 tup.end()
             }
         }
     });
 
-    // Generate deserialization branches using the static hashes
-    let deserialize_branches =
-        variant_names
-            .iter()
-            .zip(field_types.iter())
-            .map(|(variant_name, field_type)| {
-                quote! {
-                    if &hash_bytes == &schema_struct_value.#variant_name.hash {
-                        let payload = seq.next_element::<#field_type>()?.ok_or_else(||
-                            serde::de::Error::custom("missing payload"))?;
-                        return Ok(#enum_name::#variant_name(payload));
-                    }
+    // Generate deserialization arms, indexed by the position in the dispatch table
+    let deserialize_arms = variant_names
+        .iter()
+        .zip(field_types.iter())
+        .enumerate()
+        .map(|(index, (variant_name, field_type))| {
+            let ident = variant_name.to_string();
+            quote! {
+                Some(#index) => {
+                    let payload = seq.next_element::<#field_type>()?.ok_or_else(||
+                        serde::de::Error::custom("missing payload"))?;
+                    ::irpc_schema::telemetry::record_message(
+                        ::irpc_schema::telemetry::Direction::Deserialize,
+                        #ident,
+                        &schema_struct_value.#variant_name.hash,
+                        &payload,
+                    );
+                    return Ok(#enum_name::#variant_name(payload));
                 }
-            });
+            }
+        });
 
-    // Generate the implementation
-    let generated_impls = quote! {
+    let protocol_impl =
+        generate_protocol_impl(enum_name, &schema_struct_name, &variant_names, &field_types);
+
+    quote! {
         // The original enum definition
         #original_enum
 
@@ -435,7 +1142,9 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #[allow(non_snake_case)]
         #[derive(Debug)]
         struct #schema_struct_name {
-            #(#schema_struct_fields),*
+            #(#schema_struct_fields,)*
+            __dispatch: ::irpc_schema::dispatch::DispatchTable,
+            #short_field
         }
 
         // Create a static instance of our hashes using std::sync::OnceLock
@@ -446,9 +1155,14 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
         impl #schema_struct_name {
             // Create a new instance with all the hashes computed
             fn new() -> Self {
-                Self {
-                    #(#schema_struct_inits),*
-                }
+                let mut res = Self {
+                    #(#schema_struct_inits,)*
+                    __dispatch: ::irpc_schema::dispatch::DispatchTable::default(),
+                    #short_init
+                };
+                res.__dispatch = ::irpc_schema::dispatch::DispatchTable::new([#(res.#variant_names.hash),*]);
+                #short_check
+                res
             }
 
             // Static accessor function to get or initialize the global instance
@@ -462,8 +1176,47 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 let schema_struct_value = #schema_struct_name::get();
                 [#(#schema_struct_to_tuples),*].into_iter()
             }
+
+            /// Serializes the message with postcard into a single buffer of the exact size.
+            ///
+            /// The result is identical to `postcard::to_allocvec`.
+            pub fn to_postcard(&self) -> ::std::result::Result<::std::vec::Vec<u8>, ::irpc_schema::wire::Error> {
+                let schema_struct_value = #schema_struct_name::get();
+                match self {
+                    #(#to_postcard_arms),*
+                }
+            }
+
+            /// Deserializes a message with postcard, like `postcard::from_bytes`.
+            ///
+            /// Unlike plain deserialization, this reports messages with unknown hashes
+            /// together with their length, see `irpc_schema::telemetry`.
+            pub fn from_postcard(bytes: &[u8]) -> ::std::result::Result<Self, ::irpc_schema::wire::Error> {
+                let schema_struct_value = #schema_struct_name::get();
+                let hash_len = ::std::mem::size_of::<#hash_type>();
+                if let Some(hash_bytes) = bytes.get(..hash_len) {
+                    let hash_bytes = <#hash_type>::try_from(hash_bytes).unwrap();
+                    if schema_struct_value.#table.get(&hash_bytes).is_none() {
+                        ::irpc_schema::telemetry::report_unknown_message(
+                            #unknown_hash,
+                            Some(bytes.len()),
+                            Self::schemas(),
+                        );
+                        return Err(::irpc_schema::wire::Error::SerdeDeCustom);
+                    }
+                }
+                ::irpc_schema::wire::from_bytes(bytes)
+            }
         }
 
+        impl ::irpc_schema::vectors::HasTestVectors for #enum_name {
+            fn test_vectors() -> ::std::vec::Vec<::irpc_schema::vectors::TestVector> {
+                ::irpc_schema::vectors::test_vectors(Self::schemas())
+            }
+        }
+
+        #protocol_impl
+
         // Implementation of serde::Serialize for the enum
         impl serde::Serialize for #enum_name {
             fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -500,16 +1253,24 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
                         A: serde::de::SeqAccess<'de>,
                     {
                         // Deserialize the hash discriminator (first element)
-                        let hash_bytes = seq.next_element::<[u8; 32]>()?.ok_or_else(||
+                        let hash_bytes = seq.next_element::<#hash_type>()?.ok_or_else(||
                             serde::de::Error::custom("missing hash"))?;
 
                         // Get the schema hashes
                         let schema_struct_value = #schema_struct_name::get();
 
-                        // Check against our static hashes
-                        #(#deserialize_branches)*
+                        // Look up the hash in the dispatch table
+                        match schema_struct_value.#table.get(&hash_bytes) {
+                            #(#deserialize_arms)*
+                            _ => {}
+                        }
 
-                        // If none matched, return an error
+                        // If none matched, report and return an error
+                        ::irpc_schema::telemetry::report_unknown_message(
+                            #unknown_hash,
+                            None,
+                            #enum_name::schemas(),
+                        );
                         Err(serde::de::Error::custom("unknown discriminator"))
                     }
                 }
@@ -518,222 +1279,466 @@ pub fn serialize_stable(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 deserializer.deserialize_tuple(2, Visitor)
             }
         }
-    };
-
-    // Return the generated code
-    TokenStream::from(generated_impls)
+    }
 }
 
-/// This is identical to `serialize_stable`, but for a specific service.
+/// Composes several protocols into one.
 ///
-/// The schema hashes for each variant will include not just the message type itself,
-/// but also the kind (none, oneshot, spsc) and type of tx and rx channels it uses.
-///
-/// That way any change to channel kinds and types will result in a different hash,
-/// just like a change to the message type itself.
+/// Each variant must have a single unnamed field, whose type is a
+/// `serialize_stable`, `serialize_service` or `serialize_nested` enum. The
+/// messages of the parent are the messages of all children, named
+/// `Variant.Message`, with the child schema named after the variant.
 ///
 /// Usage:
-/// ```rust
-/// #[serialize_service(MyService)]
-/// enum MyServiceProto {}
+/// ```ignore
+/// #[serialize_nested]
+/// enum Root {
+///     Kv(kv::Proto),
+///     Auth(auth::Proto),
+/// }
 /// ```
 ///
-/// This macro requires that `irpc::Channels` is implemented for the given service type
-/// for each variant of the enum. It also requires that HasSchema is implemented for
-/// all channlels payload types.
+/// With `#[serialize_nested(router)]`, a `RootRouter<T>` is generated, with one
+/// handler per child protocol. See `irpc_schema::nested`.
 #[proc_macro_attribute]
-pub fn serialize_service(attr: TokenStream, item: TokenStream) -> TokenStream {
-    // Service for which this macro is applied
-    let service = parse_macro_input!(attr as syn::Ident);
+pub fn serialize_nested(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(
+        attr with syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated
+    );
+    let mut router = false;
+    for arg in args {
+        match arg.to_string().as_str() {
+            "router" => router = true,
+            other => panic!("Unsupported option {} in serialize_nested attribute", other),
+        }
+    }
 
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(item as ItemEnum);
-
-    // Get the original enum
     let original_enum = input.clone();
-
-    // Get the name of the enum
     let enum_name = &input.ident;
-
-    // Generate names for our hash struct
     let schema_struct_name = syn::Ident::new(&format!("{}Schemas", enum_name), enum_name.span());
     let schema_struct_static_name =
         syn::Ident::new(&format!("__{}_SCHEMAS", enum_name), enum_name.span());
 
-    // Collect all variants
-    let variants = &input.variants;
-
-    // Make sure all variants have a single unnamed field
-    for variant in variants {
+    // Collect all variant names and their child protocols
+    let mut variant_names = Vec::new();
+    let mut field_types = Vec::new();
+    for variant in &input.variants {
         match &variant.fields {
             Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
-                // This is good - a single unnamed field
+                variant_names.push(&variant.ident);
+                field_types.push(&fields.unnamed.first().unwrap().ty);
             }
-            _ => panic!("HashDiscriminator only supports variants with a single unnamed field"),
+            _ => panic!("serialize_nested only supports variants with a single unnamed field"),
         }
     }
+    let indices = (0..variant_names.len()).collect::<Vec<_>>();
+
+    let pushes = variant_names
+        .iter()
+        .zip(field_types.iter())
+        .zip(indices.iter())
+        .map(|((variant_name, field_type), index)| {
+            let ident = variant_name.to_string();
+            quote! {
+                res.push::<#field_type>(#index, #ident);
+            }
+        });
+    let hash_arms = variant_names
+        .iter()
+        .zip(indices.iter())
+        .map(|(variant_name, index)| {
+            quote! {
+                #enum_name::#variant_name(child) => (#index, ::irpc_schema::nested::Protocol::message_hash(child))
+            }
+        });
+    let payload_arms = variant_names.iter().map(|variant_name| {
+        quote! {
+            #enum_name::#variant_name(child) => ::irpc_schema::nested::Protocol::serialize_payload(child, serializer)
+        }
+    });
+    let deserialize_arms = variant_names
+        .iter()
+        .zip(field_types.iter())
+        .zip(indices.iter())
+        .map(|((variant_name, field_type), index)| {
+            quote! {
+                #index => <#field_type as ::irpc_schema::nested::Protocol>::deserialize_payload(&entry.child_hash, deserializer)
+                    .map(#enum_name::#variant_name)
+            }
+        });
 
-    // Collect all variant names and their field types
-    let mut variant_names = Vec::new();
-    let mut field_types = Vec::new();
-
-    for variant in variants {
-        let variant_name = &variant.ident;
-        variant_names.push(variant_name);
-
-        let field_type = match &variant.fields {
-            Fields::Unnamed(fields) => &fields.unnamed.first().unwrap().ty,
-            _ => unreachable!(), // We've already checked this above
-        };
-
-        field_types.push(field_type);
-    }
+    let generated_impls = quote! {
+        #original_enum
 
-    // Define fields for our SchemaHashes struct
-    let schema_struct_fields = variant_names.iter().map(|variant_name| {
-        quote! { pub #variant_name: ::irpc_schema::SchemaAndHash }
-    });
+        // The messages of all children, computed on first use
+        struct #schema_struct_name;
 
-    // Generate initialization for our SchemaHashes struct
-    let schema_struct_inits =
-        variant_names
-            .iter()
-            .zip(field_types.iter())
-            .map(|(variant_name, field_type)| {
-                quote! {
-                    #variant_name: ::irpc_schema::SchemaAndHash::from(<#field_type as ::irpc_schema::ChannelsSchema<#service>>::schema())
-                }
-            });
+        #[allow(non_upper_case_globals)]
+        static #schema_struct_static_name: ::std::sync::OnceLock<::irpc_schema::nested::NestedSchemas> =
+            ::std::sync::OnceLock::new();
 
-    let schema_struct_to_tuples = variant_names.iter().map(|variant_name| {
-        let ident = variant_name.to_string();
-        quote! {
-            (#ident, &schema_struct_value.#variant_name.schema, schema_struct_value.#variant_name.hash)
+        impl #schema_struct_name {
+            fn get() -> &'static ::irpc_schema::nested::NestedSchemas {
+                #schema_struct_static_name.get_or_init(|| {
+                    let mut res = ::irpc_schema::nested::NestedSchemas::new();
+                    #(#pushes)*
+                    res
+                })
+            }
         }
-    });
 
-    // Generate serialization arms using the static hashes
-    let serialize_arms = variant_names.iter().map(|variant_name| {
-        quote! {
-            #enum_name::#variant_name(payload) => {
-                let hash = schema_struct_value.#variant_name.hash;
+        impl #enum_name {
+            pub fn schemas() -> impl ::std::iter::Iterator<Item = (&'static str, &'static ::irpc_schema::Schema, [u8; 32])> {
+                #schema_struct_name::get().schemas()
+            }
 
-                let mut tup = serializer.serialize_tuple(2)?;
-                tup.serialize_element(&hash)?;
-                tup.serialize_element(payload)?;
-                tup.end()
+            /// Serializes the message with postcard into a single buffer of the exact size.
+            ///
+            /// The result is identical to `postcard::to_allocvec`.
+            pub fn to_postcard(&self) -> ::std::result::Result<::std::vec::Vec<u8>, ::irpc_schema::wire::Error> {
+                ::irpc_schema::wire::to_vec(
+                    &::irpc_schema::nested::Protocol::message_hash(self),
+                    &::irpc_schema::nested::Payload(self),
+                )
             }
-        }
-    });
 
-    // Generate deserialization branches using the static hashes
-    let deserialize_branches =
-        variant_names
-            .iter()
-            .zip(field_types.iter())
-            .map(|(variant_name, field_type)| {
-                quote! {
-                    if &hash_bytes == &schema_struct_value.#variant_name.hash {
-                        let payload = seq.next_element::<#field_type>()?.ok_or_else(||
-                            serde::de::Error::custom("missing payload"))?;
-                        return Ok(#enum_name::#variant_name(payload));
+            /// Deserializes a message with postcard, like `postcard::from_bytes`.
+            ///
+            /// Unlike plain deserialization, this reports messages with unknown hashes
+            /// together with their length, see `irpc_schema::telemetry`.
+            pub fn from_postcard(bytes: &[u8]) -> ::std::result::Result<Self, ::irpc_schema::wire::Error> {
+                if let Some(hash_bytes) = bytes.get(..32) {
+                    let hash = <[u8; 32]>::try_from(hash_bytes).unwrap();
+                    if !Self::schemas().any(|(_, _, known)| known == hash) {
+                        ::irpc_schema::telemetry::report_unknown_message(hash, Some(bytes.len()), Self::schemas());
+                        return Err(::irpc_schema::wire::Error::SerdeDeCustom);
                     }
                 }
-            });
-
-    // Generate the implementation
-    let generated_impls = quote! {
-        // The original enum definition
-        #original_enum
+                ::irpc_schema::wire::from_bytes(bytes)
+            }
+        }
 
-        // Define a struct to hold the schema hashes
-        #[allow(non_snake_case)]
-        struct #schema_struct_name {
-            #(#schema_struct_fields),*
+        impl ::irpc_schema::vectors::HasTestVectors for #enum_name {
+            fn test_vectors() -> ::std::vec::Vec<::irpc_schema::vectors::TestVector> {
+                ::irpc_schema::vectors::test_vectors(Self::schemas())
+            }
         }
 
-        // Create a static instance of our hashes using std::sync::OnceLock
-        use std::sync::OnceLock;
-        #[allow(non_upper_case_globals)]
-        static #schema_struct_static_name: OnceLock<#schema_struct_name> = OnceLock::new();
+        impl ::irpc_schema::nested::Protocol for #enum_name {
+            fn schemas() -> impl ::std::iter::Iterator<Item = (&'static str, &'static ::irpc_schema::Schema, [u8; 32])> {
+                #enum_name::schemas()
+            }
 
-        impl #schema_struct_name {
-            // Create a new instance with all the hashes computed
-            fn new() -> Self {
-                Self {
-                    #(#schema_struct_inits),*
-                }
+            fn message_hash(&self) -> [u8; 32] {
+                let (variant, child_hash) = match self {
+                    #(#hash_arms),*
+                };
+                #schema_struct_name::get()
+                    .get_by_child(variant, &child_hash)
+                    .expect("child message is not part of the child protocol")
+                    .schema
+                    .hash
             }
 
-            // Static accessor function to get or initialize the global instance
-            fn get() -> &'static Self {
-                #schema_struct_static_name.get_or_init(|| Self::new())
+            fn serialize_payload<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    #(#payload_arms),*
+                }
             }
-        }
 
-        impl #enum_name {
-            pub fn schemas() -> impl ::std::iter::Iterator<Item = (&'static str, &'static ::irpc_schema::Schema, [u8; 32])> {
-                let schema_struct_value = #schema_struct_name::get();
-                [#(#schema_struct_to_tuples),*].into_iter()
+            fn deserialize_payload<'de, D: serde::Deserializer<'de>>(
+                hash: &[u8; 32],
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                let entry = #schema_struct_name::get()
+                    .get(hash)
+                    .ok_or_else(|| serde::de::Error::custom("unknown discriminator"))?;
+                match entry.variant {
+                    #(#deserialize_arms,)*
+                    _ => unreachable!(),
+                }
             }
         }
 
-        // Implementation of serde::Serialize for the enum
         impl serde::Serialize for #enum_name {
             fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
             where
                 S: serde::Serializer,
             {
-                use serde::ser::SerializeTuple;
-                let schema_struct_value = #schema_struct_name::get();
-
-                match self {
-                    #(#serialize_arms),*
-                }
+                ::irpc_schema::nested::serialize(self, serializer)
             }
         }
 
-        // Implementation of serde::Deserialize for the enum with visitor inside
         impl<'de> serde::Deserialize<'de> for #enum_name {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
             where
                 D: serde::Deserializer<'de>,
             {
-                // Define the visitor struct inside the deserialize implementation
-                struct Visitor;
+                ::irpc_schema::nested::deserialize(deserializer)
+            }
+        }
+    };
 
-                impl<'de> serde::de::Visitor<'de> for Visitor {
-                    type Value = #enum_name;
+    let router_impls = if router {
+        let router_name = syn::Ident::new(&format!("{}Router", enum_name), enum_name.span());
+        generate_router(
+            &input.vis,
+            &router_name,
+            enum_name,
+            &variant_names,
+            &field_types,
+        )
+    } else {
+        quote! {}
+    };
 
-                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                        formatter.write_str("a tuple with a hash discriminator and payload")
-                    }
+    TokenStream::from(quote! {
+        #generated_impls
+        #router_impls
+    })
+}
 
-                    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-                    where
-                        A: serde::de::SeqAccess<'de>,
-                    {
-                        // Deserialize the hash discriminator (first element)
-                        let hash_bytes = seq.next_element::<[u8; 32]>()?.ok_or_else(||
-                            serde::de::Error::custom("missing hash"))?;
+/// The parts of a `serialize_stable` or `serialize_service` enum that differ
+/// between full and short hashes on the wire.
+struct ShortMode {
+    /// Additional field of the schema struct.
+    field: proc_macro2::TokenStream,
+    /// Initial value of the additional field.
+    init: proc_macro2::TokenStream,
+    /// Collision check and setup of the additional field, given `res`.
+    check: proc_macro2::TokenStream,
+    /// Type of the hash on the wire.
+    hash_type: proc_macro2::TokenStream,
+    /// Dispatch table field for the hash on the wire.
+    table: proc_macro2::TokenStream,
+    /// Full hash to report for an unknown `hash_bytes`.
+    unknown_hash: proc_macro2::TokenStream,
+}
 
-                        // Get the schema hashes
-                        let schema_struct_value = #schema_struct_name::get();
+impl ShortMode {
+    fn new(short: bool, variant_names: &[&syn::Ident]) -> Self {
+        if !short {
+            return Self {
+                field: quote! {},
+                init: quote! {},
+                check: quote! {},
+                hash_type: quote! { [u8; 32] },
+                table: quote! { __dispatch },
+                unknown_hash: quote! { hash_bytes },
+            };
+        }
+        let idents = variant_names.iter().map(|name| name.to_string());
+        Self {
+            field: quote! {
+                __short: ::irpc_schema::dispatch::DispatchTable<[u8; ::irpc_schema::short::SHORT_LEN]>,
+            },
+            init: quote! {
+                __short: ::irpc_schema::dispatch::DispatchTable::default(),
+            },
+            check: quote! {
+                if let Err(e) = ::irpc_schema::short::ShortIndex::new([#((#idents, res.#variant_names.hash)),*]) {
+                    panic!("{}", e);
+                }
+                res.__short = ::irpc_schema::dispatch::DispatchTable::new([
+                    #(::irpc_schema::short::short_hash(&res.#variant_names.hash)),*
+                ]);
+            },
+            hash_type: quote! { [u8; ::irpc_schema::short::SHORT_LEN] },
+            table: quote! { __short },
+            unknown_hash: quote! { ::irpc_schema::short::pad(&hash_bytes) },
+        }
+    }
+}
+
+/// Generates the `Protocol` impl of a `serialize_stable` or `serialize_service` enum.
+fn generate_protocol_impl(
+    enum_name: &syn::Ident,
+    schema_struct_name: &syn::Ident,
+    variant_names: &[&syn::Ident],
+    field_types: &[&syn::Type],
+) -> proc_macro2::TokenStream {
+    let hash_arms = variant_names.iter().map(|variant_name| {
+        quote! {
+            #enum_name::#variant_name(_) => schema_struct_value.#variant_name.hash
+        }
+    });
+    let payload_arms = variant_names.iter().map(|variant_name| {
+        quote! {
+            #enum_name::#variant_name(payload) => serde::Serialize::serialize(payload, serializer)
+        }
+    });
+    let deserialize_arms = variant_names
+        .iter()
+        .zip(field_types.iter())
+        .enumerate()
+        .map(|(index, (variant_name, field_type))| {
+            quote! {
+                Some(#index) => <#field_type as serde::Deserialize>::deserialize(deserializer)
+                    .map(#enum_name::#variant_name)
+            }
+        });
+    quote! {
+        impl ::irpc_schema::nested::Protocol for #enum_name {
+            fn schemas() -> impl ::std::iter::Iterator<Item = (&'static str, &'static ::irpc_schema::Schema, [u8; 32])> {
+                #enum_name::schemas()
+            }
 
-                        // Check against our static hashes
-                        #(#deserialize_branches)*
+            fn message_hash(&self) -> [u8; 32] {
+                let schema_struct_value = #schema_struct_name::get();
+                match self {
+                    #(#hash_arms),*
+                }
+            }
 
-                        // If none matched, return an error
-                        Err(serde::de::Error::custom("unknown discriminator"))
+            fn serialize_payload<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    #(#payload_arms),*
+                }
+            }
+
+            fn deserialize_payload<'de, D: serde::Deserializer<'de>>(
+                hash: &[u8; 32],
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                match #schema_struct_name::get().__dispatch.get(hash) {
+                    #(#deserialize_arms,)*
+                    _ => Err(serde::de::Error::custom("unknown discriminator")),
+                }
+            }
+        }
+    }
+}
+
+/// Generates a router dispatching the variants of a message enum to handlers.
+fn generate_router(
+    vis: &syn::Visibility,
+    router_name: &syn::Ident,
+    enum_name: &syn::Ident,
+    variant_names: &[&syn::Ident],
+    field_types: &[&syn::Type],
+) -> proc_macro2::TokenStream {
+    let handler_names = variant_names
+        .iter()
+        .map(|variant_name| {
+            syn::Ident::new(&snake_case(&variant_name.to_string()), variant_name.span())
+        })
+        .collect::<Vec<_>>();
+    let fields = handler_names
+        .iter()
+        .zip(field_types.iter())
+        .map(|(name, field_type)| {
+            quote! { #name: ::std::option::Option<::irpc_schema::router::Handler<#field_type, T>> }
+        });
+    let inits = handler_names.iter().map(|name| quote! { #name: None });
+    let setters = handler_names
+        .iter()
+        .zip(field_types.iter())
+        .map(|(name, field_type)| {
+            let setter = syn::Ident::new(&format!("on_{}", name), name.span());
+            quote! {
+                pub fn #setter<F, Fut>(mut self, f: F) -> Self
+                where
+                    F: Fn(#field_type) -> Fut + Send + Sync + 'static,
+                    Fut: ::std::future::Future<Output = T> + Send + 'static,
+                {
+                    self.#name = Some(::irpc_schema::router::handler(f));
+                    self
+                }
+            }
+        });
+    let dispatch_arms =
+        variant_names
+            .iter()
+            .zip(handler_names.iter())
+            .map(|(variant_name, name)| {
+                let ident = variant_name.to_string();
+                quote! {
+                    #enum_name::#variant_name(msg) => match &self.#name {
+                        Some(f) => Ok(f(msg).await),
+                        None => Err(::irpc_schema::router::RouteError::NoHandler { name: #ident }),
                     }
                 }
+            });
+    quote! {
+        /// Dispatches messages to handlers registered per message.
+        #vis struct #router_name<T> {
+            #(#fields,)*
+            unknown: ::std::option::Option<::irpc_schema::router::UnknownHandler<T>>,
+            registry: ::std::option::Option<::irpc_schema::registry::StrictRegistry>,
+        }
 
-                // Use the locally-defined visitor
-                deserializer.deserialize_tuple(2, Visitor)
+        impl<T> Default for #router_name<T> {
+            fn default() -> Self {
+                Self {
+                    #(#inits,)*
+                    unknown: None,
+                    registry: None,
+                }
             }
         }
-    };
 
-    // Return the generated code
-    TokenStream::from(generated_impls)
+        impl<T> #router_name<T> {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            #(#setters)*
+
+            /// Sets the handler for messages with unknown hashes.
+            pub fn on_unknown<F, Fut>(mut self, f: F) -> Self
+            where
+                F: Fn([u8; 32], Vec<u8>) -> Fut + Send + Sync + 'static,
+                Fut: ::std::future::Future<Output = T> + Send + 'static,
+            {
+                self.unknown = Some(::irpc_schema::router::unknown_handler(f));
+                self
+            }
+
+            /// Only accepts hashes allowed by the registry.
+            pub fn with_registry(mut self, registry: ::irpc_schema::registry::StrictRegistry) -> Self {
+                self.registry = Some(registry);
+                self
+            }
+
+            /// Decodes a message and dispatches it to its handler.
+            pub async fn handle(&self, bytes: &[u8]) -> Result<T, ::irpc_schema::router::RouteError> {
+                let hash = ::irpc_schema::router::check_hash(bytes, self.registry.as_ref())?;
+                if !#enum_name::schemas().any(|(_, _, known)| known == hash) {
+                    ::irpc_schema::router::report_unknown(bytes);
+                    return match &self.unknown {
+                        Some(f) => Ok(f(hash, bytes[32..].to_vec()).await),
+                        None => Err(::irpc_schema::router::RouteError::Schema(
+                            ::irpc_schema::SchemaError::UnknownHash { hash },
+                        )),
+                    };
+                }
+                let msg = ::irpc_schema::router::decode::<#enum_name>(bytes)?;
+                self.dispatch(msg).await
+            }
+
+            /// Dispatches a decoded message to its handler.
+            pub async fn dispatch(&self, msg: #enum_name) -> Result<T, ::irpc_schema::router::RouteError> {
+                match msg {
+                    #(#dispatch_arms),*
+                }
+            }
+        }
+    }
+}
+
+/// Converts a variant name like `GetRequest` to a method name like `get_request`.
+fn snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                res.push('_');
+            }
+            res.extend(c.to_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
 }
@@ -0,0 +1,138 @@
+//! Equivalent atom names.
+//!
+//! Atoms are identified by name, so the same wire type can show up under
+//! several names: `usize` is a `u64` on 64 bit platforms, and a wrapper like
+//! `NonZeroU64` may be sent as a plain `u64`. Such schemas are compatible, but
+//! compare as different and produce spurious
+//! [`AtomChanged`](crate::diff::ChangeKind::AtomChanged) changes.
+//!
+//! An [`AtomAliases`] table declares groups of atom names as equivalent.
+//! Comparisons and diffs through the table replace every atom with the
+//! canonical name of its group, which is the first name of the group:
+//!
+//! ```
+//! use irpc_schema::{aliases::AtomAliases, HasSchema, Schema};
+//!
+//! let aliases = AtomAliases::new().with(["u64", "usize", "NonZeroU64"]);
+//! let usize = Schema::Atom("usize".to_string());
+//! assert!(aliases.equal(&u64::schema(), &usize));
+//! assert!(aliases.diff(&u64::schema(), &usize).is_empty());
+//! ```
+//!
+//! Hashes are not affected, since peers would need to agree on the table.
+use std::collections::BTreeMap;
+
+use crate::{diff::SchemaDiff, Named, Schema};
+
+/// A table of equivalent atom names, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AtomAliases {
+    /// The canonical name for every name that is not canonical itself.
+    canonical: BTreeMap<String, String>,
+}
+
+impl AtomAliases {
+    /// An empty table, in which every atom is only equivalent to itself.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a group of atom names as equivalent.
+    pub fn with<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.add(names);
+        self
+    }
+
+    /// Declares a group of atom names as equivalent.
+    ///
+    /// A group that shares names with existing groups is merged with them,
+    /// keeping the canonical name of the first existing group it names.
+    pub fn add<S: Into<String>>(&mut self, names: impl IntoIterator<Item = S>) {
+        let canonicals = names
+            .into_iter()
+            .map(|name| self.canonical(&name.into()).to_string())
+            .collect::<Vec<_>>();
+        let known = |name: &String| {
+            self.canonical.contains_key(name) || self.canonical.values().any(|c| c == name)
+        };
+        let Some(canonical) = canonicals
+            .iter()
+            .find(|name| known(name))
+            .or(canonicals.first())
+            .cloned()
+        else {
+            return;
+        };
+        for other in canonicals {
+            if other == canonical {
+                continue;
+            }
+            // move the whole group of `other` over
+            for target in self.canonical.values_mut() {
+                if *target == other {
+                    *target = canonical.clone();
+                }
+            }
+            self.canonical.insert(other, canonical.clone());
+        }
+    }
+
+    /// The canonical name of an atom.
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        self.canonical.get(name).map_or(name, String::as_str)
+    }
+
+    /// True if the two atom names are equivalent.
+    pub fn equivalent(&self, a: &str, b: &str) -> bool {
+        self.canonical(a) == self.canonical(b)
+    }
+
+    /// The schema with every atom replaced by its canonical name.
+    pub fn normalize(&self, schema: &Schema) -> Schema {
+        let items = |items: &[Schema]| items.iter().map(|item| self.normalize(item)).collect();
+        let fields = |fields: &[Named]| {
+            fields
+                .iter()
+                .map(|field| Named(field.0.clone(), self.normalize(&field.1)))
+                .collect()
+        };
+        match schema {
+            Schema::Unit | Schema::Bottom | Schema::Enumeration(_) => schema.clone(),
+            Schema::Atom(name) => Schema::Atom(self.canonical(name).to_string()),
+            Schema::Product(types) => Schema::Product(items(types)),
+            Schema::Sum(types) => Schema::Sum(items(types)),
+            Schema::Struct(types) => Schema::Struct(fields(types)),
+            Schema::Enum(types) => Schema::Enum(fields(types)),
+            Schema::Named(named) => Schema::named(named.0.clone(), self.normalize(&named.1)),
+            Schema::Seq(item) => Schema::Seq(Box::new(self.normalize(item))),
+            Schema::Set(item) => Schema::Set(Box::new(self.normalize(item))),
+            Schema::Map(key, value) => Schema::Map(
+                Box::new(self.normalize(key)),
+                Box::new(self.normalize(value)),
+            ),
+            Schema::Optional(item) => Schema::Optional(Box::new(self.normalize(item))),
+            Schema::Tagged(tagging, item) => {
+                Schema::Tagged(tagging.clone(), Box::new(self.normalize(item)))
+            }
+            Schema::UnorderedSet(item) => Schema::UnorderedSet(Box::new(self.normalize(item))),
+            Schema::UnorderedMap(key, value) => Schema::UnorderedMap(
+                Box::new(self.normalize(key)),
+                Box::new(self.normalize(value)),
+            ),
+            Schema::Stream(item) => Schema::Stream(Box::new(self.normalize(item))),
+        }
+    }
+
+    /// True if the schemas are equal up to equivalent atoms.
+    pub fn equal(&self, a: &Schema, b: &Schema) -> bool {
+        self.normalize(a) == self.normalize(b)
+    }
+
+    /// The differences between two schemas, up to equivalent atoms.
+    ///
+    /// Changes are reported for the normalized schemas, so atoms show up with
+    /// their canonical names.
+    pub fn diff(&self, old: &Schema, new: &Schema) -> SchemaDiff {
+        crate::diff::diff(&self.normalize(old), &self.normalize(new))
+    }
+}
@@ -0,0 +1,519 @@
+//! Flat storage for many schemas.
+//!
+//! A [`Schema`] is a tree of boxes and vectors, so registries holding
+//! thousands of schemas spend a lot of allocations on them, and comparing or
+//! hashing them means chasing pointers. A [`SchemaArena`] stores the nodes of
+//! all schemas in flat vectors, and refers to them with [`SchemaId`] handles.
+//!
+//! Nodes and names are interned: inserting a schema that is structurally
+//! equal to one already in the arena returns the same id, and shared subtrees
+//! like common field types are stored once. Two schemas of the same arena are
+//! equal if and only if their ids are equal.
+//!
+//! ```ignore
+//! let mut arena = SchemaArena::new();
+//! let a = arena.insert(&Get::schema());
+//! let b = arena.insert(&Get::schema());
+//! assert_eq!(a, b);
+//! assert_eq!(arena.get(a), Get::schema());
+//! assert_eq!(arena.stable_hash(a), Get::schema().stable_hash());
+//! ```
+use std::collections::HashMap;
+
+use serde::{
+    ser::{SerializeSeq, SerializeTupleStruct, SerializeTupleVariant},
+    Serialize, Serializer,
+};
+
+use crate::{hash_postcard, Named, Schema, Tagging};
+
+/// A handle to a schema in a [`SchemaArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaId(u32);
+
+/// A handle to a name in a [`SchemaArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NameId(u32);
+
+/// A range of items or fields in a [`SchemaArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    start: u32,
+    len: u32,
+}
+
+impl Span {
+    fn range(self) -> std::ops::Range<usize> {
+        self.start as usize..(self.start + self.len) as usize
+    }
+}
+
+/// A node of a schema in a [`SchemaArena`], see [`Schema`] for the meaning
+/// of the variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Node {
+    Unit,
+    Bottom,
+    Atom(NameId),
+    /// Items, see [`SchemaArena::items`].
+    Product(Span),
+    /// Items, see [`SchemaArena::items`].
+    Sum(Span),
+    /// Fields, see [`SchemaArena::fields`].
+    Struct(Span),
+    /// Fields, see [`SchemaArena::fields`].
+    Enum(Span),
+    Named(NameId, SchemaId),
+    Seq(SchemaId),
+    Set(SchemaId),
+    Map(SchemaId, SchemaId),
+    Optional(SchemaId),
+    Tagged(NodeTagging, SchemaId),
+    UnorderedSet(SchemaId),
+    UnorderedMap(SchemaId, SchemaId),
+    /// Cases, see [`SchemaArena::cases`].
+    Enumeration(Span),
+    Stream(SchemaId),
+}
+
+/// A [`Tagging`] in a [`SchemaArena`], with names replaced by handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeTagging {
+    Internal(NameId),
+    Adjacent(NameId, NameId),
+    Untagged,
+}
+
+/// Schemas stored in flat vectors, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct SchemaArena {
+    nodes: Vec<Node>,
+    node_ids: HashMap<Node, SchemaId>,
+    names: Vec<String>,
+    name_ids: HashMap<String, NameId>,
+    items: Vec<SchemaId>,
+    item_spans: HashMap<Vec<SchemaId>, Span>,
+    fields: Vec<(NameId, SchemaId)>,
+    field_spans: HashMap<Vec<(NameId, SchemaId)>, Span>,
+    cases: Vec<(NameId, u32)>,
+    case_spans: HashMap<Vec<(NameId, u32)>, Span>,
+}
+
+impl SchemaArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a schema, returning the id of an equal schema if there is one.
+    pub fn insert(&mut self, schema: &Schema) -> SchemaId {
+        let node = match schema {
+            Schema::Unit => Node::Unit,
+            Schema::Bottom => Node::Bottom,
+            Schema::Atom(name) => Node::Atom(self.intern_name(name)),
+            Schema::Product(items) => Node::Product(self.insert_items(items)),
+            Schema::Sum(items) => Node::Sum(self.insert_items(items)),
+            Schema::Struct(fields) => Node::Struct(self.insert_fields(fields)),
+            Schema::Enum(fields) => Node::Enum(self.insert_fields(fields)),
+            Schema::Named(named) => {
+                let name = self.intern_name(&named.0);
+                Node::Named(name, self.insert(&named.1))
+            }
+            Schema::Seq(item) => Node::Seq(self.insert(item)),
+            Schema::Set(item) => Node::Set(self.insert(item)),
+            Schema::Map(key, value) => {
+                let key = self.insert(key);
+                Node::Map(key, self.insert(value))
+            }
+            Schema::Optional(item) => Node::Optional(self.insert(item)),
+            Schema::Tagged(tagging, item) => {
+                let tagging = self.intern_tagging(tagging);
+                Node::Tagged(tagging, self.insert(item))
+            }
+            Schema::UnorderedSet(item) => Node::UnorderedSet(self.insert(item)),
+            Schema::UnorderedMap(key, value) => {
+                let key = self.insert(key);
+                Node::UnorderedMap(key, self.insert(value))
+            }
+            Schema::Enumeration(cases) => Node::Enumeration(self.insert_cases(cases)),
+            Schema::Stream(item) => Node::Stream(self.insert(item)),
+        };
+        self.intern_node(node)
+    }
+
+    /// Rebuilds the schema with the given id.
+    pub fn get(&self, id: SchemaId) -> Schema {
+        match self.node(id) {
+            Node::Unit => Schema::Unit,
+            Node::Bottom => Schema::Bottom,
+            Node::Atom(name) => Schema::Atom(self.name(name).to_string()),
+            Node::Product(span) => Schema::Product(self.get_items(span)),
+            Node::Sum(span) => Schema::Sum(self.get_items(span)),
+            Node::Struct(span) => Schema::Struct(self.get_fields(span)),
+            Node::Enum(span) => Schema::Enum(self.get_fields(span)),
+            Node::Named(name, id) => Schema::named(self.name(name), self.get(id)),
+            Node::Seq(id) => Schema::Seq(Box::new(self.get(id))),
+            Node::Set(id) => Schema::Set(Box::new(self.get(id))),
+            Node::Map(key, value) => {
+                Schema::Map(Box::new(self.get(key)), Box::new(self.get(value)))
+            }
+            Node::Optional(id) => Schema::Optional(Box::new(self.get(id))),
+            Node::Tagged(tagging, id) => {
+                Schema::Tagged(self.tagging(tagging), Box::new(self.get(id)))
+            }
+            Node::UnorderedSet(id) => Schema::UnorderedSet(Box::new(self.get(id))),
+            Node::UnorderedMap(key, value) => {
+                Schema::UnorderedMap(Box::new(self.get(key)), Box::new(self.get(value)))
+            }
+            Node::Enumeration(span) => Schema::Enumeration(
+                self.cases(span)
+                    .iter()
+                    .map(|(name, tag)| (self.name(*name).to_string(), *tag))
+                    .collect(),
+            ),
+            Node::Stream(id) => Schema::Stream(Box::new(self.get(id))),
+        }
+    }
+
+    /// The id of a schema, if it is in the arena.
+    pub fn find(&self, schema: &Schema) -> Option<SchemaId> {
+        let node = match schema {
+            Schema::Unit => Node::Unit,
+            Schema::Bottom => Node::Bottom,
+            Schema::Atom(name) => Node::Atom(*self.name_ids.get(name)?),
+            Schema::Product(items) => Node::Product(self.find_items(items)?),
+            Schema::Sum(items) => Node::Sum(self.find_items(items)?),
+            Schema::Struct(fields) => Node::Struct(self.find_fields(fields)?),
+            Schema::Enum(fields) => Node::Enum(self.find_fields(fields)?),
+            Schema::Named(named) => {
+                Node::Named(*self.name_ids.get(&named.0)?, self.find(&named.1)?)
+            }
+            Schema::Seq(item) => Node::Seq(self.find(item)?),
+            Schema::Set(item) => Node::Set(self.find(item)?),
+            Schema::Map(key, value) => Node::Map(self.find(key)?, self.find(value)?),
+            Schema::Optional(item) => Node::Optional(self.find(item)?),
+            Schema::Tagged(tagging, item) => {
+                let tagging = match tagging {
+                    Tagging::Internal(tag) => NodeTagging::Internal(*self.name_ids.get(tag)?),
+                    Tagging::Adjacent(tag, content) => NodeTagging::Adjacent(
+                        *self.name_ids.get(tag)?,
+                        *self.name_ids.get(content)?,
+                    ),
+                    Tagging::Untagged => NodeTagging::Untagged,
+                };
+                Node::Tagged(tagging, self.find(item)?)
+            }
+            Schema::UnorderedSet(item) => Node::UnorderedSet(self.find(item)?),
+            Schema::UnorderedMap(key, value) => {
+                Node::UnorderedMap(self.find(key)?, self.find(value)?)
+            }
+            Schema::Enumeration(cases) => {
+                let ids = cases
+                    .iter()
+                    .map(|(name, tag)| Some((*self.name_ids.get(name)?, *tag)))
+                    .collect::<Option<Vec<_>>>()?;
+                Node::Enumeration(*self.case_spans.get(&ids)?)
+            }
+            Schema::Stream(item) => Node::Stream(self.find(item)?),
+        };
+        self.node_ids.get(&node).copied()
+    }
+
+    /// The node with the given id.
+    ///
+    /// Panics if the id does not belong to this arena.
+    pub fn node(&self, id: SchemaId) -> Node {
+        self.nodes[id.0 as usize]
+    }
+
+    /// The name with the given id.
+    pub fn name(&self, id: NameId) -> &str {
+        &self.names[id.0 as usize]
+    }
+
+    /// The tagging of a tagged node, with the names resolved.
+    pub fn tagging(&self, tagging: NodeTagging) -> Tagging {
+        match tagging {
+            NodeTagging::Internal(tag) => Tagging::Internal(self.name(tag).to_string()),
+            NodeTagging::Adjacent(tag, content) => {
+                Tagging::Adjacent(self.name(tag).to_string(), self.name(content).to_string())
+            }
+            NodeTagging::Untagged => Tagging::Untagged,
+        }
+    }
+
+    /// The items of a product or sum node.
+    pub fn items(&self, span: Span) -> &[SchemaId] {
+        &self.items[span.range()]
+    }
+
+    /// The fields of a struct or enum node.
+    pub fn fields(&self, span: Span) -> &[(NameId, SchemaId)] {
+        &self.fields[span.range()]
+    }
+
+    /// The cases of an enumeration node.
+    pub fn cases(&self, span: Span) -> &[(NameId, u32)] {
+        &self.cases[span.range()]
+    }
+
+    /// The stable hash of the schema with the given id, identical to
+    /// [`Schema::stable_hash`] of the rebuilt schema.
+    pub fn stable_hash(&self, id: SchemaId) -> blake3::Hash {
+        hash_postcard(&self.serializable(id))
+    }
+
+    /// The schema with the given id, serializing like the rebuilt schema.
+    pub fn serializable(&self, id: SchemaId) -> ArenaSchema<'_> {
+        ArenaSchema { arena: self, id }
+    }
+
+    /// The number of distinct nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the arena is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn intern_node(&mut self, node: Node) -> SchemaId {
+        if let Some(id) = self.node_ids.get(&node) {
+            return *id;
+        }
+        let id = SchemaId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        self.node_ids.insert(node, id);
+        id
+    }
+
+    fn intern_name(&mut self, name: &str) -> NameId {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+        let id = NameId(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn intern_tagging(&mut self, tagging: &Tagging) -> NodeTagging {
+        match tagging {
+            Tagging::Internal(tag) => NodeTagging::Internal(self.intern_name(tag)),
+            Tagging::Adjacent(tag, content) => {
+                NodeTagging::Adjacent(self.intern_name(tag), self.intern_name(content))
+            }
+            Tagging::Untagged => NodeTagging::Untagged,
+        }
+    }
+
+    fn insert_items(&mut self, items: &[Schema]) -> Span {
+        let ids = items
+            .iter()
+            .map(|item| self.insert(item))
+            .collect::<Vec<_>>();
+        if let Some(span) = self.item_spans.get(&ids) {
+            return *span;
+        }
+        let span = Span {
+            start: self.items.len() as u32,
+            len: ids.len() as u32,
+        };
+        self.items.extend_from_slice(&ids);
+        self.item_spans.insert(ids, span);
+        span
+    }
+
+    fn insert_fields(&mut self, fields: &[Named]) -> Span {
+        let ids = fields
+            .iter()
+            .map(|field| (self.intern_name(&field.0), self.insert(&field.1)))
+            .collect::<Vec<_>>();
+        if let Some(span) = self.field_spans.get(&ids) {
+            return *span;
+        }
+        let span = Span {
+            start: self.fields.len() as u32,
+            len: ids.len() as u32,
+        };
+        self.fields.extend_from_slice(&ids);
+        self.field_spans.insert(ids, span);
+        span
+    }
+
+    fn insert_cases(&mut self, cases: &[(String, u32)]) -> Span {
+        let ids = cases
+            .iter()
+            .map(|(name, tag)| (self.intern_name(name), *tag))
+            .collect::<Vec<_>>();
+        if let Some(span) = self.case_spans.get(&ids) {
+            return *span;
+        }
+        let span = Span {
+            start: self.cases.len() as u32,
+            len: ids.len() as u32,
+        };
+        self.cases.extend_from_slice(&ids);
+        self.case_spans.insert(ids, span);
+        span
+    }
+
+    fn find_items(&self, items: &[Schema]) -> Option<Span> {
+        let ids = items
+            .iter()
+            .map(|item| self.find(item))
+            .collect::<Option<Vec<_>>>()?;
+        self.item_spans.get(&ids).copied()
+    }
+
+    fn find_fields(&self, fields: &[Named]) -> Option<Span> {
+        let ids = fields
+            .iter()
+            .map(|field| Some((*self.name_ids.get(&field.0)?, self.find(&field.1)?)))
+            .collect::<Option<Vec<_>>>()?;
+        self.field_spans.get(&ids).copied()
+    }
+
+    fn get_items(&self, span: Span) -> Vec<Schema> {
+        self.items(span).iter().map(|id| self.get(*id)).collect()
+    }
+
+    fn get_fields(&self, span: Span) -> Vec<Named> {
+        self.fields(span)
+            .iter()
+            .map(|(name, id)| Named::new(self.name(*name), self.get(*id)))
+            .collect()
+    }
+}
+
+/// A schema in a [`SchemaArena`], serializing exactly like the [`Schema`] it
+/// stands for, without rebuilding it.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaSchema<'a> {
+    arena: &'a SchemaArena,
+    id: SchemaId,
+}
+
+impl<'a> ArenaSchema<'a> {
+    fn child(&self, id: SchemaId) -> ArenaSchema<'a> {
+        ArenaSchema {
+            arena: self.arena,
+            id,
+        }
+    }
+}
+
+/// Serializes like `Vec<Schema>`.
+struct Items<'a>(ArenaSchema<'a>, Span);
+
+impl Serialize for Items<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let items = self.0.arena.items(self.1);
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for id in items {
+            seq.serialize_element(&self.0.child(*id))?;
+        }
+        seq.end()
+    }
+}
+
+/// Serializes like `Named`.
+struct Field<'a>(ArenaSchema<'a>, NameId, SchemaId);
+
+impl Serialize for Field<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tup = serializer.serialize_tuple_struct("Named", 2)?;
+        tup.serialize_field(self.0.arena.name(self.1))?;
+        tup.serialize_field(&self.0.child(self.2))?;
+        tup.end()
+    }
+}
+
+/// Serializes like `Vec<Named>`.
+struct Fields<'a>(ArenaSchema<'a>, Span);
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = self.0.arena.fields(self.1);
+        let mut seq = serializer.serialize_seq(Some(fields.len()))?;
+        for (name, id) in fields {
+            seq.serialize_element(&Field(self.0, *name, *id))?;
+        }
+        seq.end()
+    }
+}
+
+/// Serializes like `Vec<(String, u32)>`.
+struct Cases<'a>(ArenaSchema<'a>, Span);
+
+impl Serialize for Cases<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let cases = self.0.arena.cases(self.1);
+        let mut seq = serializer.serialize_seq(Some(cases.len()))?;
+        for (name, tag) in cases {
+            seq.serialize_element(&(self.0.arena.name(*name), tag))?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for ArenaSchema<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // variant indices and names as in the derived impl of Schema
+        const NAME: &str = "Schema";
+        match self.arena.node(self.id) {
+            Node::Unit => serializer.serialize_unit_variant(NAME, 0, "Unit"),
+            Node::Bottom => serializer.serialize_unit_variant(NAME, 1, "Bottom"),
+            Node::Atom(name) => {
+                serializer.serialize_newtype_variant(NAME, 2, "Atom", self.arena.name(name))
+            }
+            Node::Product(span) => {
+                serializer.serialize_newtype_variant(NAME, 3, "Product", &Items(*self, span))
+            }
+            Node::Sum(span) => {
+                serializer.serialize_newtype_variant(NAME, 4, "Sum", &Items(*self, span))
+            }
+            Node::Struct(span) => {
+                serializer.serialize_newtype_variant(NAME, 5, "Struct", &Fields(*self, span))
+            }
+            Node::Enum(span) => {
+                serializer.serialize_newtype_variant(NAME, 6, "Enum", &Fields(*self, span))
+            }
+            Node::Named(name, id) => {
+                serializer.serialize_newtype_variant(NAME, 7, "Named", &Field(*self, name, id))
+            }
+            Node::Seq(id) => serializer.serialize_newtype_variant(NAME, 8, "Seq", &self.child(id)),
+            Node::Set(id) => serializer.serialize_newtype_variant(NAME, 9, "Set", &self.child(id)),
+            Node::Map(key, value) => {
+                let mut tup = serializer.serialize_tuple_variant(NAME, 10, "Map", 2)?;
+                tup.serialize_field(&self.child(key))?;
+                tup.serialize_field(&self.child(value))?;
+                tup.end()
+            }
+            Node::Optional(id) => {
+                serializer.serialize_newtype_variant(NAME, 11, "Optional", &self.child(id))
+            }
+            Node::Tagged(tagging, id) => {
+                let mut tup = serializer.serialize_tuple_variant(NAME, 12, "Tagged", 2)?;
+                tup.serialize_field(&self.arena.tagging(tagging))?;
+                tup.serialize_field(&self.child(id))?;
+                tup.end()
+            }
+            Node::UnorderedSet(id) => {
+                serializer.serialize_newtype_variant(NAME, 13, "UnorderedSet", &self.child(id))
+            }
+            Node::UnorderedMap(key, value) => {
+                let mut tup = serializer.serialize_tuple_variant(NAME, 14, "UnorderedMap", 2)?;
+                tup.serialize_field(&self.child(key))?;
+                tup.serialize_field(&self.child(value))?;
+                tup.end()
+            }
+            Node::Enumeration(span) => {
+                serializer.serialize_newtype_variant(NAME, 15, "Enumeration", &Cases(*self, span))
+            }
+            Node::Stream(id) => {
+                serializer.serialize_newtype_variant(NAME, 16, "Stream", &self.child(id))
+            }
+        }
+    }
+}
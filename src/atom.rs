@@ -0,0 +1,156 @@
+//! Wrappers that describe a value as an opaque atom.
+//!
+//! [`AtomOf<T, N>`] serializes exactly like `T`, but its schema is the atom
+//! named by `N`, no matter what the schema of `T` is. This hides the structure
+//! of a subtree from the hash, e.g. for a type whose encoding is stable but
+//! whose schema is not, or for a type without a schema at all:
+//!
+//! ```
+//! use irpc_schema::{atom::{AtomName, AtomOf}, schema, HasSchema, Schema};
+//!
+//! struct Uuid;
+//!
+//! impl AtomName for Uuid {
+//!     const NAME: &'static str = "Uuid";
+//! }
+//!
+//! #[schema(Nominal)]
+//! struct User {
+//!     id: AtomOf<[u8; 16], Uuid>,
+//! }
+//!
+//! assert_eq!(
+//!     <AtomOf<[u8; 16], Uuid>>::schema(),
+//!     Schema::Atom("Uuid".to_string())
+//! );
+//! ```
+//!
+//! Names are given by a marker type, since string const generics are not
+//! stable yet.
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{HasSchema, Schema};
+
+/// The name of the atom of an [`AtomOf`].
+pub trait AtomName {
+    /// The atom name.
+    const NAME: &'static str;
+}
+
+/// A `T` whose schema is the atom [`N::NAME`](AtomName::NAME), see the
+/// [module docs](self).
+///
+/// All traits are implemented without bounds on `N`, so any marker type works.
+#[repr(transparent)]
+pub struct AtomOf<T, N> {
+    /// The wrapped value.
+    pub value: T,
+    name: PhantomData<fn() -> N>,
+}
+
+impl<T, N> AtomOf<T, N> {
+    /// Wraps a value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            name: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, N: AtomName> HasSchema for AtomOf<T, N> {
+    fn schema() -> Schema {
+        Schema::Atom(N::NAME.to_string())
+    }
+}
+
+impl<T: Serialize, N> Serialize for AtomOf<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, N> Deserialize<'de> for AtomOf<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl<T, N> From<T> for AtomOf<T, N> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, N> Deref for AtomOf<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, N> DerefMut for AtomOf<T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug, N> fmt::Debug for AtomOf<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: Clone, N> Clone for AtomOf<T, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T: Copy, N> Copy for AtomOf<T, N> {}
+
+impl<T: Default, N> Default for AtomOf<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: PartialEq, N> PartialEq for AtomOf<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, N> Eq for AtomOf<T, N> {}
+
+impl<T: PartialOrd, N> PartialOrd for AtomOf<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord, N> Ord for AtomOf<T, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: Hash, N> Hash for AtomOf<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
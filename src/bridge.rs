@@ -0,0 +1,170 @@
+//! Bridging between two versions of a protocol.
+//!
+//! A [`VersionBridge`] holds the manifests of an old and a new version. Messages
+//! of the new version pass through unchanged. Messages of the old version are
+//! decoded dynamically, migrated to the schema of the message with the same
+//! name in the new version, see [`migrate_value`], and encoded again. So a server
+//! only needs handlers for the new version while old clients are still around.
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    codec::{decode_postcard, encode_postcard, DecodeError, EncodeError},
+    diff::{diff, SchemaDiff},
+    manifest::SchemaManifest,
+    migrate::{migrate_value, MigrationError},
+    service::request_schema,
+    telemetry::{report_unknown_hash, UnknownHash},
+    Schema, SchemaError,
+};
+
+/// Errors when bridging a message.
+#[derive(Debug)]
+pub enum BridgeError {
+    /// The message is too short to contain a hash, or the hash is in neither
+    /// manifest.
+    Schema(SchemaError),
+    /// The message of the old version has no counterpart in the new version.
+    Removed { name: String },
+    /// The old message could not be decoded.
+    Decode(DecodeError),
+    /// The old message could not be migrated.
+    Migrate(MigrationError),
+    /// The migrated message could not be encoded.
+    Encode(EncodeError),
+    /// The message could not be decoded as the new message type.
+    Deserialize(postcard::Error),
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeError::Schema(e) => e.fmt(f),
+            BridgeError::Removed { name } => write!(f, "message {} was removed", name),
+            BridgeError::Decode(e) => write!(f, "failed to decode: {}", e),
+            BridgeError::Migrate(e) => write!(f, "failed to migrate: {}", e),
+            BridgeError::Encode(e) => write!(f, "failed to encode: {}", e),
+            BridgeError::Deserialize(e) => write!(f, "failed to deserialize: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BridgeError::Schema(e) => Some(e),
+            BridgeError::Decode(e) => Some(e),
+            BridgeError::Migrate(e) => Some(e),
+            BridgeError::Encode(e) => Some(e),
+            BridgeError::Deserialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// How to bring a message of the old version to the new version.
+#[derive(Debug, Clone)]
+struct Route {
+    name: String,
+    old: Schema,
+    /// The new payload schema and hash, if the message still exists.
+    new: Option<(Schema, [u8; 32])>,
+    diff: SchemaDiff,
+}
+
+/// Converts messages of an old protocol version to the new version.
+#[derive(Debug, Clone)]
+pub struct VersionBridge {
+    old: SchemaManifest,
+    new: SchemaManifest,
+    routes: BTreeMap<[u8; 32], Route>,
+}
+
+impl VersionBridge {
+    /// Creates a bridge, matching messages of both versions by name.
+    ///
+    /// Manifests of `serialize_service` enums are supported as well, only the
+    /// message part of their schemas is migrated.
+    pub fn new(old: SchemaManifest, new: SchemaManifest) -> Self {
+        let mut routes = BTreeMap::new();
+        for entry in &old.messages {
+            if new.get_by_hash(&entry.hash).is_some() {
+                continue;
+            }
+            let old_schema = request_schema(&entry.schema).clone();
+            let new_entry = new
+                .get(&entry.name)
+                .map(|e| (request_schema(&e.schema).clone(), e.hash));
+            let diff = match &new_entry {
+                Some((new_schema, _)) => diff(&old_schema, new_schema),
+                None => SchemaDiff::default(),
+            };
+            routes.insert(
+                entry.hash,
+                Route {
+                    name: entry.name.clone(),
+                    old: old_schema,
+                    new: new_entry,
+                    diff,
+                },
+            );
+        }
+        Self { old, new, routes }
+    }
+
+    /// The manifest of the old version.
+    pub fn old_manifest(&self) -> &SchemaManifest {
+        &self.old
+    }
+
+    /// The manifest of the new version.
+    pub fn new_manifest(&self) -> &SchemaManifest {
+        &self.new
+    }
+
+    /// The diff from the old to the new version of a message, by old hash.
+    ///
+    /// Returns `None` for hashes that don't need a migration.
+    pub fn diff(&self, hash: &[u8; 32]) -> Option<&SchemaDiff> {
+        self.routes.get(hash).map(|route| &route.diff)
+    }
+
+    /// Converts a message to the new version.
+    ///
+    /// Messages of the new version are returned unchanged.
+    pub fn translate<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, [u8]>, BridgeError> {
+        let hash: [u8; 32] = bytes
+            .get(..32)
+            .ok_or(BridgeError::Schema(SchemaError::MissingHash))?
+            .try_into()
+            .unwrap();
+        if self.new.get_by_hash(&hash).is_some() {
+            return Ok(Cow::Borrowed(bytes));
+        }
+        let Some(route) = self.routes.get(&hash) else {
+            report_unknown_hash(UnknownHash {
+                hash,
+                nearest: None,
+                len: Some(bytes.len()),
+            });
+            return Err(BridgeError::Schema(SchemaError::UnknownHash { hash }));
+        };
+        let (new_schema, new_hash) = route.new.as_ref().ok_or_else(|| BridgeError::Removed {
+            name: route.name.clone(),
+        })?;
+        let value = decode_postcard(&route.old, &bytes[32..]).map_err(BridgeError::Decode)?;
+        let value = migrate_value(value, &route.diff).map_err(BridgeError::Migrate)?;
+        let payload = encode_postcard(new_schema, &value).map_err(BridgeError::Encode)?;
+        let mut res = new_hash.to_vec();
+        res.extend_from_slice(&payload);
+        Ok(Cow::Owned(res))
+    }
+
+    /// Converts a message to the new version and decodes it as `T`, the
+    /// `serialize_stable` or `serialize_service` enum of the new version.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, BridgeError> {
+        let bytes = self.translate(bytes)?;
+        postcard::from_bytes(&bytes).map_err(BridgeError::Deserialize)
+    }
+}
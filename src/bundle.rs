@@ -0,0 +1,747 @@
+//! Compact binary bundles containing many schemas.
+//!
+//! A bundle (conventionally stored with the `.isb` extension) contains a set of
+//! schemas together with an index sorted by stable hash. Strings and identical
+//! subtrees are stored only once, so a registry with thousands of message types
+//! that share most of their building blocks stays small. The string and node
+//! data is deflate compressed on top of that, in independent chunks.
+//!
+//! All tables in a bundle are fixed width and uncompressed, so a
+//! [`BundleReader`] can look up a single schema by hash without parsing
+//! anything else. A lookup only decompresses the chunks containing the strings
+//! and nodes it reads, and schemas are only materialized when they are
+//! requested. With the `mmap` feature, a bundle can be read directly from a
+//! memory-mapped file.
+//!
+//! # Layout
+//!
+//! All integers in the header and tables are little endian `u32`.
+//!
+//! ```text
+//! header:       magic "ISB\0", version, entry count, string count, node count, chunk length, chunk count
+//! entries:      entry count * (hash: [u8; 32], name: string index, root: node index)
+//! string table: (string count + 1) offsets into string data
+//! node table:   (node count + 1) offsets into node data
+//! chunk table:  (chunk count + 1) offsets into chunk data
+//! chunk data:   chunk count deflate compressed chunks
+//! ```
+//!
+//! The string data are utf8 strings, and the node data postcard encoded nodes,
+//! each back to back. The node data follows the string data, and together they
+//! are split into chunks of chunk length bytes, except for the last chunk,
+//! which may be shorter. Each chunk is compressed on its own. Entries are
+//! sorted by hash. Nodes refer to strings and to other nodes by index, and a
+//! node only ever refers to nodes with a smaller index.
+use std::{
+    cell::{Cell, RefCell},
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fmt,
+    io::{self, Read, Write},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{Named, Schema, Tagging};
+
+/// Magic bytes at the start of every bundle.
+pub const MAGIC: [u8; 4] = *b"ISB\0";
+
+/// The bundle format version written by [`BundleBuilder`].
+pub const VERSION: u32 = 1;
+
+/// The maximum number of nodes of a schema in a bundle.
+///
+/// Nodes are shared in a bundle, but expanded at every reference when a schema
+/// is decoded, so a small bundle can describe a huge schema.
+pub const MAX_NODES: usize = 1 << 20;
+
+/// The maximum nesting depth of a schema in a bundle.
+pub const MAX_DEPTH: usize = 128;
+
+/// The uncompressed length of the chunks written by [`BundleBuilder`].
+///
+/// This is also the maximum chunk length a [`BundleReader`] accepts, which
+/// bounds the memory a single chunk can take when it is decompressed.
+pub const CHUNK_LEN: usize = 1 << 14;
+
+const HEADER_LEN: usize = 28;
+const ENTRY_LEN: usize = 40;
+
+/// Errors that can occur when writing or reading a bundle.
+#[derive(Debug)]
+pub enum BundleError {
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    /// The bundle was written with an unsupported format version.
+    UnsupportedVersion(u32),
+    /// The data is shorter than the header or tables claim.
+    Truncated,
+    /// The data is structurally invalid.
+    Corrupt(&'static str),
+    /// A schema or the bundle exceeds the limits of the format.
+    TooLarge(&'static str),
+    /// An io error occurred while opening a bundle file.
+    Io(io::Error),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::BadMagic => write!(f, "not a schema bundle"),
+            BundleError::UnsupportedVersion(v) => write!(f, "unsupported bundle version {}", v),
+            BundleError::Truncated => write!(f, "truncated bundle"),
+            BundleError::Corrupt(msg) => write!(f, "corrupt bundle: {}", msg),
+            BundleError::TooLarge(msg) => write!(f, "bundle too large: {}", msg),
+            BundleError::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BundleError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BundleError {
+    fn from(e: io::Error) -> Self {
+        BundleError::Io(e)
+    }
+}
+
+/// A single schema node, with children replaced by indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Node {
+    Unit,
+    Bottom,
+    Atom(u32),
+    Product(Vec<u32>),
+    Sum(Vec<u32>),
+    Struct(Vec<(u32, u32)>),
+    Enum(Vec<(u32, u32)>),
+    Named(u32, u32),
+    Seq(u32),
+    Set(u32),
+    Map(u32, u32),
+    Optional(u32),
+    Tagged(TaggingNode, u32),
+    UnorderedSet(u32),
+    UnorderedMap(u32, u32),
+    Enumeration(Vec<(u32, u32)>),
+    Stream(u32),
+}
+
+/// A [`Tagging`], with names replaced by string indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum TaggingNode {
+    Internal(u32),
+    Adjacent(u32, u32),
+    Untagged,
+}
+
+/// Builds a bundle from a set of schemas.
+#[derive(Debug, Default)]
+pub struct BundleBuilder {
+    strings: Vec<String>,
+    string_ids: HashMap<String, u32>,
+    nodes: Vec<Node>,
+    node_ids: HashMap<Node, u32>,
+    entries: BTreeMap<[u8; 32], (u32, u32)>,
+}
+
+impl BundleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a schema under the given name and returns its stable hash.
+    ///
+    /// If a schema with the same hash was already added, the bundle keeps the
+    /// name it was first added with. Fails if the schema exceeds [`MAX_NODES`]
+    /// or [`MAX_DEPTH`], so it could not be read back.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        schema: &Schema,
+    ) -> Result<[u8; 32], BundleError> {
+        let mut budget = MAX_NODES;
+        check_size(schema, 0, &mut budget)?;
+        let hash = *schema.stable_hash().as_bytes();
+        if !self.entries.contains_key(&hash) {
+            let name = self.string(name.into());
+            let root = self.node(schema);
+            self.entries.insert(hash, (name, root));
+        }
+        Ok(hash)
+    }
+
+    /// Number of distinct schemas added so far.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn string(&mut self, text: String) -> u32 {
+        if let Some(id) = self.string_ids.get(&text) {
+            return *id;
+        }
+        let id = self.strings.len() as u32;
+        self.strings.push(text.clone());
+        self.string_ids.insert(text, id);
+        id
+    }
+
+    fn node(&mut self, schema: &Schema) -> u32 {
+        let node = match schema {
+            Schema::Unit => Node::Unit,
+            Schema::Bottom => Node::Bottom,
+            Schema::Atom(name) => Node::Atom(self.string(name.clone())),
+            Schema::Product(items) => Node::Product(items.iter().map(|s| self.node(s)).collect()),
+            Schema::Sum(items) => Node::Sum(items.iter().map(|s| self.node(s)).collect()),
+            Schema::Struct(fields) => Node::Struct(fields.iter().map(|n| self.named(n)).collect()),
+            Schema::Enum(cases) => Node::Enum(cases.iter().map(|n| self.named(n)).collect()),
+            Schema::Named(named) => {
+                let (name, inner) = self.named(named);
+                Node::Named(name, inner)
+            }
+            Schema::Seq(item) => Node::Seq(self.node(item)),
+            Schema::Set(item) => Node::Set(self.node(item)),
+            Schema::Map(key, value) => Node::Map(self.node(key), self.node(value)),
+            Schema::Optional(item) => Node::Optional(self.node(item)),
+            Schema::Tagged(tagging, item) => {
+                let tagging = match tagging {
+                    Tagging::Internal(tag) => TaggingNode::Internal(self.string(tag.clone())),
+                    Tagging::Adjacent(tag, content) => TaggingNode::Adjacent(
+                        self.string(tag.clone()),
+                        self.string(content.clone()),
+                    ),
+                    Tagging::Untagged => TaggingNode::Untagged,
+                };
+                Node::Tagged(tagging, self.node(item))
+            }
+            Schema::UnorderedSet(item) => Node::UnorderedSet(self.node(item)),
+            Schema::UnorderedMap(key, value) => {
+                Node::UnorderedMap(self.node(key), self.node(value))
+            }
+            Schema::Enumeration(cases) => Node::Enumeration(
+                cases
+                    .iter()
+                    .map(|(name, tag)| (self.string(name.clone()), *tag))
+                    .collect(),
+            ),
+            Schema::Stream(item) => Node::Stream(self.node(item)),
+        };
+        if let Some(id) = self.node_ids.get(&node) {
+            return *id;
+        }
+        let id = self.nodes.len() as u32;
+        self.nodes.push(node.clone());
+        self.node_ids.insert(node, id);
+        id
+    }
+
+    fn named(&mut self, named: &Named) -> (u32, u32) {
+        (self.string(named.0.clone()), self.node(&named.1))
+    }
+
+    /// Serializes the bundle.
+    ///
+    /// Fails if the tables or the data do not fit the `u32` fields of the format.
+    pub fn finish(self) -> Result<Vec<u8>, BundleError> {
+        let encoded_nodes = self
+            .nodes
+            .iter()
+            .map(|node| postcard::to_allocvec(node).expect("node encoding is infallible"))
+            .collect::<Vec<_>>();
+        let mut data = Vec::new();
+        for text in &self.strings {
+            data.extend_from_slice(text.as_bytes());
+        }
+        for node in &encoded_nodes {
+            data.extend_from_slice(node);
+        }
+        let chunks = data
+            .chunks(CHUNK_LEN)
+            .map(|chunk| {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+                encoder
+                    .write_all(chunk)
+                    .and_then(|_| encoder.finish())
+                    .expect("writing to a vec is infallible")
+            })
+            .collect::<Vec<_>>();
+        let mut res = Vec::new();
+        res.extend_from_slice(&MAGIC);
+        for value in [
+            VERSION,
+            to_u32(self.entries.len(), "too many entries")?,
+            to_u32(self.strings.len(), "too many strings")?,
+            to_u32(self.nodes.len(), "too many nodes")?,
+            CHUNK_LEN as u32,
+            to_u32(chunks.len(), "too many chunks")?,
+        ] {
+            res.extend_from_slice(&value.to_le_bytes());
+        }
+        for (hash, (name, root)) in &self.entries {
+            res.extend_from_slice(hash);
+            res.extend_from_slice(&name.to_le_bytes());
+            res.extend_from_slice(&root.to_le_bytes());
+        }
+        write_offsets(
+            &mut res,
+            self.strings.iter().map(|s| s.len()),
+            "string data too long",
+        )?;
+        write_offsets(
+            &mut res,
+            encoded_nodes.iter().map(|n| n.len()),
+            "node data too long",
+        )?;
+        to_u32(data.len(), "data too long")?;
+        write_offsets(
+            &mut res,
+            chunks.iter().map(|c| c.len()),
+            "chunk data too long",
+        )?;
+        for chunk in &chunks {
+            res.extend_from_slice(chunk);
+        }
+        Ok(res)
+    }
+}
+
+/// Checks that `schema` does not exceed [`MAX_DEPTH`], and takes its nodes
+/// from `budget`.
+fn check_size(schema: &Schema, depth: usize, budget: &mut usize) -> Result<(), BundleError> {
+    if depth >= MAX_DEPTH {
+        return Err(BundleError::TooLarge("schema nested too deeply"));
+    }
+    *budget = budget
+        .checked_sub(1)
+        .ok_or(BundleError::TooLarge("schema has too many nodes"))?;
+    let mut child = |child: &Schema| check_size(child, depth + 1, budget);
+    match schema {
+        Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => Ok(()),
+        Schema::Product(items) | Schema::Sum(items) => items.iter().try_for_each(child),
+        Schema::Struct(fields) | Schema::Enum(fields) => {
+            fields.iter().try_for_each(|field| child(&field.1))
+        }
+        Schema::Named(named) => child(&named.1),
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::UnorderedSet(item)
+        | Schema::Optional(item)
+        | Schema::Tagged(_, item)
+        | Schema::Stream(item) => child(item),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            child(key)?;
+            child(value)
+        }
+    }
+}
+
+fn to_u32(value: usize, what: &'static str) -> Result<u32, BundleError> {
+    u32::try_from(value).map_err(|_| BundleError::TooLarge(what))
+}
+
+fn write_offsets(
+    res: &mut Vec<u8>,
+    lens: impl Iterator<Item = usize>,
+    what: &'static str,
+) -> Result<(), BundleError> {
+    let mut offset = 0u32;
+    res.extend_from_slice(&offset.to_le_bytes());
+    for len in lens {
+        offset = to_u32(len, what)?
+            .checked_add(offset)
+            .ok_or(BundleError::TooLarge(what))?;
+        res.extend_from_slice(&offset.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Read access to a bundle.
+///
+/// Only the header is validated on construction. Lookups decode just the parts
+/// of the bundle they need, so this works well on top of a memory-mapped file.
+#[derive(Debug)]
+pub struct BundleReader<B> {
+    data: B,
+    entry_count: usize,
+    string_count: usize,
+    node_count: usize,
+    strings_table: usize,
+    nodes_table: usize,
+    chunks_table: usize,
+    /// Offset of the chunk data.
+    chunk_data: usize,
+    chunk_len: usize,
+    chunk_count: usize,
+    /// Length of the uncompressed string data, where the node data starts.
+    node_data: usize,
+    /// Length of the uncompressed string and node data.
+    data_len: usize,
+}
+
+/// The chunks decompressed during a lookup, by chunk index.
+type Chunks = RefCell<HashMap<usize, Vec<u8>>>;
+
+impl<B: AsRef<[u8]>> BundleReader<B> {
+    /// Creates a reader for the given bundle bytes.
+    pub fn new(data: B) -> Result<Self, BundleError> {
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_LEN {
+            return Err(if bytes.starts_with(&MAGIC) || MAGIC.starts_with(bytes) {
+                BundleError::Truncated
+            } else {
+                BundleError::BadMagic
+            });
+        }
+        if bytes[..4] != MAGIC {
+            return Err(BundleError::BadMagic);
+        }
+        let version = read_u32(bytes, 4)?;
+        if version != VERSION {
+            return Err(BundleError::UnsupportedVersion(version));
+        }
+        let entry_count = read_u32(bytes, 8)? as usize;
+        let string_count = read_u32(bytes, 12)? as usize;
+        let node_count = read_u32(bytes, 16)? as usize;
+        let chunk_len = read_u32(bytes, 20)? as usize;
+        let chunk_count = read_u32(bytes, 24)? as usize;
+        let table = |start: usize, count: usize| {
+            (count + 1)
+                .checked_mul(4)
+                .and_then(|n| n.checked_add(start))
+                .ok_or(BundleError::Truncated)
+        };
+        let strings_table = entry_count
+            .checked_mul(ENTRY_LEN)
+            .and_then(|n| n.checked_add(HEADER_LEN))
+            .ok_or(BundleError::Truncated)?;
+        let nodes_table = table(strings_table, string_count)?;
+        let chunks_table = table(nodes_table, node_count)?;
+        let chunk_data = table(chunks_table, chunk_count)?;
+        let end = chunk_data
+            .checked_add(read_u32(bytes, chunk_data - 4)? as usize)
+            .ok_or(BundleError::Truncated)?;
+        if bytes.len() < end {
+            return Err(BundleError::Truncated);
+        }
+        let node_data = read_u32(bytes, nodes_table - 4)? as usize;
+        let data_len = node_data
+            .checked_add(read_u32(bytes, chunks_table - 4)? as usize)
+            .ok_or(BundleError::Corrupt("data too large"))?;
+        if chunk_len > CHUNK_LEN {
+            return Err(BundleError::Corrupt("chunk length too large"));
+        }
+        if chunk_len == 0 || chunk_count != data_len.div_ceil(chunk_len) {
+            return Err(BundleError::Corrupt("chunks do not cover the data"));
+        }
+        Ok(Self {
+            data,
+            entry_count,
+            string_count,
+            node_count,
+            strings_table,
+            nodes_table,
+            chunks_table,
+            chunk_data,
+            chunk_len,
+            chunk_count,
+            node_data,
+            data_len,
+        })
+    }
+
+    /// Returns the underlying bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// Number of schemas in the bundle.
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Iterates over the hashes of all schemas in the bundle, in sorted order.
+    pub fn hashes(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        (0..self.entry_count).map(move |i| self.entry_hash(i))
+    }
+
+    /// Returns true if the bundle contains a schema with the given hash.
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.find(hash).is_some()
+    }
+
+    /// Returns the name the schema with the given hash was added under.
+    pub fn name(&self, hash: &[u8; 32]) -> Result<Option<String>, BundleError> {
+        let Some(i) = self.find(hash) else {
+            return Ok(None);
+        };
+        let (name, _) = self.entry_refs(i)?;
+        self.string(name, &Chunks::default()).map(Some)
+    }
+
+    /// Decodes the schema with the given hash.
+    ///
+    /// Fails if the decoded schema does not have the hash it is stored under,
+    /// or exceeds [`MAX_NODES`] or [`MAX_DEPTH`].
+    pub fn get(&self, hash: &[u8; 32]) -> Result<Option<Schema>, BundleError> {
+        let Some(i) = self.find(hash) else {
+            return Ok(None);
+        };
+        let (_, root) = self.entry_refs(i)?;
+        self.root_schema(root, hash, &Chunks::default()).map(Some)
+    }
+
+    /// Decodes all entries of the bundle, as `(name, schema, hash)` triples.
+    pub fn entries(
+        &self,
+    ) -> impl Iterator<Item = Result<(String, Schema, [u8; 32]), BundleError>> + '_ {
+        let chunks = Chunks::default();
+        (0..self.entry_count).map(move |i| {
+            let hash = self.entry_hash(i);
+            let (name, root) = self.entry_refs(i)?;
+            let name = self.string(name, &chunks)?;
+            let schema = self.root_schema(root, &hash, &chunks)?;
+            Ok((name, schema, hash))
+        })
+    }
+
+    fn entry_hash(&self, i: usize) -> [u8; 32] {
+        let start = HEADER_LEN + i * ENTRY_LEN;
+        self.as_bytes()[start..start + 32].try_into().unwrap()
+    }
+
+    fn entry_refs(&self, i: usize) -> Result<(u32, u32), BundleError> {
+        let start = HEADER_LEN + i * ENTRY_LEN + 32;
+        Ok((
+            read_u32(self.as_bytes(), start)?,
+            read_u32(self.as_bytes(), start + 4)?,
+        ))
+    }
+
+    fn find(&self, hash: &[u8; 32]) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.entry_count);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.entry_hash(mid).cmp(hash) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    fn string(&self, id: u32, chunks: &Chunks) -> Result<String, BundleError> {
+        let bytes = self.item(
+            id,
+            self.string_count,
+            self.strings_table,
+            0,
+            "string index out of range",
+            chunks,
+        )?;
+        String::from_utf8(bytes).map_err(|_| BundleError::Corrupt("invalid utf8 in string"))
+    }
+
+    fn item(
+        &self,
+        id: u32,
+        count: usize,
+        table: usize,
+        data: usize,
+        msg: &'static str,
+        chunks: &Chunks,
+    ) -> Result<Vec<u8>, BundleError> {
+        let id = id as usize;
+        if id >= count {
+            return Err(BundleError::Corrupt(msg));
+        }
+        let bytes = self.as_bytes();
+        let start = data + read_u32(bytes, table + id * 4)? as usize;
+        let end = data + read_u32(bytes, table + id * 4 + 4)? as usize;
+        if start > end {
+            return Err(BundleError::Corrupt("offsets not monotonic"));
+        }
+        if end > self.data_len {
+            return Err(BundleError::Corrupt("offset out of range"));
+        }
+        // the offsets are not trusted yet, so let the result grow as data arrives
+        let mut res = Vec::with_capacity((end - start).min(CHUNK_LEN));
+        let mut pos = start;
+        while pos < end {
+            let index = pos / self.chunk_len;
+            let chunk_start = index * self.chunk_len;
+            let mut chunks = chunks.borrow_mut();
+            let chunk = match chunks.entry(index) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.decompress(index)?),
+            };
+            let chunk_end = end.min(chunk_start + chunk.len());
+            res.extend_from_slice(&chunk[pos - chunk_start..chunk_end - chunk_start]);
+            pos = chunk_end;
+        }
+        Ok(res)
+    }
+
+    /// Decompresses chunk `index`.
+    fn decompress(&self, index: usize) -> Result<Vec<u8>, BundleError> {
+        debug_assert!(index < self.chunk_count);
+        let bytes = self.as_bytes();
+        let start = read_u32(bytes, self.chunks_table + index * 4)? as usize;
+        let end = read_u32(bytes, self.chunks_table + index * 4 + 4)? as usize;
+        if start > end {
+            return Err(BundleError::Corrupt("offsets not monotonic"));
+        }
+        let compressed = bytes
+            .get(self.chunk_data + start..self.chunk_data + end)
+            .ok_or(BundleError::Corrupt("offset out of range"))?;
+        let len = self.chunk_len.min(self.data_len - index * self.chunk_len);
+        let mut res = Vec::with_capacity(len);
+        DeflateDecoder::new(compressed)
+            .take(len as u64 + 1)
+            .read_to_end(&mut res)
+            .map_err(|_| BundleError::Corrupt("invalid compressed data"))?;
+        if res.len() != len {
+            return Err(BundleError::Corrupt("compressed data has the wrong length"));
+        }
+        Ok(res)
+    }
+
+    /// Decodes the schema of an entry and checks its hash.
+    fn root_schema(
+        &self,
+        root: u32,
+        hash: &[u8; 32],
+        chunks: &Chunks,
+    ) -> Result<Schema, BundleError> {
+        let schema = self.schema(root, u32::MAX, 0, &Cell::new(MAX_NODES), chunks)?;
+        if schema.stable_hash().as_bytes() != hash {
+            return Err(BundleError::Corrupt("schema does not match its hash"));
+        }
+        Ok(schema)
+    }
+
+    /// Decodes node `id`, which must be smaller than `parent`.
+    ///
+    /// `budget` is the number of nodes that may still be decoded.
+    fn schema(
+        &self,
+        id: u32,
+        parent: u32,
+        depth: usize,
+        budget: &Cell<usize>,
+        chunks: &Chunks,
+    ) -> Result<Schema, BundleError> {
+        if id >= parent {
+            return Err(BundleError::Corrupt("node refers forward"));
+        }
+        if depth >= MAX_DEPTH {
+            return Err(BundleError::Corrupt("schema nested too deeply"));
+        }
+        if budget.get() == 0 {
+            return Err(BundleError::Corrupt("schema has too many nodes"));
+        }
+        budget.set(budget.get() - 1);
+        let bytes = self.item(
+            id,
+            self.node_count,
+            self.nodes_table,
+            self.node_data,
+            "node index out of range",
+            chunks,
+        )?;
+        let node: Node =
+            postcard::from_bytes(&bytes).map_err(|_| BundleError::Corrupt("invalid node"))?;
+        let string = |id: u32| self.string(id, chunks);
+        let child = |child: u32| self.schema(child, id, depth + 1, budget, chunks);
+        let named = |(name, child): (u32, u32)| {
+            Ok::<_, BundleError>(Named(
+                string(name)?,
+                self.schema(child, id, depth + 1, budget, chunks)?,
+            ))
+        };
+        Ok(match node {
+            Node::Unit => Schema::Unit,
+            Node::Bottom => Schema::Bottom,
+            Node::Atom(name) => Schema::Atom(string(name)?),
+            Node::Product(items) => {
+                Schema::Product(items.into_iter().map(child).collect::<Result<_, _>>()?)
+            }
+            Node::Sum(items) => {
+                Schema::Sum(items.into_iter().map(child).collect::<Result<_, _>>()?)
+            }
+            Node::Struct(fields) => {
+                Schema::Struct(fields.into_iter().map(named).collect::<Result<_, _>>()?)
+            }
+            Node::Enum(cases) => {
+                Schema::Enum(cases.into_iter().map(named).collect::<Result<_, _>>()?)
+            }
+            Node::Named(name, inner) => Schema::Named(Box::new(named((name, inner))?)),
+            Node::Seq(item) => Schema::Seq(Box::new(child(item)?)),
+            Node::Set(item) => Schema::Set(Box::new(child(item)?)),
+            Node::Map(key, value) => Schema::Map(Box::new(child(key)?), Box::new(child(value)?)),
+            Node::Optional(item) => Schema::Optional(Box::new(child(item)?)),
+            Node::Tagged(tagging, item) => {
+                let tagging = match tagging {
+                    TaggingNode::Internal(tag) => Tagging::Internal(string(tag)?),
+                    TaggingNode::Adjacent(tag, content) => {
+                        Tagging::Adjacent(string(tag)?, string(content)?)
+                    }
+                    TaggingNode::Untagged => Tagging::Untagged,
+                };
+                Schema::Tagged(tagging, Box::new(child(item)?))
+            }
+            Node::UnorderedSet(item) => Schema::UnorderedSet(Box::new(child(item)?)),
+            Node::UnorderedMap(key, value) => {
+                Schema::UnorderedMap(Box::new(child(key)?), Box::new(child(value)?))
+            }
+            Node::Enumeration(cases) => Schema::Enumeration(
+                cases
+                    .into_iter()
+                    .map(|(name, tag)| Ok((string(name)?, tag)))
+                    .collect::<Result<_, BundleError>>()?,
+            ),
+            Node::Stream(item) => Schema::Stream(Box::new(child(item)?)),
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "mmap")))]
+impl BundleReader<memmap2::Mmap> {
+    /// Opens a bundle file by memory-mapping it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the reader is alive,
+    /// e.g. by another process. Modifying a mapped file is undefined behavior,
+    /// see [`memmap2::Mmap::map`].
+    pub unsafe fn open_mmap(path: impl AsRef<std::path::Path>) -> Result<Self, BundleError> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the caller guarantees that the file is not modified while it is mapped.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::new(mmap)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, BundleError> {
+    let slice = bytes
+        .get(offset..offset + 4)
+        .ok_or(BundleError::Truncated)?;
+    Ok(u32::from_le_bytes(slice.try_into().unwrap()))
+}
@@ -0,0 +1,70 @@
+//! Compact advertisement of supported methods.
+//!
+//! [`Capabilities`] is a set of truncated method hashes. It is much smaller
+//! than a [`ServiceDescriptor`] or a manifest, so lightweight peers can
+//! advertise what they support, e.g. in a handshake or a discovery record, and
+//! the other side can check individual methods with [`Capabilities::supports`].
+use serde::{Deserialize, Serialize};
+
+use crate::service::ServiceDescriptor;
+
+/// Number of bytes of each hash that are kept.
+///
+/// 8 bytes make accidental collisions between the methods of a service
+/// practically impossible.
+pub const PREFIX_LEN: usize = 8;
+
+/// A set of supported method hashes, truncated to [`PREFIX_LEN`] bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Sorted and deduplicated.
+    prefixes: Vec<[u8; PREFIX_LEN]>,
+}
+
+fn prefix(hash: &[u8; 32]) -> [u8; PREFIX_LEN] {
+    hash[..PREFIX_LEN].try_into().unwrap()
+}
+
+impl Capabilities {
+    /// Creates capabilities from full schema hashes.
+    pub fn from_hashes<'a>(hashes: impl IntoIterator<Item = &'a [u8; 32]>) -> Self {
+        Self::from_prefixes(hashes.into_iter().map(prefix).collect())
+    }
+
+    /// Creates capabilities for all methods of a service.
+    pub fn from_descriptor(descriptor: &ServiceDescriptor) -> Self {
+        Self::from_hashes(descriptor.methods.iter().map(|method| &method.hash))
+    }
+
+    /// True if the method or message with the given schema hash is supported.
+    pub fn supports(&self, hash: &[u8; 32]) -> bool {
+        self.prefixes.binary_search(&prefix(hash)).is_ok()
+    }
+
+    /// The number of supported methods.
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Encodes the capabilities, a length followed by the prefixes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+
+    /// Decodes capabilities encoded with [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, postcard::Error> {
+        let res: Self = postcard::from_bytes(bytes)?;
+        // normalize, so queries work for capabilities from any encoder
+        Ok(Self::from_prefixes(res.prefixes))
+    }
+
+    fn from_prefixes(mut prefixes: Vec<[u8; PREFIX_LEN]>) -> Self {
+        prefixes.sort_unstable();
+        prefixes.dedup();
+        Self { prefixes }
+    }
+}
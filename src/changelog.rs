@@ -0,0 +1,206 @@
+//! Changelogs between two versions of a protocol.
+use std::fmt::Write;
+
+use crate::{
+    diff::{diff, Compat, SchemaDiff},
+    manifest::{ManifestEntry, SchemaManifest},
+    parallel,
+    policy::{EvolutionPolicy, PolicyViolation},
+};
+
+/// A message that kept its schema but changed its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRenamed {
+    pub old_name: String,
+    pub new_name: String,
+    pub hash: [u8; 32],
+}
+
+/// A message that kept its name but changed its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageChanged {
+    pub name: String,
+    pub old_hash: [u8; 32],
+    pub new_hash: [u8; 32],
+    pub diff: SchemaDiff,
+}
+
+/// Structured changelog between two manifests.
+///
+/// Messages are first matched by hash, then by name. Messages that can not be
+/// matched either way are reported as added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changelog {
+    /// Label of the old manifest.
+    pub from: String,
+    /// Label of the new manifest.
+    pub to: String,
+    pub added: Vec<ManifestEntry>,
+    pub removed: Vec<ManifestEntry>,
+    pub renamed: Vec<MessageRenamed>,
+    pub changed: Vec<MessageChanged>,
+}
+
+impl Changelog {
+    /// Computes the changelog from `old` to `new`.
+    pub fn new(old: &SchemaManifest, new: &SchemaManifest) -> Self {
+        let mut res = Changelog {
+            from: format!("{} {}", old.name, old.version),
+            to: format!("{} {}", new.name, new.version),
+            added: Vec::new(),
+            removed: Vec::new(),
+            renamed: Vec::new(),
+            changed: Vec::new(),
+        };
+        let mut old_rest = Vec::new();
+        for entry in &old.messages {
+            match new.get_by_hash(&entry.hash) {
+                Some(n) if n.name != entry.name => res.renamed.push(MessageRenamed {
+                    old_name: entry.name.clone(),
+                    new_name: n.name.clone(),
+                    hash: entry.hash,
+                }),
+                Some(_) => {}
+                None => old_rest.push(entry),
+            }
+        }
+        let new_rest = new
+            .messages
+            .iter()
+            .filter(|entry| old.get_by_hash(&entry.hash).is_none())
+            .collect::<Vec<_>>();
+        let mut changed = Vec::new();
+        for entry in &old_rest {
+            match new_rest.iter().find(|n| n.name == entry.name) {
+                Some(n) => changed.push((*entry, *n)),
+                None => res.removed.push((*entry).clone()),
+            }
+        }
+        res.changed = parallel::map(&changed, |(o, n)| MessageChanged {
+            name: o.name.clone(),
+            old_hash: o.hash,
+            new_hash: n.hash,
+            diff: diff(&o.schema, &n.schema),
+        });
+        for entry in new_rest {
+            if !old_rest.iter().any(|o| o.name == entry.name) {
+                res.added.push(entry.clone());
+            }
+        }
+        res
+    }
+
+    /// True if both manifests contain the same messages under the same names.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// The most severe classification of all changes.
+    ///
+    /// Removing a message is breaking, adding or renaming one is compatible.
+    pub fn compat(&self) -> Compat {
+        let removed = if self.removed.is_empty() {
+            Compat::Compatible
+        } else {
+            Compat::Breaking
+        };
+        self.changed
+            .iter()
+            .map(|c| c.diff.compat())
+            .fold(removed, Compat::max)
+    }
+
+    /// Checks the schema changes of all changed messages against a policy.
+    ///
+    /// Added, removed and renamed messages are not covered by policies, see
+    /// [`compat`](Self::compat) for them.
+    pub fn check(&self, policy: &EvolutionPolicy) -> Result<(), PolicyViolation> {
+        let changes = self
+            .changed
+            .iter()
+            .filter_map(|c| policy.check(&c.diff).err())
+            .flat_map(|violation| violation.changes)
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(PolicyViolation { changes })
+        }
+    }
+
+    /// Renders the changelog as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut res = String::new();
+        writeln!(res, "# Changes from {} to {}", self.from, self.to).unwrap();
+        writeln!(res).unwrap();
+        writeln!(res, "Overall compatibility: **{}**", self.compat()).unwrap();
+        if self.is_empty() {
+            writeln!(res).unwrap();
+            writeln!(res, "No changes.").unwrap();
+            return res;
+        }
+        if !self.added.is_empty() {
+            writeln!(res, "\n## Added messages\n").unwrap();
+            for entry in &self.added {
+                writeln!(res, "- `{}` ({})", entry.name, short_hash(&entry.hash)).unwrap();
+            }
+        }
+        if !self.removed.is_empty() {
+            writeln!(res, "\n## Removed messages\n").unwrap();
+            for entry in &self.removed {
+                writeln!(res, "- `{}` ({})", entry.name, short_hash(&entry.hash)).unwrap();
+            }
+        }
+        if !self.renamed.is_empty() {
+            writeln!(res, "\n## Renamed messages\n").unwrap();
+            for r in &self.renamed {
+                writeln!(
+                    res,
+                    "- `{}` → `{}` ({})",
+                    r.old_name,
+                    r.new_name,
+                    short_hash(&r.hash)
+                )
+                .unwrap();
+            }
+        }
+        if !self.changed.is_empty() {
+            writeln!(res, "\n## Changed messages").unwrap();
+            for c in &self.changed {
+                writeln!(
+                    res,
+                    "\n### `{}` ({} → {}, {})\n",
+                    c.name,
+                    short_hash(&c.old_hash),
+                    short_hash(&c.new_hash),
+                    c.diff.compat()
+                )
+                .unwrap();
+                writeln!(res, "| Path | Change | Compatibility |").unwrap();
+                writeln!(res, "| --- | --- | --- |").unwrap();
+                for change in &c.diff.changes {
+                    writeln!(
+                        res,
+                        "| `{}` | {} | {} |",
+                        escape_cell(&change.path.to_string()),
+                        escape_cell(&change.kind.to_string()),
+                        change.compat()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        res
+    }
+}
+
+fn short_hash(hash: &[u8; 32]) -> String {
+    blake3::Hash::from(*hash).to_hex()[..8].to_string()
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
@@ -0,0 +1,249 @@
+//! Well-formedness checks for schemas.
+//!
+//! Any value of [`Schema`] hashes fine, but not every value makes sense. A
+//! struct with two fields of the same name, or an enum with two variants of
+//! the same name, can not be exported to JSON, the text format or other
+//! languages without ambiguity. [`Schema::validate`] finds such problems, and
+//! [`Schema::struct_checked`] and [`Schema::enum_checked`] reject them at
+//! construction. All of them report an [`InvalidSchema`] wrapped in a
+//! [`SchemaError`].
+//!
+//! Names of atoms and nominal types end up in hashes and in every exported
+//! format, so they must be [valid names](is_valid_name) as well.
+//! [`Schema::atom_checked`] and [`Schema::named_checked`] reject invalid names
+//! at construction, and [`sanitize_name`] turns arbitrary text into a valid
+//! name.
+//!
+//! The derive rejects invalid explicit names at compile time:
+//!
+//! ```compile_fail
+//! use irpc_schema::schema;
+//!
+//! #[schema(Nominal(name = ""))]
+//! struct Anonymous;
+//! ```
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    diff::{Path, PathSegment},
+    Named, Schema, SchemaError,
+};
+
+/// The kind of problem found by [`Schema::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A struct has two fields with the same name.
+    DuplicateField(String),
+    /// An enum has two variants with the same name.
+    DuplicateVariant(String),
+    /// An enumeration has two variants with the same discriminant.
+    DuplicateDiscriminant(u32),
+    /// A nominal type name violates the [naming rules](crate::naming).
+    TypeNaming(String),
+    /// A field name violates the [naming rules](crate::naming).
+    FieldNaming(String),
+    /// A variant name violates the [naming rules](crate::naming).
+    VariantNaming(String),
+    /// The name of an atom or nominal type is not [valid](is_valid_name).
+    InvalidName(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DuplicateField(name) => write!(f, "duplicate field {}", name),
+            Problem::DuplicateVariant(name) => write!(f, "duplicate variant {}", name),
+            Problem::DuplicateDiscriminant(tag) => write!(f, "duplicate discriminant {}", tag),
+            Problem::TypeNaming(name) => write!(f, "type name {} violates the naming rules", name),
+            Problem::FieldNaming(name) => {
+                write!(f, "field name {} violates the naming rules", name)
+            }
+            Problem::VariantNaming(name) => {
+                write!(f, "variant name {} violates the naming rules", name)
+            }
+            Problem::InvalidName(name) => write!(f, "invalid name {:?}", name),
+        }
+    }
+}
+
+/// A schema is not well-formed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSchema {
+    /// Location of the offending node.
+    pub path: Path,
+    /// What is wrong with it.
+    pub problem: Problem,
+}
+
+impl fmt::Display for InvalidSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.problem)
+    }
+}
+
+impl std::error::Error for InvalidSchema {}
+
+/// True if the name can be used for an atom or a nominal type.
+///
+/// A valid name is not empty, does not start or end with whitespace, and
+/// contains no control characters, no whitespace other than a plain space,
+/// and no `"` or `\`, which exporters would have to escape.
+pub fn is_valid_name(name: &str) -> bool {
+    let trimmed = name.trim();
+    !trimmed.is_empty()
+        && trimmed.len() == name.len()
+        && name
+            .chars()
+            .all(|c| !c.is_control() && (c == ' ' || !c.is_whitespace()) && c != '"' && c != '\\')
+}
+
+/// Turns text into a [valid name](is_valid_name).
+///
+/// Runs of whitespace and control characters become a single space,
+/// `"` and `\` are dropped, and the result is trimmed. Returns `None` if
+/// nothing is left.
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let mut res = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_control() || c.is_whitespace() {
+            if !res.ends_with(' ') {
+                res.push(' ');
+            }
+        } else if c != '"' && c != '\\' {
+            res.push(c);
+        }
+    }
+    let res = res.trim();
+    (!res.is_empty()).then(|| res.to_string())
+}
+
+fn check_name(name: &str, path: &Path) -> Result<(), InvalidSchema> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(InvalidSchema {
+            path: path.clone(),
+            problem: Problem::InvalidName(name.to_string()),
+        })
+    }
+}
+
+impl Schema {
+    /// An atom, failing if the name is not [valid](is_valid_name).
+    pub fn atom_checked(name: impl Into<String>) -> Result<Schema, SchemaError> {
+        let name = name.into();
+        check_name(&name, &Path::default())?;
+        Ok(Schema::Atom(name))
+    }
+
+    /// A nominal type, failing if the name is not [valid](is_valid_name).
+    pub fn named_checked(name: impl Into<String>, schema: Schema) -> Result<Schema, SchemaError> {
+        let name = name.into();
+        check_name(&name, &Path::default())?;
+        Ok(Schema::named(name, schema))
+    }
+
+    /// A struct, failing if two fields have the same name.
+    pub fn struct_checked(fields: Vec<Named>) -> Result<Schema, SchemaError> {
+        unique(&fields, Problem::DuplicateField, &Path::default())?;
+        Ok(Schema::Struct(fields))
+    }
+
+    /// An enum, failing if two variants have the same name.
+    pub fn enum_checked(cases: Vec<Named>) -> Result<Schema, SchemaError> {
+        unique(&cases, Problem::DuplicateVariant, &Path::default())?;
+        Ok(Schema::Enum(cases))
+    }
+
+    /// An enumeration, failing if two variants have the same name or
+    /// discriminant.
+    pub fn enumeration_checked(cases: Vec<(String, u32)>) -> Result<Schema, SchemaError> {
+        unique_cases(&cases, &Path::default())?;
+        Ok(Schema::Enumeration(cases))
+    }
+
+    /// Checks that the schema is well-formed, reporting the first problem.
+    ///
+    /// Field names must be unique within a struct and variant names within an
+    /// enum, discriminants must be unique within an enumeration, and names of
+    /// atoms and nominal types must be [valid](is_valid_name).
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        Ok(validate(self, &Path::default())?)
+    }
+}
+
+fn unique(
+    items: &[Named],
+    problem: fn(String) -> Problem,
+    path: &Path,
+) -> Result<(), InvalidSchema> {
+    let mut seen = BTreeSet::new();
+    match items.iter().find(|item| !seen.insert(item.0.as_str())) {
+        Some(item) => Err(InvalidSchema {
+            path: path.clone(),
+            problem: problem(item.0.clone()),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn unique_cases(cases: &[(String, u32)], path: &Path) -> Result<(), InvalidSchema> {
+    let mut names = BTreeSet::new();
+    let mut tags = BTreeSet::new();
+    for (name, tag) in cases {
+        let problem = if !names.insert(name.as_str()) {
+            Problem::DuplicateVariant(name.clone())
+        } else if !tags.insert(*tag) {
+            Problem::DuplicateDiscriminant(*tag)
+        } else {
+            continue;
+        };
+        return Err(InvalidSchema {
+            path: path.clone(),
+            problem,
+        });
+    }
+    Ok(())
+}
+
+fn validate(schema: &Schema, path: &Path) -> Result<(), InvalidSchema> {
+    match schema {
+        Schema::Unit | Schema::Bottom => Ok(()),
+        Schema::Atom(name) => check_name(name, path),
+        Schema::Product(items) | Schema::Sum(items) => {
+            for (i, item) in items.iter().enumerate() {
+                validate(item, &path.join(PathSegment::Index(i)))?;
+            }
+            Ok(())
+        }
+        Schema::Struct(fields) => {
+            unique(fields, Problem::DuplicateField, path)?;
+            for field in fields {
+                validate(&field.1, &path.join(PathSegment::Field(field.0.clone())))?;
+            }
+            Ok(())
+        }
+        Schema::Enum(cases) => {
+            unique(cases, Problem::DuplicateVariant, path)?;
+            for case in cases {
+                validate(&case.1, &path.join(PathSegment::Variant(case.0.clone())))?;
+            }
+            Ok(())
+        }
+        Schema::Enumeration(cases) => unique_cases(cases, path),
+        Schema::Named(named) => {
+            check_name(&named.0, path)?;
+            validate(&named.1, &path.join(PathSegment::Named(named.0.clone())))
+        }
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::UnorderedSet(item)
+        | Schema::Stream(item) => validate(item, &path.join(PathSegment::Item)),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            validate(key, &path.join(PathSegment::Key))?;
+            validate(value, &path.join(PathSegment::Value))
+        }
+        Schema::Optional(item) => validate(item, &path.join(PathSegment::Index(1))),
+        Schema::Tagged(_, item) => validate(item, path),
+    }
+}
@@ -0,0 +1,155 @@
+//! Support for typed clients.
+//!
+//! `#[serialize_service(MyService, client)]` generates a `MyServiceClient`
+//! with one method per variant. Each method performs the interaction that
+//! matches the channels of the request, see [`Interaction`].
+//!
+//! The service must implement `From` for all request types, and its message
+//! type must implement `From<WithChannels<_, _>>` for them, as generated by
+//! `irpc::rpc_requests`.
+use std::future::Future;
+
+use irpc::{
+    channel::{
+        mpsc,
+        none::{NoReceiver, NoSender},
+        oneshot,
+    },
+    Channels, Client, RpcMessage, Service, WithChannels,
+};
+
+/// Default capacity of local channels created by generated clients.
+pub const DEFAULT_LOCAL_CAPACITY: usize = 16;
+
+/// The interaction for a `(Tx, Rx)` channel pair of a request.
+///
+/// | Tx | Rx | call | output |
+/// |----|----|------|--------|
+/// | `NoSender` | `NoReceiver` | `notify` | `()` |
+/// | `oneshot::Sender<R>` | `NoReceiver` | `rpc` | `R` |
+/// | `mpsc::Sender<R>` | `NoReceiver` | `server_streaming` | `mpsc::Receiver<R>` |
+/// | `oneshot::Sender<R>` | `mpsc::Receiver<U>` | `client_streaming` | `(mpsc::Sender<U>, oneshot::Receiver<R>)` |
+/// | `mpsc::Sender<R>` | `mpsc::Receiver<U>` | `bidi_streaming` | `(mpsc::Sender<U>, mpsc::Receiver<R>)` |
+pub trait Interaction<S: Service, Req> {
+    /// What the caller gets back.
+    type Output;
+
+    /// Performs the request. `local_capacity` is the capacity of channels
+    /// created for local services.
+    ///
+    /// This takes the client by value, so the future does not borrow it.
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<Self::Output>> + Send + 'static;
+}
+
+/// The output of calling a request of a service.
+pub type CallOutput<S, Req> =
+    <(<Req as Channels<S>>::Tx, <Req as Channels<S>>::Rx) as Interaction<S, Req>>::Output;
+
+/// Performs the interaction matching the channels of a request.
+pub fn call<S, Req>(
+    client: &Client<S>,
+    msg: Req,
+    local_capacity: usize,
+) -> impl Future<Output = irpc::Result<CallOutput<S, Req>>> + Send + 'static
+where
+    S: Service,
+    Req: Channels<S>,
+    (Req::Tx, Req::Rx): Interaction<S, Req>,
+{
+    <(Req::Tx, Req::Rx)>::call(client.clone(), msg, local_capacity)
+}
+
+impl<S, Req> Interaction<S, Req> for (NoSender, NoReceiver)
+where
+    S: Service + From<Req>,
+    S::Message: From<WithChannels<Req, S>>,
+    Req: Channels<S, Tx = NoSender, Rx = NoReceiver>,
+{
+    type Output = ();
+
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        _local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<()>> + Send + 'static {
+        client.notify(msg)
+    }
+}
+
+impl<S, Req, Res> Interaction<S, Req> for (oneshot::Sender<Res>, NoReceiver)
+where
+    S: Service + From<Req>,
+    S::Message: From<WithChannels<Req, S>>,
+    Req: Channels<S, Tx = oneshot::Sender<Res>, Rx = NoReceiver>,
+    Res: RpcMessage,
+{
+    type Output = Res;
+
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        _local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<Res>> + Send + 'static {
+        client.rpc(msg)
+    }
+}
+
+impl<S, Req, Res> Interaction<S, Req> for (mpsc::Sender<Res>, NoReceiver)
+where
+    S: Service + From<Req>,
+    S::Message: From<WithChannels<Req, S>>,
+    Req: Channels<S, Tx = mpsc::Sender<Res>, Rx = NoReceiver>,
+    Res: RpcMessage,
+{
+    type Output = mpsc::Receiver<Res>;
+
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<mpsc::Receiver<Res>>> + Send + 'static {
+        client.server_streaming(msg, local_capacity)
+    }
+}
+
+impl<S, Req, Update, Res> Interaction<S, Req> for (oneshot::Sender<Res>, mpsc::Receiver<Update>)
+where
+    S: Service + From<Req>,
+    S::Message: From<WithChannels<Req, S>>,
+    Req: Channels<S, Tx = oneshot::Sender<Res>, Rx = mpsc::Receiver<Update>>,
+    Update: RpcMessage,
+    Res: RpcMessage,
+{
+    type Output = (mpsc::Sender<Update>, oneshot::Receiver<Res>);
+
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<Self::Output>> + Send + 'static {
+        client.client_streaming(msg, local_capacity)
+    }
+}
+
+impl<S, Req, Update, Res> Interaction<S, Req> for (mpsc::Sender<Res>, mpsc::Receiver<Update>)
+where
+    S: Service + From<Req>,
+    S::Message: From<WithChannels<Req, S>>,
+    Req: Channels<S, Tx = mpsc::Sender<Res>, Rx = mpsc::Receiver<Update>>,
+    Update: RpcMessage,
+    Res: RpcMessage,
+{
+    type Output = (mpsc::Sender<Update>, mpsc::Receiver<Res>);
+
+    fn call(
+        client: Client<S>,
+        msg: Req,
+        local_capacity: usize,
+    ) -> impl Future<Output = irpc::Result<Self::Output>> + Send + 'static {
+        client.bidi_streaming(msg, local_capacity, local_capacity)
+    }
+}
@@ -0,0 +1,623 @@
+//! Schema-guided postcard encoding and decoding of dynamic values.
+//!
+//! This interprets postcard bytes using only a [`Schema`], without the Rust
+//! type being compiled in. Atoms are encoded according to the std types they
+//! name, see [`crate::value`]. Opaque atoms can not be encoded or decoded,
+//! since their encoding is unknown.
+use std::fmt;
+
+use crate::{
+    diff::{Path, PathSegment},
+    value::{option_inner, ConformanceError, Primitive, Value},
+    Schema,
+};
+
+/// Error when decoding postcard bytes with a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    /// Byte offset at which the error occurred.
+    pub offset: usize,
+    /// Location in the schema at which the error occurred.
+    pub path: Path,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at offset {} ({}): {}",
+            self.offset, self.path, self.message
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Error when encoding a value with a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeError {
+    /// Location in the value at which the error occurred.
+    pub path: Path,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl From<ConformanceError> for EncodeError {
+    fn from(value: ConformanceError) -> Self {
+        Self {
+            path: value.path,
+            message: value.message,
+        }
+    }
+}
+
+/// The maximum length of a sequence, set or map of a zero sized type like
+/// `()` when decoding.
+///
+/// Other lengths are checked against the remaining input, since every item
+/// takes at least a byte, but items of a zero sized type take no space at all.
+pub const MAX_EMPTY_ITEMS: usize = 1 << 16;
+
+/// Decodes postcard bytes into a [`Value`] of the given schema.
+///
+/// All bytes must be consumed by the value.
+pub fn decode_postcard(schema: &Schema, bytes: &[u8]) -> Result<Value, DecodeError> {
+    let mut decoder = Decoder::new(bytes);
+    let value = decoder.value(schema, &Path::default())?;
+    if decoder.pos != bytes.len() {
+        return Err(decoder.error(
+            &Path::default(),
+            format!("{} trailing bytes", bytes.len() - decoder.pos),
+        ));
+    }
+    Ok(value)
+}
+
+/// A cursor over postcard bytes.
+pub(crate) struct Decoder<'a> {
+    pub(crate) bytes: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub(crate) fn error(&self, path: &Path, message: impl Into<String>) -> DecodeError {
+        DecodeError {
+            offset: self.pos,
+            path: path.clone(),
+            message: message.into(),
+        }
+    }
+
+    fn take(&mut self, n: usize, path: &Path) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| self.error(path, "unexpected end of input"))?;
+        let res = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(res)
+    }
+
+    fn byte(&mut self, path: &Path) -> Result<u8, DecodeError> {
+        Ok(self.take(1, path)?[0])
+    }
+
+    /// Decodes a varint of at most `bits` bits.
+    pub(crate) fn varint(&mut self, bits: u32, path: &Path) -> Result<u128, DecodeError> {
+        let start = self.pos;
+        let max_bytes = bits.div_ceil(7);
+        let mut res = 0u128;
+        for i in 0..max_bytes {
+            let byte = self.byte(path)?;
+            res |= ((byte & 0x7f) as u128) << (7 * i);
+            if byte & 0x80 == 0 {
+                if bits < 128 && res >> bits != 0 {
+                    self.pos = start;
+                    return Err(self.error(path, "varint out of range"));
+                }
+                return Ok(res);
+            }
+        }
+        self.pos = start;
+        Err(self.error(path, "varint too long"))
+    }
+
+    /// Decodes a length or discriminant.
+    pub(crate) fn usize(&mut self, path: &Path) -> Result<usize, DecodeError> {
+        let start = self.pos;
+        let value = self.varint(64, path)?;
+        usize::try_from(value).map_err(|_| {
+            self.pos = start;
+            self.error(path, "length out of range")
+        })
+    }
+
+    /// Decodes the length of a collection with items of at least `item_len`
+    /// bytes, checking that the items can fit in the remaining input.
+    pub(crate) fn len(&mut self, item_len: usize, path: &Path) -> Result<usize, DecodeError> {
+        let start = self.pos;
+        let len = self.usize(path)?;
+        let remaining = self.bytes.len() - self.pos;
+        let max = match item_len {
+            0 => MAX_EMPTY_ITEMS,
+            n => remaining / n,
+        };
+        if len > max {
+            self.pos = start;
+            return Err(self.error(path, format!("length {} exceeds the input", len)));
+        }
+        Ok(len)
+    }
+
+    fn zigzag(&mut self, bits: u32, path: &Path) -> Result<i128, DecodeError> {
+        let value = self.varint(bits, path)?;
+        Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+    }
+
+    fn str(&mut self, path: &Path) -> Result<&'a str, DecodeError> {
+        let len = self.usize(path)?;
+        let start = self.pos;
+        let bytes = self.take(len, path)?;
+        std::str::from_utf8(bytes).map_err(|_| {
+            self.pos = start;
+            self.error(path, "invalid utf8")
+        })
+    }
+
+    fn primitive(&mut self, primitive: Primitive, path: &Path) -> Result<Value, DecodeError> {
+        Ok(match primitive {
+            Primitive::Bool => {
+                let start = self.pos;
+                match self.byte(path)? {
+                    0 => Value::Bool(false),
+                    1 => Value::Bool(true),
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error(path, "invalid bool"));
+                    }
+                }
+            }
+            Primitive::U8 => Value::UInt(self.byte(path)? as u128),
+            Primitive::I8 => Value::Int(self.byte(path)? as i8 as i128),
+            Primitive::U16 => Value::UInt(self.varint(16, path)?),
+            Primitive::U32 => Value::UInt(self.varint(32, path)?),
+            Primitive::U64 => Value::UInt(self.varint(64, path)?),
+            Primitive::U128 => Value::UInt(self.varint(128, path)?),
+            Primitive::I16 => Value::Int(self.zigzag(16, path)?),
+            Primitive::I32 => Value::Int(self.zigzag(32, path)?),
+            Primitive::I64 => Value::Int(self.zigzag(64, path)?),
+            Primitive::I128 => Value::Int(self.zigzag(128, path)?),
+            Primitive::F32 => {
+                let bytes = self.take(4, path)?;
+                Value::Float(f32::from_le_bytes(bytes.try_into().unwrap()) as f64)
+            }
+            Primitive::F64 => {
+                let bytes = self.take(8, path)?;
+                Value::Float(f64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            Primitive::Char => {
+                let start = self.pos;
+                let text = self.str(path)?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Value::Char(c),
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error(path, "invalid char"));
+                    }
+                }
+            }
+            Primitive::Str => Value::Str(self.str(path)?.to_string()),
+            Primitive::Bytes => {
+                let len = self.usize(path)?;
+                Value::Bytes(self.take(len, path)?.to_vec())
+            }
+        })
+    }
+
+    pub(crate) fn discriminant(&mut self, count: usize, path: &Path) -> Result<u32, DecodeError> {
+        let start = self.pos;
+        let index = self.varint(32, path)? as u32;
+        if index as usize >= count {
+            self.pos = start;
+            return Err(self.error(path, format!("unknown discriminant {}", index)));
+        }
+        Ok(index)
+    }
+
+    /// Reads the tag of an [enumeration](Schema::Enumeration) and finds its
+    /// case.
+    pub(crate) fn tag<'s>(
+        &mut self,
+        cases: &'s [(String, u32)],
+        path: &Path,
+    ) -> Result<&'s (String, u32), DecodeError> {
+        let start = self.pos;
+        let tag = self.varint(32, path)? as u32;
+        match cases.iter().find(|case| case.1 == tag) {
+            Some(case) => Ok(case),
+            None => {
+                self.pos = start;
+                Err(self.error(path, format!("unknown discriminant {}", tag)))
+            }
+        }
+    }
+
+    /// Decodes a value of the given schema.
+    pub(crate) fn value(&mut self, schema: &Schema, path: &Path) -> Result<Value, DecodeError> {
+        Ok(match schema {
+            Schema::Named(named) => {
+                return self.value(&named.1, &path.join(PathSegment::Named(named.0.clone())))
+            }
+            Schema::Unit => Value::Unit,
+            Schema::Bottom => return Err(self.error(path, "can not decode the bottom type")),
+            Schema::Stream(_) => {
+                return Err(self.error(
+                    path,
+                    "can not decode a stream, its items are sent separately",
+                ))
+            }
+            Schema::Atom(name) => match Primitive::from_atom(name) {
+                Some(primitive) => self.primitive(primitive, path)?,
+                None => {
+                    return Err(self.error(path, format!("can not decode opaque atom {}", name)))
+                }
+            },
+            Schema::Product(items) => Value::Tuple(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| self.value(item, &path.join(PathSegment::Index(i))))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Schema::Struct(fields) => Value::Struct(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let value =
+                            self.value(&field.1, &path.join(PathSegment::Field(field.0.clone())))?;
+                        Ok((field.0.clone(), value))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Schema::Sum(cases) => {
+                let index = self.discriminant(cases.len(), path)?;
+                let case_path = path.join(PathSegment::Index(index as usize));
+                let value = self.value(&cases[index as usize], &case_path)?;
+                if option_inner(schema).is_some() {
+                    Value::Optional((index == 1).then(|| Box::new(value)))
+                } else {
+                    Value::Variant {
+                        index,
+                        name: None,
+                        value: Box::new(value),
+                    }
+                }
+            }
+            Schema::Enum(cases) => {
+                let index = self.discriminant(cases.len(), path)?;
+                let case = &cases[index as usize];
+                let value =
+                    self.value(&case.1, &path.join(PathSegment::Variant(case.0.clone())))?;
+                Value::Variant {
+                    index,
+                    name: Some(case.0.clone()),
+                    value: Box::new(value),
+                }
+            }
+            Schema::Enumeration(cases) => {
+                let (name, tag) = self.tag(cases, path)?;
+                Value::Variant {
+                    index: *tag,
+                    name: Some(name.clone()),
+                    value: Box::new(Value::Unit),
+                }
+            }
+            Schema::Seq(item) => Value::Seq(self.items(item, path)?),
+            Schema::Set(item) | Schema::UnorderedSet(item) => Value::Set(self.items(item, path)?),
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                let len = self.len(min_len(key).saturating_add(min_len(value)), path)?;
+                let mut entries = Vec::with_capacity(len.min(MAX_EMPTY_ITEMS));
+                for i in 0..len {
+                    let entry = path.join(PathSegment::Index(i));
+                    let k = self.value(key, &entry.join(PathSegment::Key))?;
+                    let v = self.value(value, &entry.join(PathSegment::Value))?;
+                    entries.push((k, v));
+                }
+                Value::Map(entries)
+            }
+            Schema::Optional(item) => {
+                let value = match self.discriminant(2, path)? {
+                    0 => None,
+                    _ => Some(Box::new(
+                        self.value(item, &path.join(PathSegment::Index(1)))?,
+                    )),
+                };
+                Value::Optional(value)
+            }
+            // postcard is not self-describing, so serde can only decode
+            // externally tagged enums from it
+            Schema::Tagged(..) => {
+                return Err(self.error(path, "can not decode enums that are not externally tagged"))
+            }
+        })
+    }
+
+    /// Skips over a value of the given schema, without building it.
+    ///
+    /// Strings are not checked to be valid utf8, and errors are reported at
+    /// `path` instead of the exact location, to avoid tracking paths.
+    pub(crate) fn skip(&mut self, schema: &Schema, path: &Path) -> Result<(), DecodeError> {
+        match schema {
+            Schema::Named(named) => self.skip(&named.1, path)?,
+            Schema::Atom(name) => match Primitive::from_atom(name) {
+                Some(Primitive::Bool | Primitive::U8 | Primitive::I8) => {
+                    self.take(1, path)?;
+                }
+                Some(Primitive::F32) => {
+                    self.take(4, path)?;
+                }
+                Some(Primitive::F64) => {
+                    self.take(8, path)?;
+                }
+                Some(Primitive::Char | Primitive::Str | Primitive::Bytes) => {
+                    let len = self.usize(path)?;
+                    self.take(len, path)?;
+                }
+                Some(_) => {
+                    self.varint(128, path)?;
+                }
+                None => {
+                    return Err(self.error(path, format!("can not decode opaque atom {}", name)))
+                }
+            },
+            Schema::Product(items) => {
+                for item in items {
+                    self.skip(item, path)?;
+                }
+            }
+            Schema::Struct(fields) => {
+                for field in fields {
+                    self.skip(&field.1, path)?;
+                }
+            }
+            Schema::Sum(cases) => {
+                let index = self.discriminant(cases.len(), path)? as usize;
+                self.skip(&cases[index], path)?;
+            }
+            Schema::Enum(cases) => {
+                let index = self.discriminant(cases.len(), path)? as usize;
+                self.skip(&cases[index].1, path)?;
+            }
+            Schema::Enumeration(cases) => {
+                self.tag(cases, path)?;
+            }
+            Schema::Optional(item) => {
+                if self.discriminant(2, path)? == 1 {
+                    self.skip(item, path)?;
+                }
+            }
+            Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item) => {
+                let item_len = min_len(item);
+                let len = self.len(item_len, path)?;
+                // zero sized items have nothing to skip
+                for _ in 0..if item_len == 0 { 0 } else { len } {
+                    self.skip(item, path)?;
+                }
+            }
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                let entry_len = min_len(key).saturating_add(min_len(value));
+                let len = self.len(entry_len, path)?;
+                for _ in 0..if entry_len == 0 { 0 } else { len } {
+                    self.skip(key, path)?;
+                    self.skip(value, path)?;
+                }
+            }
+            Schema::Unit | Schema::Bottom | Schema::Tagged(..) | Schema::Stream(_) => {
+                self.value(schema, path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn items(&mut self, item: &Schema, path: &Path) -> Result<Vec<Value>, DecodeError> {
+        let len = self.len(min_len(item), path)?;
+        let mut res = Vec::with_capacity(len);
+        for i in 0..len {
+            res.push(self.value(item, &path.join(PathSegment::Index(i)))?);
+        }
+        Ok(res)
+    }
+}
+
+/// The minimum number of bytes a value of the schema takes.
+///
+/// This is only zero for schemas made of units, which always take no bytes.
+/// Schemas that can not be decoded count as a byte.
+pub(crate) fn min_len(schema: &Schema) -> usize {
+    match schema {
+        Schema::Unit => 0,
+        Schema::Named(named) => min_len(&named.1),
+        Schema::Product(items) => items.iter().map(min_len).fold(0, usize::saturating_add),
+        Schema::Struct(fields) => fields
+            .iter()
+            .map(|field| min_len(&field.1))
+            .fold(0, usize::saturating_add),
+        Schema::Atom(name) => match Primitive::from_atom(name) {
+            Some(Primitive::F32) => 4,
+            Some(Primitive::F64) => 8,
+            _ => 1,
+        },
+        _ => 1,
+    }
+}
+
+/// Encodes a [`Value`] of the given schema as postcard bytes.
+///
+/// The value is checked against the schema first, so the result is always a
+/// valid encoding of the schema.
+pub fn encode_postcard(schema: &Schema, value: &Value) -> Result<Vec<u8>, EncodeError> {
+    value.check_conforms(schema)?;
+    let mut out = Vec::new();
+    encode(schema, value, &Path::default(), &mut out)?;
+    Ok(out)
+}
+
+fn write_varint(mut value: u128, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_varint(bytes.len() as u128, out);
+    out.extend_from_slice(bytes);
+}
+
+fn encode_primitive(primitive: Primitive, value: &Value, out: &mut Vec<u8>) {
+    // the value has been checked to conform, so integers are in range
+    let (int, uint) = match value {
+        Value::Int(v) => (*v, *v as u128),
+        Value::UInt(v) => (*v as i128, *v),
+        _ => (0, 0),
+    };
+    match (primitive, value) {
+        (Primitive::Bool, Value::Bool(v)) => out.push(*v as u8),
+        (Primitive::U8 | Primitive::I8, _) => out.push(int as u8),
+        (Primitive::U16 | Primitive::U32 | Primitive::U64 | Primitive::U128, _) => {
+            write_varint(uint, out)
+        }
+        (Primitive::I16 | Primitive::I32 | Primitive::I64 | Primitive::I128, _) => {
+            write_varint(((int << 1) ^ (int >> 127)) as u128, out)
+        }
+        (Primitive::F32, Value::Float(v)) => out.extend_from_slice(&(*v as f32).to_le_bytes()),
+        (Primitive::F64, Value::Float(v)) => out.extend_from_slice(&v.to_le_bytes()),
+        (Primitive::Char, Value::Char(c)) => {
+            write_bytes(c.encode_utf8(&mut [0; 4]).as_bytes(), out)
+        }
+        (Primitive::Str, Value::Str(s)) => write_bytes(s.as_bytes(), out),
+        (Primitive::Bytes, Value::Bytes(b)) => write_bytes(b, out),
+        _ => unreachable!("value conforms to the primitive"),
+    }
+}
+
+/// Encodes a value that is known to conform to the schema.
+fn encode(
+    schema: &Schema,
+    value: &Value,
+    path: &Path,
+    out: &mut Vec<u8>,
+) -> Result<(), EncodeError> {
+    match (schema, value) {
+        (Schema::Named(named), _) => encode(
+            &named.1,
+            value,
+            &path.join(PathSegment::Named(named.0.clone())),
+            out,
+        )?,
+        (Schema::Atom(name), _) => match Primitive::from_atom(name) {
+            Some(primitive) => encode_primitive(primitive, value, out),
+            None => {
+                return Err(EncodeError {
+                    path: path.clone(),
+                    message: format!("can not encode opaque atom {}", name),
+                })
+            }
+        },
+        (Schema::Product(items), Value::Tuple(values)) => {
+            for (i, (item, value)) in items.iter().zip(values).enumerate() {
+                encode(item, value, &path.join(PathSegment::Index(i)), out)?;
+            }
+        }
+        (Schema::Struct(fields), Value::Struct(values)) => {
+            for (field, (_, value)) in fields.iter().zip(values) {
+                encode(
+                    &field.1,
+                    value,
+                    &path.join(PathSegment::Field(field.0.clone())),
+                    out,
+                )?;
+            }
+        }
+        (Schema::Sum(cases), Value::Optional(value)) => match value {
+            Some(value) => {
+                write_varint(1, out);
+                encode(&cases[1], value, &path.join(PathSegment::Index(1)), out)?;
+            }
+            None => write_varint(0, out),
+        },
+        (Schema::Optional(item), Value::Optional(value)) => match value {
+            Some(value) => {
+                write_varint(1, out);
+                encode(item, value, &path.join(PathSegment::Index(1)), out)?;
+            }
+            None => write_varint(0, out),
+        },
+        (Schema::Sum(cases), Value::Variant { index, value, .. }) => {
+            let index = *index as usize;
+            write_varint(index as u128, out);
+            encode(
+                &cases[index],
+                value,
+                &path.join(PathSegment::Index(index)),
+                out,
+            )?;
+        }
+        (Schema::Enum(cases), Value::Variant { index, value, .. }) => {
+            let case = &cases[*index as usize];
+            write_varint(*index as u128, out);
+            encode(
+                &case.1,
+                value,
+                &path.join(PathSegment::Variant(case.0.clone())),
+                out,
+            )?;
+        }
+        (Schema::Enumeration(_), Value::Variant { index, .. }) => {
+            write_varint(*index as u128, out);
+        }
+        (Schema::Seq(item), Value::Seq(values))
+        | (Schema::Set(item) | Schema::UnorderedSet(item), Value::Set(values)) => {
+            write_varint(values.len() as u128, out);
+            for (i, value) in values.iter().enumerate() {
+                encode(item, value, &path.join(PathSegment::Index(i)), out)?;
+            }
+        }
+        (
+            Schema::Map(key, value_schema) | Schema::UnorderedMap(key, value_schema),
+            Value::Map(entries),
+        ) => {
+            write_varint(entries.len() as u128, out);
+            for (i, (k, v)) in entries.iter().enumerate() {
+                let entry = path.join(PathSegment::Index(i));
+                encode(key, k, &entry.join(PathSegment::Key), out)?;
+                encode(value_schema, v, &entry.join(PathSegment::Value), out)?;
+            }
+        }
+        (Schema::Tagged(..), _) => {
+            return Err(EncodeError {
+                path: path.clone(),
+                message: "can not encode enums that are not externally tagged".to_string(),
+            })
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Schema hashes as constants.
+//!
+//! Schemas are built at runtime, and blake3 can not be evaluated in const
+//! contexts, so schema hashes are normally only available after the schema
+//! has been built. To use the hash of a type in match arms, const assertions
+//! or contexts without allocation, it can be pinned in the `schema` attribute:
+//!
+//! ```ignore
+//! #[schema(Nominal(hash = "9f2c…"))]
+//! struct Get {
+//!     key: String,
+//! }
+//!
+//! match hash {
+//!     Get::SCHEMA_HASH => { /* ... */ }
+//!     _ => { /* ... */ }
+//! }
+//! ```
+//!
+//! The pinned hash is checked when the schema of the type is first built, and
+//! a mismatch panics, so a forgotten update after a schema change is caught by
+//! any test touching the type. The hash to pin is printed by [`pin`], or can
+//! be taken from the panic message.
+//!
+//! Since `serialize_stable` uses the hash of the payload type as the
+//! discriminator, the constants of the payload types are also the
+//! discriminators of the messages.
+use std::fmt;
+
+use crate::{HasSchema, Schema};
+
+/// A type whose schema hash is known at compile time.
+pub trait ConstSchemaHash: HasSchema {
+    /// The stable hash of the schema of this type.
+    const SCHEMA_HASH: [u8; 32];
+}
+
+/// A pinned schema hash that does not match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub type_name: &'static str,
+    /// The pinned hash.
+    pub expected: [u8; 32],
+    /// The hash of the schema.
+    pub actual: [u8; 32],
+}
+
+impl fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pinned schema hash of {} is {}, but the schema hash is {}",
+            self.type_name,
+            blake3::Hash::from(self.expected),
+            blake3::Hash::from(self.actual)
+        )
+    }
+}
+
+impl std::error::Error for HashMismatch {}
+
+/// Checks the pinned hash of a type against its schema.
+pub fn check<T: ConstSchemaHash + 'static>() -> Result<(), HashMismatch> {
+    let actual = *T::static_schema().stable_hash().as_bytes();
+    if actual == T::SCHEMA_HASH {
+        Ok(())
+    } else {
+        Err(HashMismatch {
+            type_name: std::any::type_name::<T>(),
+            expected: T::SCHEMA_HASH,
+            actual,
+        })
+    }
+}
+
+/// The `hash` parameter to pin the hash of a schema, e.g. `hash = "9f2c…"`.
+pub fn pin(schema: &Schema) -> String {
+    format!("hash = \"{}\"", schema.stable_hash().to_hex())
+}
+
+/// Panics if the pinned hash of a type does not match its schema.
+///
+/// This is called by the code generated for `#[schema(..)]`.
+#[doc(hidden)]
+pub fn check_pinned(type_name: &'static str, schema: &Schema, expected: &[u8; 32]) {
+    let actual = *schema.stable_hash().as_bytes();
+    if &actual != expected {
+        let e = HashMismatch {
+            type_name,
+            expected: *expected,
+            actual,
+        };
+        panic!("{}, pin it with {}", e, pin(schema));
+    }
+}
@@ -0,0 +1,243 @@
+//! Inspection of wire bytes using schemas.
+//!
+//! [`explain`] renders the structure of postcard bytes like a protocol
+//! dissector, with the byte offset and length of every node. Decoding stops
+//! at the first error, which is shown in place, so truncated payloads and
+//! unknown discriminators can be diagnosed from captured bytes.
+use std::fmt::Write;
+
+use crate::{
+    codec::{min_len, DecodeError, Decoder},
+    diff::{Path, PathSegment},
+    value::{option_inner, Value},
+    Named, Schema,
+};
+
+/// Maximum number of bytes shown in hex per line.
+const MAX_HEX: usize = 8;
+
+struct Line {
+    offset: usize,
+    /// The length, or `None` if decoding the node failed.
+    len: Option<usize>,
+    depth: usize,
+    text: String,
+}
+
+struct Explainer<'a> {
+    decoder: Decoder<'a>,
+    lines: Vec<Line>,
+}
+
+/// A short rendering of a decoded atom.
+fn summary(value: &Value) -> String {
+    match value {
+        Value::Bool(v) => v.to_string(),
+        Value::Int(v) => v.to_string(),
+        Value::UInt(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Char(v) => format!("{:?}", v),
+        Value::Str(v) => format!("{:?}", v),
+        Value::Bytes(v) => format!("{} bytes", v.len()),
+        other => other.kind().to_string(),
+    }
+}
+
+impl Explainer<'_> {
+    /// Adds a line starting at the current offset, returning its index.
+    fn line(&mut self, depth: usize, text: String) -> usize {
+        self.lines.push(Line {
+            offset: self.decoder.pos,
+            len: None,
+            depth,
+            text,
+        });
+        self.lines.len() - 1
+    }
+
+    /// Sets the length of a line to end at the current offset.
+    fn end(&mut self, line: usize) {
+        self.lines[line].len = Some(self.decoder.pos - self.lines[line].offset);
+    }
+
+    fn node(
+        &mut self,
+        schema: &Schema,
+        label: &str,
+        depth: usize,
+        path: &Path,
+    ) -> Result<(), DecodeError> {
+        match schema {
+            Schema::Named(named) => {
+                let label = format!("{} ({})", label, named.0);
+                let path = path.join(PathSegment::Named(named.0.clone()));
+                self.node(&named.1, &label, depth, &path)
+            }
+            Schema::Unit
+            | Schema::Bottom
+            | Schema::Atom(_)
+            | Schema::Tagged(..)
+            | Schema::Enumeration(_)
+            | Schema::Stream(_) => {
+                let line = self.line(depth, label.to_string());
+                let value = self.decoder.value(schema, path)?;
+                self.end(line);
+                write!(self.lines[line].text, " = {}", summary(&value)).unwrap();
+                Ok(())
+            }
+            Schema::Product(items) => {
+                let line = self.line(depth, format!("{}: tuple", label));
+                for (i, item) in items.iter().enumerate() {
+                    let path = path.join(PathSegment::Index(i));
+                    self.node(item, &i.to_string(), depth + 1, &path)?;
+                }
+                self.end(line);
+                Ok(())
+            }
+            Schema::Struct(fields) => {
+                let line = self.line(depth, format!("{}: struct", label));
+                for Named(name, field) in fields {
+                    let path = path.join(PathSegment::Field(name.clone()));
+                    self.node(field, name, depth + 1, &path)?;
+                }
+                self.end(line);
+                Ok(())
+            }
+            Schema::Sum(_) | Schema::Enum(_) | Schema::Optional(_) => {
+                let (kind, count) = match schema {
+                    Schema::Enum(cases) => ("enum", cases.len()),
+                    Schema::Optional(_) => ("option", 2),
+                    Schema::Sum(_) if option_inner(schema).is_some() => ("option", 2),
+                    Schema::Sum(cases) => ("sum", cases.len()),
+                    _ => unreachable!(),
+                };
+                let line = self.line(depth, format!("{}: {}", label, kind));
+                let disc = self.line(depth + 1, "discriminant".to_string());
+                let index = self.decoder.discriminant(count, path)? as usize;
+                self.end(disc);
+                let (label, case, path) = match schema {
+                    Schema::Enum(cases) => {
+                        let Named(name, case) = &cases[index];
+                        (
+                            name.clone(),
+                            case,
+                            path.join(PathSegment::Variant(name.clone())),
+                        )
+                    }
+                    Schema::Sum(cases) => (
+                        index.to_string(),
+                        &cases[index],
+                        path.join(PathSegment::Index(index)),
+                    ),
+                    Schema::Optional(item) => (
+                        index.to_string(),
+                        if index == 0 { &Schema::Unit } else { &**item },
+                        path.join(PathSegment::Index(index)),
+                    ),
+                    _ => unreachable!(),
+                };
+                write!(self.lines[disc].text, " = {} ({})", index, label).unwrap();
+                self.node(case, &label, depth + 1, &path)?;
+                self.end(line);
+                Ok(())
+            }
+            Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item) => {
+                let line = self.line(depth, label.to_string());
+                let len = self.decoder.len(min_len(item), path)?;
+                let kind = match schema {
+                    Schema::Seq(_) => "seq",
+                    Schema::Set(_) => "set",
+                    _ => "unordered set",
+                };
+                write!(self.lines[line].text, ": {} of {}", kind, len).unwrap();
+                for i in 0..len {
+                    let path = path.join(PathSegment::Index(i));
+                    self.node(item, &format!("[{}]", i), depth + 1, &path)?;
+                }
+                self.end(line);
+                Ok(())
+            }
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                let line = self.line(depth, label.to_string());
+                let len = self
+                    .decoder
+                    .len(min_len(key).saturating_add(min_len(value)), path)?;
+                let kind = if matches!(schema, Schema::Map(..)) {
+                    "map"
+                } else {
+                    "unordered map"
+                };
+                write!(self.lines[line].text, ": {} of {}", kind, len).unwrap();
+                for i in 0..len {
+                    let path = path.join(PathSegment::Index(i));
+                    let label = format!("[{}].key", i);
+                    self.node(key, &label, depth + 1, &path.join(PathSegment::Key))?;
+                    let label = format!("[{}].value", i);
+                    self.node(value, &label, depth + 1, &path.join(PathSegment::Value))?;
+                }
+                self.end(line);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Renders the structure of postcard encoded `bytes` of the given schema.
+///
+/// Every line shows the offset and length of a node in bytes, the node and
+/// the start of its bytes in hex. Composite nodes span all their children.
+/// Errors and trailing bytes are reported at the end.
+pub fn explain(schema: &Schema, bytes: &[u8]) -> String {
+    let mut explainer = Explainer {
+        decoder: Decoder::new(bytes),
+        lines: Vec::new(),
+    };
+    let res = explainer.node(schema, "<root>", 0, &Path::default());
+    let pos = explainer.decoder.pos;
+    let lines = explainer.lines;
+    let width = lines
+        .iter()
+        .map(|line| line.depth * 2 + line.text.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>6} {:>5}  {:<width$}  bytes",
+        "offset", "len", "node"
+    )
+    .unwrap();
+    for line in &lines {
+        // nodes that failed to decode span up to the error
+        let len = line.len.unwrap_or(pos - line.offset);
+        let data = &bytes[line.offset..line.offset + len];
+        let mut hex = data
+            .iter()
+            .take(MAX_HEX)
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if data.len() > MAX_HEX {
+            hex.push_str(" ..");
+        }
+        let node = format!("{}{}", "  ".repeat(line.depth), line.text);
+        writeln!(
+            out,
+            "{:>6} {:>5}  {:<width$}  {}",
+            line.offset, len, node, hex
+        )
+        .unwrap();
+    }
+    match res {
+        Err(e) => writeln!(out, "error {}", e).unwrap(),
+        Ok(()) if pos < bytes.len() => writeln!(
+            out,
+            "error: {} trailing bytes at offset {}",
+            bytes.len() - pos,
+            pos
+        )
+        .unwrap(),
+        Ok(()) => {}
+    }
+    out
+}
@@ -0,0 +1,814 @@
+//! Structural differences between schemas.
+//!
+//! [`diff`] compares two schemas and produces a list of [`Change`]s, each
+//! located by a [`Path`] from the root of the schema. Struct fields and enum
+//! variants are matched by name, products and sums by position. Variants of
+//! enumerations are matched by name, and their discriminants are compared.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{value::option_inner, Named, Schema, Tagging};
+
+/// One step in a [`Path`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// The inside of a named type.
+    Named(String),
+    /// A struct field.
+    Field(String),
+    /// An enum variant.
+    Variant(String),
+    /// An element of a product or sum, by position.
+    Index(usize),
+    /// The item type of a sequence or set.
+    Item,
+    /// The key type of a map.
+    Key,
+    /// The value type of a map.
+    Value,
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Named(name) | PathSegment::Field(name) | PathSegment::Variant(name) => {
+                write!(f, "{}", name)
+            }
+            PathSegment::Index(i) => write!(f, "{}", i),
+            PathSegment::Item => write!(f, "[]"),
+            PathSegment::Key => write!(f, "{{key}}"),
+            PathSegment::Value => write!(f, "{{value}}"),
+        }
+    }
+}
+
+/// Location of a node within a schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Path(pub Vec<PathSegment>);
+
+impl Path {
+    /// Returns a new path with `segment` appended.
+    pub fn join(&self, segment: PathSegment) -> Path {
+        let mut res = self.clone();
+        res.0.push(segment);
+        res
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "<root>");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// What changed at a given path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A named type was renamed.
+    Renamed { old: String, new: String },
+    /// A struct field was added.
+    FieldAdded {
+        name: String,
+        index: usize,
+        schema: Schema,
+    },
+    /// A struct field was removed.
+    FieldRemoved {
+        name: String,
+        index: usize,
+        schema: Schema,
+    },
+    /// A struct field was renamed, keeping its position and schema.
+    FieldRenamed {
+        old: String,
+        new: String,
+        index: usize,
+    },
+    /// A struct field changed position.
+    FieldMoved {
+        name: String,
+        old_index: usize,
+        new_index: usize,
+    },
+    /// An enum variant was added.
+    VariantAdded {
+        name: String,
+        index: usize,
+        schema: Schema,
+    },
+    /// An enum variant was removed.
+    VariantRemoved {
+        name: String,
+        index: usize,
+        schema: Schema,
+    },
+    /// An enum variant was renamed, keeping its position and schema.
+    VariantRenamed {
+        old: String,
+        new: String,
+        index: usize,
+    },
+    /// An enum variant changed position.
+    VariantMoved {
+        name: String,
+        old_index: usize,
+        new_index: usize,
+    },
+    /// An element was added to a product.
+    ElementAdded { index: usize, schema: Schema },
+    /// An element was removed from a product.
+    ElementRemoved { index: usize, schema: Schema },
+    /// A case was added to a sum.
+    CaseAdded { index: usize, schema: Schema },
+    /// A case was removed from a sum.
+    CaseRemoved { index: usize, schema: Schema },
+    /// A type `T` was replaced with `Option<T>`.
+    BecameOptional,
+    /// A type `Option<T>` was replaced with `T`.
+    BecameRequired,
+    /// A set or map became unordered, e.g. a `BTreeMap` was replaced with a
+    /// `HashMap`.
+    BecameUnordered,
+    /// An unordered set or map became ordered, e.g. a `HashMap` was replaced
+    /// with a `BTreeMap`.
+    BecameOrdered,
+    /// An enum of unit variants became an [enumeration](Schema::Enumeration)
+    /// with the variant indices as discriminants.
+    BecameEnumeration,
+    /// An [enumeration](Schema::Enumeration) became an enum of unit
+    /// variants, with the discriminants as variant indices.
+    BecameEnum,
+    /// The variants of an enumeration changed order, keeping their
+    /// discriminants.
+    VariantsReordered,
+    /// An atom was replaced with a different atom.
+    AtomChanged { old: String, new: String },
+    /// The representation of an enum changed. `None` is the default,
+    /// externally tagged representation.
+    TaggingChanged {
+        old: Option<Tagging>,
+        new: Option<Tagging>,
+    },
+    /// The node was replaced with something of a different kind.
+    Replaced { old: Schema, new: Schema },
+}
+
+/// A single difference between two schemas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Where the change happened.
+    pub path: Path,
+    /// What changed.
+    pub kind: ChangeKind,
+}
+
+impl Change {
+    /// A rough measure of how big this change is, in schema nodes.
+    pub fn weight(&self) -> usize {
+        match &self.kind {
+            ChangeKind::FieldAdded { schema, .. }
+            | ChangeKind::FieldRemoved { schema, .. }
+            | ChangeKind::VariantAdded { schema, .. }
+            | ChangeKind::VariantRemoved { schema, .. }
+            | ChangeKind::ElementAdded { schema, .. }
+            | ChangeKind::ElementRemoved { schema, .. }
+            | ChangeKind::CaseAdded { schema, .. }
+            | ChangeKind::CaseRemoved { schema, .. } => node_count(schema),
+            ChangeKind::Replaced { old, new } => node_count(old) + node_count(new),
+            _ => 1,
+        }
+    }
+
+    /// Classifies the change by its effect on data written with the old schema.
+    pub fn compat(&self) -> Compat {
+        match &self.kind {
+            ChangeKind::Renamed { .. }
+            | ChangeKind::FieldRenamed { .. }
+            | ChangeKind::VariantRenamed { .. }
+            | ChangeKind::VariantAdded { .. }
+            | ChangeKind::CaseAdded { .. }
+            | ChangeKind::BecameUnordered
+            | ChangeKind::BecameOrdered
+            | ChangeKind::BecameEnumeration
+            | ChangeKind::BecameEnum
+            | ChangeKind::VariantsReordered => Compat::Compatible,
+            ChangeKind::FieldAdded { .. }
+            | ChangeKind::FieldMoved { .. }
+            | ChangeKind::VariantMoved { .. }
+            | ChangeKind::ElementAdded { .. }
+            | ChangeKind::BecameOptional => Compat::Migratable,
+            ChangeKind::AtomChanged { old, new } if wire_compatible_atoms(old, new) => {
+                Compat::Compatible
+            }
+            ChangeKind::FieldRemoved { .. }
+            | ChangeKind::VariantRemoved { .. }
+            | ChangeKind::ElementRemoved { .. }
+            | ChangeKind::CaseRemoved { .. }
+            | ChangeKind::BecameRequired
+            | ChangeKind::AtomChanged { .. }
+            | ChangeKind::TaggingChanged { .. }
+            | ChangeKind::Replaced { .. } => Compat::Breaking,
+        }
+    }
+}
+
+/// How a change affects data written with the old schema.
+///
+/// Ordered from least to most severe, so the classification of a set of
+/// changes is the maximum of the individual classifications.
+///
+/// Serializes in lower case, e.g. as `"migratable"`, for
+/// [policy](crate::policy) files.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Compat {
+    /// Old data can be read with the new schema as is. Only names changed, or
+    /// the new schema accepts strictly more values with the same encoding.
+    #[default]
+    Compatible,
+    /// Old data can not be read as is, but can be converted without loss,
+    /// e.g. by filling in defaults for new fields or wrapping values in `Some`.
+    Migratable,
+    /// Old data can not be converted without loss.
+    Breaking,
+}
+
+impl fmt::Display for Compat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compat::Compatible => "compatible",
+            Compat::Migratable => "migratable",
+            Compat::Breaking => "breaking",
+        })
+    }
+}
+
+/// True if values of atom `old` are encoded the same way as values of atom `new`.
+///
+/// Postcard encodes all integers wider than a byte as varints, so widening
+/// them does not change the encoding of existing values.
+pub(crate) fn wire_compatible_atoms(old: &str, new: &str) -> bool {
+    const UNSIGNED: &[&str] = &["u16", "u32", "u64", "u128"];
+    const SIGNED: &[&str] = &["i16", "i32", "i64", "i128"];
+    const STRINGS: &[&str] = &["String", "&str"];
+    const BYTES: &[&str] = &["&[u8]", "bytes::Bytes"];
+    let widens = |group: &[&str]| {
+        let old = group.iter().position(|t| *t == old);
+        let new = group.iter().position(|t| *t == new);
+        matches!((old, new), (Some(old), Some(new)) if old <= new)
+    };
+    widens(UNSIGNED)
+        || widens(SIGNED)
+        || [STRINGS, BYTES]
+            .iter()
+            .any(|group| group.contains(&old) && group.contains(&new))
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Renamed { old, new } => write!(f, "renamed `{}` to `{}`", old, new),
+            ChangeKind::FieldAdded {
+                name,
+                index,
+                schema,
+            } => {
+                write!(
+                    f,
+                    "added field `{}: {}` at position {}",
+                    name, schema, index
+                )
+            }
+            ChangeKind::FieldRemoved {
+                name,
+                index,
+                schema,
+            } => {
+                write!(
+                    f,
+                    "removed field `{}: {}` at position {}",
+                    name, schema, index
+                )
+            }
+            ChangeKind::FieldRenamed { old, new, .. } => {
+                write!(f, "renamed field `{}` to `{}`", old, new)
+            }
+            ChangeKind::FieldMoved {
+                name,
+                old_index,
+                new_index,
+            } => write!(
+                f,
+                "moved field `{}` from position {} to {}",
+                name, old_index, new_index
+            ),
+            ChangeKind::VariantAdded {
+                name,
+                index,
+                schema,
+            } => {
+                write!(
+                    f,
+                    "added variant `{}: {}` at position {}",
+                    name, schema, index
+                )
+            }
+            ChangeKind::VariantRemoved {
+                name,
+                index,
+                schema,
+            } => {
+                write!(
+                    f,
+                    "removed variant `{}: {}` at position {}",
+                    name, schema, index
+                )
+            }
+            ChangeKind::VariantRenamed { old, new, .. } => {
+                write!(f, "renamed variant `{}` to `{}`", old, new)
+            }
+            ChangeKind::VariantMoved {
+                name,
+                old_index,
+                new_index,
+            } => write!(
+                f,
+                "moved variant `{}` from position {} to {}",
+                name, old_index, new_index
+            ),
+            ChangeKind::ElementAdded { index, schema } => {
+                write!(f, "added element `{}` at position {}", schema, index)
+            }
+            ChangeKind::ElementRemoved { index, schema } => {
+                write!(f, "removed element `{}` at position {}", schema, index)
+            }
+            ChangeKind::CaseAdded { index, schema } => {
+                write!(f, "added case `{}` at position {}", schema, index)
+            }
+            ChangeKind::CaseRemoved { index, schema } => {
+                write!(f, "removed case `{}` at position {}", schema, index)
+            }
+            ChangeKind::BecameOptional => write!(f, "became optional"),
+            ChangeKind::BecameRequired => write!(f, "became required"),
+            ChangeKind::BecameUnordered => write!(f, "became unordered"),
+            ChangeKind::BecameOrdered => write!(f, "became ordered"),
+            ChangeKind::BecameEnumeration => write!(f, "became an enumeration"),
+            ChangeKind::BecameEnum => write!(f, "became an enum"),
+            ChangeKind::VariantsReordered => write!(f, "reordered variants"),
+            ChangeKind::AtomChanged { old, new } => {
+                write!(f, "changed type `{}` to `{}`", old, new)
+            }
+            ChangeKind::TaggingChanged { old, new } => write!(
+                f,
+                "changed representation from {} to {}",
+                representation(old.as_ref()),
+                representation(new.as_ref())
+            ),
+            ChangeKind::Replaced { old, new } => write!(f, "replaced `{}` with `{}`", old, new),
+        }
+    }
+}
+
+/// All differences between two schemas.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub changes: Vec<Change>,
+}
+
+impl SchemaDiff {
+    /// True if the schemas are identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The sum of the weights of all changes.
+    ///
+    /// This is zero for identical schemas and grows with the number and size
+    /// of changes, so it can be used to find the most similar schema in a set.
+    pub fn distance(&self) -> usize {
+        self.changes.iter().map(Change::weight).sum()
+    }
+
+    /// The most severe classification of all changes.
+    pub fn compat(&self) -> Compat {
+        self.changes
+            .iter()
+            .map(Change::compat)
+            .max()
+            .unwrap_or(Compat::Compatible)
+    }
+
+    /// The diff without fields and variants that just changed position.
+    ///
+    /// For formats that key fields and variants by name, where their order
+    /// is insignificant, see [`Schema::to_unordered`].
+    pub fn ignore_order(mut self) -> SchemaDiff {
+        self.changes.retain(|change| {
+            !matches!(
+                change.kind,
+                ChangeKind::FieldMoved { .. } | ChangeKind::VariantMoved { .. }
+            )
+        });
+        self
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}: {} ({})", change.path, change.kind, change.compat())?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes an enum representation for [`ChangeKind::TaggingChanged`].
+fn representation(tagging: Option<&Tagging>) -> String {
+    match tagging {
+        None => "externally tagged".to_string(),
+        Some(Tagging::Internal(tag)) => format!("internally tagged with `{}`", tag),
+        Some(Tagging::Adjacent(tag, content)) => {
+            format!("adjacently tagged with `{}` and `{}`", tag, content)
+        }
+        Some(Tagging::Untagged) => "untagged".to_string(),
+    }
+}
+
+/// Computes the differences between an old and a new schema.
+pub fn diff(old: &Schema, new: &Schema) -> SchemaDiff {
+    let mut changes = Vec::new();
+    diff_rec(old, new, &Path::default(), &mut changes);
+    SchemaDiff { changes }
+}
+
+/// Panics unless `new` is a change of `old` that is more severe than `accepted`.
+///
+/// This is the negative counterpart of a compatibility check, for tests that
+/// prove that a deliberate breaking change is detected: the hashes must
+/// differ, and the [`compat`](SchemaDiff::compat) of the diff must exceed the
+/// most severe classification the policy accepts. See
+/// [`assert_schemas_incompatible!`](crate::assert_schemas_incompatible) for
+/// types.
+#[track_caller]
+pub fn assert_incompatible(old: &Schema, new: &Schema, accepted: Compat) {
+    if old.stable_hash() == new.stable_hash() {
+        panic!("schemas have the same hash: {}", old);
+    }
+    let diff = diff(old, new);
+    let compat = diff.compat();
+    if compat <= accepted {
+        panic!(
+            "schemas are {}, which is accepted by a {} policy:\n{}",
+            compat, accepted, diff
+        );
+    }
+}
+
+fn node_count(schema: &Schema) -> usize {
+    1 + match schema {
+        Schema::Unit | Schema::Bottom | Schema::Atom(_) => 0,
+        Schema::Enumeration(cases) => cases.len(),
+        Schema::Product(items) | Schema::Sum(items) => items.iter().map(node_count).sum(),
+        Schema::Struct(fields) | Schema::Enum(fields) => {
+            fields.iter().map(|f| node_count(&f.1)).sum()
+        }
+        Schema::Named(named) => node_count(&named.1),
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::UnorderedSet(item)
+        | Schema::Optional(item)
+        | Schema::Tagged(_, item)
+        | Schema::Stream(item) => node_count(item),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            node_count(key) + node_count(value)
+        }
+    }
+}
+
+fn diff_rec(old: &Schema, new: &Schema, path: &Path, out: &mut Vec<Change>) {
+    if old == new {
+        return;
+    }
+    let push = |out: &mut Vec<Change>, kind| {
+        out.push(Change {
+            path: path.clone(),
+            kind,
+        })
+    };
+    match (old, new) {
+        (Schema::Named(a), Schema::Named(b)) => {
+            if a.0 != b.0 {
+                push(
+                    out,
+                    ChangeKind::Renamed {
+                        old: a.0.clone(),
+                        new: b.0.clone(),
+                    },
+                );
+            }
+            diff_rec(&a.1, &b.1, &path.join(PathSegment::Named(b.0.clone())), out);
+        }
+        (Schema::Tagged(..), _) | (_, Schema::Tagged(..)) => {
+            let (old_tagging, old) = untagged(old);
+            let (new_tagging, new) = untagged(new);
+            if old_tagging != new_tagging {
+                let (old, new) = (old_tagging.cloned(), new_tagging.cloned());
+                push(out, ChangeKind::TaggingChanged { old, new });
+            }
+            diff_rec(old, new, path, out);
+        }
+        (Schema::Struct(a), Schema::Struct(b)) => diff_named(a, b, path, false, out),
+        (Schema::Enum(a), Schema::Enum(b)) => diff_named(a, b, path, true, out),
+        (Schema::Enumeration(a), Schema::Enumeration(b)) => diff_enumeration(a, b, path, out),
+        (Schema::Enum(a), Schema::Enumeration(b)) if unit_cases(a).is_some() => {
+            push(out, ChangeKind::BecameEnumeration);
+            diff_enumeration(&unit_cases(a).unwrap(), b, path, out)
+        }
+        (Schema::Enumeration(a), Schema::Enum(b)) if unit_cases(b).is_some() => {
+            push(out, ChangeKind::BecameEnum);
+            diff_enumeration(a, &unit_cases(b).unwrap(), path, out)
+        }
+        (Schema::Product(a), Schema::Product(b)) => {
+            diff_positional(a, b, path, out);
+            for (index, schema) in b.iter().enumerate().skip(a.len()) {
+                let schema = schema.clone();
+                push(out, ChangeKind::ElementAdded { index, schema });
+            }
+            for (index, schema) in a.iter().enumerate().skip(b.len()) {
+                let schema = schema.clone();
+                push(out, ChangeKind::ElementRemoved { index, schema });
+            }
+        }
+        (Schema::Sum(a), Schema::Sum(b)) => {
+            diff_positional(a, b, path, out);
+            for (index, schema) in b.iter().enumerate().skip(a.len()) {
+                let schema = schema.clone();
+                push(out, ChangeKind::CaseAdded { index, schema });
+            }
+            for (index, schema) in a.iter().enumerate().skip(b.len()) {
+                let schema = schema.clone();
+                push(out, ChangeKind::CaseRemoved { index, schema });
+            }
+        }
+        // both encodings of options are the same on the wire
+        (old, new) if option_inner(old).is_some() && option_inner(new).is_some() => diff_rec(
+            option_inner(old).unwrap(),
+            option_inner(new).unwrap(),
+            &path.join(PathSegment::Index(1)),
+            out,
+        ),
+        (Schema::Seq(a), Schema::Seq(b)) | (Schema::Stream(a), Schema::Stream(b)) => {
+            diff_rec(a, b, &path.join(PathSegment::Item), out)
+        }
+        (Schema::Set(a) | Schema::UnorderedSet(a), Schema::Set(b) | Schema::UnorderedSet(b)) => {
+            if let Some(kind) = ordering_change(old, new) {
+                push(out, kind);
+            }
+            diff_rec(a, b, &path.join(PathSegment::Item), out)
+        }
+        (
+            Schema::Map(ak, av) | Schema::UnorderedMap(ak, av),
+            Schema::Map(bk, bv) | Schema::UnorderedMap(bk, bv),
+        ) => {
+            if let Some(kind) = ordering_change(old, new) {
+                push(out, kind);
+            }
+            diff_rec(ak, bk, &path.join(PathSegment::Key), out);
+            diff_rec(av, bv, &path.join(PathSegment::Value), out);
+        }
+        (Schema::Atom(a), Schema::Atom(b)) => {
+            let (old, new) = (a.clone(), b.clone());
+            push(out, ChangeKind::AtomChanged { old, new })
+        }
+        (old, new) if option_inner(new) == Some(old) => push(out, ChangeKind::BecameOptional),
+        (old, new) if option_inner(old) == Some(new) => push(out, ChangeKind::BecameRequired),
+        (old, new) => {
+            let (old, new) = (old.clone(), new.clone());
+            push(out, ChangeKind::Replaced { old, new })
+        }
+    }
+}
+
+/// The change between two sets or two maps that differ in their ordering.
+fn ordering_change(old: &Schema, new: &Schema) -> Option<ChangeKind> {
+    let unordered = |schema| matches!(schema, &Schema::UnorderedSet(_) | &Schema::UnorderedMap(..));
+    match (unordered(old), unordered(new)) {
+        (false, true) => Some(ChangeKind::BecameUnordered),
+        (true, false) => Some(ChangeKind::BecameOrdered),
+        _ => None,
+    }
+}
+
+/// The tagging of a schema and the schema without it.
+fn untagged(schema: &Schema) -> (Option<&Tagging>, &Schema) {
+    match schema {
+        Schema::Tagged(tagging, inner) => (Some(tagging), inner),
+        _ => (None, schema),
+    }
+}
+
+/// The variants of an enum of unit variants as enumeration cases, with the
+/// variant indices as discriminants.
+fn unit_cases(cases: &[Named]) -> Option<Vec<(String, u32)>> {
+    cases
+        .iter()
+        .enumerate()
+        .map(|(index, case)| (case.1 == Schema::Unit).then(|| (case.0.clone(), index as u32)))
+        .collect()
+}
+
+/// Diffs the variants of enumerations, matching them by name.
+///
+/// Since discriminants are explicit, only changed discriminants are reported
+/// as moves, and a variant added with an unused discriminant, e.g. at the
+/// end, is compatible no matter where it appears in the list.
+fn diff_enumeration(
+    old: &[(String, u32)],
+    new: &[(String, u32)],
+    path: &Path,
+    out: &mut Vec<Change>,
+) {
+    let find = |cases: &[(String, u32)], name: &str| cases.iter().find(|c| c.0 == name).cloned();
+    let mut removed = old
+        .iter()
+        .filter(|o| find(new, &o.0).is_none())
+        .collect::<Vec<_>>();
+    let mut changes = Vec::new();
+    let mut added = Vec::new();
+    for (name, tag) in new {
+        match find(old, name) {
+            Some((_, old_tag)) if old_tag != *tag => changes.push(ChangeKind::VariantMoved {
+                name: name.clone(),
+                old_index: old_tag as usize,
+                new_index: *tag as usize,
+            }),
+            Some(_) => {}
+            // a removed variant with the same discriminant is a rename
+            None => match removed.iter().position(|o| o.1 == *tag) {
+                Some(i) => changes.push(ChangeKind::VariantRenamed {
+                    old: removed.remove(i).0.clone(),
+                    new: name.clone(),
+                    index: *tag as usize,
+                }),
+                None => added.push(ChangeKind::VariantAdded {
+                    name: name.clone(),
+                    index: *tag as usize,
+                    schema: Schema::Unit,
+                }),
+            },
+        }
+    }
+    changes.extend(added);
+    changes.extend(removed.into_iter().map(|o| ChangeKind::VariantRemoved {
+        name: o.0.clone(),
+        index: o.1 as usize,
+        schema: Schema::Unit,
+    }));
+    if changes.is_empty() && old != new {
+        changes.push(ChangeKind::VariantsReordered);
+    }
+    out.extend(changes.into_iter().map(|kind| Change {
+        path: path.clone(),
+        kind,
+    }));
+}
+
+fn diff_positional(old: &[Schema], new: &[Schema], path: &Path, out: &mut Vec<Change>) {
+    for (i, (a, b)) in old.iter().zip(new.iter()).enumerate() {
+        diff_rec(a, b, &path.join(PathSegment::Index(i)), out);
+    }
+}
+
+/// Diffs struct fields or enum variants, matching them by name.
+///
+/// A variant is reported as moved whenever its index changes, since the index
+/// is its discriminator on the wire. A field is only reported as moved if its
+/// order relative to the other fields changed.
+fn diff_named(old: &[Named], new: &[Named], path: &Path, variants: bool, out: &mut Vec<Change>) {
+    let position = |items: &[Named], name: &str| items.iter().position(|n| n.0 == name);
+    let mut removed = old
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| position(new, &n.0).is_none())
+        .collect::<Vec<_>>();
+    // relative order of the items present in both, to detect moves
+    let old_common = old
+        .iter()
+        .filter(|o| position(new, &o.0).is_some())
+        .map(|o| o.0.as_str())
+        .collect::<Vec<_>>();
+    let new_common = new
+        .iter()
+        .filter(|n| position(old, &n.0).is_some())
+        .map(|n| n.0.as_str())
+        .collect::<Vec<_>>();
+    let mut added = Vec::new();
+    for (new_index, n) in new.iter().enumerate() {
+        let Some(old_index) = position(old, &n.0) else {
+            // a removed item at the same position with the same schema is a rename
+            if let Some(i) = removed
+                .iter()
+                .position(|(i, o)| *i == new_index && o.1 == n.1)
+            {
+                let (index, o) = removed.remove(i);
+                let (old, new) = (o.0.clone(), n.0.clone());
+                let kind = if variants {
+                    ChangeKind::VariantRenamed { old, new, index }
+                } else {
+                    ChangeKind::FieldRenamed { old, new, index }
+                };
+                out.push(Change {
+                    path: path.clone(),
+                    kind,
+                });
+            } else {
+                added.push((new_index, n));
+            }
+            continue;
+        };
+        let segment = if variants {
+            PathSegment::Variant(n.0.clone())
+        } else {
+            PathSegment::Field(n.0.clone())
+        };
+        diff_rec(&old[old_index].1, &n.1, &path.join(segment), out);
+        let rank = |common: &[&str]| common.iter().position(|c| *c == n.0);
+        let moved = if variants {
+            old_index != new_index
+        } else {
+            rank(&old_common) != rank(&new_common)
+        };
+        if moved {
+            let name = n.0.clone();
+            let kind = if variants {
+                ChangeKind::VariantMoved {
+                    name,
+                    old_index,
+                    new_index,
+                }
+            } else {
+                ChangeKind::FieldMoved {
+                    name,
+                    old_index,
+                    new_index,
+                }
+            };
+            out.push(Change {
+                path: path.clone(),
+                kind,
+            });
+        }
+    }
+    for (index, n) in added {
+        let (name, schema) = (n.0.clone(), n.1.clone());
+        let kind = if variants {
+            ChangeKind::VariantAdded {
+                name,
+                index,
+                schema,
+            }
+        } else {
+            ChangeKind::FieldAdded {
+                name,
+                index,
+                schema,
+            }
+        };
+        out.push(Change {
+            path: path.clone(),
+            kind,
+        });
+    }
+    for (index, o) in removed {
+        let (name, schema) = (o.0.clone(), o.1.clone());
+        let kind = if variants {
+            ChangeKind::VariantRemoved {
+                name,
+                index,
+                schema,
+            }
+        } else {
+            ChangeKind::FieldRemoved {
+                name,
+                index,
+                schema,
+            }
+        };
+        out.push(Change {
+            path: path.clone(),
+            kind,
+        });
+    }
+}
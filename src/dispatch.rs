@@ -0,0 +1,62 @@
+//! Lookup of messages by schema hash.
+//!
+//! Message enums generated by `serialize_stable`, `serialize_service` and
+//! `serialize_nested` keep a [`DispatchTable`] next to their schemas. When
+//! deserializing, the hash discriminator is looked up with a binary search
+//! instead of being compared with the hash of every variant in turn, which
+//! keeps decoding fast and the generated code small for protocols with many
+//! messages. In the [short](crate::short) wire mode, the table is keyed by
+//! the short hashes instead.
+
+/// A table of message hashes, sorted for binary search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispatchTable<K = [u8; 32]> {
+    entries: Vec<(K, usize)>,
+}
+
+impl<K> Default for DispatchTable<K> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<K: Ord> DispatchTable<K> {
+    /// Creates a table from the hashes of the messages, in variant order.
+    pub fn new(hashes: impl IntoIterator<Item = K>) -> Self {
+        let mut entries = hashes
+            .into_iter()
+            .enumerate()
+            .map(|(index, hash)| (hash, index))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    /// The index of the message with the given hash.
+    ///
+    /// If several messages have the same hash, the first one is returned.
+    pub fn get(&self, hash: &K) -> Option<usize> {
+        let start = self.entries.partition_point(|(h, _)| h < hash);
+        self.entries
+            .get(start)
+            .filter(|(h, _)| h == hash)
+            .map(|(_, index)| *index)
+    }
+
+    /// Whether the table contains the given hash.
+    pub fn contains(&self, hash: &K) -> bool {
+        self.get(hash).is_some()
+    }
+
+    /// The number of messages.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,233 @@
+//! Structured documentation of schemas.
+//!
+//! Doc comments and `#[deprecated]` attributes are not part of the schema, so
+//! they don't affect its hash. Instead, `#[schema(..)]` collects them into a
+//! [`DocTable`], keyed by type name, for the type and all types it contains.
+//! [`Schema::docs`] combines a schema with a table into a [`DocTree`] that
+//! follows the structure of the schema, for renderers and IDE integrations:
+//!
+//! ```
+//! use irpc_schema::{docs::DocTable, schema, HasSchema};
+//!
+//! /// A user of the service.
+//! #[schema(Nominal)]
+//! struct User {
+//!     /// The login name.
+//!     name: String,
+//!     #[deprecated(note = "use `name`")]
+//!     login: String,
+//! }
+//!
+//! let tree = User::schema().docs(&DocTable::of::<User>());
+//! assert_eq!(tree.type_name.as_deref(), Some("User"));
+//! assert_eq!(tree.docs.doc.as_deref(), Some("A user of the service."));
+//! assert_eq!(tree.members[0].docs.doc.as_deref(), Some("The login name."));
+//! let deprecated = tree.members[1].docs.deprecated.as_ref().unwrap();
+//! assert_eq!(deprecated.note.as_deref(), Some("use `name`"));
+//! ```
+use std::collections::BTreeMap;
+
+use crate::{HasSchema, Named, Schema};
+
+/// A `#[deprecated]` attribute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The version since which the item is deprecated.
+    pub since: Option<String>,
+    /// Why the item is deprecated, or what to use instead.
+    pub note: Option<String>,
+}
+
+/// The documentation of a type, field or variant.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Docs {
+    /// The doc comment, without the comment markers.
+    pub doc: Option<String>,
+    /// The deprecation, if the item is deprecated.
+    pub deprecated: Option<Deprecation>,
+}
+
+impl Docs {
+    /// True if there is neither a doc comment nor a deprecation.
+    pub fn is_empty(&self) -> bool {
+        self.doc.is_none() && self.deprecated.is_none()
+    }
+}
+
+/// The documentation of a type as written in the source, with the
+/// documentation of its fields or variants.
+///
+/// Fields of variants are members of the variant. Unnamed fields are keyed
+/// by their index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemDocs {
+    /// The documentation of the item itself.
+    pub docs: Docs,
+    /// The documentation of the members, by name.
+    pub members: BTreeMap<String, ItemDocs>,
+}
+
+/// The documentation of named types, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocTable {
+    types: BTreeMap<String, ItemDocs>,
+}
+
+impl DocTable {
+    /// An empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The table with the documentation of `T` and all types it contains.
+    pub fn of<T: HasSchema + ?Sized>() -> Self {
+        let mut table = Self::new();
+        table.add::<T>();
+        table
+    }
+
+    /// Adds the documentation of `T` and all types it contains.
+    pub fn add<T: HasSchema + ?Sized>(&mut self) {
+        T::collect_docs(self);
+    }
+
+    /// Adds the documentation of a named type.
+    ///
+    /// Returns false, and keeps the existing documentation, if the table
+    /// already contains the type.
+    pub fn insert(&mut self, name: impl Into<String>, docs: ItemDocs) -> bool {
+        match self.types.entry(name.into()) {
+            std::collections::btree_map::Entry::Occupied(_) => false,
+            std::collections::btree_map::Entry::Vacant(entry) => {
+                entry.insert(docs);
+                true
+            }
+        }
+    }
+
+    /// The documentation of a named type.
+    pub fn get(&self, name: &str) -> Option<&ItemDocs> {
+        self.types.get(name)
+    }
+
+    /// The names of all documented types.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.types.keys().map(String::as_str)
+    }
+}
+
+/// The documentation of a schema, following its structure.
+///
+/// Sequences, sets, options and tagged enums are transparent, so the tree of
+/// a `Vec<User>` is the tree of `User`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocTree {
+    /// The name of the type, for named types and atoms.
+    pub type_name: Option<String>,
+    /// The documentation of the type.
+    pub docs: Docs,
+    /// The fields or variants of the type, in schema order. Elements of
+    /// tuples are named by their index, map keys and values `key` and
+    /// `value`.
+    pub members: Vec<MemberDocs>,
+}
+
+/// A field or variant in a [`DocTree`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemberDocs {
+    /// The name of the field or variant.
+    pub name: String,
+    /// The documentation of the field or variant.
+    pub docs: Docs,
+    /// The documentation of its type.
+    pub tree: DocTree,
+}
+
+impl Schema {
+    /// The documentation of the schema from a table, see the
+    /// [module docs](crate::docs).
+    ///
+    /// Types that are not in the table have empty documentation.
+    pub fn docs(&self, table: &DocTable) -> DocTree {
+        tree(self, None, table)
+    }
+}
+
+fn tree(schema: &Schema, context: Option<&ItemDocs>, table: &DocTable) -> DocTree {
+    let members = |members: Vec<(String, &Schema)>| DocTree {
+        members: members
+            .into_iter()
+            .map(|(name, schema)| member(name, schema, context, table))
+            .collect(),
+        ..DocTree::default()
+    };
+    let fields = |fields: &[Named]| {
+        members(
+            fields
+                .iter()
+                .map(|field| (field.0.clone(), &field.1))
+                .collect(),
+        )
+    };
+    let items = |items: &[Schema]| {
+        members(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| (index.to_string(), item))
+                .collect(),
+        )
+    };
+    match schema {
+        Schema::Unit | Schema::Bottom => DocTree::default(),
+        Schema::Atom(name) => DocTree {
+            type_name: Some(name.clone()),
+            docs: docs_of(table.get(name)),
+            members: vec![],
+        },
+        Schema::Named(named) => {
+            let context = table.get(&named.0);
+            DocTree {
+                type_name: Some(named.0.clone()),
+                docs: docs_of(context),
+                ..tree(&named.1, context, table)
+            }
+        }
+        Schema::Struct(types) | Schema::Enum(types) => fields(types),
+        Schema::Product(types) | Schema::Sum(types) => items(types),
+        Schema::Enumeration(cases) => members(
+            cases
+                .iter()
+                .map(|(name, _)| (name.clone(), &Schema::Unit))
+                .collect(),
+        ),
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::Optional(item)
+        | Schema::Tagged(_, item)
+        | Schema::UnorderedSet(item)
+        | Schema::Stream(item) => tree(item, context, table),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => members(vec![
+            ("key".to_string(), key.as_ref()),
+            ("value".to_string(), value.as_ref()),
+        ]),
+    }
+}
+
+fn member(
+    name: String,
+    schema: &Schema,
+    context: Option<&ItemDocs>,
+    table: &DocTable,
+) -> MemberDocs {
+    let context = context.and_then(|docs| docs.members.get(&name));
+    MemberDocs {
+        docs: docs_of(context),
+        tree: tree(schema, context, table),
+        name,
+    }
+}
+
+fn docs_of(docs: Option<&ItemDocs>) -> Docs {
+    docs.map(|docs| docs.docs.clone()).unwrap_or_default()
+}
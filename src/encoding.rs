@@ -0,0 +1,121 @@
+//! Stability checks for the encoding of schemas.
+//!
+//! Schema hashes are the hash of the postcard encoding of [`Schema`], which is
+//! produced by its serde derive. Reordering or renaming variants, or changing
+//! their fields, silently changes every hash, and with it the wire format of
+//! every protocol. [`verify_encoding_stability`] catches this by hashing a
+//! fixed [reference corpus](reference_corpus) and comparing against the
+//! pinned [`REFERENCE_HASH`].
+//!
+//! Downstream crates can call it from a test, to make sure the version of this
+//! crate they build against produces the hashes they expect:
+//!
+//! ```
+//! irpc_schema::encoding::verify_encoding_stability().unwrap();
+//! ```
+use std::fmt;
+
+use crate::{hash_postcard, Named, Schema, Tagging};
+
+/// The version of the canonical encoding of schemas.
+///
+/// This is only incremented together with a deliberate change of the
+/// encoding, and with it of [`REFERENCE_HASH`].
+pub const ENCODING_VERSION: u32 = 1;
+
+/// The hash of the encoding of the [reference corpus](reference_corpus) in
+/// the current [`ENCODING_VERSION`].
+///
+/// This also changes when a variant is added to [`Schema`] and with it to the
+/// corpus, which leaves the hashes of existing schemas unchanged.
+pub const REFERENCE_HASH: [u8; 32] = [
+    0x9f, 0x97, 0xee, 0x17, 0x51, 0x59, 0x96, 0x2c, 0xd9, 0x63, 0xaa, 0x6e, 0xc6, 0xf8, 0xdc, 0x75,
+    0x22, 0x34, 0x57, 0xaf, 0x5e, 0xdc, 0x59, 0xcc, 0x2f, 0xac, 0x04, 0x5a, 0x29, 0x21, 0x11, 0x33,
+];
+
+/// The encoding of schemas differs from the pinned one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingChanged {
+    /// The pinned hash of the reference corpus.
+    pub expected: [u8; 32],
+    /// The hash of the reference corpus with the current encoding.
+    pub actual: [u8; 32],
+}
+
+impl fmt::Display for EncodingChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "schema encoding version {} changed: reference corpus hashes to {}, expected {}",
+            ENCODING_VERSION,
+            blake3::Hash::from(self.actual).to_hex(),
+            blake3::Hash::from(self.expected).to_hex(),
+        )
+    }
+}
+
+impl std::error::Error for EncodingChanged {}
+
+/// A fixed set of schemas that uses every variant of [`Schema`].
+///
+/// Variants are used both empty and with more than one child, and names
+/// contain characters that need escaping in text formats.
+pub fn reference_corpus() -> Vec<Schema> {
+    let atom = |name: &str| Schema::Atom(name.to_string());
+    let field = |name: &str, schema| Named(name.to_string(), schema);
+    vec![
+        Schema::Unit,
+        Schema::Bottom,
+        atom("u32"),
+        atom("we\"ird ünïcode\n"),
+        Schema::Product(vec![]),
+        Schema::Product(vec![atom("u8"), Schema::Unit]),
+        Schema::Sum(vec![]),
+        Schema::Sum(vec![Schema::Unit, atom("String")]),
+        Schema::Struct(vec![]),
+        Schema::Struct(vec![
+            field("id", atom("u64")),
+            field("name", atom("String")),
+        ]),
+        Schema::Enum(vec![]),
+        Schema::Enum(vec![field("A", Schema::Unit), field("B", atom("i128"))]),
+        Schema::named("Entry", Schema::Struct(vec![field("key", atom("String"))])),
+        Schema::Seq(Box::new(atom("u8"))),
+        Schema::Set(Box::new(atom("u16"))),
+        Schema::Map(Box::new(atom("String")), Box::new(atom("f64"))),
+        Schema::Optional(Box::new(atom("char"))),
+        Schema::Tagged(
+            Tagging::Internal("type".to_string()),
+            Box::new(Schema::Enum(vec![field("A", Schema::Unit)])),
+        ),
+        Schema::Tagged(
+            Tagging::Adjacent("t".to_string(), "c".to_string()),
+            Box::new(Schema::Enum(vec![field("B", atom("u8"))])),
+        ),
+        Schema::Tagged(Tagging::Untagged, Box::new(Schema::Sum(vec![]))),
+        Schema::UnorderedSet(Box::new(atom("u16"))),
+        Schema::UnorderedMap(Box::new(atom("String")), Box::new(atom("f64"))),
+        Schema::Enumeration(vec![]),
+        Schema::Enumeration(vec![("Red".to_string(), 0), ("Gr\"een".to_string(), 7)]),
+        Schema::Stream(Box::new(atom("u32"))),
+    ]
+}
+
+/// The hash of the [reference corpus](reference_corpus) with the current
+/// encoding.
+pub fn reference_hash() -> [u8; 32] {
+    *hash_postcard(&reference_corpus()).as_bytes()
+}
+
+/// Checks that the encoding of schemas matches [`ENCODING_VERSION`].
+pub fn verify_encoding_stability() -> Result<(), EncodingChanged> {
+    let actual = reference_hash();
+    if actual == REFERENCE_HASH {
+        Ok(())
+    } else {
+        Err(EncodingChanged {
+            expected: REFERENCE_HASH,
+            actual,
+        })
+    }
+}
@@ -0,0 +1,261 @@
+//! Standalone values tagged with the hash of their schema.
+//!
+//! `#[serialize_stable]` prefixes every message of an enum with the hash of
+//! its schema. [`Envelope<T>`] does the same for a single value, so event
+//! logs and persistent queues can store heterogeneous messages and tell them
+//! apart later. An envelope is encoded in the same wire format, the 32 byte
+//! hash followed by the postcard encoded payload:
+//!
+//! ```
+//! use irpc_schema::{envelope::{self, Envelope}, schema};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[schema(Nominal)]
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct UserCreated {
+//!     name: String,
+//! }
+//!
+//! let event = Envelope::new(UserCreated { name: "alice".into() });
+//! let bytes = event.to_postcard().unwrap();
+//!
+//! // look at the hash without decoding the payload
+//! assert_eq!(envelope::peek_hash(&bytes), Some(Envelope::<UserCreated>::hash()));
+//! assert!(Envelope::<UserCreated>::matches(&bytes));
+//! let decoded = Envelope::<UserCreated>::from_postcard(&bytes).unwrap();
+//! assert_eq!(decoded, event);
+//! ```
+use std::{
+    any::TypeId,
+    collections::BTreeMap,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::RwLock,
+};
+
+use serde::{de::DeserializeOwned, ser::SerializeTuple, Deserialize, Deserializer, Serialize};
+
+use crate::{
+    telemetry::{self, Direction, UnknownHash},
+    wire, HasSchema, SchemaError,
+};
+
+/// A value that is serialized together with the hash of its schema.
+///
+/// Serializes as the tuple `(hash, value)`. Deserializing fails unless the
+/// hash is the hash of the schema of `T`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Envelope<T>(pub T);
+
+/// Errors when decoding an [`Envelope`].
+#[derive(Debug)]
+pub enum EnvelopeError {
+    /// The bytes are too short to contain a hash, see
+    /// [`SchemaError::MissingHash`].
+    Schema(SchemaError),
+    /// The hash is not the hash of the expected schema.
+    HashMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The hash matches, but the payload could not be decoded.
+    Decode(postcard::Error),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::Schema(e) => e.fmt(f),
+            EnvelopeError::HashMismatch { expected, actual } => write!(
+                f,
+                "envelope has schema {}, expected {}",
+                blake3::Hash::from(*actual),
+                blake3::Hash::from(*expected)
+            ),
+            EnvelopeError::Decode(e) => write!(f, "decode error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EnvelopeError::Schema(e) => Some(e),
+            EnvelopeError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The hash of an encoded envelope, without decoding the payload.
+///
+/// Returns `None` if the bytes are too short to contain a hash.
+pub fn peek_hash(bytes: &[u8]) -> Option<[u8; 32]> {
+    bytes.get(..32).map(|hash| hash.try_into().unwrap())
+}
+
+/// Splits an encoded envelope into the hash and the encoded payload.
+pub fn split(bytes: &[u8]) -> Option<([u8; 32], &[u8])> {
+    Some((peek_hash(bytes)?, &bytes[32..]))
+}
+
+impl<T> Envelope<T> {
+    /// Wraps a value.
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: HasSchema + 'static> Envelope<T> {
+    /// The hash of the schema of `T`, which prefixes every envelope.
+    ///
+    /// The hash is computed once per type.
+    pub fn hash() -> [u8; 32] {
+        cached_hash::<T>()
+    }
+
+    /// True if the encoded envelope has the hash of `T`.
+    pub fn matches(bytes: &[u8]) -> bool {
+        peek_hash(bytes) == Some(Self::hash())
+    }
+}
+
+impl<T: HasSchema + Serialize + 'static> Envelope<T> {
+    /// Serializes the envelope with postcard into a single buffer of the
+    /// exact size.
+    ///
+    /// The result is identical to `postcard::to_allocvec`.
+    pub fn to_postcard(&self) -> Result<Vec<u8>, wire::Error> {
+        let hash = Self::hash();
+        telemetry::record_message(
+            Direction::Serialize,
+            std::any::type_name::<T>(),
+            &hash,
+            &self.0,
+        );
+        wire::to_vec(&hash, &self.0)
+    }
+}
+
+impl<T: HasSchema + Serialize + DeserializeOwned + 'static> Envelope<T> {
+    /// Decodes an envelope, checking the hash before decoding the payload.
+    pub fn from_postcard(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        let (actual, payload) =
+            split(bytes).ok_or(EnvelopeError::Schema(SchemaError::MissingHash))?;
+        let expected = Self::hash();
+        if actual != expected {
+            report(actual, Some(bytes.len()));
+            return Err(EnvelopeError::HashMismatch { expected, actual });
+        }
+        let value = postcard::from_bytes(payload).map_err(EnvelopeError::Decode)?;
+        telemetry::record_message(
+            Direction::Deserialize,
+            std::any::type_name::<T>(),
+            &actual,
+            &value,
+        );
+        Ok(Self(value))
+    }
+}
+
+impl<T: HasSchema + Serialize + 'static> Serialize for Envelope<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hash = Self::hash();
+        telemetry::record_message(
+            Direction::Serialize,
+            std::any::type_name::<T>(),
+            &hash,
+            &self.0,
+        );
+        let mut tup = serializer.serialize_tuple(2)?;
+        tup.serialize_element(&hash)?;
+        tup.serialize_element(&self.0)?;
+        tup.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Envelope<T>
+where
+    T: HasSchema + Deserialize<'de> + Serialize + 'static,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T> serde::de::Visitor<'de> for Visitor<T>
+        where
+            T: HasSchema + Deserialize<'de> + Serialize + 'static,
+        {
+            type Value = Envelope<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a tuple with a schema hash and payload")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let hash = seq
+                    .next_element::<[u8; 32]>()?
+                    .ok_or_else(|| serde::de::Error::custom("missing hash"))?;
+                if hash != Envelope::<T>::hash() {
+                    report(hash, None);
+                    return Err(serde::de::Error::custom("schema hash mismatch"));
+                }
+                let value = seq
+                    .next_element::<T>()?
+                    .ok_or_else(|| serde::de::Error::custom("missing payload"))?;
+                let name = std::any::type_name::<T>();
+                telemetry::record_message(Direction::Deserialize, name, &hash, &value);
+                Ok(Envelope(value))
+            }
+        }
+
+        deserializer.deserialize_tuple(2, Visitor(std::marker::PhantomData))
+    }
+}
+
+impl<T> From<T> for Envelope<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Envelope<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Envelope<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+fn report(hash: [u8; 32], len: Option<usize>) {
+    telemetry::report_unknown_hash(UnknownHash {
+        hash,
+        nearest: None,
+        len,
+    });
+}
+
+/// Looks up the hash of the schema of a type, computing it if needed.
+fn cached_hash<T: HasSchema + 'static>() -> [u8; 32] {
+    static CACHE: RwLock<BTreeMap<TypeId, [u8; 32]>> = RwLock::new(BTreeMap::new());
+    let id = TypeId::of::<T>();
+    if let Some(hash) = CACHE.read().unwrap().get(&id) {
+        return *hash;
+    }
+    let hash = *T::static_schema().stable_hash().as_bytes();
+    CACHE.write().unwrap().insert(id, hash);
+    hash
+}
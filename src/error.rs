@@ -0,0 +1,262 @@
+//! The common error type for operations on schemas.
+//!
+//! Most modules have an error type of their own that describes exactly what
+//! went wrong. [`SchemaError`] wraps all of them, so code combining several
+//! operations, such as parsing, registering, checking and encoding, can use `?`
+//! throughout.
+use std::fmt;
+
+#[cfg(feature = "bundle")]
+use crate::bundle::BundleError;
+#[cfg(feature = "irpc")]
+use crate::validate::ValidationError;
+use crate::{
+    check::InvalidSchema,
+    codec::{DecodeError, EncodeError},
+    const_hash::HashMismatch,
+    encoding::EncodingChanged,
+    envelope::EnvelopeError,
+    framing::FramingError,
+    hashing::UnknownHashScheme,
+    migrate::MigrationError,
+    policy::PolicyError,
+    registry::{MergeError, StrictError},
+    short::ShortCollision,
+    text::ParseError,
+};
+
+/// An error about a schema.
+///
+/// Some variants only exist with the feature of their module, so this can grow
+/// when another crate in the build enables a feature.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SchemaError {
+    /// A message is too short to contain a schema hash.
+    MissingHash,
+    /// A message has a schema hash that is not known.
+    UnknownHash { hash: [u8; 32] },
+    /// The schema could not be encoded.
+    Encoding(postcard::Error),
+    /// The schema is not well-formed.
+    Invalid(InvalidSchema),
+    /// The schema does not have its pinned hash.
+    HashMismatch(HashMismatch),
+    /// Two schemas share a short hash.
+    ShortCollision(ShortCollision),
+    /// The encoding of schemas has changed.
+    EncodingChanged(EncodingChanged),
+    /// The hash scheme version is not supported.
+    UnknownHashScheme(UnknownHashScheme),
+    /// The text format of a schema could not be parsed.
+    Parse(ParseError),
+    /// A [`StrictRegistry`](crate::registry::StrictRegistry) rejected a message.
+    Strict(Box<StrictError>),
+    /// Registries could not be merged.
+    Merge(MergeError),
+    /// An evolution policy could not be parsed.
+    Policy(PolicyError),
+    /// A migration between two schemas is not possible.
+    Migration(MigrationError),
+    /// A value does not match its schema.
+    Decode(DecodeError),
+    /// A value could not be encoded with its schema.
+    ValueEncode(EncodeError),
+    /// A framed message could not be read or written.
+    Framing(Box<FramingError>),
+    /// An envelope could not be decoded.
+    Envelope(Box<EnvelopeError>),
+    /// A bundle could not be written or read.
+    #[cfg(feature = "bundle")]
+    Bundle(BundleError),
+    /// A service message failed validation.
+    #[cfg(feature = "irpc")]
+    Validation(ValidationError),
+    /// A json value does not match its schema.
+    #[cfg(feature = "json")]
+    Json(crate::json::ValidationError),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::MissingHash => write!(f, "message too short to contain a hash"),
+            SchemaError::UnknownHash { hash } => {
+                write!(f, "unknown schema {}", blake3::Hash::from(*hash))
+            }
+            SchemaError::Encoding(e) => write!(f, "failed to encode schema: {}", e),
+            SchemaError::Invalid(e) => write!(f, "invalid schema: {}", e),
+            SchemaError::HashMismatch(e) => e.fmt(f),
+            SchemaError::ShortCollision(e) => e.fmt(f),
+            SchemaError::EncodingChanged(e) => e.fmt(f),
+            SchemaError::UnknownHashScheme(e) => e.fmt(f),
+            SchemaError::Parse(e) => e.fmt(f),
+            SchemaError::Strict(e) => e.fmt(f),
+            SchemaError::Merge(e) => e.fmt(f),
+            SchemaError::Policy(e) => e.fmt(f),
+            SchemaError::Migration(e) => e.fmt(f),
+            SchemaError::Decode(e) => e.fmt(f),
+            SchemaError::ValueEncode(e) => e.fmt(f),
+            SchemaError::Framing(e) => e.fmt(f),
+            SchemaError::Envelope(e) => e.fmt(f),
+            #[cfg(feature = "bundle")]
+            SchemaError::Bundle(e) => e.fmt(f),
+            #[cfg(feature = "irpc")]
+            SchemaError::Validation(e) => e.fmt(f),
+            #[cfg(feature = "json")]
+            SchemaError::Json(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SchemaError::MissingHash | SchemaError::UnknownHash { .. } => None,
+            SchemaError::Encoding(e) => Some(e),
+            SchemaError::Invalid(e) => Some(e),
+            SchemaError::HashMismatch(e) => Some(e),
+            SchemaError::ShortCollision(e) => Some(e),
+            SchemaError::EncodingChanged(e) => Some(e),
+            SchemaError::UnknownHashScheme(e) => Some(e),
+            SchemaError::Parse(e) => Some(e),
+            SchemaError::Strict(e) => Some(e),
+            SchemaError::Merge(e) => Some(e),
+            SchemaError::Policy(e) => Some(e),
+            SchemaError::Migration(e) => Some(e),
+            SchemaError::Decode(e) => Some(e),
+            SchemaError::ValueEncode(e) => Some(e),
+            SchemaError::Framing(e) => Some(e),
+            SchemaError::Envelope(e) => Some(e),
+            #[cfg(feature = "bundle")]
+            SchemaError::Bundle(e) => Some(e),
+            #[cfg(feature = "irpc")]
+            SchemaError::Validation(e) => Some(e),
+            #[cfg(feature = "json")]
+            SchemaError::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<postcard::Error> for SchemaError {
+    fn from(e: postcard::Error) -> Self {
+        SchemaError::Encoding(e)
+    }
+}
+
+impl From<InvalidSchema> for SchemaError {
+    fn from(e: InvalidSchema) -> Self {
+        SchemaError::Invalid(e)
+    }
+}
+
+impl From<HashMismatch> for SchemaError {
+    fn from(e: HashMismatch) -> Self {
+        SchemaError::HashMismatch(e)
+    }
+}
+
+impl From<ShortCollision> for SchemaError {
+    fn from(e: ShortCollision) -> Self {
+        SchemaError::ShortCollision(e)
+    }
+}
+
+impl From<EncodingChanged> for SchemaError {
+    fn from(e: EncodingChanged) -> Self {
+        SchemaError::EncodingChanged(e)
+    }
+}
+
+impl From<UnknownHashScheme> for SchemaError {
+    fn from(e: UnknownHashScheme) -> Self {
+        SchemaError::UnknownHashScheme(e)
+    }
+}
+
+impl From<ParseError> for SchemaError {
+    fn from(e: ParseError) -> Self {
+        SchemaError::Parse(e)
+    }
+}
+
+/// A [`StrictError::Schema`] is unwrapped.
+impl From<StrictError> for SchemaError {
+    fn from(e: StrictError) -> Self {
+        match e {
+            StrictError::Schema(e) => e,
+            e => SchemaError::Strict(Box::new(e)),
+        }
+    }
+}
+
+impl From<MergeError> for SchemaError {
+    fn from(e: MergeError) -> Self {
+        SchemaError::Merge(e)
+    }
+}
+
+impl From<PolicyError> for SchemaError {
+    fn from(e: PolicyError) -> Self {
+        SchemaError::Policy(e)
+    }
+}
+
+impl From<MigrationError> for SchemaError {
+    fn from(e: MigrationError) -> Self {
+        SchemaError::Migration(e)
+    }
+}
+
+impl From<DecodeError> for SchemaError {
+    fn from(e: DecodeError) -> Self {
+        SchemaError::Decode(e)
+    }
+}
+
+impl From<EncodeError> for SchemaError {
+    fn from(e: EncodeError) -> Self {
+        SchemaError::ValueEncode(e)
+    }
+}
+
+/// A [`FramingError::Schema`] is unwrapped.
+impl From<FramingError> for SchemaError {
+    fn from(e: FramingError) -> Self {
+        match e {
+            FramingError::Schema(e) => e,
+            e => SchemaError::Framing(Box::new(e)),
+        }
+    }
+}
+
+/// A [`EnvelopeError::Schema`] is unwrapped.
+impl From<EnvelopeError> for SchemaError {
+    fn from(e: EnvelopeError) -> Self {
+        match e {
+            EnvelopeError::Schema(e) => e,
+            e => SchemaError::Envelope(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(feature = "bundle")]
+impl From<BundleError> for SchemaError {
+    fn from(e: BundleError) -> Self {
+        SchemaError::Bundle(e)
+    }
+}
+
+#[cfg(feature = "irpc")]
+impl From<ValidationError> for SchemaError {
+    fn from(e: ValidationError) -> Self {
+        SchemaError::Validation(e)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<crate::json::ValidationError> for SchemaError {
+    fn from(e: crate::json::ValidationError) -> Self {
+        SchemaError::Json(e)
+    }
+}
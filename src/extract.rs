@@ -0,0 +1,219 @@
+//! Partial decoding of postcard bytes.
+//!
+//! [`extract`] reads a single node of a message by path, skipping over
+//! everything before it and stopping as soon as it is read. Routers that only
+//! need a key or a tenant id don't have to decode large payloads.
+//!
+//! Paths are written like the [`Path`] display format, as dot separated
+//! segments, e.g. `PutRequest.key`:
+//!
+//! - struct fields and enum variants are selected by name
+//! - elements of products, cases of sums and items of sequences and sets are
+//!   selected by index
+//! - names of named types are optional, `key` selects the same node as
+//!   `PutRequest.key` in a `PutRequest`
+use std::fmt;
+
+use crate::{
+    codec::{DecodeError, Decoder},
+    diff::{Path, PathSegment},
+    value::Value,
+    Schema,
+};
+
+/// Error when extracting a node from postcard bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// The path does not exist in the schema.
+    InvalidPath { path: String, message: String },
+    /// The bytes don't match the schema.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::InvalidPath { path, message } => {
+                write!(f, "invalid path {}: {}", path, message)
+            }
+            ExtractError::Decode(e) => write!(f, "failed to decode: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExtractError::InvalidPath { .. } => None,
+            ExtractError::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl From<DecodeError> for ExtractError {
+    fn from(value: DecodeError) -> Self {
+        ExtractError::Decode(value)
+    }
+}
+
+/// Extracts the node at `path` from postcard encoded `bytes` of the given
+/// schema.
+///
+/// Returns `None` if the path goes through an enum variant, sum case or
+/// option that is not present in the bytes, or through an item beyond the
+/// end of a sequence. Bytes after the extracted node are not inspected.
+pub fn extract(schema: &Schema, bytes: &[u8], path: &str) -> Result<Option<Value>, ExtractError> {
+    let segments = if path.is_empty() {
+        Vec::new()
+    } else {
+        path.split('.').collect()
+    };
+    // check the path first, so invalid paths are reported regardless of the bytes
+    check(schema, &segments, &Path::default()).map_err(|message| ExtractError::InvalidPath {
+        path: path.to_string(),
+        message,
+    })?;
+    let mut decoder = Decoder::new(bytes);
+    Ok(walk(&mut decoder, schema, &segments, &Path::default())?)
+}
+
+/// Checks that the path exists in the schema.
+fn check(schema: &Schema, segments: &[&str], path: &Path) -> Result<(), String> {
+    if let Schema::Named(named) = schema {
+        let path = path.join(PathSegment::Named(named.0.clone()));
+        return match segments.split_first() {
+            Some((first, rest)) if *first == named.0 => check(&named.1, rest, &path),
+            _ => check(&named.1, segments, &path),
+        };
+    }
+    let Some((segment, rest)) = segments.split_first() else {
+        return Ok(());
+    };
+    let index = || {
+        segment
+            .parse::<usize>()
+            .map_err(|_| format!("expected an index at {}, found {}", path, segment))
+    };
+    match schema {
+        Schema::Product(items) | Schema::Sum(items) => {
+            let i = index()?;
+            match items.get(i) {
+                Some(item) => check(item, rest, &path.join(PathSegment::Index(i))),
+                None => Err(format!("no element {} at {}", i, path)),
+            }
+        }
+        Schema::Struct(fields) => match fields.iter().find(|f| f.0 == *segment) {
+            Some(field) => check(
+                &field.1,
+                rest,
+                &path.join(PathSegment::Field(field.0.clone())),
+            ),
+            None => Err(format!("no field {} at {}", segment, path)),
+        },
+        Schema::Enum(cases) => match cases.iter().find(|c| c.0 == *segment) {
+            Some(case) => check(
+                &case.1,
+                rest,
+                &path.join(PathSegment::Variant(case.0.clone())),
+            ),
+            None => Err(format!("no variant {} at {}", segment, path)),
+        },
+        Schema::Enumeration(cases) => match cases.iter().find(|c| c.0 == *segment) {
+            Some(case) => check(
+                &Schema::Unit,
+                rest,
+                &path.join(PathSegment::Variant(case.0.clone())),
+            ),
+            None => Err(format!("no variant {} at {}", segment, path)),
+        },
+        Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item) => {
+            let i = index()?;
+            check(item, rest, &path.join(PathSegment::Index(i)))
+        }
+        Schema::Optional(item) => match index()? {
+            0 => check(&Schema::Unit, rest, &path.join(PathSegment::Index(0))),
+            1 => check(item, rest, &path.join(PathSegment::Index(1))),
+            i => Err(format!("no element {} at {}", i, path)),
+        },
+        _ => Err(format!("can not select {} at {}", segment, path)),
+    }
+}
+
+/// Walks to the node at a path that has been checked to exist.
+fn walk(
+    decoder: &mut Decoder<'_>,
+    schema: &Schema,
+    segments: &[&str],
+    path: &Path,
+) -> Result<Option<Value>, DecodeError> {
+    if let Schema::Named(named) = schema {
+        let path = path.join(PathSegment::Named(named.0.clone()));
+        let rest = match segments.first() {
+            Some(first) if *first == named.0 => &segments[1..],
+            _ => segments,
+        };
+        return walk(decoder, &named.1, rest, &path);
+    }
+    let Some((segment, rest)) = segments.split_first() else {
+        return decoder.value(schema, path).map(Some);
+    };
+    let index = || segment.parse::<usize>().expect("path is checked");
+    match schema {
+        Schema::Product(items) => {
+            let i = index();
+            for item in &items[..i] {
+                decoder.skip(item, path)?;
+            }
+            walk(decoder, &items[i], rest, &path.join(PathSegment::Index(i)))
+        }
+        Schema::Struct(fields) => {
+            let i = fields.iter().position(|f| f.0 == *segment).unwrap();
+            for field in &fields[..i] {
+                decoder.skip(&field.1, path)?;
+            }
+            let path = path.join(PathSegment::Field(fields[i].0.clone()));
+            walk(decoder, &fields[i].1, rest, &path)
+        }
+        Schema::Sum(cases) => {
+            let i = index();
+            if decoder.discriminant(cases.len(), path)? as usize != i {
+                return Ok(None);
+            }
+            walk(decoder, &cases[i], rest, &path.join(PathSegment::Index(i)))
+        }
+        Schema::Enum(cases) => {
+            let i = cases.iter().position(|c| c.0 == *segment).unwrap();
+            if decoder.discriminant(cases.len(), path)? as usize != i {
+                return Ok(None);
+            }
+            let path = path.join(PathSegment::Variant(cases[i].0.clone()));
+            walk(decoder, &cases[i].1, rest, &path)
+        }
+        Schema::Enumeration(cases) => {
+            if decoder.tag(cases, path)?.0 != *segment {
+                return Ok(None);
+            }
+            let path = path.join(PathSegment::Variant(segment.to_string()));
+            walk(decoder, &Schema::Unit, rest, &path)
+        }
+        Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item) => {
+            let i = index();
+            if i >= decoder.usize(path)? {
+                return Ok(None);
+            }
+            for _ in 0..i {
+                decoder.skip(item, path)?;
+            }
+            walk(decoder, item, rest, &path.join(PathSegment::Index(i)))
+        }
+        Schema::Optional(item) => {
+            let i = index();
+            if decoder.discriminant(2, path)? as usize != i {
+                return Ok(None);
+            }
+            let case = if i == 0 { &Schema::Unit } else { &**item };
+            walk(decoder, case, rest, &path.join(PathSegment::Index(i)))
+        }
+        _ => unreachable!("path is checked"),
+    }
+}
@@ -0,0 +1,257 @@
+//! A C API, for services written in other languages that need to check the
+//! schema hashes of messages they handle.
+//!
+//! The declarations are in `include/irpc_schema.h`. Build a shared or static
+//! library with
+//!
+//! ```text
+//! cargo rustc --release --no-default-features --features ffi --crate-type cdylib
+//! cargo rustc --release --no-default-features --features ffi --crate-type staticlib
+//! ```
+//!
+//! Schemas and manifests are passed around as opaque handles, created from
+//! text or postcard bytes. The caller owns the handles and must release each
+//! of them exactly once with the matching `_free` function, not with `free`.
+//! Text results are written into caller provided buffers in the style of
+//! `snprintf`: the return value is the length of the full result, excluding
+//! the terminating nul, and the result is truncated if the buffer is too
+//! small. Functions return a negative value or a null pointer on invalid
+//! arguments. `isize` results are `ptrdiff_t` and `usize` arguments `size_t`
+//! in C.
+use std::{
+    ffi::{c_char, c_int, CStr},
+    ptr, slice,
+};
+
+use crate::{
+    diff::{self, Compat},
+    manifest::SchemaManifest,
+    pretty::PrettyOptions,
+    Schema,
+};
+
+/// An opaque handle to a [`Schema`].
+pub struct IrpcSchema(Schema);
+
+/// An opaque handle to a [`SchemaManifest`].
+pub struct IrpcSchemaManifest(SchemaManifest);
+
+/// Writes `text` into `buf` as a nul terminated string, truncating it to
+/// `len - 1` bytes if needed.
+///
+/// # Safety
+///
+/// `buf` must be null or valid for writes of `len` bytes.
+unsafe fn write_buf(text: &str, buf: *mut c_char, len: usize) -> isize {
+    if !buf.is_null() && len > 0 {
+        let n = text.len().min(len - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), buf.cast(), n);
+        *buf.add(n) = 0;
+    }
+    text.len() as isize
+}
+
+/// Parses a schema from its text form, returning null on failure.
+///
+/// The caller owns the returned schema and must release it with
+/// [`irpc_schema_free`].
+///
+/// # Safety
+///
+/// `text` must be null or a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_parse(text: *const c_char) -> *mut IrpcSchema {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(text) = CStr::from_ptr(text).to_str() else {
+        return ptr::null_mut();
+    };
+    match text.parse() {
+        Ok(schema) => Box::into_raw(Box::new(IrpcSchema(schema))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a schema returned by [`irpc_schema_parse`]. Null is ignored.
+///
+/// The handle must not be used afterwards.
+///
+/// # Safety
+///
+/// `schema` must be null or a handle returned by [`irpc_schema_parse`] that
+/// has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_free(schema: *mut IrpcSchema) {
+    if !schema.is_null() {
+        drop(Box::from_raw(schema));
+    }
+}
+
+/// Writes the 32 byte stable hash of a schema to `out`.
+///
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `schema` must be null or a live handle, and `out` null or valid for
+/// writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_hash(schema: *const IrpcSchema, out: *mut u8) -> c_int {
+    if schema.is_null() || out.is_null() {
+        return -1;
+    }
+    let hash = (*schema).0.stable_hash();
+    ptr::copy_nonoverlapping(hash.as_bytes().as_ptr(), out, 32);
+    0
+}
+
+/// Pretty prints a schema into `buf`, writing nodes of up to
+/// `max_inline_width` characters on a single line.
+///
+/// # Safety
+///
+/// `schema` must be null or a live handle, and `buf` null or valid for
+/// writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_pretty_print(
+    schema: *const IrpcSchema,
+    max_inline_width: usize,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    if schema.is_null() {
+        return -1;
+    }
+    let options = PrettyOptions {
+        max_inline_width,
+        ..PrettyOptions::default()
+    };
+    write_buf(&(*schema).0.pretty_print_with(0, &options), buf, len)
+}
+
+/// Writes the changes from `old` to `new` into `buf`, one per line, see
+/// [`diff::diff`].
+///
+/// # Safety
+///
+/// `old` and `new` must be null or live handles, and `buf` null or valid for
+/// writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_diff(
+    old: *const IrpcSchema,
+    new: *const IrpcSchema,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    if old.is_null() || new.is_null() {
+        return -1;
+    }
+    let diff = diff::diff(&(*old).0, &(*new).0);
+    write_buf(&diff.to_string(), buf, len)
+}
+
+/// Classifies the changes from `old` to `new`: 0 for compatible, 1 for
+/// migratable and 2 for breaking, see [`Compat`].
+///
+/// # Safety
+///
+/// `old` and `new` must be null or live handles.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_schema_compat(
+    old: *const IrpcSchema,
+    new: *const IrpcSchema,
+) -> c_int {
+    if old.is_null() || new.is_null() {
+        return -1;
+    }
+    match diff::diff(&(*old).0, &(*new).0).compat() {
+        Compat::Compatible => 0,
+        Compat::Migratable => 1,
+        Compat::Breaking => 2,
+    }
+}
+
+/// Decodes a postcard encoded manifest, returning null on failure.
+///
+/// The caller owns the returned manifest and must release it with
+/// [`irpc_manifest_free`].
+///
+/// # Safety
+///
+/// `bytes` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_manifest_decode(
+    bytes: *const u8,
+    len: usize,
+) -> *mut IrpcSchemaManifest {
+    if bytes.is_null() {
+        return ptr::null_mut();
+    }
+    match postcard::from_bytes(slice::from_raw_parts(bytes, len)) {
+        Ok(manifest) => Box::into_raw(Box::new(IrpcSchemaManifest(manifest))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a manifest returned by [`irpc_manifest_decode`]. Null is ignored.
+///
+/// The handle must not be used afterwards.
+///
+/// # Safety
+///
+/// `manifest` must be null or a handle returned by [`irpc_manifest_decode`]
+/// that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_manifest_free(manifest: *mut IrpcSchemaManifest) {
+    if !manifest.is_null() {
+        drop(Box::from_raw(manifest));
+    }
+}
+
+/// Writes the 32 byte hash of the whole manifest to `out`.
+///
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `manifest` must be null or a live handle, and `out` null or valid for
+/// writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_manifest_hash(
+    manifest: *const IrpcSchemaManifest,
+    out: *mut u8,
+) -> c_int {
+    if manifest.is_null() || out.is_null() {
+        return -1;
+    }
+    let hash = (*manifest).0.hash();
+    ptr::copy_nonoverlapping(hash.as_ptr(), out, 32);
+    0
+}
+
+/// Looks up a message by its 32 byte schema hash, and writes its name into
+/// `buf`.
+///
+/// Returns -1 if the hash is not part of the manifest.
+///
+/// # Safety
+///
+/// `manifest` must be null or a live handle, `hash` null or valid for reads
+/// of 32 bytes, and `buf` null or valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn irpc_manifest_lookup(
+    manifest: *const IrpcSchemaManifest,
+    hash: *const u8,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    if manifest.is_null() || hash.is_null() {
+        return -1;
+    }
+    let hash = &*hash.cast::<[u8; 32]>();
+    match (*manifest).0.get_by_hash(hash) {
+        Some(entry) => write_buf(&entry.name, buf, len),
+        None => -1,
+    }
+}
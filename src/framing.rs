@@ -0,0 +1,252 @@
+//! Framing of messages for transports without message boundaries.
+//!
+//! irpc takes care of message boundaries, but plain TCP streams, WebSocket
+//! connections or HTTP bodies carrying several messages need framing. A frame
+//! is the length of the message as a LEB128 varint, like postcard encodes
+//! lengths, followed by the message in the stable wire format, i.e. the 32 byte
+//! schema hash followed by the postcard encoded payload:
+//!
+//! ```text
+//! varint(32 + payload.len()) || hash || payload
+//! ```
+//!
+//! This allows reusing the stable-hash protocol format outside irpc, e.g. for
+//! browser clients talking to a gateway.
+use std::{fmt, io};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::SchemaError;
+
+/// Default maximum length of a message, excluding the length prefix.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Maximum length of the varint length prefix.
+const MAX_PREFIX_LEN: usize = 10;
+
+/// Errors when reading or writing frames.
+#[derive(Debug)]
+pub enum FramingError {
+    /// The length prefix is not a valid varint.
+    InvalidLength,
+    /// The message is longer than allowed.
+    TooLarge { len: usize, max: usize },
+    /// The message is too short to contain a hash, see
+    /// [`SchemaError::MissingHash`].
+    Schema(SchemaError),
+    /// The stream ended within a frame.
+    UnexpectedEof,
+    /// The message could not be encoded or decoded.
+    Postcard(postcard::Error),
+    /// Reading or writing failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::InvalidLength => write!(f, "invalid length prefix"),
+            FramingError::TooLarge { len, max } => {
+                write!(f, "message of {} bytes exceeds the maximum of {}", len, max)
+            }
+            FramingError::Schema(e) => e.fmt(f),
+            FramingError::UnexpectedEof => write!(f, "stream ended within a frame"),
+            FramingError::Postcard(e) => write!(f, "postcard error: {}", e),
+            FramingError::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FramingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FramingError::Schema(e) => Some(e),
+            FramingError::Postcard(e) => Some(e),
+            FramingError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FramingError {
+    fn from(value: io::Error) -> Self {
+        FramingError::Io(value)
+    }
+}
+
+/// A decoded frame, borrowing from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// The schema hash of the message.
+    pub hash: [u8; 32],
+    /// The postcard encoded payload.
+    pub payload: &'a [u8],
+}
+
+impl Frame<'_> {
+    /// The message in the stable wire format, i.e. hash and payload.
+    pub fn to_message(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(32 + self.payload.len());
+        res.extend_from_slice(&self.hash);
+        res.extend_from_slice(self.payload);
+        res
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a varint, returning `None` if the input ends within it.
+fn read_varint(bytes: &[u8]) -> Result<Option<(usize, usize)>, FramingError> {
+    let mut value = 0usize;
+    for (i, byte) in bytes.iter().enumerate().take(MAX_PREFIX_LEN) {
+        let bits = (*byte & 0x7f) as usize;
+        let shift = 7 * i as u32;
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(FramingError::InvalidLength);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    if bytes.len() >= MAX_PREFIX_LEN {
+        Err(FramingError::InvalidLength)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Frames a message given by hash and payload.
+pub fn encode_frame(hash: &[u8; 32], payload: &[u8]) -> Vec<u8> {
+    let len = 32 + payload.len();
+    let mut res = Vec::with_capacity(MAX_PREFIX_LEN + len);
+    push_varint(&mut res, len);
+    res.extend_from_slice(hash);
+    res.extend_from_slice(payload);
+    res
+}
+
+/// Frames a message in the stable wire format, e.g. a `serialize_stable` or
+/// `serialize_service` enum.
+pub fn encode<T: Serialize>(msg: &T) -> Result<Vec<u8>, FramingError> {
+    let message = postcard::to_allocvec(msg).map_err(FramingError::Postcard)?;
+    if message.len() < 32 {
+        return Err(FramingError::Schema(SchemaError::MissingHash));
+    }
+    let mut res = Vec::with_capacity(MAX_PREFIX_LEN + message.len());
+    push_varint(&mut res, message.len());
+    res.extend_from_slice(&message);
+    Ok(res)
+}
+
+/// Decodes the first frame of `bytes`.
+///
+/// Returns the frame and the number of bytes it occupies, or `None` if
+/// `bytes` does not contain a complete frame yet. Fails if the message is
+/// longer than `max_len`, even if it is incomplete.
+pub fn decode_frame(
+    bytes: &[u8],
+    max_len: usize,
+) -> Result<Option<(Frame<'_>, usize)>, FramingError> {
+    let Some((len, prefix)) = read_varint(bytes)? else {
+        return Ok(None);
+    };
+    if len > max_len {
+        return Err(FramingError::TooLarge { len, max: max_len });
+    }
+    if len < 32 {
+        return Err(FramingError::Schema(SchemaError::MissingHash));
+    }
+    let Some(message) = bytes.get(prefix..prefix + len) else {
+        return Ok(None);
+    };
+    let frame = Frame {
+        hash: message[..32].try_into().unwrap(),
+        payload: &message[32..],
+    };
+    Ok(Some((frame, prefix + len)))
+}
+
+/// Decodes the first frame of `bytes` as a message in the stable wire format.
+///
+/// Returns the message and the number of bytes its frame occupies, or `None`
+/// if `bytes` does not contain a complete frame yet.
+pub fn decode<T: DeserializeOwned>(
+    bytes: &[u8],
+    max_len: usize,
+) -> Result<Option<(T, usize)>, FramingError> {
+    let Some((frame, len)) = decode_frame(bytes, max_len)? else {
+        return Ok(None);
+    };
+    let start = len - 32 - frame.payload.len();
+    let msg = postcard::from_bytes(&bytes[start..len]).map_err(FramingError::Postcard)?;
+    Ok(Some((msg, len)))
+}
+
+/// Writes a message in the stable wire format as a frame.
+pub fn write_frame<W: io::Write, T: Serialize>(
+    writer: &mut W,
+    msg: &T,
+) -> Result<(), FramingError> {
+    writer.write_all(&encode(msg)?)?;
+    Ok(())
+}
+
+/// Reads a frame and returns the message in the stable wire format.
+///
+/// Returns `None` if the stream ends before the frame starts.
+pub fn read_frame<R: io::Read>(
+    reader: &mut R,
+    max_len: usize,
+) -> Result<Option<Vec<u8>>, FramingError> {
+    let mut prefix = Vec::with_capacity(MAX_PREFIX_LEN);
+    let len = loop {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            return if prefix.is_empty() {
+                Ok(None)
+            } else {
+                Err(FramingError::UnexpectedEof)
+            };
+        }
+        prefix.push(byte[0]);
+        if let Some((len, _)) = read_varint(&prefix)? {
+            break len;
+        }
+    };
+    if len > max_len {
+        return Err(FramingError::TooLarge { len, max: max_len });
+    }
+    if len < 32 {
+        return Err(FramingError::Schema(SchemaError::MissingHash));
+    }
+    let mut message = vec![0u8; len];
+    reader
+        .read_exact(&mut message)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => FramingError::UnexpectedEof,
+            _ => FramingError::Io(e),
+        })?;
+    Ok(Some(message))
+}
+
+/// Reads a frame and decodes it as a message in the stable wire format.
+///
+/// Returns `None` if the stream ends before the frame starts.
+pub fn read<R: io::Read, T: DeserializeOwned>(
+    reader: &mut R,
+    max_len: usize,
+) -> Result<Option<T>, FramingError> {
+    match read_frame(reader, max_len)? {
+        Some(message) => postcard::from_bytes(&message)
+            .map(Some)
+            .map_err(FramingError::Postcard),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,80 @@
+//! Fuzz harness for schema conformance.
+//!
+//! [`fuzz_roundtrip`] checks that a type agrees with its schema on arbitrary
+//! input bytes. It can be used directly as the body of a cargo-fuzz target:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| irpc_schema::fuzz::fuzz_roundtrip::<PutRequest>(data));
+//! ```
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    codec::{decode_postcard, encode_postcard},
+    value::Primitive,
+    HasSchema, Schema,
+};
+
+/// True if values of the schema can be decoded dynamically.
+pub(crate) fn is_transparent(schema: &Schema) -> bool {
+    match schema {
+        Schema::Unit | Schema::Bottom | Schema::Enumeration(_) => true,
+        // postcard can only represent externally tagged enums, and streams
+        // are not part of a message
+        Schema::Tagged(..) | Schema::Stream(_) => false,
+        Schema::Atom(name) => Primitive::from_atom(name).is_some(),
+        Schema::Named(named) => is_transparent(&named.1),
+        Schema::Product(items) | Schema::Sum(items) => items.iter().all(is_transparent),
+        Schema::Struct(fields) | Schema::Enum(fields) => {
+            fields.iter().all(|f| is_transparent(&f.1))
+        }
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::UnorderedSet(item)
+        | Schema::Optional(item) => is_transparent(item),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            is_transparent(key) && is_transparent(value)
+        }
+    }
+}
+
+/// Checks that `T` and its schema agree on `data`, panicking if they don't.
+///
+/// Inputs that `T` does not accept are ignored. For inputs that it accepts:
+///
+/// - re-encoding the decoded value must be canonical, i.e. decoding and
+///   encoding the re-encoded bytes must give the same bytes
+/// - the accepted bytes must conform to `T::schema()`, and encoding them
+///   dynamically must give the same bytes as `T`
+///
+/// The schema checks are skipped if the schema contains opaque atoms, which
+/// can not be decoded without the type. The byte comparisons are skipped if
+/// the schema contains unordered collections like `HashMap`, see
+/// [`Schema::has_unordered`]. Otherwise, `T` must serialize deterministically.
+pub fn fuzz_roundtrip<T: HasSchema + Serialize + DeserializeOwned>(data: &[u8]) {
+    let Ok((value, rest)) = postcard::take_from_bytes::<T>(data) else {
+        return;
+    };
+    let schema = T::schema();
+    let ordered = !schema.has_unordered();
+    let accepted = &data[..data.len() - rest.len()];
+    let bytes = postcard::to_allocvec(&value).expect("failed to re-encode a decoded value");
+    let value = postcard::from_bytes::<T>(&bytes).expect("failed to decode re-encoded bytes");
+    let again = postcard::to_allocvec(&value).expect("failed to re-encode a decoded value");
+    if ordered {
+        assert_eq!(bytes, again, "re-encoding is not canonical");
+    }
+    if !is_transparent(&schema) {
+        return;
+    }
+    let dynamic = decode_postcard(&schema, accepted)
+        .unwrap_or_else(|e| panic!("accepted bytes do not conform to the schema: {}", e));
+    let encoded = encode_postcard(&schema, &dynamic)
+        .unwrap_or_else(|e| panic!("failed to encode a decoded value: {}", e));
+    if !ordered {
+        return;
+    }
+    assert_eq!(
+        bytes, encoded,
+        "encoding with the schema differs from encoding with the type"
+    );
+}
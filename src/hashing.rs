@@ -0,0 +1,216 @@
+//! Versioned hash schemes.
+//!
+//! The hash of a schema depends on how the schema is built and encoded, so a
+//! change to how a type is described changes the hashes of existing types.
+//! Each such change gets a new hash scheme version, and the hash functions of
+//! earlier versions are kept, so a registry can store every schema under the
+//! hashes of several schemes while a deployment migrates:
+//!
+//! ```
+//! use irpc_schema::{hashing, HasSchema, SchemaAndHash};
+//!
+//! let current = SchemaAndHash::from(<Option<u32>>::schema());
+//! let legacy = current.rehash(hashing::LEGACY_HASH_SCHEME_VERSION).unwrap();
+//! assert_eq!(current.hash, *hashing::hash_v4(&current.schema).as_bytes());
+//! assert_eq!(legacy.hash, *hashing::hash_v1(&current.schema).as_bytes());
+//! ```
+//!
+//! The versions differ in these changes:
+//!
+//! - Version 2 describes the channels of a `serialize_service` variant as the
+//!   [`CHANNELS_SCHEMA_NAME`] struct with `msg`, `rx` and `tx` parts instead
+//!   of an anonymous `(msg, rx, tx)` product.
+//! - Version 2 describes `Option<T>` as [`Optional`](Schema::Optional) with
+//!   the `optional-schema` feature.
+//! - Version 2 describes every nominal enum as an [`Enum`](Schema::Enum),
+//!   variants with named fields as a [`Struct`](Schema::Struct) and variants
+//!   with unnamed fields as a [`Product`](Schema::Product), unless the enum
+//!   uses `legacy_enum`.
+//! - Version 2 wraps enums with a serde `tag` or `untagged` attribute in
+//!   [`Tagged`](Schema::Tagged).
+//! - Version 3 describes `HashSet` and `HashMap` as
+//!   [`UnorderedSet`](Schema::UnorderedSet) and
+//!   [`UnorderedMap`](Schema::UnorderedMap).
+//! - Version 3 describes enums with the `enumeration` option as an
+//!   [`Enumeration`](Schema::Enumeration).
+//! - Version 4 describes irpc mpsc channels as a [`Stream`](Schema::Stream).
+//!
+//! Version 1 is the scheme before all of these changes, so its hashes match
+//! those sent by peers built before hash schemes were versioned.
+//!
+//! [`CHANNELS_SCHEMA_NAME`]: crate::service::CHANNELS_SCHEMA_NAME
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{service::CHANNELS_SCHEMA_NAME, Named, Schema, SchemaAndHash};
+
+/// The hash scheme used by [`Schema::stable_hash`].
+pub const HASH_SCHEME_VERSION: u32 = 4;
+
+/// The hash scheme before hash schemes were versioned, see [`hash_v1`].
+pub const LEGACY_HASH_SCHEME_VERSION: u32 = 1;
+
+/// All hash scheme versions that can be computed.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u32> =
+    LEGACY_HASH_SCHEME_VERSION..=HASH_SCHEME_VERSION;
+
+/// A hash scheme version that is not [supported](SUPPORTED_VERSIONS).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownHashScheme(pub u32);
+
+impl fmt::Display for UnknownHashScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown hash scheme version {}, supported are {} to {}",
+            self.0,
+            SUPPORTED_VERSIONS.start(),
+            SUPPORTED_VERSIONS.end()
+        )
+    }
+}
+
+impl std::error::Error for UnknownHashScheme {}
+
+/// Version 1: the [version 2](hash_v2) hash of the schema with every change
+/// of version 2 undone.
+///
+/// Channels schemas become `(msg, rx, tx)` products,
+/// [`Optional`](Schema::Optional) becomes `Sum([Unit, T])`, see
+/// [`Schema::to_legacy`], [`Tagged`](Schema::Tagged) is dropped, and named
+/// enums use the `legacy_enum` mapping, where a single variant enum is a
+/// [`Struct`](Schema::Struct) and variants with several fields are an
+/// [`Enum`](Schema::Enum) or a [`Sum`](Schema::Sum).
+pub fn hash_v1(schema: &Schema) -> blake3::Hash {
+    erase_v2(&schema_v2(schema).to_legacy()).stable_hash()
+}
+
+/// Version 2: the [version 3](hash_v3) hash of the schema with unordered sets
+/// and maps replaced by plain ones, see [`Schema::erase_unordered`], and
+/// enumerations replaced by enums of unit variants.
+pub fn hash_v2(schema: &Schema) -> blake3::Hash {
+    schema_v2(schema).stable_hash()
+}
+
+/// Version 3: the hash of the schema with streams replaced by named irpc mpsc
+/// channels, see [`Schema::erase_streams`].
+pub fn hash_v3(schema: &Schema) -> blake3::Hash {
+    schema.erase_streams().stable_hash()
+}
+
+/// Version 4: the hash of the postcard encoding of the schema.
+///
+/// This is the same as [`Schema::stable_hash`].
+pub fn hash_v4(schema: &Schema) -> blake3::Hash {
+    schema.stable_hash()
+}
+
+/// The hash of a schema in the given hash scheme version.
+pub fn hash(schema: &Schema, version: u32) -> Result<blake3::Hash, UnknownHashScheme> {
+    match version {
+        1 => Ok(hash_v1(schema)),
+        2 => Ok(hash_v2(schema)),
+        3 => Ok(hash_v3(schema)),
+        4 => Ok(hash_v4(schema)),
+        _ => Err(UnknownHashScheme(version)),
+    }
+}
+
+/// The hashes of a schema in all supported versions, oldest first.
+///
+/// Versions in which the schema has the same hash are all listed.
+pub fn all_hashes(schema: &Schema) -> Vec<(u32, blake3::Hash)> {
+    SUPPORTED_VERSIONS
+        .map(|version| (version, hash(schema, version).unwrap()))
+        .collect()
+}
+
+impl SchemaAndHash {
+    /// The schema with its hash in the given hash scheme version.
+    pub fn rehash(&self, version: u32) -> Result<SchemaAndHash, UnknownHashScheme> {
+        Ok(SchemaAndHash {
+            schema: self.schema.clone(),
+            hash: *hash(&self.schema, version)?.as_bytes(),
+        })
+    }
+}
+
+/// The schema as described in hash scheme version 2.
+fn schema_v2(schema: &Schema) -> Schema {
+    erase_enumerations(&schema.erase_streams()).erase_unordered()
+}
+
+/// Replaces every [`Enumeration`](Schema::Enumeration) with the enum of unit
+/// variants the derive produces without the `enumeration` option.
+fn erase_enumerations(schema: &Schema) -> Schema {
+    match schema {
+        Schema::Enumeration(cases) => Schema::Enum(
+            cases
+                .iter()
+                .map(|(name, _)| Named(name.clone(), Schema::Unit))
+                .collect(),
+        ),
+        _ => map_children(schema, erase_enumerations),
+    }
+}
+
+/// Undoes the changes of hash scheme version 2, except for
+/// [`Optional`](Schema::Optional).
+fn erase_v2(schema: &Schema) -> Schema {
+    match schema {
+        Schema::Tagged(_, item) => erase_v2(item),
+        Schema::Named(named) => match (named.0.as_str(), &named.1) {
+            (CHANNELS_SCHEMA_NAME, Schema::Struct(parts)) => {
+                Schema::Product(parts.iter().map(|part| erase_v2(&part.1)).collect())
+            }
+            (name, schema) => match erase_v2(schema) {
+                Schema::Enum(variants) => Schema::named(name, legacy_enum(variants)),
+                schema => Schema::named(name, schema),
+            },
+        },
+        _ => map_children(schema, erase_v2),
+    }
+}
+
+/// The `legacy_enum` mapping of the variants of a named enum.
+fn legacy_enum(variants: Vec<Named>) -> Schema {
+    let variants = variants
+        .into_iter()
+        .map(|variant| match variant.1 {
+            Schema::Struct(fields) if fields.len() > 1 => Named(variant.0, Schema::Enum(fields)),
+            Schema::Product(items) if items.len() > 1 => Named(variant.0, Schema::Sum(items)),
+            _ => variant,
+        })
+        .collect::<Vec<_>>();
+    if variants.len() == 1 {
+        Schema::Struct(variants)
+    } else {
+        Schema::Enum(variants)
+    }
+}
+
+/// The schema with `f` applied to each of its children.
+fn map_children(schema: &Schema, f: fn(&Schema) -> Schema) -> Schema {
+    let boxed = |item: &Schema| Box::new(f(item));
+    let fields = |fields: &[Named]| {
+        fields
+            .iter()
+            .map(|field| Named(field.0.clone(), f(&field.1)))
+            .collect()
+    };
+    match schema {
+        Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => schema.clone(),
+        Schema::Product(items) => Schema::Product(items.iter().map(f).collect()),
+        Schema::Sum(items) => Schema::Sum(items.iter().map(f).collect()),
+        Schema::Struct(items) => Schema::Struct(fields(items)),
+        Schema::Enum(items) => Schema::Enum(fields(items)),
+        Schema::Named(named) => Schema::named(named.0.clone(), f(&named.1)),
+        Schema::Seq(item) => Schema::Seq(boxed(item)),
+        Schema::Set(item) => Schema::Set(boxed(item)),
+        Schema::UnorderedSet(item) => Schema::UnorderedSet(boxed(item)),
+        Schema::Map(key, value) => Schema::Map(boxed(key), boxed(value)),
+        Schema::UnorderedMap(key, value) => Schema::UnorderedMap(boxed(key), boxed(value)),
+        Schema::Optional(item) => Schema::Optional(boxed(item)),
+        Schema::Tagged(tagging, item) => Schema::Tagged(tagging.clone(), boxed(item)),
+        Schema::Stream(item) => Schema::Stream(boxed(item)),
+    }
+}
@@ -0,0 +1,794 @@
+//! Validation of JSON documents against schemas.
+//!
+//! JSON is expected in the shape produced by `serde_json` for the Rust types
+//! the schema was derived from:
+//!
+//! - unit is `null`, `Option<T>` is `null` or the value of `T`
+//! - products are arrays, except for products with a single element, which are
+//!   newtypes and therefore represented by their element
+//! - structs are objects. Fields of type `Option<T>` may be missing, unknown
+//!   fields are rejected
+//! - enum variants are externally tagged: `"Name"` for unit variants and
+//!   `{"Name": value}` otherwise. Enums wrapped in [`Schema::Tagged`] use the
+//!   corresponding serde representation instead
+//! - sequences and sets are arrays, bytes are arrays of numbers
+//! - maps are objects, with keys that are strings or stringified integers
+//!
+//! Since sum types don't have case names, a sum value is accepted if its
+//! content matches any of the cases. When converting [`Value`]s to JSON, the
+//! case index is used as the tag.
+use std::fmt;
+
+use serde_json::{Map, Value as Json};
+
+use crate::{
+    diff::{Path, PathSegment},
+    value::{option_inner, Primitive, Value},
+    Named, Schema, Tagging,
+};
+
+/// A location in a JSON document that does not match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Where in the document the mismatch was found.
+    pub path: Path,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Validates a JSON document against a schema, reporting all mismatches.
+pub fn validate_json(schema: &Schema, json: &Json) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    validate(schema, json, &Path::default(), &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn kind(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(_) => "bool",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+fn validate(schema: &Schema, json: &Json, path: &Path, errors: &mut Vec<ValidationError>) {
+    let mut fail = |message: String| {
+        errors.push(ValidationError {
+            path: path.clone(),
+            message,
+        })
+    };
+    match (schema, json) {
+        (Schema::Named(named), _) => validate(
+            &named.1,
+            json,
+            &path.join(PathSegment::Named(named.0.clone())),
+            errors,
+        ),
+        (Schema::Bottom, _) => fail("no value conforms to the bottom type".to_string()),
+        (Schema::Stream(_), _) => fail("a stream has no value within a message".to_string()),
+        (Schema::Unit, Json::Null) => {}
+        (Schema::Unit, _) => fail(format!("expected null, found {}", kind(json))),
+        (Schema::Atom(name), _) => {
+            if let Some(primitive) = Primitive::from_atom(name) {
+                if let Err(message) = validate_primitive(primitive, json) {
+                    fail(message);
+                }
+            }
+        }
+        (Schema::Product(items), _) if items.len() == 1 => validate(&items[0], json, path, errors),
+        (Schema::Product(items), Json::Array(values)) => {
+            if items.len() != values.len() {
+                fail(format!(
+                    "expected {} elements, found {}",
+                    items.len(),
+                    values.len()
+                ));
+                return;
+            }
+            for (i, (item, value)) in items.iter().zip(values).enumerate() {
+                validate(item, value, &path.join(PathSegment::Index(i)), errors);
+            }
+        }
+        (Schema::Product(_), _) => fail(format!("expected array, found {}", kind(json))),
+        (Schema::Struct(fields), Json::Object(object)) => {
+            for Named(name, field) in fields {
+                match object.get(name) {
+                    Some(value) => validate(
+                        field,
+                        value,
+                        &path.join(PathSegment::Field(name.clone())),
+                        errors,
+                    ),
+                    None if option_inner(field).is_some() => {}
+                    None => errors.push(ValidationError {
+                        path: path.clone(),
+                        message: format!("missing field {}", name),
+                    }),
+                }
+            }
+            for key in object.keys() {
+                if !fields.iter().any(|field| &field.0 == key) {
+                    errors.push(ValidationError {
+                        path: path.clone(),
+                        message: format!("unknown field {}", key),
+                    });
+                }
+            }
+        }
+        (Schema::Struct(_), _) => fail(format!("expected object, found {}", kind(json))),
+        (Schema::Sum(_) | Schema::Optional(_), _) if option_inner(schema).is_some() => {
+            if !json.is_null() {
+                validate(option_inner(schema).unwrap(), json, path, errors);
+            }
+        }
+        (Schema::Sum(cases), Json::String(_)) => {
+            if !cases.contains(&Schema::Unit) {
+                fail("no unit case matches".to_string());
+            }
+        }
+        (Schema::Sum(cases), Json::Object(object)) if object.len() == 1 => {
+            let value = object.values().next().unwrap();
+            let matches = cases.iter().any(|case| {
+                let mut errors = Vec::new();
+                validate(case, value, path, &mut errors);
+                errors.is_empty()
+            });
+            if !matches {
+                fail("content matches none of the cases".to_string());
+            }
+        }
+        (Schema::Sum(_), _) => fail(format!(
+            "expected string or object with a single key, found {}",
+            kind(json)
+        )),
+        (Schema::Enum(cases), Json::String(name)) => match cases.iter().find(|c| &c.0 == name) {
+            Some(Named(_, Schema::Unit)) => {}
+            Some(_) => fail(format!("variant {} is not a unit variant", name)),
+            None => fail(format!("unknown variant {}", name)),
+        },
+        (Schema::Enum(cases), Json::Object(object)) if object.len() == 1 => {
+            let (name, value) = object.iter().next().unwrap();
+            match cases.iter().find(|c| &c.0 == name) {
+                Some(Named(_, case)) => validate(
+                    case,
+                    value,
+                    &path.join(PathSegment::Variant(name.clone())),
+                    errors,
+                ),
+                None => fail(format!("unknown variant {}", name)),
+            }
+        }
+        (Schema::Enum(_), _) => fail(format!(
+            "expected string or object with a single key, found {}",
+            kind(json)
+        )),
+        (Schema::Enumeration(cases), Json::String(name)) => {
+            if !cases.iter().any(|case| &case.0 == name) {
+                fail(format!("unknown variant {}", name));
+            }
+        }
+        (Schema::Enumeration(_), _) => fail(format!("expected string, found {}", kind(json))),
+        (
+            Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item),
+            Json::Array(values),
+        ) => {
+            for (i, value) in values.iter().enumerate() {
+                validate(item, value, &path.join(PathSegment::Index(i)), errors);
+            }
+        }
+        (Schema::Seq(_) | Schema::Set(_) | Schema::UnorderedSet(_), _) => {
+            fail(format!("expected array, found {}", kind(json)))
+        }
+        (
+            Schema::Map(key, value_schema) | Schema::UnorderedMap(key, value_schema),
+            Json::Object(object),
+        ) => {
+            for (k, v) in object {
+                let entry = path.join(PathSegment::Field(k.clone()));
+                if let Err(message) = validate_key(key, k) {
+                    errors.push(ValidationError {
+                        path: entry.join(PathSegment::Key),
+                        message,
+                    });
+                }
+                validate(value_schema, v, &entry, errors);
+            }
+        }
+        (Schema::Map(_, _) | Schema::UnorderedMap(_, _), _) => {
+            fail(format!("expected object, found {}", kind(json)))
+        }
+        (Schema::Optional(_), _) => unreachable!("options are handled above"),
+        (Schema::Tagged(tagging, inner), _) => match to_external(tagging, inner, json) {
+            Ok(json) => validate(inner, &json, path, errors),
+            Err(message) => fail(message),
+        },
+    }
+}
+
+/// The variants of an enum, looking through named types.
+fn variants(schema: &Schema) -> Option<&[Named]> {
+    match schema {
+        Schema::Named(named) => variants(&named.1),
+        Schema::Enum(cases) => Some(cases),
+        _ => None,
+    }
+}
+
+/// The externally tagged form of a variant.
+fn external(name: &str, case: &Schema, content: Json) -> Json {
+    if case == &Schema::Unit {
+        return Json::String(name.to_string());
+    }
+    let mut object = Map::new();
+    object.insert(name.to_string(), content);
+    Json::Object(object)
+}
+
+/// Converts an enum in the given representation to the externally tagged
+/// representation.
+fn to_external(tagging: &Tagging, schema: &Schema, json: &Json) -> Result<Json, String> {
+    let Some(cases) = variants(schema) else {
+        return Err("only enums can be tagged".to_string());
+    };
+    let variant = |name: &str| {
+        cases
+            .iter()
+            .find(|case| case.0 == name)
+            .ok_or_else(|| format!("unknown variant {}", name))
+    };
+    let (tag, object) = match (tagging, json) {
+        (Tagging::Untagged, _) => {
+            // unit variants are null
+            return cases
+                .iter()
+                .filter(|Named(_, case)| case != &Schema::Unit || json.is_null())
+                .map(|Named(name, case)| external(name, case, json.clone()))
+                .find(|candidate| {
+                    let mut errors = Vec::new();
+                    validate(schema, candidate, &Path::default(), &mut errors);
+                    errors.is_empty()
+                })
+                .ok_or_else(|| "content matches none of the variants".to_string());
+        }
+        (Tagging::Internal(tag) | Tagging::Adjacent(tag, _), Json::Object(object)) => (tag, object),
+        _ => return Err(format!("expected object, found {}", kind(json))),
+    };
+    let Some(Json::String(name)) = object.get(tag) else {
+        return Err(format!("missing tag {}", tag));
+    };
+    let case = &variant(name)?.1;
+    let mut rest = object.clone();
+    rest.remove(tag);
+    let content = match tagging {
+        Tagging::Adjacent(_, content) => {
+            let value = rest.remove(content).unwrap_or(Json::Null);
+            if let Some(key) = rest.keys().next() {
+                return Err(format!("unknown field {}", key));
+            }
+            value
+        }
+        _ if case == &Schema::Unit && !rest.is_empty() => {
+            return Err(format!("unit variant {} has fields", name));
+        }
+        _ => Json::Object(rest),
+    };
+    Ok(external(name, case, content))
+}
+
+/// Converts an externally tagged enum to the given representation.
+fn from_external(tagging: &Tagging, json: Json) -> Result<Json, String> {
+    let (name, content) = match json {
+        Json::String(name) => (name, None),
+        Json::Object(object) if object.len() == 1 => {
+            let (name, content) = object.into_iter().next().unwrap();
+            (name, Some(content))
+        }
+        _ => unreachable!("enums are externally tagged"),
+    };
+    Ok(match (tagging, content) {
+        (Tagging::Untagged, content) => content.unwrap_or(Json::Null),
+        (Tagging::Internal(tag), None) => {
+            Json::Object(Map::from_iter([(tag.clone(), name.into())]))
+        }
+        (Tagging::Internal(tag), Some(Json::Object(mut object))) => {
+            object.insert(tag.clone(), name.into());
+            Json::Object(object)
+        }
+        (Tagging::Internal(_), Some(_)) => {
+            return Err(format!(
+                "internally tagged variant {} is not an object",
+                name
+            ))
+        }
+        (Tagging::Adjacent(tag, content_key), content) => {
+            let mut object = Map::from_iter([(tag.clone(), name.into())]);
+            if let Some(content) = content {
+                object.insert(content_key.clone(), content);
+            }
+            Json::Object(object)
+        }
+    })
+}
+
+fn validate_primitive(primitive: Primitive, json: &Json) -> Result<(), String> {
+    if let Some((min, max)) = primitive.int_range() {
+        let in_range = match json {
+            Json::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(v), _) => (v as u128) <= max,
+                (None, Some(v)) => (v as i128) >= min,
+                (None, None) => return Err(format!("expected {}, found float", primitive.name())),
+            },
+            _ => {
+                return Err(format!(
+                    "expected {}, found {}",
+                    primitive.name(),
+                    kind(json)
+                ))
+            }
+        };
+        return if in_range {
+            Ok(())
+        } else {
+            Err(format!("integer out of range for {}", primitive.name()))
+        };
+    }
+    let ok = match (primitive, json) {
+        (Primitive::Bool, Json::Bool(_)) => true,
+        (Primitive::F32 | Primitive::F64, Json::Number(_)) => true,
+        (Primitive::Str, Json::String(_)) => true,
+        (Primitive::Char, Json::String(s)) => s.chars().count() == 1,
+        (Primitive::Bytes, Json::Array(items)) => items
+            .iter()
+            .all(|item| item.as_u64().is_some_and(|v| v <= u8::MAX as u64)),
+        _ => false,
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {}, found {}",
+            primitive.name(),
+            kind(json)
+        ))
+    }
+}
+
+/// Validates a map key, which JSON always represents as a string.
+fn validate_key(schema: &Schema, key: &str) -> Result<(), String> {
+    match schema {
+        Schema::Named(named) => validate_key(&named.1, key),
+        Schema::Atom(name) => match Primitive::from_atom(name) {
+            Some(Primitive::Str) | None => Ok(()),
+            Some(Primitive::Char) if key.chars().count() == 1 => Ok(()),
+            Some(primitive) if primitive.int_range().is_some() => {
+                let json = if let Ok(v) = key.parse::<u64>() {
+                    Json::from(v)
+                } else if let Ok(v) = key.parse::<i64>() {
+                    Json::from(v)
+                } else {
+                    return Err(format!("expected {} key, found {:?}", name, key));
+                };
+                validate_primitive(primitive, &json)
+            }
+            Some(primitive) => Err(format!(
+                "{} keys are not supported in JSON",
+                primitive.name()
+            )),
+        },
+        _ => Err("only string and integer keys are supported in JSON".to_string()),
+    }
+}
+
+fn error<T>(path: &Path, message: impl Into<String>) -> Result<T, ValidationError> {
+    Err(ValidationError {
+        path: path.clone(),
+        message: message.into(),
+    })
+}
+
+/// Converts a value of the given schema to JSON.
+///
+/// The value is expected to conform to the schema, see
+/// [`Value::check_conforms`]. Opaque atoms, non-finite floats and integers
+/// outside of the 64 bit range can not be represented.
+pub fn to_json(schema: &Schema, value: &Value) -> Result<Json, ValidationError> {
+    value_to_json(schema, value, &Path::default())
+}
+
+fn value_to_json(schema: &Schema, value: &Value, path: &Path) -> Result<Json, ValidationError> {
+    Ok(match (schema, value) {
+        (Schema::Named(named), _) => {
+            let path = path.join(PathSegment::Named(named.0.clone()));
+            return value_to_json(&named.1, value, &path);
+        }
+        (Schema::Unit, Value::Unit) => Json::Null,
+        (Schema::Atom(name), _) => {
+            if Primitive::from_atom(name).is_none() {
+                return error(
+                    path,
+                    format!("opaque atom {} has no JSON representation", name),
+                );
+            }
+            match value {
+                Value::Bool(v) => Json::Bool(*v),
+                Value::Int(v) => match i64::try_from(*v) {
+                    Ok(v) => Json::from(v),
+                    Err(_) => return error(path, "integer out of range for JSON"),
+                },
+                Value::UInt(v) => match u64::try_from(*v) {
+                    Ok(v) => Json::from(v),
+                    Err(_) => return error(path, "integer out of range for JSON"),
+                },
+                Value::Float(v) => match serde_json::Number::from_f64(*v) {
+                    Some(v) => Json::Number(v),
+                    None => return error(path, "non-finite float"),
+                },
+                Value::Char(v) => Json::String(v.to_string()),
+                Value::Str(v) => Json::String(v.clone()),
+                Value::Bytes(v) => Json::Array(v.iter().map(|b| Json::from(*b)).collect()),
+                _ => return error(path, format!("expected {}, found {}", name, value.kind())),
+            }
+        }
+        (Schema::Product(items), Value::Tuple(values)) if items.len() == 1 && values.len() == 1 => {
+            value_to_json(&items[0], &values[0], &path.join(PathSegment::Index(0)))?
+        }
+        (Schema::Product(items), Value::Tuple(values)) if items.len() == values.len() => {
+            Json::Array(
+                items
+                    .iter()
+                    .zip(values)
+                    .enumerate()
+                    .map(|(i, (item, value))| {
+                        value_to_json(item, value, &path.join(PathSegment::Index(i)))
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        (Schema::Struct(fields), Value::Struct(values)) => {
+            let mut object = Map::new();
+            for (Named(name, schema), (_, value)) in fields.iter().zip(values) {
+                let path = path.join(PathSegment::Field(name.clone()));
+                object.insert(name.clone(), value_to_json(schema, value, &path)?);
+            }
+            Json::Object(object)
+        }
+        (Schema::Sum(_) | Schema::Optional(_), Value::Optional(value))
+            if option_inner(schema).is_some() =>
+        {
+            match value {
+                Some(value) => value_to_json(
+                    option_inner(schema).unwrap(),
+                    value,
+                    &path.join(PathSegment::Index(1)),
+                )?,
+                None => Json::Null,
+            }
+        }
+        (Schema::Sum(cases), Value::Variant { index, value, .. }) => {
+            let Some(case) = cases.get(*index as usize) else {
+                return error(path, format!("case index {} out of range", index));
+            };
+            tagged(
+                index.to_string(),
+                case,
+                value,
+                &path.join(PathSegment::Index(*index as usize)),
+            )?
+        }
+        (Schema::Enum(cases), Value::Variant { index, value, .. }) => {
+            let Some(Named(name, case)) = cases.get(*index as usize) else {
+                return error(path, format!("variant index {} out of range", index));
+            };
+            tagged(
+                name.clone(),
+                case,
+                value,
+                &path.join(PathSegment::Variant(name.clone())),
+            )?
+        }
+        (Schema::Enumeration(cases), Value::Variant { index, .. }) => {
+            let Some((name, _)) = cases.iter().find(|case| case.1 == *index) else {
+                return error(path, format!("unknown discriminant {}", index));
+            };
+            Json::String(name.clone())
+        }
+        (Schema::Seq(item), Value::Seq(values))
+        | (Schema::Set(item) | Schema::UnorderedSet(item), Value::Set(values)) => Json::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| value_to_json(item, value, &path.join(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()?,
+        ),
+        (
+            Schema::Map(key_schema, value_schema) | Schema::UnorderedMap(key_schema, value_schema),
+            Value::Map(entries),
+        ) => {
+            let mut object = Map::new();
+            for (i, (key, value)) in entries.iter().enumerate() {
+                let entry = path.join(PathSegment::Index(i));
+                let key = match key {
+                    Value::Str(v) => v.clone(),
+                    Value::Char(v) => v.to_string(),
+                    Value::Int(v) => v.to_string(),
+                    Value::UInt(v) => v.to_string(),
+                    _ => {
+                        return error(
+                            &entry.join(PathSegment::Key),
+                            "only string and integer keys are supported in JSON",
+                        )
+                    }
+                };
+                validate_key(key_schema, &key).or_else(|e| error(&entry, e))?;
+                let value = value_to_json(value_schema, value, &entry.join(PathSegment::Value))?;
+                object.insert(key, value);
+            }
+            Json::Object(object)
+        }
+        (Schema::Tagged(tagging, inner), _) => {
+            if variants(inner).is_none() {
+                return error(path, "only enums can be tagged");
+            }
+            let json = value_to_json(inner, value, path)?;
+            return from_external(tagging, json).or_else(|e| error(path, e));
+        }
+        _ => {
+            return error(
+                path,
+                format!("value of kind {} does not match the schema", value.kind()),
+            )
+        }
+    })
+}
+
+/// An externally tagged case: just the tag for unit cases, otherwise an
+/// object with the tag as the only key.
+fn tagged(
+    tag: String,
+    schema: &Schema,
+    value: &Value,
+    path: &Path,
+) -> Result<Json, ValidationError> {
+    if schema == &Schema::Unit {
+        return Ok(Json::String(tag));
+    }
+    let mut object = Map::new();
+    object.insert(tag, value_to_json(schema, value, path)?);
+    Ok(Json::Object(object))
+}
+
+/// Converts a JSON document of the given schema to a value.
+///
+/// This accepts the same documents as [`validate_json`], but stops at the
+/// first mismatch. Sum cases are identified by their index if the tag is a
+/// number, otherwise the first case that matches is used.
+pub fn from_json(schema: &Schema, json: &Json) -> Result<Value, ValidationError> {
+    json_to_value(schema, json, &Path::default())
+}
+
+fn json_to_value(schema: &Schema, json: &Json, path: &Path) -> Result<Value, ValidationError> {
+    Ok(match (schema, json) {
+        (Schema::Named(named), _) => {
+            let path = path.join(PathSegment::Named(named.0.clone()));
+            return json_to_value(&named.1, json, &path);
+        }
+        (Schema::Unit, Json::Null) => Value::Unit,
+        (Schema::Atom(name), _) => {
+            let Some(primitive) = Primitive::from_atom(name) else {
+                return error(
+                    path,
+                    format!("opaque atom {} has no JSON representation", name),
+                );
+            };
+            validate_primitive(primitive, json).or_else(|e| error(path, e))?;
+            match (primitive, json) {
+                (Primitive::Bool, Json::Bool(v)) => Value::Bool(*v),
+                (Primitive::F32 | Primitive::F64, Json::Number(v)) => {
+                    Value::Float(v.as_f64().unwrap_or_default())
+                }
+                (Primitive::Str, Json::String(v)) => Value::Str(v.clone()),
+                (Primitive::Char, Json::String(v)) => Value::Char(v.chars().next().unwrap()),
+                (Primitive::Bytes, Json::Array(items)) => Value::Bytes(
+                    items
+                        .iter()
+                        .map(|item| item.as_u64().unwrap_or_default() as u8)
+                        .collect(),
+                ),
+                (primitive, Json::Number(v)) => {
+                    let v = v
+                        .as_i64()
+                        .map(i128::from)
+                        .or_else(|| v.as_u64().map(i128::from))
+                        .expect("validated to be an integer");
+                    // the variant follows the type, not the sign of the number
+                    if primitive.int_range().is_some_and(|(min, _)| min < 0) {
+                        Value::Int(v)
+                    } else {
+                        Value::UInt(v as u128)
+                    }
+                }
+                _ => unreachable!("validated to match"),
+            }
+        }
+        (Schema::Product(items), _) if items.len() == 1 => {
+            let path = path.join(PathSegment::Index(0));
+            Value::Tuple(vec![json_to_value(&items[0], json, &path)?])
+        }
+        (Schema::Product(items), Json::Array(values)) if items.len() == values.len() => {
+            Value::Tuple(
+                items
+                    .iter()
+                    .zip(values)
+                    .enumerate()
+                    .map(|(i, (item, value))| {
+                        json_to_value(item, value, &path.join(PathSegment::Index(i)))
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        (Schema::Struct(fields), Json::Object(object)) => {
+            if let Some(key) = object.keys().find(|k| !fields.iter().any(|f| &f.0 == *k)) {
+                return error(path, format!("unknown field {}", key));
+            }
+            let mut values = Vec::new();
+            for Named(name, field) in fields {
+                let value = match object.get(name) {
+                    Some(json) => {
+                        json_to_value(field, json, &path.join(PathSegment::Field(name.clone())))?
+                    }
+                    None if option_inner(field).is_some() => Value::Optional(None),
+                    None => return error(path, format!("missing field {}", name)),
+                };
+                values.push((name.clone(), value));
+            }
+            Value::Struct(values)
+        }
+        (Schema::Sum(_) | Schema::Optional(_), _) if option_inner(schema).is_some() => match json {
+            Json::Null => Value::Optional(None),
+            json => {
+                let inner = option_inner(schema).unwrap();
+                let value = json_to_value(inner, json, &path.join(PathSegment::Index(1)))?;
+                Value::Optional(Some(Box::new(value)))
+            }
+        },
+        (Schema::Sum(cases), Json::String(tag)) => {
+            let index = match tag.parse::<usize>() {
+                Ok(index) => Some(index).filter(|i| cases.get(*i) == Some(&Schema::Unit)),
+                Err(_) => cases.iter().position(|case| case == &Schema::Unit),
+            };
+            let Some(index) = index else {
+                return error(path, "no unit case matches");
+            };
+            Value::Variant {
+                index: index as u32,
+                name: None,
+                value: Box::new(Value::Unit),
+            }
+        }
+        (Schema::Sum(cases), Json::Object(object)) if object.len() == 1 => {
+            let (tag, json) = object.iter().next().unwrap();
+            let candidates = match tag.parse::<usize>() {
+                Ok(index) if index < cases.len() => index..index + 1,
+                _ => 0..cases.len(),
+            };
+            let Some((index, value)) = candidates.into_iter().find_map(|i| {
+                let value = json_to_value(&cases[i], json, &path.join(PathSegment::Index(i)));
+                value.ok().map(|value| (i, value))
+            }) else {
+                return error(path, "content matches none of the cases");
+            };
+            Value::Variant {
+                index: index as u32,
+                name: None,
+                value: Box::new(value),
+            }
+        }
+        (Schema::Enum(cases), Json::String(tag)) => match cases.iter().position(|c| &c.0 == tag) {
+            Some(index) if cases[index].1 == Schema::Unit => Value::Variant {
+                index: index as u32,
+                name: Some(tag.clone()),
+                value: Box::new(Value::Unit),
+            },
+            Some(_) => return error(path, format!("variant {} is not a unit variant", tag)),
+            None => return error(path, format!("unknown variant {}", tag)),
+        },
+        (Schema::Enumeration(cases), Json::String(name)) => {
+            match cases.iter().find(|case| &case.0 == name) {
+                Some((name, tag)) => Value::Variant {
+                    index: *tag,
+                    name: Some(name.clone()),
+                    value: Box::new(Value::Unit),
+                },
+                None => return error(path, format!("unknown variant {}", name)),
+            }
+        }
+        (Schema::Enum(cases), Json::Object(object)) if object.len() == 1 => {
+            let (tag, json) = object.iter().next().unwrap();
+            let Some(index) = cases.iter().position(|c| &c.0 == tag) else {
+                return error(path, format!("unknown variant {}", tag));
+            };
+            let path = path.join(PathSegment::Variant(tag.clone()));
+            Value::Variant {
+                index: index as u32,
+                name: Some(tag.clone()),
+                value: Box::new(json_to_value(&cases[index].1, json, &path)?),
+            }
+        }
+        (
+            Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item),
+            Json::Array(values),
+        ) => {
+            let values = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| json_to_value(item, value, &path.join(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()?;
+            if matches!(schema, Schema::Seq(_)) {
+                Value::Seq(values)
+            } else {
+                Value::Set(values)
+            }
+        }
+        (
+            Schema::Map(key_schema, value_schema) | Schema::UnorderedMap(key_schema, value_schema),
+            Json::Object(object),
+        ) => {
+            let mut entries = Vec::new();
+            for (key, value) in object {
+                let entry = path.join(PathSegment::Field(key.clone()));
+                validate_key(key_schema, key)
+                    .or_else(|e| error(&entry.join(PathSegment::Key), e))?;
+                let key = json_key_to_value(key_schema, key);
+                entries.push((key, json_to_value(value_schema, value, &entry)?));
+            }
+            Value::Map(entries)
+        }
+        (Schema::Tagged(tagging, inner), _) => {
+            let json = to_external(tagging, inner, json).or_else(|e| error(path, e))?;
+            return json_to_value(inner, &json, path);
+        }
+        _ => {
+            let mut errors = Vec::new();
+            validate(schema, json, path, &mut errors);
+            return Err(errors
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| ValidationError {
+                    path: path.clone(),
+                    message: format!("unexpected {}", kind(json)),
+                }));
+        }
+    })
+}
+
+/// Converts a validated map key to a value.
+fn json_key_to_value(schema: &Schema, key: &str) -> Value {
+    match schema {
+        Schema::Named(named) => json_key_to_value(&named.1, key),
+        Schema::Atom(name) => match Primitive::from_atom(name) {
+            Some(Primitive::Char) => Value::Char(key.chars().next().unwrap()),
+            Some(primitive) if primitive.int_range().is_some() => match key.parse::<u128>() {
+                Ok(v) => Value::UInt(v),
+                Err(_) => Value::Int(key.parse().unwrap_or_default()),
+            },
+            _ => Value::Str(key.to_string()),
+        },
+        _ => Value::Str(key.to_string()),
+    }
+}
@@ -1,13 +1,89 @@
 #![cfg_attr(irpc_schema_docsrs, feature(doc_cfg))]
 use std::{
+    any::TypeId,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    fmt, vec,
+    fmt,
+    sync::RwLock,
+    vec,
 };
 
 use serde::{Deserialize, Serialize};
 
+pub use crate::error::SchemaError;
+use crate::pretty::PrettyOptions;
+
+pub mod aliases;
+pub mod arena;
+pub mod atom;
+pub mod bridge;
+#[cfg(feature = "bundle")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "bundle")))]
+pub mod bundle;
+pub mod capabilities;
+pub mod changelog;
+pub mod check;
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub mod client;
+pub mod codec;
+pub mod const_hash;
+pub mod debug;
+pub mod diff;
+pub mod dispatch;
+pub mod docs;
+pub mod encoding;
+pub mod envelope;
+pub mod error;
+pub mod extract;
+#[cfg(feature = "ffi")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "ffi")))]
+pub mod ffi;
+pub mod framing;
+pub mod fuzz;
+pub mod hashing;
+#[cfg(feature = "json")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "json")))]
+pub mod json;
+pub mod manifest;
+pub mod migrate;
+pub mod mock;
+pub mod naming;
+pub mod negotiate;
+pub mod nested;
+mod parallel;
+pub mod policy;
+pub mod pretty;
+#[cfg(feature = "prost")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "prost")))]
+pub mod protobuf;
+#[cfg(feature = "pyo3")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "pyo3")))]
+pub mod python;
+pub mod query;
+pub mod registry;
+pub mod router;
+pub mod service;
+pub mod short;
+#[cfg(feature = "proptest")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "proptest")))]
+pub mod strategy;
+pub mod telemetry;
+pub mod text;
+#[cfg(feature = "json")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "json")))]
+pub mod transcode;
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub mod validate;
+pub mod value;
+pub mod vectors;
+#[cfg(feature = "wasm")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "wasm")))]
+pub mod wasm;
+pub mod wire;
+
 /// Wraps a schema with a name.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Named(pub String, pub Schema);
 
 #[cfg(all(feature = "derive", feature = "irpc"))]
@@ -18,10 +94,13 @@ pub struct Named(pub String, pub Schema);
 pub use irpc_schema_derive::serialize_service;
 #[cfg(feature = "derive")]
 #[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "derive")))]
-pub use irpc_schema_derive::{schema, serialize_stable};
+pub use irpc_schema_derive::{schema, serialize_nested, serialize_stable};
 
 /// The schema enum
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// To avoid per node allocations for large numbers of schemas, use a
+/// [`SchemaArena`](arena::SchemaArena).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Schema {
     /// the unit type
     Unit,
@@ -41,16 +120,70 @@ pub enum Schema {
     Named(Box<Named>),
     /// a sequence type
     Seq(Box<Schema>),
-    /// a set type
+    /// a set type, serialized in sorted order like `BTreeSet`
     Set(Box<Schema>),
-    /// a map type
+    /// a map type, serialized in key order like `BTreeMap`
     Map(Box<Schema>, Box<Schema>),
+    /// an optional value
+    ///
+    /// This encodes like `Sum([Unit, T])`, but tells compatibility tooling that
+    /// the value may be absent. `Option<T>` only uses it with the
+    /// `optional-schema` feature, see [`Schema::to_legacy`].
+    Optional(Box<Schema>),
+    /// an enum with a representation other than the default externally
+    /// tagged one, see [`Tagging`]
+    Tagged(Tagging, Box<Schema>),
+    /// a set type, serialized in no particular order like `HashSet`
+    ///
+    /// This encodes like [`Set`](Schema::Set), but the order of the items in
+    /// a payload depends on the sender, so equal sets do not necessarily have
+    /// equal encodings.
+    UnorderedSet(Box<Schema>),
+    /// a map type, serialized in no particular order like `HashMap`, see
+    /// [`UnorderedSet`](Schema::UnorderedSet)
+    UnorderedMap(Box<Schema>, Box<Schema>),
+    /// an enum whose variants are all unit, with the tag of every variant
+    ///
+    /// A variant is encoded as its tag, like the variant index of an
+    /// [`Enum`](Schema::Enum) of [`Unit`](Schema::Unit) variants, so the
+    /// derive, which uses the variant indices as tags, produces the same
+    /// encoding as for an enum. Exporters can emit a plain enumeration, and
+    /// compatibility tooling can tell a variant appended at the end from one
+    /// that shifts the tags of others.
+    Enumeration(Vec<(String, u32)>),
+    /// a stream of items, like an irpc mpsc channel
+    ///
+    /// The items of a stream are not part of the message, but are sent one at
+    /// a time over a channel of their own, so a stream has no encoding within
+    /// a message. The schema lets tooling tell "a stream of T" from "a value
+    /// containing T", e.g. to find streaming methods of a service.
+    Stream(Box<Schema>),
+}
+
+/// How an enum is represented in self-describing formats.
+///
+/// This mirrors the [serde enum representations]. The default, externally
+/// tagged representation is not recorded, so only enums with a serde `tag` or
+/// `untagged` attribute are wrapped in [`Schema::Tagged`]. Two enums that
+/// differ only in their representation are not wire compatible, so the
+/// representation is part of the hash.
+///
+/// [serde enum representations]: https://serde.rs/enum-representations.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Tagging {
+    /// `#[serde(tag = "t")]`: the variant name is a field of the content.
+    Internal(String),
+    /// `#[serde(tag = "t", content = "c")]`: the variant name and the content
+    /// are two fields of a struct.
+    Adjacent(String, String),
+    /// `#[serde(untagged)]`: just the content, without the variant name.
+    Untagged,
 }
 
 /// Combines a schema with its stable hash.
 ///
 /// This is just to avoid the overhead of calling `stable_hash` every time.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SchemaAndHash {
     pub schema: Schema,
     pub hash: [u8; 32],
@@ -63,14 +196,59 @@ impl From<Schema> for SchemaAndHash {
     }
 }
 
+/// Writes `"name":schema`, which can be parsed back with
+/// [`FromStr`](std::str::FromStr). With `{:#}`, the named schema is pretty
+/// printed, see [`Schema`].
 impl fmt::Display for Named {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\"{}\":{}", self.0, self.1)
+        if f.alternate() {
+            return self.pretty_fmt_with(f, 0, &alternate_options(f));
+        }
+        text::write_quoted(f, &self.0)?;
+        write!(f, ":{}", self.1)
+    }
+}
+
+/// The pretty printing options for `{:#}`.
+fn alternate_options(f: &fmt::Formatter<'_>) -> PrettyOptions {
+    PrettyOptions {
+        max_inline_width: f.width().unwrap_or(0),
+        ..PrettyOptions::default()
     }
 }
 
+/// Writes items in parentheses, without allocating.
+///
+/// With fewer than two items, the separator is also written after the last
+/// one, to tell products from sums.
+fn fmt_list<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    items: &[T],
+    separator: &str,
+) -> fmt::Result {
+    f.write_str("(")?;
+    for (i, t) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{}", t)?;
+    }
+    if items.len() < 2 {
+        f.write_str(separator)?;
+    }
+    f.write_str(")")
+}
+
+/// `{}` writes the schema on a single line, without spaces, in a form that can
+/// be parsed back with [`FromStr`](std::str::FromStr). `{:#}` pretty prints it
+/// like [`Schema::pretty_print`], and `{:#80}` additionally writes nodes of up
+/// to 80 characters on a single line, see
+/// [`PrettyOptions::max_inline_width`].
 impl fmt::Display for Schema {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.pretty_fmt_with(f, 0, &alternate_options(f));
+        }
         match self {
             // Bottom type ⊥
             Schema::Bottom => write!(f, "⊥"),
@@ -78,75 +256,101 @@ impl fmt::Display for Schema {
             // Unit type ()
             Schema::Unit => write!(f, "()"),
 
-            // Atom (String, u32, etc.)
-            Schema::Atom(name) => write!(f, "\"{}\"", name),
-
-            // Product types, tuples with one or more fields: X, Y, Z,
-            Schema::Product(types) => {
-                let elements = types
-                    .iter()
-                    .map(|t| format!("{}", t))
-                    .collect::<Vec<_>>()
-                    .join(",");
-                write!(f, "({})", elements)
-            }
+            // Atom (String, u32, etc.): "X"
+            Schema::Atom(name) => text::write_quoted(f, name),
 
-            // Named struct: "field": X, "field2": Y,
-            Schema::Struct(fields) => {
-                let fields_str = fields
-                    .iter()
-                    .map(|t| format!("{}", t))
-                    .collect::<Vec<_>>()
-                    .join(",");
-                write!(f, "({})", fields_str)
-            }
+            // Product types, tuples: (X,Y,Z) or (X,) or (,)
+            Schema::Product(types) => fmt_list(f, types, ","),
 
-            // Sum types, enums with one or more variants: X | Y | Z |
-            Schema::Sum(types) => {
-                let variants = types
-                    .iter()
-                    .map(|t| format!("{}", t))
-                    .collect::<Vec<_>>()
-                    .join("|");
-                write!(f, "({})", variants)
-            }
+            // Named struct: ("field":X,"field2":Y), empty (:,)
+            Schema::Struct(fields) if fields.is_empty() => f.write_str("(:,)"),
+            Schema::Struct(fields) => fmt_list(f, fields, ","),
 
-            // Named enum: "variant": X | "variant2": Y |
-            Schema::Enum(variants) => {
-                let variants_str = variants
-                    .iter()
-                    .map(|t| format!("{}", t))
-                    .collect::<Vec<_>>()
-                    .join("|");
-                write!(f, "({})", variants_str)
-            }
+            // Sum types: (X|Y|Z) or (X|) or (|)
+            Schema::Sum(types) => fmt_list(f, types, "|"),
+
+            // Named enum: ("variant":X|"variant2":Y), empty (:|)
+            Schema::Enum(variants) if variants.is_empty() => f.write_str("(:|)"),
+            Schema::Enum(variants) => fmt_list(f, variants, "|"),
 
-            // Named type: Named("name": X)
+            // Named type: "name"=X
             Schema::Named(named) => {
-                write!(f, "{}", named)
+                text::write_quoted(f, &named.0)?;
+                write!(f, "={}", named.1)
             }
 
-            // Sequence type (array): Seq(X)
+            // Sequence type (array): [X]
             Schema::Seq(item) => write!(f, "[{}]", item),
 
-            // Set type: Set(X)
+            // Set type: {X}
             Schema::Set(item) => write!(f, "{{{}}}", item),
 
-            // Map type: Map(X, Y)
+            // Map type: {X:Y}
             Schema::Map(key, value) => write!(f, "{{{}:{}}}", key, value),
+
+            // Optional type: ?X
+            Schema::Optional(item) => write!(f, "?{}", item),
+
+            // Tagged enum: @("tag")X, @("tag","content")X or @()X
+            Schema::Tagged(tagging, item) => write!(f, "@{}{}", tagging, item),
+
+            // Unordered set type: ~{X}
+            Schema::UnorderedSet(item) => write!(f, "~{{{}}}", item),
+
+            // Unordered map type: ~{X:Y}
+            Schema::UnorderedMap(key, value) => write!(f, "~{{{}:{}}}", key, value),
+
+            // Enumeration: #("variant":0|"variant2":1), empty #()
+            Schema::Enumeration(cases) => {
+                f.write_str("#(")?;
+                for (i, (name, tag)) in cases.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("|")?;
+                    }
+                    text::write_quoted(f, name)?;
+                    write!(f, ":{}", tag)?;
+                }
+                f.write_str(")")
+            }
+
+            // Stream: *X
+            Schema::Stream(item) => write!(f, "*{}", item),
         }
     }
 }
 
+/// Writes the tag and content field names in parentheses, e.g. `("t","c")`.
+/// Untagged is written as `()`.
+impl fmt::Display for Tagging {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        match self {
+            Tagging::Internal(tag) => text::write_quoted(f, tag)?,
+            Tagging::Adjacent(tag, content) => {
+                text::write_quoted(f, tag)?;
+                f.write_str(",")?;
+                text::write_quoted(f, content)?;
+            }
+            Tagging::Untagged => {}
+        }
+        f.write_str(")")
+    }
+}
+
 impl Named {
     pub fn new(name: impl Into<String>, schema: Schema) -> Self {
         Named(name.into(), schema)
     }
 
     pub fn pretty_print(&self, indent: usize) -> String {
-        let indentation = " ".repeat(indent);
-        let inner = self.1.pretty_print(indent);
-        format!("{}\"{}\": {}", indentation, self.0, inner.trim_start(),)
+        let mut res = String::new();
+        self.pretty_fmt(&mut res, indent).unwrap();
+        res
+    }
+
+    /// Writes the pretty printed schema, see [`Schema::pretty_fmt`].
+    pub fn pretty_fmt(&self, f: &mut impl fmt::Write, indent: usize) -> fmt::Result {
+        self.pretty_fmt_with(f, indent, &PrettyOptions::default())
     }
 }
 
@@ -155,97 +359,588 @@ impl Schema {
         Schema::Named(Box::new(Named::new(name, schema)))
     }
 
+    /// Writes the schema in a multi line format, with each line indented by at
+    /// least `indent` spaces.
+    ///
+    /// See [`Self::pretty_fmt_with`] for more control over the output.
+    pub fn pretty_fmt(&self, f: &mut impl fmt::Write, indent: usize) -> fmt::Result {
+        self.pretty_fmt_with(f, indent, &PrettyOptions::default())
+    }
+
+    /// Formats the schema in a multi line format, see [`Self::pretty_fmt`].
     pub fn pretty_print(&self, indent: usize) -> String {
-        let indentation = " ".repeat(indent);
+        let mut res = String::new();
+        self.pretty_fmt(&mut res, indent).unwrap();
+        res
+    }
+
+    /// The hash of the postcard encoding of the schema.
+    ///
+    /// Encoding a schema into a hasher can not fail, see
+    /// [`Self::try_stable_hash`] for a variant that reports errors anyway.
+    pub fn stable_hash(&self) -> blake3::Hash {
+        hash_postcard(self)
+    }
+
+    /// Like [`Self::stable_hash`], but returns encoding errors instead of
+    /// panicking.
+    pub fn try_stable_hash(&self) -> Result<blake3::Hash, SchemaError> {
+        let hasher = postcard::serialize_with_flavor(self, HashFlavor(blake3::Hasher::new()))?;
+        Ok(hasher.finalize())
+    }
+
+    /// The schema with every [`Optional`](Schema::Optional) replaced by the
+    /// equivalent `Sum([Unit, T])`.
+    ///
+    /// This is how `Option<T>` is described without the `optional-schema`
+    /// feature, so it is a compat shim for deployments migrating to the
+    /// feature: peers can accept both the new hash and the hash of the legacy
+    /// schema until all of them are upgraded.
+    pub fn to_legacy(&self) -> Schema {
+        let items = |items: &[Schema]| items.iter().map(Schema::to_legacy).collect();
+        let fields = |fields: &[Named]| {
+            fields
+                .iter()
+                .map(|field| Named(field.0.clone(), field.1.to_legacy()))
+                .collect()
+        };
         match self {
-            Schema::Bottom => format!("{}⊥", indentation),
-            Schema::Unit => format!("{}()", indentation),
-            Schema::Atom(name) => format!("{}\"{}\"", indentation, name),
-
-            // Product: Each field on a new line, indented
-            Schema::Product(types) => {
-                let elements = types
-                    .iter()
-                    .map(|t| t.pretty_print(indent + 2))
-                    .collect::<Vec<_>>()
-                    .join(",\n");
-                format!("{}(\n{}\n{})", indentation, elements, indentation)
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => {
+                self.clone()
             }
-
-            // Named Struct: Each "field": value on a new line
-            Schema::Struct(fields) => {
-                let fields_str = fields
-                    .iter()
-                    .map(|t| t.pretty_print(indent + 2))
-                    .collect::<Vec<_>>()
-                    .join(",\n");
-                format!("{}(\n{}\n{})", indentation, fields_str, indentation)
+            Schema::Product(types) => Schema::Product(items(types)),
+            Schema::Sum(types) => Schema::Sum(items(types)),
+            Schema::Struct(types) => Schema::Struct(fields(types)),
+            Schema::Enum(types) => Schema::Enum(fields(types)),
+            Schema::Named(named) => Schema::named(named.0.clone(), named.1.to_legacy()),
+            Schema::Seq(item) => Schema::Seq(Box::new(item.to_legacy())),
+            Schema::Set(item) => Schema::Set(Box::new(item.to_legacy())),
+            Schema::Map(key, value) => {
+                Schema::Map(Box::new(key.to_legacy()), Box::new(value.to_legacy()))
             }
-
-            // Sum types: Each variant on a new line, separated by |
-            Schema::Sum(types) => {
-                let variants = types
-                    .iter()
-                    .map(|t| t.pretty_print(indent + 2))
-                    .collect::<Vec<_>>()
-                    .join(" |\n");
-                format!("{}(\n{}\n{})", indentation, variants, indentation)
+            Schema::Optional(item) => Schema::Sum(vec![Schema::Unit, item.to_legacy()]),
+            Schema::Tagged(tagging, item) => {
+                Schema::Tagged(tagging.clone(), Box::new(item.to_legacy()))
+            }
+            Schema::UnorderedSet(item) => Schema::UnorderedSet(Box::new(item.to_legacy())),
+            Schema::UnorderedMap(key, value) => {
+                Schema::UnorderedMap(Box::new(key.to_legacy()), Box::new(value.to_legacy()))
             }
+            Schema::Stream(item) => Schema::Stream(Box::new(item.to_legacy())),
+        }
+    }
+
+    /// The stable hash of the [legacy schema](Self::to_legacy).
+    pub fn legacy_hash(&self) -> blake3::Hash {
+        self.to_legacy().stable_hash()
+    }
 
-            // Named Enum: Each "variant": value on a new line
-            Schema::Enum(variants) => {
-                let variants_str = variants
-                    .iter()
-                    .map(|t| t.pretty_print(indent + 2))
-                    .collect::<Vec<_>>()
-                    .join(" |\n");
-                format!("{}(\n{}\n{})", indentation, variants_str, indentation)
+    /// The schema with the fields of every struct and the variants of every
+    /// enum sorted by name.
+    ///
+    /// Postcard encodes fields and variants by position, so their order is
+    /// part of the schema. Formats that key fields and variants by name, like
+    /// JSON or CBOR maps, don't care about the order, so two schemas that are
+    /// equal after this normalization are compatible in such formats.
+    pub fn to_unordered(&self) -> Schema {
+        let items = |items: &[Schema]| items.iter().map(Schema::to_unordered).collect();
+        let fields = |fields: &[Named]| {
+            let mut fields = fields
+                .iter()
+                .map(|field| Named(field.0.clone(), field.1.to_unordered()))
+                .collect::<Vec<_>>();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            fields
+        };
+        match self {
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) => self.clone(),
+            Schema::Product(types) => Schema::Product(items(types)),
+            Schema::Sum(types) => Schema::Sum(items(types)),
+            Schema::Struct(types) => Schema::Struct(fields(types)),
+            Schema::Enum(types) => Schema::Enum(fields(types)),
+            Schema::Named(named) => Schema::named(named.0.clone(), named.1.to_unordered()),
+            Schema::Seq(item) => Schema::Seq(Box::new(item.to_unordered())),
+            Schema::Set(item) => Schema::Set(Box::new(item.to_unordered())),
+            Schema::Map(key, value) => {
+                Schema::Map(Box::new(key.to_unordered()), Box::new(value.to_unordered()))
+            }
+            Schema::Optional(item) => Schema::Optional(Box::new(item.to_unordered())),
+            Schema::Tagged(tagging, item) => {
+                Schema::Tagged(tagging.clone(), Box::new(item.to_unordered()))
             }
+            Schema::UnorderedSet(item) => Schema::UnorderedSet(Box::new(item.to_unordered())),
+            Schema::UnorderedMap(key, value) => {
+                Schema::UnorderedMap(Box::new(key.to_unordered()), Box::new(value.to_unordered()))
+            }
+            Schema::Enumeration(cases) => {
+                let mut cases = cases.clone();
+                cases.sort_by(|a, b| a.0.cmp(&b.0));
+                Schema::Enumeration(cases)
+            }
+            Schema::Stream(item) => Schema::Stream(Box::new(item.to_unordered())),
+        }
+    }
 
-            // Named Type
-            Schema::Named(named) => named.pretty_print(indent),
+    /// True if the schemas are equal, ignoring the order of struct fields and
+    /// enum variants, see [`Self::to_unordered`].
+    pub fn eq_unordered(&self, other: &Schema) -> bool {
+        self.to_unordered() == other.to_unordered()
+    }
 
-            // Sequence
-            Schema::Seq(item) => format!(
-                "{}[\n{}\n{}]",
-                indentation,
-                item.pretty_print(indent + 2),
-                indentation
-            ),
+    /// The stable hash of the [unordered schema](Self::to_unordered), for
+    /// schemas used with formats that key fields and variants by name.
+    pub fn unordered_hash(&self) -> blake3::Hash {
+        self.to_unordered().stable_hash()
+    }
+
+    /// True if the schema contains an [`UnorderedSet`](Schema::UnorderedSet)
+    /// or [`UnorderedMap`](Schema::UnorderedMap).
+    ///
+    /// Equal values of such a schema do not necessarily have equal encodings,
+    /// so their encodings can not be hashed or compared directly.
+    pub fn has_unordered(&self) -> bool {
+        match self {
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => false,
+            Schema::UnorderedSet(_) | Schema::UnorderedMap(_, _) => true,
+            Schema::Product(items) | Schema::Sum(items) => items.iter().any(Schema::has_unordered),
+            Schema::Struct(fields) | Schema::Enum(fields) => {
+                fields.iter().any(|field| field.1.has_unordered())
+            }
+            Schema::Named(named) => named.1.has_unordered(),
+            Schema::Seq(item) | Schema::Set(item) | Schema::Optional(item) => item.has_unordered(),
+            Schema::Tagged(_, item) | Schema::Stream(item) => item.has_unordered(),
+            Schema::Map(key, value) => key.has_unordered() || value.has_unordered(),
+        }
+    }
 
-            // Set
-            Schema::Set(item) => format!(
-                "{}{{\n{}\n{}}}",
-                indentation,
-                item.pretty_print(indent + 2),
-                indentation
+    /// The schema with every [`UnorderedSet`](Schema::UnorderedSet) and
+    /// [`UnorderedMap`](Schema::UnorderedMap) replaced by a plain
+    /// [`Set`](Schema::Set) or [`Map`](Schema::Map).
+    ///
+    /// This is how `HashSet` and `HashMap` were described before [hash scheme
+    /// version 3](hashing::HASH_SCHEME_VERSION), so it is used to compute the
+    /// hashes of earlier schemes.
+    pub fn erase_unordered(&self) -> Schema {
+        let items = |items: &[Schema]| items.iter().map(Schema::erase_unordered).collect();
+        let fields = |fields: &[Named]| {
+            fields
+                .iter()
+                .map(|field| Named(field.0.clone(), field.1.erase_unordered()))
+                .collect()
+        };
+        match self {
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => {
+                self.clone()
+            }
+            Schema::Product(types) => Schema::Product(items(types)),
+            Schema::Sum(types) => Schema::Sum(items(types)),
+            Schema::Struct(types) => Schema::Struct(fields(types)),
+            Schema::Enum(types) => Schema::Enum(fields(types)),
+            Schema::Named(named) => Schema::named(named.0.clone(), named.1.erase_unordered()),
+            Schema::Seq(item) => Schema::Seq(Box::new(item.erase_unordered())),
+            Schema::Set(item) | Schema::UnorderedSet(item) => {
+                Schema::Set(Box::new(item.erase_unordered()))
+            }
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => Schema::Map(
+                Box::new(key.erase_unordered()),
+                Box::new(value.erase_unordered()),
             ),
+            Schema::Optional(item) => Schema::Optional(Box::new(item.erase_unordered())),
+            Schema::Tagged(tagging, item) => {
+                Schema::Tagged(tagging.clone(), Box::new(item.erase_unordered()))
+            }
+            Schema::Stream(item) => Schema::Stream(Box::new(item.erase_unordered())),
+        }
+    }
 
-            // Map
-            Schema::Map(key, value) => {
-                let k = key.pretty_print(indent + 2);
-                let v = value.pretty_print(indent + 2);
-                format!(
-                    "{}{{\n{}: {}\n{}}}",
-                    indentation,
-                    k,
-                    v.trim_start(),
-                    indentation
-                )
+    /// The schema with every [`Stream`](Schema::Stream) replaced by the named
+    /// irpc mpsc channel it describes.
+    ///
+    /// This is how mpsc channels were described before [hash scheme version
+    /// 4](hashing::HASH_SCHEME_VERSION), so it is used to compute the hashes
+    /// of earlier schemes. Streams in the `tx` part of a [channels
+    /// schema](service::channels_schema) become an `mpsc::Sender`, all others
+    /// an `mpsc::Receiver`.
+    pub fn erase_streams(&self) -> Schema {
+        let items = |items: &[Schema]| items.iter().map(Schema::erase_streams).collect();
+        let fields = |fields: &[Named]| {
+            fields
+                .iter()
+                .map(|field| Named(field.0.clone(), field.1.erase_streams()))
+                .collect()
+        };
+        match self {
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => {
+                self.clone()
+            }
+            Schema::Product(types) => Schema::Product(items(types)),
+            Schema::Sum(types) => Schema::Sum(items(types)),
+            Schema::Struct(types) => Schema::Struct(fields(types)),
+            Schema::Enum(types) => Schema::Enum(fields(types)),
+            Schema::Named(named) => match &named.1 {
+                Schema::Struct(parts) if named.0 == service::CHANNELS_SCHEMA_NAME => {
+                    let parts = parts
+                        .iter()
+                        .map(|part| match &part.1 {
+                            Schema::Stream(item) if part.0 == "tx" => Named(
+                                part.0.clone(),
+                                Schema::named("irpc::channel::mpsc::Sender", item.erase_streams()),
+                            ),
+                            schema => Named(part.0.clone(), schema.erase_streams()),
+                        })
+                        .collect();
+                    Schema::named(named.0.clone(), Schema::Struct(parts))
+                }
+                schema => Schema::named(named.0.clone(), schema.erase_streams()),
+            },
+            Schema::Seq(item) => Schema::Seq(Box::new(item.erase_streams())),
+            Schema::Set(item) => Schema::Set(Box::new(item.erase_streams())),
+            Schema::UnorderedSet(item) => Schema::UnorderedSet(Box::new(item.erase_streams())),
+            Schema::Map(key, value) => Schema::Map(
+                Box::new(key.erase_streams()),
+                Box::new(value.erase_streams()),
+            ),
+            Schema::UnorderedMap(key, value) => Schema::UnorderedMap(
+                Box::new(key.erase_streams()),
+                Box::new(value.erase_streams()),
+            ),
+            Schema::Optional(item) => Schema::Optional(Box::new(item.erase_streams())),
+            Schema::Tagged(tagging, item) => {
+                Schema::Tagged(tagging.clone(), Box::new(item.erase_streams()))
+            }
+            Schema::Stream(item) => {
+                Schema::named("irpc::channel::mpsc::Receiver", item.erase_streams())
             }
         }
     }
+}
 
-    pub fn stable_hash(&self) -> blake3::Hash {
-        let bytes = postcard::to_allocvec(self).unwrap();
-        blake3::hash(&bytes)
+/// Hashes the postcard encoding of a value.
+///
+/// The encoding is fed into the hasher as it is produced, without building it
+/// in memory first. The hash is identical to hashing the output of
+/// `postcard::to_allocvec`.
+pub fn hash_postcard<T: Serialize + ?Sized>(value: &T) -> blake3::Hash {
+    postcard::serialize_with_flavor(value, HashFlavor(blake3::Hasher::new()))
+        .expect("serializing into a hasher does not fail")
+        .finalize()
+}
+
+/// A postcard flavor writing into a hasher.
+struct HashFlavor(blake3::Hasher);
+
+impl postcard::ser_flavors::Flavor for HashFlavor {
+    type Output = blake3::Hasher;
+
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.0.update(&[data]);
+        Ok(())
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        self.0.update(data);
+        Ok(())
+    }
+
+    fn finalize(self) -> postcard::Result<blake3::Hasher> {
+        Ok(self.0)
     }
 }
 
 /// Trait to attach a schema to a type.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not have a schema",
+    label = "`{Self}` does not implement `HasSchema`",
+    note = "use `#[schema(Nominal)]` or `#[schema(Structural)]` on your own types, or implement `HasSchema` manually"
+)]
 pub trait HasSchema {
     /// Returns the schema for this type.
     fn schema() -> Schema;
+
+    /// Returns the schema for this type, built only once.
+    ///
+    /// Types using `#[schema(..)]` keep their schema in a static of their own.
+    /// For all other types, the schema is built on first use and kept in a
+    /// global cache.
+    fn static_schema() -> &'static Schema
+    where
+        Self: Sized + 'static,
+    {
+        cached_schema::<Self>()
+    }
+
+    /// Adds the documentation of this type and all types it contains to a
+    /// table, see [`docs`].
+    ///
+    /// Types using `#[schema(..)]` add their doc comments. The default adds
+    /// nothing, which is right for atoms.
+    fn collect_docs(_docs: &mut docs::DocTable) {}
+}
+
+/// Types whose schema can be borrowed for the lifetime of the program.
+///
+/// This is implemented for all `'static` types with a schema, and is meant as
+/// a bound for hot paths like routing or validation, that need to look at the
+/// schema of a type many times without cloning or rebuilding it.
+pub trait HasStaticSchema: HasSchema {
+    /// Returns the schema for this type, see [`HasSchema::static_schema`].
+    fn schema_ref() -> &'static Schema;
+}
+
+impl<T: HasSchema + 'static> HasStaticSchema for T {
+    fn schema_ref() -> &'static Schema {
+        T::static_schema()
+    }
+}
+
+/// The schema of a type, e.g. `schema_of!(BTreeMap<String, Vec<Entry>>)`.
+///
+/// This is short for `<T as HasSchema>::schema()`, which is awkward to write
+/// for composite types.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use irpc_schema::{schema_of, HasSchema, Schema};
+///
+/// assert_eq!(
+///     schema_of!(BTreeMap<String, Vec<u8>>),
+///     Schema::Map(
+///         Box::new(String::schema()),
+///         Box::new(Schema::Seq(Box::new(u8::schema()))),
+///     )
+/// );
+/// ```
+#[macro_export]
+macro_rules! schema_of {
+    ($t:ty $(,)?) => {
+        <$t as $crate::HasSchema>::schema()
+    };
+}
+
+/// Describes a type of another crate, like serde's remote derive.
+///
+/// The orphan rule does not allow implementing [`HasSchema`] for a foreign
+/// type. Instead, this defines a local type with the schema of the foreign
+/// type, and fields of the foreign type refer to it with
+/// `#[schema(with = "Local")]`. The schema uses the name of the foreign type.
+///
+/// The fields are checked against the foreign type at compile time, so they
+/// must be public and complete.
+///
+/// ```
+/// use irpc_schema::{impl_has_schema_remote, schema, HasSchema, Named, Schema};
+///
+/// mod other_crate {
+///     pub struct Point {
+///         pub x: f64,
+///         pub y: f64,
+///     }
+///
+///     pub struct Color(pub u32);
+/// }
+///
+/// impl_has_schema_remote!(other_crate::Point as Nominal { x: f64, y: f64 } => PointDef);
+/// impl_has_schema_remote!(other_crate::Color as Atom => ColorDef);
+///
+/// #[schema(Nominal)]
+/// struct Line {
+///     #[schema(with = "PointDef")]
+///     from: other_crate::Point,
+///     #[schema(with = "PointDef")]
+///     to: other_crate::Point,
+///     #[schema(with = "ColorDef")]
+///     color: other_crate::Color,
+/// }
+///
+/// let point = Schema::named(
+///     "Point",
+///     Schema::Struct(vec![
+///         Named::new("x", f64::schema()),
+///         Named::new("y", f64::schema()),
+///     ]),
+/// );
+/// assert_eq!(PointDef::schema(), point);
+/// assert_eq!(ColorDef::schema(), Schema::Atom("Color".to_string()));
+/// ```
+#[macro_export]
+macro_rules! impl_has_schema_remote {
+    ($remote:path as Nominal { $($field:ident : $ty:ty),* $(,)? } => $vis:vis $local:ident) => {
+        #[allow(dead_code)]
+        $vis struct $local;
+
+        impl $crate::HasSchema for $local {
+            fn schema() -> $crate::Schema {
+                let name = stringify!($remote).rsplit("::").next().unwrap().trim();
+                $crate::Schema::named(
+                    name,
+                    $crate::Schema::Struct(vec![
+                        $($crate::Named::new(
+                            stringify!($field),
+                            <$ty as $crate::HasSchema>::schema(),
+                        )),*
+                    ]),
+                )
+            }
+
+            fn collect_docs(docs: &mut $crate::docs::DocTable) {
+                $(<$ty as $crate::HasSchema>::collect_docs(docs);)*
+            }
+        }
+
+        // fails to compile if the fields don't match the foreign type
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(value: &$remote) {
+                let $remote { $($field),* } = value;
+                $(let _: &$ty = $field;)*
+            }
+        };
+    };
+    ($remote:path as Atom => $vis:vis $local:ident) => {
+        #[allow(dead_code)]
+        $vis struct $local;
+
+        impl $crate::HasSchema for $local {
+            fn schema() -> $crate::Schema {
+                let name = stringify!($remote).rsplit("::").next().unwrap().trim();
+                $crate::Schema::Atom(name.to_string())
+            }
+        }
+    };
+}
+
+/// Describes a trait object, for protocols with dynamic payloads.
+///
+/// Payloads like `Box<dyn Plugin>` are serialized with erased serialization,
+/// e.g. with `typetag`, which writes the name of the implementation followed
+/// by its value. This implements [`HasSchema`] for the trait object, so
+/// `Box`, `Arc` and `Rc` of it have a schema too. The schema uses the name of
+/// the trait, and is either
+///
+/// - an atom, if the set of implementations is not known, or
+/// - an enum of the known implementations, keyed by their tag. Implementations
+///   that are added later show up as added variants in a diff.
+///
+/// ```
+/// use irpc_schema::{impl_has_schema_dyn, schema, HasSchema, Named, Schema};
+///
+/// trait Plugin {}
+/// trait Codec {}
+///
+/// #[schema(Nominal)]
+/// struct Echo {
+///     text: String,
+/// }
+///
+/// #[schema(Nominal)]
+/// struct Count;
+///
+/// impl_has_schema_dyn!(dyn Plugin as Enum { Echo: Echo, Count: Count });
+/// impl_has_schema_dyn!(dyn Codec + Send + Sync as Atom);
+///
+/// assert_eq!(
+///     <Box<dyn Plugin>>::schema(),
+///     Schema::named(
+///         "Plugin",
+///         Schema::Enum(vec![
+///             Named::new("Echo", Echo::schema()),
+///             Named::new("Count", Count::schema()),
+///         ]),
+///     )
+/// );
+/// assert_eq!(
+///     <std::sync::Arc<dyn Codec + Send + Sync>>::schema(),
+///     Schema::Atom("Codec".to_string())
+/// );
+/// ```
+#[macro_export]
+macro_rules! impl_has_schema_dyn {
+    (dyn $($trait:ident)::+ $(+ $bound:tt)* as Atom) => {
+        impl $crate::HasSchema for dyn $($trait)::+ $(+ $bound)* {
+            fn schema() -> $crate::Schema {
+                let name = stringify!($($trait)::+).rsplit("::").next().unwrap().trim();
+                $crate::Schema::Atom(name.to_string())
+            }
+        }
+    };
+    (dyn $($trait:ident)::+ $(+ $bound:tt)* as Enum { $($tag:ident : $ty:ty),* $(,)? }) => {
+        impl $crate::HasSchema for dyn $($trait)::+ $(+ $bound)* {
+            fn schema() -> $crate::Schema {
+                let name = stringify!($($trait)::+).rsplit("::").next().unwrap().trim();
+                $crate::Schema::named(
+                    name,
+                    $crate::Schema::Enum(vec![
+                        $($crate::Named::new(
+                            stringify!($tag),
+                            <$ty as $crate::HasSchema>::schema(),
+                        )),*
+                    ]),
+                )
+            }
+
+            fn collect_docs(docs: &mut $crate::docs::DocTable) {
+                $(<$ty as $crate::HasSchema>::collect_docs(docs);)*
+            }
+        }
+    };
+}
+
+/// Asserts that the schemas of two types are not compatible, for tests that
+/// prove that a deliberate breaking change produces a new hash and is flagged
+/// by [`diff`].
+///
+/// The optional third argument is the most severe
+/// [`Compat`](diff::Compat) that is still accepted, and defaults to
+/// `Compat::Compatible`, so migratable changes count as incompatible. See
+/// [`diff::assert_incompatible`] for schemas.
+///
+/// ```
+/// use irpc_schema::{assert_schemas_incompatible, diff::Compat, schema};
+///
+/// #[schema(Nominal(name = "User"))]
+/// struct UserV1 {
+///     name: String,
+///     age: u8,
+/// }
+///
+/// #[schema(Nominal(name = "User"))]
+/// struct UserV2 {
+///     name: String,
+/// }
+///
+/// assert_schemas_incompatible!(UserV1, UserV2);
+/// assert_schemas_incompatible!(UserV1, UserV2, Compat::Migratable);
+/// ```
+#[macro_export]
+macro_rules! assert_schemas_incompatible {
+    ($old:ty, $new:ty $(,)?) => {
+        $crate::assert_schemas_incompatible!($old, $new, $crate::diff::Compat::Compatible)
+    };
+    ($old:ty, $new:ty, $accepted:expr $(,)?) => {
+        $crate::diff::assert_incompatible(
+            &<$old as $crate::HasSchema>::schema(),
+            &<$new as $crate::HasSchema>::schema(),
+            $accepted,
+        )
+    };
+}
+
+/// Looks up the schema of a type in the global cache, building it if needed.
+fn cached_schema<T: HasSchema + 'static>() -> &'static Schema {
+    static CACHE: RwLock<BTreeMap<TypeId, &'static Schema>> = RwLock::new(BTreeMap::new());
+    let id = TypeId::of::<T>();
+    if let Some(schema) = CACHE.read().unwrap().get(&id) {
+        return schema;
+    }
+    // build without holding the lock, since the schema of a type can depend on
+    // the cached schemas of other types
+    let schema = T::schema();
+    CACHE
+        .write()
+        .unwrap()
+        .entry(id)
+        .or_insert_with(|| Box::leak(Box::new(schema)))
 }
 
 // Declare Schema for atom types
@@ -292,48 +987,95 @@ impl<T: HasSchema> HasSchema for Vec<T> {
     fn schema() -> Schema {
         Schema::Seq(Box::new(T::schema()))
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
+}
+
+impl<T: HasSchema, const N: usize> HasSchema for [T; N] {
+    fn schema() -> Schema {
+        Schema::Product(vec![T::schema(); N])
+    }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
 }
 
 impl<T: HasSchema> HasSchema for BTreeSet<T> {
     fn schema() -> Schema {
         Schema::Set(Box::new(T::schema()))
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
 }
 
 impl<K: HasSchema, V: HasSchema> HasSchema for BTreeMap<K, V> {
     fn schema() -> Schema {
         Schema::Map(Box::new(K::schema()), Box::new(V::schema()))
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        K::collect_docs(docs);
+        V::collect_docs(docs);
+    }
 }
 
 impl<T: HasSchema> HasSchema for HashSet<T> {
     fn schema() -> Schema {
-        Schema::Set(Box::new(T::schema()))
+        Schema::UnorderedSet(Box::new(T::schema()))
+    }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
     }
 }
 
 impl<T: HasSchema> HasSchema for Option<T> {
     fn schema() -> Schema {
-        Schema::Sum(vec![Schema::Unit, T::schema()])
+        if cfg!(feature = "optional-schema") {
+            Schema::Optional(Box::new(T::schema()))
+        } else {
+            Schema::Sum(vec![Schema::Unit, T::schema()])
+        }
+    }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
     }
 }
 
-impl<T: HasSchema> HasSchema for Box<T> {
+impl<T: HasSchema + ?Sized> HasSchema for Box<T> {
     fn schema() -> Schema {
         T::schema()
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
 }
 
-impl<T: HasSchema> HasSchema for std::sync::Arc<T> {
+impl<T: HasSchema + ?Sized> HasSchema for std::sync::Arc<T> {
     fn schema() -> Schema {
         T::schema()
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
 }
 
-impl<T: HasSchema> HasSchema for std::rc::Rc<T> {
+impl<T: HasSchema + ?Sized> HasSchema for std::rc::Rc<T> {
     fn schema() -> Schema {
         T::schema()
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        T::collect_docs(docs);
+    }
 }
 
 impl HasSchema for () {
@@ -349,23 +1091,44 @@ impl<A: HasSchema, B: HasSchema> HasSchema for std::result::Result<A, B> {
             Named("Err".to_string(), B::schema()),
         ])
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        A::collect_docs(docs);
+        B::collect_docs(docs);
+    }
 }
 
 impl<A: HasSchema, B: HasSchema> HasSchema for (A, B) {
     fn schema() -> Schema {
         Schema::Product(vec![A::schema(), B::schema()])
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        A::collect_docs(docs);
+        B::collect_docs(docs);
+    }
 }
 
 impl<A: HasSchema, B: HasSchema, C: HasSchema> HasSchema for (A, B, C) {
     fn schema() -> Schema {
         Schema::Product(vec![A::schema(), B::schema(), C::schema()])
     }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        A::collect_docs(docs);
+        B::collect_docs(docs);
+        C::collect_docs(docs);
+    }
 }
 
 impl<K: HasSchema, V: HasSchema> HasSchema for HashMap<K, V> {
     fn schema() -> Schema {
-        Schema::Map(Box::new(K::schema()), Box::new(V::schema()))
+        Schema::UnorderedMap(Box::new(K::schema()), Box::new(V::schema()))
+    }
+
+    fn collect_docs(docs: &mut docs::DocTable) {
+        K::collect_docs(docs);
+        V::collect_docs(docs);
     }
 }
 
@@ -377,11 +1140,19 @@ mod irpc_instances {
         fn schema() -> Schema {
             Schema::named("irpc::channel::oneshot::Receiver", T::schema())
         }
+
+        fn collect_docs(docs: &mut crate::docs::DocTable) {
+            T::collect_docs(docs);
+        }
     }
 
     impl<T: HasSchema> HasSchema for irpc::channel::mpsc::Receiver<T> {
         fn schema() -> Schema {
-            Schema::named("irpc::channel::mpsc::Receiver", T::schema())
+            Schema::Stream(Box::new(T::schema()))
+        }
+
+        fn collect_docs(docs: &mut crate::docs::DocTable) {
+            T::collect_docs(docs);
         }
     }
 
@@ -395,11 +1166,19 @@ mod irpc_instances {
         fn schema() -> Schema {
             Schema::named("irpc::channel::oneshot::Sender", T::schema())
         }
+
+        fn collect_docs(docs: &mut crate::docs::DocTable) {
+            T::collect_docs(docs);
+        }
     }
 
     impl<T: HasSchema> HasSchema for irpc::channel::mpsc::Sender<T> {
         fn schema() -> Schema {
-            Schema::named("irpc::channel::mpsc::Sender", T::schema())
+            Schema::Stream(Box::new(T::schema()))
+        }
+
+        fn collect_docs(docs: &mut crate::docs::DocTable) {
+            T::collect_docs(docs);
         }
     }
 
@@ -411,8 +1190,20 @@ mod irpc_instances {
 
     /// Helper trait to summon a schema for that includes the initial message type
     /// as well as the receiver and sender types, for a given service.
+    ///
+    /// All irpc channel kinds (oneshot, mpsc and none) have schemas if their items
+    /// have schemas, so if this is not implemented, either the message type or one of
+    /// the channel item types lacks a `HasSchema` impl.
+    #[diagnostic::on_unimplemented(
+        message = "`{Self}` or its channels for service `{S}` do not have a schema",
+        label = "missing schema for the message or its channels",
+        note = "`{Self}` must implement `irpc::Channels<{S}>` and `HasSchema`, and the item types of its `Rx` and `Tx` channels must implement `HasSchema`"
+    )]
     pub trait ChannelsSchema<S: irpc::Service>: irpc::Channels<S> {
         /// Returns the schema for this type, including the receiver and sender kinds and types.
+        ///
+        /// This is a struct with the parts `msg`, `rx` and `tx`, see
+        /// [`channels_schema`](crate::service::channels_schema).
         fn schema() -> Schema;
     }
 
@@ -425,7 +1216,7 @@ mod irpc_instances {
         C: HasSchema,
     {
         fn schema() -> Schema {
-            <(C, C::Rx, C::Tx)>::schema()
+            crate::service::channels_schema(C::schema(), C::Rx::schema(), C::Tx::schema())
         }
     }
 }
@@ -433,3 +1224,97 @@ mod irpc_instances {
 #[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
 #[cfg(feature = "irpc")]
 pub use irpc_instances::ChannelsSchema;
+
+/// Random schemas for fuzzing.
+///
+/// Generated schemas have a bounded depth and width, so they stay small enough
+/// to be useful for fuzzing normalization, diffing and the hash encoding.
+#[cfg(feature = "arbitrary")]
+mod arbitrary_instances {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::{Named, Schema, Tagging};
+
+    /// Maximum nesting depth of generated schemas.
+    const MAX_DEPTH: usize = 6;
+    /// Maximum number of children of a generated schema node.
+    const MAX_WIDTH: usize = 5;
+
+    /// Atom names, so that generated atoms are mostly well known ones.
+    const ATOMS: &[&str] = &[
+        "bool", "char", "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+        "f32", "f64", "String", "&str", "&[u8]",
+    ];
+
+    fn name(u: &mut Unstructured<'_>) -> Result<String> {
+        let len = u.int_in_range(1..=8)?;
+        (0..len)
+            .map(|_| Ok(*u.choose(b"ABCXYZabcxyz_")? as char))
+            .collect()
+    }
+
+    fn atom(u: &mut Unstructured<'_>) -> Result<String> {
+        if u.ratio(1, 8)? {
+            String::arbitrary(u)
+        } else {
+            Ok(u.choose(ATOMS)?.to_string())
+        }
+    }
+
+    fn children<T>(
+        u: &mut Unstructured<'_>,
+        mut f: impl FnMut(&mut Unstructured<'_>) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let len = u.int_in_range(0..=MAX_WIDTH)?;
+        (0..len).map(|_| f(u)).collect()
+    }
+
+    fn tagging(u: &mut Unstructured<'_>) -> Result<Tagging> {
+        Ok(match u.choose_index(3)? {
+            0 => Tagging::Internal(name(u)?),
+            1 => Tagging::Adjacent(name(u)?, name(u)?),
+            _ => Tagging::Untagged,
+        })
+    }
+
+    fn named(u: &mut Unstructured<'_>, depth: usize) -> Result<Named> {
+        Ok(Named(name(u)?, schema(u, depth)?))
+    }
+
+    fn schema(u: &mut Unstructured<'_>, depth: usize) -> Result<Schema> {
+        // leaves only, once the maximum depth is reached
+        let kinds = if depth >= MAX_DEPTH { 3 } else { 17 };
+        let depth = depth + 1;
+        Ok(match u.choose_index(kinds)? {
+            0 => Schema::Unit,
+            1 => Schema::Bottom,
+            2 => Schema::Atom(atom(u)?),
+            3 => Schema::Product(children(u, |u| schema(u, depth))?),
+            4 => Schema::Sum(children(u, |u| schema(u, depth))?),
+            5 => Schema::Struct(children(u, |u| named(u, depth))?),
+            6 => Schema::Enum(children(u, |u| named(u, depth))?),
+            7 => Schema::Named(Box::new(named(u, depth)?)),
+            8 => Schema::Seq(Box::new(schema(u, depth)?)),
+            9 => Schema::Set(Box::new(schema(u, depth)?)),
+            10 => Schema::Optional(Box::new(schema(u, depth)?)),
+            11 => Schema::Tagged(tagging(u)?, Box::new(schema(u, depth)?)),
+            12 => Schema::UnorderedSet(Box::new(schema(u, depth)?)),
+            13 => Schema::UnorderedMap(Box::new(schema(u, depth)?), Box::new(schema(u, depth)?)),
+            14 => Schema::Enumeration(children(u, |u| Ok((name(u)?, u.arbitrary()?)))?),
+            15 => Schema::Stream(Box::new(schema(u, depth)?)),
+            _ => Schema::Map(Box::new(schema(u, depth)?), Box::new(schema(u, depth)?)),
+        })
+    }
+
+    impl<'a> Arbitrary<'a> for Schema {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            schema(u, 0)
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for Named {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            named(u, 0)
+        }
+    }
+}
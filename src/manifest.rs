@@ -0,0 +1,103 @@
+//! Serializable descriptions of a protocol version.
+use serde::{Deserialize, Serialize};
+
+use crate::{hash_postcard, registry::SchemaRegistry, Schema};
+
+/// A message schema within a [`SchemaManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The name of the message, e.g. the variant name.
+    pub name: String,
+    /// The schema of the message.
+    pub schema: Schema,
+    /// The stable hash of the schema.
+    pub hash: [u8; 32],
+}
+
+/// All message schemas of one version of a protocol.
+///
+/// A manifest is meant to be stored alongside releases, so that later versions
+/// can be compared against it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaManifest {
+    /// Name of the protocol or service.
+    pub name: String,
+    /// Free form version label.
+    pub version: String,
+    /// The messages, in declaration order.
+    pub messages: Vec<ManifestEntry>,
+}
+
+impl SchemaManifest {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Creates a manifest from `(name, schema, hash)` triples, as returned by
+    /// the generated `schemas()` function.
+    pub fn from_schemas<'a>(
+        name: impl Into<String>,
+        version: impl Into<String>,
+        schemas: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+    ) -> Self {
+        let mut res = Self::new(name, version);
+        res.messages = schemas
+            .into_iter()
+            .map(|(name, schema, hash)| ManifestEntry {
+                name: name.to_string(),
+                schema: schema.clone(),
+                hash,
+            })
+            .collect();
+        res
+    }
+
+    /// Adds a message and returns its hash.
+    pub fn push(&mut self, name: impl Into<String>, schema: Schema) -> [u8; 32] {
+        let hash = *schema.stable_hash().as_bytes();
+        self.messages.push(ManifestEntry {
+            name: name.into(),
+            schema,
+            hash,
+        });
+        hash
+    }
+
+    /// Looks up a message by name.
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.messages.iter().find(|entry| entry.name == name)
+    }
+
+    /// Looks up a message by hash.
+    pub fn get_by_hash(&self, hash: &[u8; 32]) -> Option<&ManifestEntry> {
+        self.messages.iter().find(|entry| &entry.hash == hash)
+    }
+
+    /// A hash identifying the set of messages in this manifest.
+    ///
+    /// This covers the message names and schema hashes, but not the manifest
+    /// name or version label.
+    pub fn hash(&self) -> [u8; 32] {
+        let parts = self
+            .messages
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.hash))
+            .collect::<Vec<_>>();
+        *hash_postcard(&parts).as_bytes()
+    }
+
+    /// Creates a registry containing all messages of this manifest.
+    pub fn to_registry(&self) -> SchemaRegistry {
+        let mut res = SchemaRegistry::new();
+        res.register_all(
+            self.messages
+                .iter()
+                .map(|entry| (entry.name.as_str(), &entry.schema, entry.hash)),
+        );
+        res
+    }
+}
@@ -0,0 +1,363 @@
+//! Migration of dynamic values along schema diffs.
+//!
+//! [`migrate_value`] converts a value of an old schema into a value of a new
+//! schema, given the [`SchemaDiff`] between the two. This is the runtime
+//! counterpart of [`Compat::Migratable`](crate::diff::Compat::Migratable):
+//!
+//! - added fields and product elements are filled in with default values, see
+//!   [`Value::default_for`]
+//! - values of types that became optional are wrapped in `Some`
+//! - renamed fields and variants get their new names
+//! - moved fields and variants get their new positions
+//!
+//! Breaking changes fail the migration, unless the value is not affected by
+//! them, e.g. a removed variant that the value does not use.
+//!
+//! [`decode_with_schema`] combines decoding, migration and deserialization,
+//! for upgrading data at rest that was written with an old version of a type:
+//!
+//! ```
+//! use irpc_schema::{migrate::decode_with_schema, schema, HasSchema};
+//! use serde::{Deserialize, Serialize};
+//!
+//! mod v1 {
+//!     use irpc_schema::schema;
+//!     use serde::{Deserialize, Serialize};
+//!
+//!     #[schema(Nominal)]
+//!     #[derive(Serialize, Deserialize)]
+//!     pub struct User {
+//!         pub name: String,
+//!     }
+//! }
+//!
+//! #[schema(Nominal)]
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct User {
+//!     name: String,
+//!     email: Option<String>,
+//! }
+//!
+//! let bytes = postcard::to_allocvec(&v1::User { name: "alice".into() }).unwrap();
+//! let user: User = decode_with_schema(&bytes, &v1::User::schema()).unwrap();
+//! assert_eq!(user, User { name: "alice".into(), email: None });
+//! ```
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    codec::{decode_postcard, encode_postcard, DecodeError, EncodeError},
+    diff::{diff, wire_compatible_atoms, ChangeKind, Path, PathSegment, SchemaDiff},
+    value::Value,
+    HasSchema, Schema,
+};
+
+/// A value can not be migrated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationError {
+    /// The location of the change that could not be applied.
+    pub path: Path,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Migrates a value of the old schema of `diff` to the new schema.
+///
+/// Enum variants are located by name, so values must carry variant names, as
+/// produced by [`decode_postcard`].
+pub fn migrate_value(mut value: Value, diff: &SchemaDiff) -> Result<Value, MigrationError> {
+    // changes at the same path have to be applied together, e.g. to reorder fields
+    let mut paths: Vec<&Path> = Vec::new();
+    for change in &diff.changes {
+        if !paths.contains(&&change.path) {
+            paths.push(&change.path);
+        }
+    }
+    for path in paths {
+        let changes = diff
+            .changes
+            .iter()
+            .filter(|c| &c.path == path)
+            .map(|c| &c.kind)
+            .collect::<Vec<_>>();
+        visit(&mut value, &path.0, &mut |value| apply(value, &changes)).map_err(|message| {
+            MigrationError {
+                path: path.clone(),
+                message,
+            }
+        })?;
+    }
+    Ok(value)
+}
+
+/// Error when decoding a payload of an old schema into a new type.
+#[derive(Debug)]
+pub enum UpgradeError {
+    /// The payload does not match the old schema.
+    Decode(DecodeError),
+    /// The value can not be migrated to the schema of the new type.
+    Migrate(MigrationError),
+    /// The migrated value can not be encoded with the new schema.
+    Encode(EncodeError),
+    /// The new type does not deserialize from its own schema.
+    Deserialize(postcard::Error),
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeError::Decode(e) => write!(f, "failed to decode old payload: {}", e),
+            UpgradeError::Migrate(e) => write!(f, "failed to migrate value: {}", e),
+            UpgradeError::Encode(e) => write!(f, "failed to encode migrated value: {}", e),
+            UpgradeError::Deserialize(e) => write!(f, "failed to deserialize: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UpgradeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UpgradeError::Decode(e) => Some(e),
+            UpgradeError::Migrate(e) => Some(e),
+            UpgradeError::Encode(e) => Some(e),
+            UpgradeError::Deserialize(e) => Some(e),
+        }
+    }
+}
+
+impl From<MigrationError> for UpgradeError {
+    fn from(e: MigrationError) -> Self {
+        UpgradeError::Migrate(e)
+    }
+}
+
+/// Decodes postcard `bytes` written with `old_schema` into a value of `T`.
+///
+/// The payload is decoded according to the old schema, migrated along the
+/// diff to the schema of `T` with [`migrate_value`], and deserialized as `T`.
+/// New optional fields become `None`, and variants are matched by name, so
+/// this works as long as the diff is compatible or migratable for the value.
+/// If the schemas are identical, the bytes are deserialized directly.
+pub fn decode_with_schema<T>(bytes: &[u8], old_schema: &Schema) -> Result<T, UpgradeError>
+where
+    T: HasSchema + DeserializeOwned,
+{
+    let new_schema = T::schema();
+    if *old_schema == new_schema {
+        return postcard::from_bytes(bytes).map_err(UpgradeError::Deserialize);
+    }
+    let value = decode_postcard(old_schema, bytes).map_err(UpgradeError::Decode)?;
+    let value = migrate_value(value, &diff(old_schema, &new_schema))?;
+    let bytes = encode_postcard(&new_schema, &value).map_err(UpgradeError::Encode)?;
+    postcard::from_bytes(&bytes).map_err(UpgradeError::Deserialize)
+}
+
+/// Calls `f` for all nodes of `value` at the given schema path.
+fn visit(
+    value: &mut Value,
+    path: &[PathSegment],
+    f: &mut dyn FnMut(&mut Value) -> Result<(), String>,
+) -> Result<(), String> {
+    let Some((segment, rest)) = path.split_first() else {
+        return f(value);
+    };
+    match (segment, value) {
+        (PathSegment::Named(_), value) => visit(value, rest, f),
+        (PathSegment::Field(name), Value::Struct(fields)) => {
+            match fields.iter_mut().find(|(n, _)| n == name) {
+                Some((_, value)) => visit(value, rest, f),
+                None => Err(format!("missing field {}", name)),
+            }
+        }
+        (PathSegment::Variant(name), Value::Variant { name: n, value, .. }) => match n {
+            Some(n) if n == name => visit(value, rest, f),
+            Some(_) => Ok(()),
+            None => Err("variant without a name".to_string()),
+        },
+        (PathSegment::Index(i), Value::Tuple(items)) => match items.get_mut(*i) {
+            Some(value) => visit(value, rest, f),
+            None => Err(format!("missing element {}", i)),
+        },
+        (PathSegment::Index(i), Value::Variant { index, value, .. }) => {
+            if *index as usize == *i {
+                visit(value, rest, f)
+            } else {
+                Ok(())
+            }
+        }
+        (PathSegment::Index(i), Value::Optional(value)) => match value {
+            Some(value) if *i == 1 => visit(value, rest, f),
+            _ => Ok(()),
+        },
+        (PathSegment::Item, Value::Seq(items) | Value::Set(items)) => {
+            items.iter_mut().try_for_each(|value| visit(value, rest, f))
+        }
+        (PathSegment::Key, Value::Map(entries)) => entries
+            .iter_mut()
+            .try_for_each(|(key, _)| visit(key, rest, f)),
+        (PathSegment::Value, Value::Map(entries)) => entries
+            .iter_mut()
+            .try_for_each(|(_, value)| visit(value, rest, f)),
+        (segment, value) => Err(format!(
+            "expected {} at {}, found {}",
+            match segment {
+                PathSegment::Named(_) => "any value",
+                PathSegment::Field(_) => "struct",
+                PathSegment::Variant(_) => "variant",
+                PathSegment::Index(_) => "tuple or variant",
+                PathSegment::Item => "sequence or set",
+                PathSegment::Key | PathSegment::Value => "map",
+            },
+            segment,
+            value.kind()
+        )),
+    }
+}
+
+/// Applies all changes at a single path to a value.
+fn apply(value: &mut Value, changes: &[&ChangeKind]) -> Result<(), String> {
+    // the original variant index, since moves are relative to it
+    let variant_index = match value {
+        Value::Variant { index, .. } => Some(*index as usize),
+        _ => None,
+    };
+    let mut slots = Vec::new();
+    for change in changes {
+        match (change, &mut *value) {
+            (ChangeKind::Renamed { .. }, _) => {}
+            (ChangeKind::FieldRenamed { old, new, index }, Value::Struct(fields)) => {
+                if let Some(field) = fields.iter_mut().find(|(n, _)| n == old) {
+                    field.0 = new.clone();
+                }
+                slots.push((new.clone(), *index));
+            }
+            (
+                ChangeKind::FieldMoved {
+                    name, new_index, ..
+                },
+                Value::Struct(_),
+            ) => slots.push((name.clone(), *new_index)),
+            (
+                ChangeKind::FieldAdded {
+                    name,
+                    index,
+                    schema,
+                },
+                Value::Struct(fields),
+            ) => {
+                let value = Value::default_for(schema)
+                    .ok_or_else(|| format!("no default value for field {}", name))?;
+                fields.push((name.clone(), value));
+                slots.push((name.clone(), *index));
+            }
+            (ChangeKind::FieldRemoved { name, .. }, Value::Struct(_)) => {
+                return Err(format!("field {} was removed", name))
+            }
+            (ChangeKind::VariantRenamed { new, index, .. }, Value::Variant { name, .. }) => {
+                if variant_index == Some(*index) {
+                    *name = Some(new.clone());
+                }
+            }
+            (
+                ChangeKind::VariantMoved {
+                    old_index,
+                    new_index,
+                    ..
+                },
+                Value::Variant { index, .. },
+            ) => {
+                if variant_index == Some(*old_index) {
+                    *index = *new_index as u32;
+                }
+            }
+            (ChangeKind::VariantRemoved { name, index, .. }, Value::Variant { .. }) => {
+                if variant_index == Some(*index) {
+                    return Err(format!("variant {} was removed", name));
+                }
+            }
+            (
+                ChangeKind::VariantAdded { .. }
+                | ChangeKind::CaseAdded { .. }
+                | ChangeKind::BecameUnordered
+                | ChangeKind::BecameOrdered
+                | ChangeKind::BecameEnumeration
+                | ChangeKind::BecameEnum
+                | ChangeKind::VariantsReordered,
+                _,
+            ) => {}
+            (ChangeKind::CaseRemoved { index, .. }, _) => {
+                if variant_index == Some(*index) {
+                    return Err(format!("case {} was removed", index));
+                }
+            }
+            (ChangeKind::ElementAdded { index, schema }, Value::Tuple(items)) => {
+                let value = Value::default_for(schema)
+                    .ok_or_else(|| format!("no default value for element {}", index))?;
+                items.insert((*index).min(items.len()), value);
+            }
+            (ChangeKind::ElementRemoved { index, .. }, Value::Tuple(_)) => {
+                return Err(format!("element {} was removed", index))
+            }
+            (ChangeKind::BecameOptional, value) => {
+                let inner = std::mem::replace(value, Value::Unit);
+                *value = Value::Optional(Some(Box::new(inner)));
+            }
+            (ChangeKind::BecameRequired, value) => match std::mem::replace(value, Value::Unit) {
+                Value::Optional(Some(inner)) => *value = *inner,
+                Value::Variant {
+                    index: 1, value: v, ..
+                } => *value = *v,
+                _ => return Err("value became required, but is missing".to_string()),
+            },
+            (ChangeKind::AtomChanged { old, new }, _) => {
+                if !wire_compatible_atoms(old, new) {
+                    return Err(format!("can not convert {} to {}", old, new));
+                }
+            }
+            (ChangeKind::Replaced { old, new }, _) => {
+                return Err(format!("can not convert {} to {}", old, new))
+            }
+            (change, value) => return Err(format!("can not apply {} to {}", change, value.kind())),
+        }
+    }
+    if let Value::Struct(fields) = value {
+        if !slots.is_empty() {
+            reorder(fields, &slots);
+        }
+    }
+    Ok(())
+}
+
+/// Moves fields to their new positions.
+///
+/// Fields with a known new position are placed there, all other fields fill
+/// the remaining positions in their current order. Since the diff only reports
+/// fields as moved if their order relative to the other fields changed, this
+/// gives the order of the new schema.
+fn reorder(fields: &mut Vec<(String, Value)>, slots: &[(String, usize)]) {
+    let mut placed = vec![None; fields.len()];
+    let mut rest = Vec::new();
+    for field in fields.drain(..) {
+        match slots.iter().find(|(name, _)| *name == field.0) {
+            Some((_, index)) if *index < placed.len() && placed[*index].is_none() => {
+                placed[*index] = Some(field)
+            }
+            _ => rest.push(field),
+        }
+    }
+    let mut rest = rest.into_iter();
+    fields.extend(
+        placed
+            .into_iter()
+            .filter_map(|slot| slot.or_else(|| rest.next())),
+    );
+}
@@ -0,0 +1,244 @@
+//! Mock servers for testing clients.
+//!
+//! A [`MockServer`] is created from a [`ServiceDescriptor`] at runtime, so it
+//! needs neither the request types nor a real backend. It accepts messages in
+//! the `serialize_service` wire format, validates the request against the
+//! schema of its method, records it, and answers with scripted responses or,
+//! if there are none, with the [default value](Value::default_for) of the
+//! response schema:
+//!
+//! ```ignore
+//! let mock = MockServer::new(descriptor)
+//!     .respond("Get", [Value::Optional(Some(Box::new(Value::Str("b".into()))))]);
+//! let items: Vec<Option<String>> = mock.call(&Proto::Get(Get { key: "a".into() }))?;
+//! assert_eq!(mock.calls()[0].method, "Get");
+//! ```
+//!
+//! Oneshot methods answer with exactly one item, streaming methods with any
+//! number of items, and methods without a response channel with none.
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    codec::{decode_postcard, encode_postcard, DecodeError, EncodeError},
+    service::{ChannelKind, MethodDescriptor, ServiceDescriptor},
+    value::Value,
+    SchemaError,
+};
+
+/// Computes the response items of a method from the request.
+pub type Responder = Arc<dyn Fn(&Value) -> Vec<Value> + Send + Sync + 'static>;
+
+/// Errors when handling a message with a mock server.
+#[derive(Debug)]
+pub enum MockError {
+    /// The message is too short to contain a hash, or the hash does not
+    /// belong to a method of the service.
+    Schema(SchemaError),
+    /// The request does not conform to the schema of the method.
+    InvalidRequest { method: String, error: DecodeError },
+    /// There is no response script, and the response schema has no default.
+    NoDefault { method: String },
+    /// The number of response items does not match the response channel.
+    InvalidResponseCount { method: String, count: usize },
+    /// A response item does not conform to the response schema.
+    InvalidResponse { method: String, error: EncodeError },
+    /// A response item could not be decoded as the expected type.
+    Deserialize(postcard::Error),
+    /// The message could not be serialized.
+    Serialize(postcard::Error),
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockError::Schema(e) => e.fmt(f),
+            MockError::InvalidRequest { method, error } => {
+                write!(f, "invalid request for {}: {}", method, error)
+            }
+            MockError::NoDefault { method } => {
+                write!(f, "no response for {} and no default value", method)
+            }
+            MockError::InvalidResponseCount { method, count } => {
+                write!(f, "invalid number of responses for {}: {}", method, count)
+            }
+            MockError::InvalidResponse { method, error } => {
+                write!(f, "invalid response for {}: {}", method, error)
+            }
+            MockError::Deserialize(e) => write!(f, "failed to deserialize a response: {}", e),
+            MockError::Serialize(e) => write!(f, "failed to serialize the message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MockError::InvalidRequest { error, .. } => Some(error),
+            MockError::InvalidResponse { error, .. } => Some(error),
+            MockError::Deserialize(e) | MockError::Serialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    /// The method name.
+    pub method: String,
+    /// The decoded request.
+    pub request: Value,
+}
+
+/// The answer of a [`MockServer`] to a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockReply {
+    /// The method name.
+    pub method: String,
+    /// The postcard encoded response items, in the order they are sent.
+    pub items: Vec<Vec<u8>>,
+}
+
+/// A server answering requests of a service with scripted or default
+/// responses.
+pub struct MockServer {
+    descriptor: ServiceDescriptor,
+    responders: BTreeMap<String, Responder>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockServer")
+            .field("descriptor", &self.descriptor)
+            .field("responders", &self.responders.keys().collect::<Vec<_>>())
+            .field("calls", &self.calls)
+            .finish()
+    }
+}
+
+impl MockServer {
+    pub fn new(descriptor: ServiceDescriptor) -> Self {
+        Self {
+            descriptor,
+            responders: BTreeMap::new(),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The descriptor of the mocked service.
+    pub fn descriptor(&self) -> &ServiceDescriptor {
+        &self.descriptor
+    }
+
+    /// Answers all requests of a method with the given items.
+    pub fn respond(
+        self,
+        method: impl Into<String>,
+        items: impl IntoIterator<Item = Value>,
+    ) -> Self {
+        let items = items.into_iter().collect::<Vec<_>>();
+        self.respond_with(method, move |_| items.clone())
+    }
+
+    /// Answers requests of a method with items computed from the request.
+    pub fn respond_with<F>(mut self, method: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(&Value) -> Vec<Value> + Send + Sync + 'static,
+    {
+        self.responders.insert(method.into(), Arc::new(f));
+        self
+    }
+
+    /// All requests received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Handles a message in the `serialize_service` wire format.
+    pub fn handle(&self, bytes: &[u8]) -> Result<MockReply, MockError> {
+        let hash: [u8; 32] = bytes
+            .get(..32)
+            .ok_or(MockError::Schema(SchemaError::MissingHash))?
+            .try_into()
+            .unwrap();
+        let method = self
+            .descriptor
+            .get_by_hash(&hash)
+            .ok_or(MockError::Schema(SchemaError::UnknownHash { hash }))?;
+        let request = decode_postcard(&method.request, &bytes[32..]).map_err(|error| {
+            MockError::InvalidRequest {
+                method: method.name.clone(),
+                error,
+            }
+        })?;
+        let items = match self.responders.get(&method.name) {
+            Some(f) => f(&request),
+            None => default_items(method)?,
+        };
+        self.calls.lock().unwrap().push(MockCall {
+            method: method.name.clone(),
+            request,
+        });
+        let (kind, item) = method.tx_kind().unwrap_or((ChannelKind::None, None));
+        let count_ok = match kind {
+            ChannelKind::None => items.is_empty(),
+            ChannelKind::Oneshot => items.len() == 1,
+            ChannelKind::Mpsc => true,
+        };
+        if !count_ok {
+            return Err(MockError::InvalidResponseCount {
+                method: method.name.clone(),
+                count: items.len(),
+            });
+        }
+        let items = match item {
+            Some(schema) => items
+                .iter()
+                .map(|value| encode_postcard(schema, value))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| MockError::InvalidResponse {
+                    method: method.name.clone(),
+                    error,
+                })?,
+            None => Vec::new(),
+        };
+        Ok(MockReply {
+            method: method.name.clone(),
+            items,
+        })
+    }
+
+    /// Serializes a message, handles it and deserializes the response items.
+    pub fn call<M, R>(&self, msg: &M) -> Result<Vec<R>, MockError>
+    where
+        M: Serialize,
+        R: DeserializeOwned,
+    {
+        let bytes = postcard::to_allocvec(msg).map_err(MockError::Serialize)?;
+        self.handle(&bytes)?
+            .items
+            .iter()
+            .map(|item| postcard::from_bytes(item).map_err(MockError::Deserialize))
+            .collect()
+    }
+}
+
+/// The default response of a method: nothing for methods without a response
+/// channel, and a single default item otherwise.
+fn default_items(method: &MethodDescriptor) -> Result<Vec<Value>, MockError> {
+    match method.response() {
+        Some(schema) => Value::default_for(schema)
+            .map(|value| vec![value])
+            .ok_or_else(|| MockError::NoDefault {
+                method: method.name.clone(),
+            }),
+        None => Ok(Vec::new()),
+    }
+}
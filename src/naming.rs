@@ -0,0 +1,377 @@
+//! Naming conventions for nominal types.
+//!
+//! The name of a nominal type is part of its hash, so once published it can
+//! not be changed without breaking peers. [`NamingRules`] describe how names
+//! should look, e.g. that every type is qualified with the crate and module
+//! and carries a version, and [`Schema::validate_naming`] and
+//! [`NamingRules::lint`] check schemas against them:
+//!
+//! ```
+//! use irpc_schema::{
+//!     naming::{Case, NamingPattern, NamingRules},
+//!     schema, HasSchema,
+//! };
+//!
+//! #[schema(Nominal(name = "myorg::users::User@v1"))]
+//! struct User {
+//!     name: String,
+//! }
+//!
+//! #[schema(Nominal)]
+//! struct Group {
+//!     members: Vec<User>,
+//! }
+//!
+//! let rules = NamingRules::new()
+//!     .types(NamingPattern::new("myorg::**::{Type}@v{N}").unwrap())
+//!     .fields(Case::Snake);
+//! assert!(User::schema().validate_naming(&rules).is_ok());
+//! assert!(Group::schema().validate_naming(&rules).is_err());
+//! ```
+//!
+//! Patterns are matched literally, except for
+//!
+//! - `*`, which matches any text within a path segment,
+//! - `**`, which matches any text including `::`,
+//! - `{Type}`, which matches an `UpperCamelCase` identifier,
+//! - `{name}`, which matches a `snake_case` identifier, and
+//! - `{N}`, which matches a decimal number.
+//!
+//! The derive enforces a pattern at compile time with a `naming` parameter:
+//!
+//! ```compile_fail
+//! use irpc_schema::schema;
+//!
+//! #[schema(Nominal(name = "myorg::users::User", naming = "myorg::**::{Type}@v{N}"))]
+//! struct User {
+//!     name: String,
+//! }
+//! ```
+use std::fmt;
+
+use crate::{
+    check::{InvalidSchema, Problem},
+    diff::{Path, PathSegment},
+    Named, Schema, SchemaError,
+};
+
+/// A pattern for type names, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingPattern(String);
+
+/// A naming pattern with an unknown placeholder or an unclosed `{`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPattern(pub String);
+
+impl fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid naming pattern `{}`", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPattern {}
+
+impl NamingPattern {
+    /// Parses a pattern.
+    pub fn new(pattern: &str) -> Result<Self, InvalidPattern> {
+        if !is_valid_pattern(pattern) {
+            return Err(InvalidPattern(pattern.to_string()));
+        }
+        Ok(Self(pattern.to_string()))
+    }
+
+    /// True if the name matches the pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        matches(&self.0, name)
+    }
+
+    /// The pattern text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for NamingPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The case convention for field or variant names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    /// `snake_case`, like Rust fields.
+    Snake,
+    /// `UpperCamelCase`, like Rust variants.
+    UpperCamel,
+}
+
+impl Case {
+    /// True if the name follows the convention.
+    pub fn matches(&self, name: &str) -> bool {
+        let class = match self {
+            Case::Snake => Class::Snake,
+            Case::UpperCamel => Class::UpperCamel,
+        };
+        let name = name.as_bytes();
+        !name.is_empty() && valid_run(class, name, 0, name.len())
+    }
+}
+
+/// A set of naming conventions, see the [module docs](self).
+///
+/// An empty rule set accepts every name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamingRules {
+    types: Vec<NamingPattern>,
+    fields: Option<Case>,
+    variants: Option<Case>,
+}
+
+impl NamingRules {
+    /// Rules that accept every name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts type names matching the pattern.
+    ///
+    /// Once there is a pattern, names of nominal types must match one of the
+    /// patterns. This includes types of other crates, like irpc channels, so
+    /// add patterns like `irpc::**` for them.
+    pub fn types(mut self, pattern: NamingPattern) -> Self {
+        self.types.push(pattern);
+        self
+    }
+
+    /// Requires field names to follow a case convention.
+    pub fn fields(mut self, case: Case) -> Self {
+        self.fields = Some(case);
+        self
+    }
+
+    /// Requires variant names to follow a case convention.
+    pub fn variants(mut self, case: Case) -> Self {
+        self.variants = Some(case);
+        self
+    }
+
+    /// True if the rules accept the name of a nominal type.
+    pub fn accepts_type(&self, name: &str) -> bool {
+        self.types.is_empty() || self.types.iter().any(|pattern| pattern.matches(name))
+    }
+
+    /// All names in the schema that violate the rules, in schema order.
+    pub fn lint(&self, schema: &Schema) -> Vec<InvalidSchema> {
+        let mut res = Vec::new();
+        self.lint_rec(schema, &Path::default(), &mut res);
+        res
+    }
+
+    fn lint_rec(&self, schema: &Schema, path: &Path, res: &mut Vec<InvalidSchema>) {
+        let mut members =
+            |members: &[Named], case: Option<Case>, problem: fn(String) -> Problem| {
+                for member in members {
+                    if case.is_some_and(|case| !case.matches(&member.0)) {
+                        res.push(InvalidSchema {
+                            path: path.clone(),
+                            problem: problem(member.0.clone()),
+                        });
+                    }
+                }
+            };
+        match schema {
+            Schema::Unit | Schema::Bottom | Schema::Atom(_) => {}
+            Schema::Product(items) | Schema::Sum(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.lint_rec(item, &path.join(PathSegment::Index(i)), res);
+                }
+            }
+            Schema::Struct(fields) => {
+                members(fields, self.fields, Problem::FieldNaming);
+                for field in fields {
+                    let path = path.join(PathSegment::Field(field.0.clone()));
+                    self.lint_rec(&field.1, &path, res);
+                }
+            }
+            Schema::Enum(cases) => {
+                members(cases, self.variants, Problem::VariantNaming);
+                for case in cases {
+                    let path = path.join(PathSegment::Variant(case.0.clone()));
+                    self.lint_rec(&case.1, &path, res);
+                }
+            }
+            Schema::Enumeration(cases) => {
+                for (name, _) in cases {
+                    if self.variants.is_some_and(|case| !case.matches(name)) {
+                        res.push(InvalidSchema {
+                            path: path.clone(),
+                            problem: Problem::VariantNaming(name.clone()),
+                        });
+                    }
+                }
+            }
+            Schema::Named(named) => {
+                if !self.accepts_type(&named.0) {
+                    res.push(InvalidSchema {
+                        path: path.clone(),
+                        problem: Problem::TypeNaming(named.0.clone()),
+                    });
+                }
+                let path = path.join(PathSegment::Named(named.0.clone()));
+                self.lint_rec(&named.1, &path, res);
+            }
+            Schema::Seq(item)
+            | Schema::Set(item)
+            | Schema::UnorderedSet(item)
+            | Schema::Stream(item) => self.lint_rec(item, &path.join(PathSegment::Item), res),
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                self.lint_rec(key, &path.join(PathSegment::Key), res);
+                self.lint_rec(value, &path.join(PathSegment::Value), res);
+            }
+            Schema::Optional(item) => self.lint_rec(item, &path.join(PathSegment::Index(1)), res),
+            Schema::Tagged(_, item) => self.lint_rec(item, path, res),
+        }
+    }
+}
+
+impl Schema {
+    /// Checks that all names in the schema follow the rules, reporting the
+    /// first violation. See [`NamingRules::lint`] for all of them.
+    pub fn validate_naming(&self, rules: &NamingRules) -> Result<(), SchemaError> {
+        match rules.lint(self).into_iter().next() {
+            Some(invalid) => Err(invalid.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// True if the name matches the pattern.
+///
+/// This is a `const fn`, so the derive can check names at compile time.
+/// Invalid patterns match nothing.
+pub const fn matches(pattern: &str, name: &str) -> bool {
+    is_valid_pattern(pattern) && match_at(pattern.as_bytes(), 0, name.as_bytes(), 0)
+}
+
+/// True if all placeholders of the pattern are known and closed.
+pub const fn is_valid_pattern(pattern: &str) -> bool {
+    let p = pattern.as_bytes();
+    let mut i = 0;
+    while i < p.len() {
+        if p[i] == b'{' {
+            let Some(close) = closing_brace(p, i) else {
+                return false;
+            };
+            if placeholder(p, i + 1, close).is_none() {
+                return false;
+            }
+            i = close;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[derive(Clone, Copy)]
+enum Class {
+    UpperCamel,
+    Snake,
+    Number,
+}
+
+const fn closing_brace(p: &[u8], open: usize) -> Option<usize> {
+    let mut i = open + 1;
+    while i < p.len() {
+        if p[i] == b'}' {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn placeholder(p: &[u8], start: usize, end: usize) -> Option<Class> {
+    if equals(p, start, end, b"Type") {
+        Some(Class::UpperCamel)
+    } else if equals(p, start, end, b"name") {
+        Some(Class::Snake)
+    } else if equals(p, start, end, b"N") {
+        Some(Class::Number)
+    } else {
+        None
+    }
+}
+
+const fn equals(p: &[u8], start: usize, end: usize, text: &[u8]) -> bool {
+    if end - start != text.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < text.len() {
+        if p[start + i] != text[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// True if the non-empty range `name[start..end]` is a word of the class
+const fn valid_run(class: Class, name: &[u8], start: usize, end: usize) -> bool {
+    let mut i = start;
+    while i < end {
+        let c = name[i];
+        let ok = match class {
+            Class::UpperCamel if i == start => c.is_ascii_uppercase(),
+            Class::UpperCamel => c.is_ascii_alphanumeric(),
+            Class::Snake if i == start => c.is_ascii_lowercase(),
+            Class::Snake => c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_',
+            Class::Number => c.is_ascii_digit(),
+        };
+        if !ok {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn match_at(p: &[u8], pi: usize, n: &[u8], ni: usize) -> bool {
+    if pi == p.len() {
+        return ni == n.len();
+    }
+    match p[pi] {
+        b'*' => {
+            let deep = pi + 1 < p.len() && p[pi + 1] == b'*';
+            let next = if deep { pi + 2 } else { pi + 1 };
+            let mut end = ni;
+            loop {
+                if match_at(p, next, n, end) {
+                    return true;
+                }
+                if end == n.len() || (!deep && n[end] == b':') {
+                    return false;
+                }
+                end += 1;
+            }
+        }
+        b'{' => {
+            let Some(close) = closing_brace(p, pi) else {
+                return false;
+            };
+            let Some(class) = placeholder(p, pi + 1, close) else {
+                return false;
+            };
+            let mut end = ni + 1;
+            while end <= n.len() && valid_run(class, n, ni, end) {
+                if match_at(p, close + 1, n, end) {
+                    return true;
+                }
+                end += 1;
+            }
+            false
+        }
+        c => ni < n.len() && c == n[ni] && match_at(p, pi + 1, n, ni + 1),
+    }
+}
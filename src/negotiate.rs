@@ -0,0 +1,194 @@
+//! Schema negotiation at session start.
+//!
+//! Both sides of a connection send a [`Hello`] with the hashes of all message
+//! schemas they support. The common subset is the set of messages that can be
+//! used on the connection, captured in a [`NegotiatedProtocol`]. If both sides
+//! have the same manifest hash, they speak the exact same protocol version.
+//!
+//! With the `irpc` feature, [`negotiate`] and [`respond`] run the exchange
+//! over an irpc service that has a `Hello` request answered with a `Hello`.
+use std::{collections::BTreeSet, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{manifest::SchemaManifest, HasSchema, Named, Schema};
+
+/// What one side of a connection supports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    /// The hash of the manifest, see [`SchemaManifest::hash`].
+    pub manifest: [u8; 32],
+    /// The schema hashes of all supported messages.
+    pub hashes: Vec<[u8; 32]>,
+}
+
+impl Hello {
+    pub fn from_manifest(manifest: &SchemaManifest) -> Self {
+        Self {
+            manifest: manifest.hash(),
+            hashes: manifest.messages.iter().map(|entry| entry.hash).collect(),
+        }
+    }
+
+    /// Creates a hello from `(name, schema, hash)` triples, as returned by the
+    /// generated `schemas()` function.
+    pub fn from_schemas<'a>(
+        schemas: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+    ) -> Self {
+        Self::from_manifest(&SchemaManifest::from_schemas("", "", schemas))
+    }
+}
+
+impl HasSchema for Hello {
+    fn schema() -> Schema {
+        Schema::named(
+            "Hello",
+            Schema::Struct(vec![
+                Named::new("manifest", <[u8; 32]>::schema()),
+                Named::new("hashes", <Vec<[u8; 32]>>::schema()),
+            ]),
+        )
+    }
+}
+
+/// Errors during or after negotiation.
+#[derive(Debug)]
+pub enum NegotiationError {
+    /// The two sides have no message in common.
+    NoCommonMessages,
+    /// A message is not supported by both sides.
+    Unsupported { hash: [u8; 32] },
+    /// The hello exchange failed.
+    #[cfg(feature = "irpc")]
+    Rpc(irpc::Error),
+}
+
+impl fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NegotiationError::NoCommonMessages => write!(f, "no messages in common"),
+            NegotiationError::Unsupported { hash } => write!(
+                f,
+                "schema {} is not supported by both sides",
+                blake3::Hash::from(*hash)
+            ),
+            #[cfg(feature = "irpc")]
+            NegotiationError::Rpc(e) => write!(f, "hello exchange failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "irpc")]
+            NegotiationError::Rpc(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of a negotiation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiatedProtocol {
+    local: [u8; 32],
+    remote: [u8; 32],
+    common: BTreeSet<[u8; 32]>,
+}
+
+impl NegotiatedProtocol {
+    /// Computes the common subset of two hellos.
+    ///
+    /// Fails if there are no messages in common, since such a connection is
+    /// useless.
+    pub fn new(local: &Hello, remote: &Hello) -> Result<Self, NegotiationError> {
+        let remote_hashes = remote.hashes.iter().collect::<BTreeSet<_>>();
+        let common = local
+            .hashes
+            .iter()
+            .filter(|hash| remote_hashes.contains(hash))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if common.is_empty() {
+            return Err(NegotiationError::NoCommonMessages);
+        }
+        Ok(Self {
+            local: local.manifest,
+            remote: remote.manifest,
+            common,
+        })
+    }
+
+    /// The manifest hash of the remote side.
+    pub fn remote_manifest(&self) -> [u8; 32] {
+        self.remote
+    }
+
+    /// True if both sides have the same manifest, so all messages are supported.
+    pub fn identical(&self) -> bool {
+        self.local == self.remote
+    }
+
+    /// The schema hashes of all messages supported by both sides.
+    pub fn common(&self) -> &BTreeSet<[u8; 32]> {
+        &self.common
+    }
+
+    /// True if a message is supported by both sides.
+    pub fn supports(&self, hash: &[u8; 32]) -> bool {
+        self.common.contains(hash)
+    }
+
+    /// Checks that a message is supported by both sides.
+    pub fn check(&self, hash: &[u8; 32]) -> Result<(), NegotiationError> {
+        if self.supports(hash) {
+            Ok(())
+        } else {
+            Err(NegotiationError::Unsupported { hash: *hash })
+        }
+    }
+}
+
+/// Sends the local hello to the server and negotiates with its answer.
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub async fn negotiate<S>(
+    client: &irpc::Client<S>,
+    local: Hello,
+) -> Result<NegotiatedProtocol, NegotiationError>
+where
+    S: irpc::Service + From<Hello>,
+    S::Message: From<irpc::WithChannels<Hello, S>>,
+    Hello: irpc::Channels<
+        S,
+        Tx = irpc::channel::oneshot::Sender<Hello>,
+        Rx = irpc::channel::none::NoReceiver,
+    >,
+{
+    let remote = client
+        .rpc(local.clone())
+        .await
+        .map_err(NegotiationError::Rpc)?;
+    NegotiatedProtocol::new(&local, &remote)
+}
+
+/// Answers a hello request from a client with the local hello.
+///
+/// The client is answered even if there are no messages in common, so it can
+/// report the failure as well.
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub async fn respond<S>(
+    local: &Hello,
+    request: irpc::WithChannels<Hello, S>,
+) -> Result<NegotiatedProtocol, NegotiationError>
+where
+    S: irpc::Service,
+    Hello: irpc::Channels<S, Tx = irpc::channel::oneshot::Sender<Hello>>,
+{
+    let irpc::WithChannels { inner, tx, .. } = request;
+    tx.send(local.clone())
+        .await
+        .map_err(|e| NegotiationError::Rpc(e.into()))?;
+    NegotiatedProtocol::new(local, &inner)
+}
@@ -0,0 +1,247 @@
+//! Composition of several protocols into one.
+//!
+//! `#[serialize_nested]` turns an enum whose variants wrap `serialize_stable`,
+//! `serialize_service` or other nested enums into a single protocol, e.g. for
+//! a server exposing several logical services on one connection:
+//!
+//! ```ignore
+//! #[serialize_nested(router)]
+//! enum Root {
+//!     Kv(kv::Proto),
+//!     Auth(auth::Proto),
+//! }
+//! ```
+//!
+//! Every message of a child is a message of the parent, named with the
+//! variant as prefix, e.g. `Kv.Get`. Its schema is the child schema named
+//! after the variant, so the parent hash embeds the child hash, and the same
+//! child mounted under two variants gets distinct hashes. On the wire, the
+//! parent hash replaces the child hash, so there is no additional overhead.
+//!
+//! Since parents list all messages of their children, registries, manifests
+//! and negotiation work on them as on any other protocol. The messages of a
+//! single child can be recovered with [`SchemaManifest::child`].
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{DeserializeSeed, Error as _, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    dispatch::DispatchTable,
+    manifest::{ManifestEntry, SchemaManifest},
+    telemetry::report_unknown_message,
+    Schema, SchemaAndHash,
+};
+
+/// Separates the variant of a parent from the name of a child message.
+pub const SEPARATOR: char = '.';
+
+/// A message enum in the hash discriminated wire format.
+///
+/// This is implemented by `serialize_stable`, `serialize_service` and
+/// `serialize_nested`, so these enums can be nested in a parent.
+pub trait Protocol: Sized {
+    /// The `(name, schema, hash)` triples of all messages.
+    fn schemas() -> impl Iterator<Item = (&'static str, &'static Schema, [u8; 32])>;
+
+    /// The hash of this message.
+    fn message_hash(&self) -> [u8; 32];
+
+    /// Serializes the payload of this message, without the hash.
+    fn serialize_payload<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+
+    /// Deserializes the payload of the message with the given hash.
+    fn deserialize_payload<'de, D: Deserializer<'de>>(
+        hash: &[u8; 32],
+        deserializer: D,
+    ) -> Result<Self, D::Error>;
+}
+
+/// The name of a child message within a parent.
+pub fn nested_name(parent: &str, child: &str) -> String {
+    format!("{}{}{}", parent, SEPARATOR, child)
+}
+
+/// The schema of a child message within a parent.
+pub fn nested_schema(parent: &str, child: &Schema) -> Schema {
+    Schema::named(parent, child.clone())
+}
+
+/// Splits a message name into the first variant and the rest, if it is the
+/// name of a nested message.
+pub fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(SEPARATOR)
+}
+
+/// A message of a parent, see [`NestedSchemas`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedEntry {
+    /// Index of the variant of the parent.
+    pub variant: usize,
+    /// The nested name, e.g. `Kv.Get`.
+    pub name: String,
+    /// The nested schema and its hash.
+    pub schema: SchemaAndHash,
+    /// The hash of the message in the child.
+    pub child_hash: [u8; 32],
+}
+
+/// The messages of a `serialize_nested` enum.
+#[derive(Debug, Clone, Default)]
+pub struct NestedSchemas {
+    entries: Vec<NestedEntry>,
+    dispatch: DispatchTable,
+}
+
+impl NestedSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all messages of the child `P`, wrapped by the given variant.
+    pub fn push<P: Protocol>(&mut self, variant: usize, name: &str) {
+        for (child, schema, child_hash) in P::schemas() {
+            self.entries.push(NestedEntry {
+                variant,
+                name: nested_name(name, child),
+                schema: SchemaAndHash::from(nested_schema(name, schema)),
+                child_hash,
+            });
+        }
+        self.dispatch = DispatchTable::new(self.entries.iter().map(|entry| entry.schema.hash));
+    }
+
+    /// The `(name, schema, hash)` triples of all messages.
+    pub fn schemas(&self) -> impl Iterator<Item = (&str, &Schema, [u8; 32])> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), &entry.schema.schema, entry.schema.hash))
+    }
+
+    /// Looks up a message by its hash in the parent.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&NestedEntry> {
+        self.dispatch.get(hash).map(|index| &self.entries[index])
+    }
+
+    /// Looks up a message by its variant and its hash in the child.
+    pub fn get_by_child(&self, variant: usize, child_hash: &[u8; 32]) -> Option<&NestedEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.variant == variant && &entry.child_hash == child_hash)
+    }
+}
+
+/// Serializes the payload of a message, see [`Protocol::serialize_payload`].
+pub struct Payload<'a, P>(pub &'a P);
+
+impl<P: Protocol> Serialize for Payload<'_, P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_payload(serializer)
+    }
+}
+
+/// Deserializes the payload of a message with a known hash.
+pub struct PayloadSeed<P> {
+    hash: [u8; 32],
+    _p: PhantomData<P>,
+}
+
+impl<P> PayloadSeed<P> {
+    pub fn new(hash: [u8; 32]) -> Self {
+        Self {
+            hash,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'de, P: Protocol> DeserializeSeed<'de> for PayloadSeed<P> {
+    type Value = P;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<P, D::Error> {
+        P::deserialize_payload(&self.hash, deserializer)
+    }
+}
+
+/// Serializes a message as a tuple of hash and payload.
+pub fn serialize<P: Protocol, S: Serializer>(msg: &P, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut tup = serializer.serialize_tuple(2)?;
+    tup.serialize_element(&msg.message_hash())?;
+    tup.serialize_element(&Payload(msg))?;
+    tup.end()
+}
+
+/// Deserializes a message from a tuple of hash and payload.
+///
+/// Unknown hashes are reported to the [telemetry](crate::telemetry) hook.
+pub fn deserialize<'de, P: Protocol, D: Deserializer<'de>>(deserializer: D) -> Result<P, D::Error> {
+    struct MessageVisitor<P>(PhantomData<P>);
+
+    impl<'de, P: Protocol> Visitor<'de> for MessageVisitor<P> {
+        type Value = P;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a tuple with a hash discriminator and payload")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<P, A::Error> {
+            let hash = seq
+                .next_element::<[u8; 32]>()?
+                .ok_or_else(|| A::Error::custom("missing hash"))?;
+            if !P::schemas().any(|(_, _, known)| known == hash) {
+                report_unknown_message(hash, None, P::schemas());
+                return Err(A::Error::custom("unknown discriminator"));
+            }
+            seq.next_element_seed(PayloadSeed::<P>::new(hash))?
+                .ok_or_else(|| A::Error::custom("missing payload"))
+        }
+    }
+
+    deserializer.deserialize_tuple(2, MessageVisitor(PhantomData))
+}
+
+impl SchemaManifest {
+    /// The distinct variants of nested messages, in declaration order.
+    pub fn children(&self) -> Vec<&str> {
+        let mut res = Vec::new();
+        for entry in &self.messages {
+            if let Some((variant, _)) = split_name(&entry.name) {
+                if !res.contains(&variant) {
+                    res.push(variant);
+                }
+            }
+        }
+        res
+    }
+
+    /// The manifest of the child protocol nested as `variant`, with the names,
+    /// schemas and hashes the messages have in the child.
+    ///
+    /// Returns `None` if there are no messages nested as `variant`.
+    pub fn child(&self, variant: &str) -> Option<SchemaManifest> {
+        let mut res = SchemaManifest::new(nested_name(&self.name, variant), self.version.clone());
+        for entry in &self.messages {
+            let Some((parent, name)) = split_name(&entry.name) else {
+                continue;
+            };
+            let Schema::Named(named) = &entry.schema else {
+                continue;
+            };
+            if parent == variant && named.0 == variant {
+                res.messages.push(ManifestEntry {
+                    name: name.to_string(),
+                    schema: named.1.clone(),
+                    hash: *named.1.stable_hash().as_bytes(),
+                });
+            }
+        }
+        if res.messages.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    }
+}
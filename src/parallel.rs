@@ -0,0 +1,24 @@
+//! Parallel evaluation for operations over many schemas.
+//!
+//! With the `rayon` feature, hashing and diffing the entries of large
+//! registries and manifests is spread over the rayon thread pool. Without it,
+//! everything runs on the calling thread. Results are the same either way, and
+//! in the same order.
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Below this number of items per task, the overhead of splitting dominates.
+#[cfg(feature = "rayon")]
+const MIN_LEN: usize = 16;
+
+/// Applies `f` to all items, preserving the order.
+#[cfg(feature = "rayon")]
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+    items.par_iter().with_min_len(MIN_LEN).map(f).collect()
+}
+
+/// Applies `f` to all items, preserving the order.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+    items.iter().map(f).collect()
+}
@@ -0,0 +1,342 @@
+//! Organization wide rules for schema evolution.
+//!
+//! [`Compat`] classifies changes by their effect on existing data, which is
+//! not always what an organization wants to enforce. Some accept new optional
+//! fields, since their services fill in defaults, others never want to see a
+//! field removed, no matter how it is encoded. An [`EvolutionPolicy`] codifies
+//! such rules once, as a list of allowed and forbidden kinds of [`Rule`]s on
+//! top of the classification:
+//!
+//! ```
+//! use irpc_schema::{
+//!     diff::{diff, Compat},
+//!     policy::{EvolutionPolicy, Rule},
+//!     HasSchema, Named, Schema,
+//! };
+//!
+//! let policy = EvolutionPolicy::new()
+//!     .allow(Rule::AddOptionalField)
+//!     .forbid(Rule::RemoveField);
+//! let old = Schema::Struct(vec![Named::new("id", u64::schema())]);
+//! let new = Schema::Struct(vec![
+//!     Named::new("id", u64::schema()),
+//!     Named::new("name", Option::<String>::schema()),
+//! ]);
+//! // adding an optional field is migratable, but allowed by the policy
+//! assert_eq!(diff(&old, &new).compat(), Compat::Migratable);
+//! assert!(policy.check(&diff(&old, &new)).is_ok());
+//! assert!(policy.check(&diff(&new, &old)).is_err());
+//! ```
+//!
+//! Policies deserialize from any serde format, and [`EvolutionPolicy::from_json`]
+//! and [`EvolutionPolicy::from_toml`] load them from config files with the
+//! `json` and `toml` features:
+//!
+//! ```toml
+//! # the most severe classification accepted for all other changes
+//! accept = "compatible"
+//! allow = ["add-optional-field", "add-variant", "widen-int"]
+//! forbid = ["remove-field", "change-atom"]
+//! ```
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diff::{diff, wire_compatible_atoms, Change, ChangeKind, Compat, SchemaDiff},
+    value::{option_inner, Primitive},
+    Schema,
+};
+
+/// A kind of change that a policy can allow or forbid.
+///
+/// Names are written in kebab case, e.g. `add-optional-field`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// A named type was renamed.
+    RenameType,
+    /// A field of type `Option<T>` was added.
+    AddOptionalField,
+    /// Any other field was added.
+    AddField,
+    /// A field was removed.
+    RemoveField,
+    /// A field was renamed.
+    RenameField,
+    /// A field changed position.
+    MoveField,
+    /// A variant was added to an enum or enumeration, or a case to a sum.
+    AddVariant,
+    /// A variant or case was removed.
+    RemoveVariant,
+    /// A variant was renamed.
+    RenameVariant,
+    /// A variant changed position or discriminant.
+    MoveVariant,
+    /// An element was added to a tuple.
+    AddElement,
+    /// An element was removed from a tuple.
+    RemoveElement,
+    /// An integer was replaced with a wider integer with the same encoding,
+    /// e.g. `u32` with `u64`. Widening a `u8`, or an unsigned into a signed
+    /// integer, changes the encoding and is a [`ChangeAtom`](Rule::ChangeAtom).
+    WidenInt,
+    /// Any other atom was replaced.
+    ChangeAtom,
+    /// A type `T` was replaced with `Option<T>`.
+    MakeOptional,
+    /// A type `Option<T>` was replaced with `T`.
+    MakeRequired,
+    /// A set or map became ordered or unordered.
+    ChangeOrdering,
+    /// The serde representation of an enum changed, or an enum became an
+    /// enumeration or back.
+    ChangeRepresentation,
+    /// A node was replaced with something of a different kind.
+    Replace,
+}
+
+impl Rule {
+    /// All rules.
+    pub const ALL: [Rule; 19] = [
+        Rule::RenameType,
+        Rule::AddOptionalField,
+        Rule::AddField,
+        Rule::RemoveField,
+        Rule::RenameField,
+        Rule::MoveField,
+        Rule::AddVariant,
+        Rule::RemoveVariant,
+        Rule::RenameVariant,
+        Rule::MoveVariant,
+        Rule::AddElement,
+        Rule::RemoveElement,
+        Rule::WidenInt,
+        Rule::ChangeAtom,
+        Rule::MakeOptional,
+        Rule::MakeRequired,
+        Rule::ChangeOrdering,
+        Rule::ChangeRepresentation,
+        Rule::Replace,
+    ];
+
+    /// The rule that covers a change.
+    pub fn of(kind: &ChangeKind) -> Rule {
+        match kind {
+            ChangeKind::Renamed { .. } => Rule::RenameType,
+            ChangeKind::FieldAdded { schema, .. } if option_inner(schema).is_some() => {
+                Rule::AddOptionalField
+            }
+            ChangeKind::FieldAdded { .. } => Rule::AddField,
+            ChangeKind::FieldRemoved { .. } => Rule::RemoveField,
+            ChangeKind::FieldRenamed { .. } => Rule::RenameField,
+            ChangeKind::FieldMoved { .. } => Rule::MoveField,
+            ChangeKind::VariantAdded { .. } | ChangeKind::CaseAdded { .. } => Rule::AddVariant,
+            ChangeKind::VariantRemoved { .. } | ChangeKind::CaseRemoved { .. } => {
+                Rule::RemoveVariant
+            }
+            ChangeKind::VariantRenamed { .. } => Rule::RenameVariant,
+            ChangeKind::VariantMoved { .. } | ChangeKind::VariantsReordered => Rule::MoveVariant,
+            ChangeKind::ElementAdded { .. } => Rule::AddElement,
+            ChangeKind::ElementRemoved { .. } => Rule::RemoveElement,
+            ChangeKind::AtomChanged { old, new } if widens(old, new) => Rule::WidenInt,
+            ChangeKind::AtomChanged { .. } => Rule::ChangeAtom,
+            ChangeKind::BecameOptional => Rule::MakeOptional,
+            ChangeKind::BecameRequired => Rule::MakeRequired,
+            ChangeKind::BecameUnordered | ChangeKind::BecameOrdered => Rule::ChangeOrdering,
+            ChangeKind::TaggingChanged { .. }
+            | ChangeKind::BecameEnumeration
+            | ChangeKind::BecameEnum => Rule::ChangeRepresentation,
+            ChangeKind::Replaced { .. } => Rule::Replace,
+        }
+    }
+
+    /// The name of the rule in config files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::RenameType => "rename-type",
+            Rule::AddOptionalField => "add-optional-field",
+            Rule::AddField => "add-field",
+            Rule::RemoveField => "remove-field",
+            Rule::RenameField => "rename-field",
+            Rule::MoveField => "move-field",
+            Rule::AddVariant => "add-variant",
+            Rule::RemoveVariant => "remove-variant",
+            Rule::RenameVariant => "rename-variant",
+            Rule::MoveVariant => "move-variant",
+            Rule::AddElement => "add-element",
+            Rule::RemoveElement => "remove-element",
+            Rule::WidenInt => "widen-int",
+            Rule::ChangeAtom => "change-atom",
+            Rule::MakeOptional => "make-optional",
+            Rule::MakeRequired => "make-required",
+            Rule::ChangeOrdering => "change-ordering",
+            Rule::ChangeRepresentation => "change-representation",
+            Rule::Replace => "replace",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = PolicyError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == name)
+            .ok_or_else(|| PolicyError(format!("unknown rule `{}`", name)))
+    }
+}
+
+/// True if integer atom `new` can represent all values of integer atom `old`,
+/// with the same encoding.
+///
+/// Postcard writes `u8` and `i8` as raw bytes and signed integers zigzag
+/// encoded, so only some widenings keep old data readable.
+fn widens(old: &str, new: &str) -> bool {
+    let range = |name| Primitive::from_atom(name).and_then(Primitive::int_range);
+    match (range(old), range(new)) {
+        (Some((old_min, old_max)), Some((new_min, new_max))) => {
+            new_min <= old_min && new_max >= old_max && wire_compatible_atoms(old, new)
+        }
+        _ => false,
+    }
+}
+
+/// Rules for schema evolution, see the [module docs](self).
+///
+/// A change is rejected if its rule is forbidden, and accepted if its rule is
+/// allowed. All other changes are accepted if their [`Compat`] is at most
+/// `accept`. The default policy accepts exactly the compatible changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvolutionPolicy {
+    /// The most severe classification accepted for changes that are neither
+    /// allowed nor forbidden.
+    pub accept: Compat,
+    /// Changes that are accepted regardless of their classification.
+    pub allow: BTreeSet<Rule>,
+    /// Changes that are rejected regardless of their classification.
+    pub forbid: BTreeSet<Rule>,
+}
+
+/// A policy that could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError(pub String);
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid evolution policy: {}", self.0)
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// The changes of a diff that a policy rejects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    /// The rejected changes, in diff order.
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "changes rejected by the evolution policy:")?;
+        for change in &self.changes {
+            writeln!(
+                f,
+                "{}: {} ({}, {})",
+                change.path,
+                change.kind,
+                change.compat(),
+                Rule::of(&change.kind)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+impl EvolutionPolicy {
+    /// The default policy, which accepts exactly the compatible changes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts changes up to a classification, unless they are forbidden.
+    pub fn accept(mut self, compat: Compat) -> Self {
+        self.accept = compat;
+        self
+    }
+
+    /// Accepts changes covered by the rule.
+    pub fn allow(mut self, rule: Rule) -> Self {
+        self.allow.insert(rule);
+        self
+    }
+
+    /// Rejects changes covered by the rule. Forbidding takes precedence over
+    /// allowing.
+    pub fn forbid(mut self, rule: Rule) -> Self {
+        self.forbid.insert(rule);
+        self
+    }
+
+    /// Loads a policy from JSON.
+    #[cfg(feature = "json")]
+    #[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "json")))]
+    pub fn from_json(text: &str) -> Result<Self, PolicyError> {
+        serde_json::from_str(text).map_err(|e| PolicyError(e.to_string()))
+    }
+
+    /// Loads a policy from TOML.
+    #[cfg(feature = "toml")]
+    #[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "toml")))]
+    pub fn from_toml(text: &str) -> Result<Self, PolicyError> {
+        toml::from_str(text).map_err(|e| PolicyError(e.message().to_string()))
+    }
+
+    /// True if the policy accepts the change.
+    pub fn accepts(&self, change: &Change) -> bool {
+        let rule = Rule::of(&change.kind);
+        !self.forbid.contains(&rule)
+            && (self.allow.contains(&rule) || change.compat() <= self.accept)
+    }
+
+    /// Checks all changes of a diff, reporting the rejected ones.
+    pub fn check(&self, diff: &SchemaDiff) -> Result<(), PolicyViolation> {
+        let changes = diff
+            .changes
+            .iter()
+            .filter(|change| !self.accepts(change))
+            .cloned()
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(PolicyViolation { changes })
+        }
+    }
+
+    /// Checks the changes from `old` to `new`.
+    pub fn check_schemas(&self, old: &Schema, new: &Schema) -> Result<(), PolicyViolation> {
+        self.check(&diff(old, new))
+    }
+
+    /// Panics unless the policy accepts all changes from `old` to `new`, for
+    /// tests.
+    #[track_caller]
+    pub fn assert_accepts(&self, old: &Schema, new: &Schema) {
+        if let Err(violation) = self.check_schemas(old, new) {
+            panic!("{}", violation);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Configurable pretty printing of schemas.
+//!
+//! [`Schema::pretty_print`] writes every node on its own line, indented by two
+//! spaces per level. [`PrettyOptions`] allows changing the indentation, using
+//! ASCII only, annotating named types with their hash, and a compact mode
+//! where nodes that are short enough are written on a single line:
+//!
+//! ```text
+//! "Entry": (
+//!   "id": "u32",
+//!   "tags": {"String": ["u8"]}
+//! )
+//! ```
+use std::fmt;
+
+use crate::{Named, Schema};
+
+/// Options for [`Schema::pretty_print_with`].
+///
+/// The default options give the same output as [`Schema::pretty_print`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// Spaces of indentation per level.
+    pub indent_width: usize,
+    /// Nodes whose single line form has at most this many characters are
+    /// written on a single line, not counting indentation. 0 disables this.
+    pub max_inline_width: usize,
+    /// Whether to use `⊥` for the bottom type. Otherwise, `!` is used and the
+    /// output is plain ASCII, as long as all names are.
+    pub unicode: bool,
+    /// Whether to annotate named types with the first 8 bytes of their stable
+    /// hash.
+    pub show_hashes: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            max_inline_width: 0,
+            unicode: true,
+            show_hashes: false,
+        }
+    }
+}
+
+impl PrettyOptions {
+    /// Options for the compact mode, which writes nodes of up to 60
+    /// characters on a single line.
+    pub fn compact() -> Self {
+        Self {
+            max_inline_width: 60,
+            ..Self::default()
+        }
+    }
+}
+
+impl Schema {
+    /// Formats the schema with the given options, see [`PrettyOptions`].
+    pub fn pretty_print_with(&self, indent: usize, options: &PrettyOptions) -> String {
+        let mut res = String::new();
+        self.pretty_fmt_with(&mut res, indent, options).unwrap();
+        res
+    }
+
+    /// Writes the schema with the given options, with each line indented by
+    /// at least `indent` spaces.
+    pub fn pretty_fmt_with(
+        &self,
+        f: &mut impl fmt::Write,
+        indent: usize,
+        options: &PrettyOptions,
+    ) -> fmt::Result {
+        write_indent(f, indent)?;
+        Printer { options }.schema(f, self, indent)
+    }
+}
+
+impl Named {
+    /// Formats the named schema with the given options, see [`PrettyOptions`].
+    pub fn pretty_print_with(&self, indent: usize, options: &PrettyOptions) -> String {
+        let mut res = String::new();
+        self.pretty_fmt_with(&mut res, indent, options).unwrap();
+        res
+    }
+
+    /// Writes the named schema with the given options, see
+    /// [`Schema::pretty_fmt_with`].
+    pub fn pretty_fmt_with(
+        &self,
+        f: &mut impl fmt::Write,
+        indent: usize,
+        options: &PrettyOptions,
+    ) -> fmt::Result {
+        write_indent(f, indent)?;
+        Printer { options }.named(f, self, indent, None)
+    }
+}
+
+fn write_indent(f: &mut impl fmt::Write, indent: usize) -> fmt::Result {
+    write!(f, "{:1$}", "", indent)
+}
+
+/// A writer that fails once more than `remaining` characters are written, to
+/// find out whether a node fits on a single line without rendering all of it.
+struct Limited {
+    text: String,
+    remaining: usize,
+}
+
+impl fmt::Write for Limited {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.chars().count();
+        if len > self.remaining {
+            return Err(fmt::Error);
+        }
+        self.remaining -= len;
+        self.text.push_str(s);
+        Ok(())
+    }
+}
+
+struct Printer<'a> {
+    options: &'a PrettyOptions,
+}
+
+impl Printer<'_> {
+    /// Writes a schema, without indenting the first line.
+    fn schema(&self, f: &mut impl fmt::Write, schema: &Schema, indent: usize) -> fmt::Result {
+        if let Some(text) = self.single_line(schema) {
+            return f.write_str(&text);
+        }
+        let inner = indent + self.options.indent_width;
+        match schema {
+            Schema::Bottom | Schema::Unit | Schema::Atom(_) => self.inline(f, schema),
+            Schema::Product(types) => self.list(f, indent, types, ",\n", |f, t| {
+                write_indent(f, inner)?;
+                self.schema(f, t, inner)
+            }),
+            Schema::Struct(fields) => self.list(f, indent, fields, ",\n", |f, t| {
+                write_indent(f, inner)?;
+                self.named(f, t, inner, None)
+            }),
+            Schema::Sum(types) => self.list(f, indent, types, " |\n", |f, t| {
+                write_indent(f, inner)?;
+                self.schema(f, t, inner)
+            }),
+            Schema::Enum(variants) => self.list(f, indent, variants, " |\n", |f, t| {
+                write_indent(f, inner)?;
+                self.named(f, t, inner, None)
+            }),
+            Schema::Named(named) => self.named(f, named, indent, Some(schema)),
+            Schema::Seq(item) => {
+                f.write_str("[\n")?;
+                write_indent(f, inner)?;
+                self.schema(f, item, inner)?;
+                f.write_str("\n")?;
+                write_indent(f, indent)?;
+                f.write_str("]")
+            }
+            Schema::Set(item) | Schema::UnorderedSet(item) => {
+                if let Schema::UnorderedSet(_) = schema {
+                    f.write_str("~")?;
+                }
+                f.write_str("{\n")?;
+                write_indent(f, inner)?;
+                self.schema(f, item, inner)?;
+                f.write_str("\n")?;
+                write_indent(f, indent)?;
+                f.write_str("}")
+            }
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                if let Schema::UnorderedMap(..) = schema {
+                    f.write_str("~")?;
+                }
+                f.write_str("{\n")?;
+                write_indent(f, inner)?;
+                self.schema(f, key, inner)?;
+                f.write_str(": ")?;
+                self.schema(f, value, inner)?;
+                f.write_str("\n")?;
+                write_indent(f, indent)?;
+                f.write_str("}")
+            }
+            Schema::Optional(item) => {
+                self.schema(f, item, indent)?;
+                f.write_str("?")
+            }
+            Schema::Tagged(tagging, item) => {
+                write!(f, "@{} ", tagging)?;
+                self.schema(f, item, indent)
+            }
+            Schema::Enumeration(cases) => {
+                f.write_str("#")?;
+                self.list(f, indent, cases, " |\n", |f, (name, tag)| {
+                    write_indent(f, inner)?;
+                    write!(f, "\"{}\": {}", name, tag)
+                })
+            }
+            Schema::Stream(item) => {
+                f.write_str("*")?;
+                self.schema(f, item, indent)
+            }
+        }
+    }
+
+    /// Writes a named schema, without indenting the first line.
+    ///
+    /// `schema` is the [`Schema::Named`] node, if this is one, to annotate
+    /// the name with its hash.
+    fn named(
+        &self,
+        f: &mut impl fmt::Write,
+        named: &Named,
+        indent: usize,
+        schema: Option<&Schema>,
+    ) -> fmt::Result {
+        self.name(f, named, schema)?;
+        self.schema(f, &named.1, indent)
+    }
+
+    fn name(&self, f: &mut dyn fmt::Write, named: &Named, schema: Option<&Schema>) -> fmt::Result {
+        write!(f, "\"{}\"", named.0)?;
+        if let Some(schema) = schema.filter(|_| self.options.show_hashes) {
+            let hash = schema.stable_hash();
+            write!(f, " [{}]", &hash.to_hex()[..16])?;
+        }
+        f.write_str(": ")
+    }
+
+    /// Writes items in parentheses, one per line.
+    fn list<W: fmt::Write, T>(
+        &self,
+        f: &mut W,
+        indent: usize,
+        items: &[T],
+        separator: &str,
+        mut item: impl FnMut(&mut W, &T) -> fmt::Result,
+    ) -> fmt::Result {
+        f.write_str("(\n")?;
+        for (i, t) in items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(separator)?;
+            }
+            item(f, t)?;
+        }
+        f.write_str("\n")?;
+        write_indent(f, indent)?;
+        f.write_str(")")
+    }
+
+    /// The single line form of a composite node, if it fits.
+    fn single_line(&self, schema: &Schema) -> Option<String> {
+        // leaves are always written on a single line, and options and
+        // streams are if their item is
+        if self.options.max_inline_width == 0
+            || matches!(
+                schema,
+                Schema::Bottom
+                    | Schema::Unit
+                    | Schema::Atom(_)
+                    | Schema::Optional(_)
+                    | Schema::Stream(_)
+            )
+        {
+            return None;
+        }
+        let mut f = Limited {
+            text: String::new(),
+            remaining: self.options.max_inline_width,
+        };
+        self.inline(&mut f, schema).ok()?;
+        Some(f.text)
+    }
+
+    /// Writes a schema on a single line.
+    fn inline(&self, f: &mut dyn fmt::Write, schema: &Schema) -> fmt::Result {
+        let list = |f: &mut dyn fmt::Write, items: &[Schema], separator: &str| {
+            f.write_str("(")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(separator)?;
+                }
+                self.inline(f, item)?;
+            }
+            f.write_str(")")
+        };
+        let named_list = |f: &mut dyn fmt::Write, items: &[Named], separator: &str| {
+            f.write_str("(")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(separator)?;
+                }
+                self.name(f, item, None)?;
+                self.inline(f, &item.1)?;
+            }
+            f.write_str(")")
+        };
+        match schema {
+            Schema::Bottom if self.options.unicode => f.write_str("⊥"),
+            Schema::Bottom => f.write_str("!"),
+            Schema::Unit => f.write_str("()"),
+            Schema::Atom(name) => write!(f, "\"{}\"", name),
+            Schema::Product(types) => list(f, types, ", "),
+            Schema::Struct(fields) => named_list(f, fields, ", "),
+            Schema::Sum(types) => list(f, types, " | "),
+            Schema::Enum(variants) => named_list(f, variants, " | "),
+            Schema::Named(named) => {
+                self.name(f, named, Some(schema))?;
+                self.inline(f, &named.1)
+            }
+            Schema::Seq(item) => {
+                f.write_str("[")?;
+                self.inline(f, item)?;
+                f.write_str("]")
+            }
+            Schema::Set(item) | Schema::UnorderedSet(item) => {
+                if let Schema::UnorderedSet(_) = schema {
+                    f.write_str("~")?;
+                }
+                f.write_str("{")?;
+                self.inline(f, item)?;
+                f.write_str("}")
+            }
+            Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+                if let Schema::UnorderedMap(..) = schema {
+                    f.write_str("~")?;
+                }
+                f.write_str("{")?;
+                self.inline(f, key)?;
+                f.write_str(": ")?;
+                self.inline(f, value)?;
+                f.write_str("}")
+            }
+            Schema::Optional(item) => {
+                self.inline(f, item)?;
+                f.write_str("?")
+            }
+            Schema::Tagged(tagging, item) => {
+                write!(f, "@{} ", tagging)?;
+                self.inline(f, item)
+            }
+            Schema::Enumeration(cases) => {
+                f.write_str("#(")?;
+                for (i, (name, tag)) in cases.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" | ")?;
+                    }
+                    write!(f, "\"{}\": {}", name, tag)?;
+                }
+                f.write_str(")")
+            }
+            Schema::Stream(item) => {
+                f.write_str("*")?;
+                self.inline(f, item)
+            }
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Schemas of protobuf messages generated by prost.
+//!
+//! Hybrid services expose some endpoints via gRPC and others via irpc. To keep
+//! a single [`SchemaRegistry`](crate::registry::SchemaRegistry) covering both,
+//! [`Descriptors`] converts the messages of a protobuf file descriptor set into
+//! schemas, and [`impl_has_schema_prost!`](crate::impl_has_schema_prost)
+//! implements [`HasSchema`] for prost generated types. The
+//! descriptor set is written by `prost_build::Config::file_descriptor_set_path`
+//! and typically included with `include_bytes!`.
+//!
+//! The schemas describe the protobuf types the way prost maps them to Rust:
+//!
+//! - a message is a nominal struct, named by its full protobuf name, e.g.
+//!   `pkg.Person`, with fields in declaration order
+//! - scalars are the atoms of the corresponding Rust types, `bytes` is
+//!   `Vec<u8>`
+//! - message fields, proto2 optional fields and proto3 `optional` fields are
+//!   options, repeated fields are sequences, and map fields are maps
+//! - an enum is a nominal [enumeration](crate::Schema::Enumeration) with the
+//!   protobuf numbers as discriminants
+//! - a oneof is an optional enum named `pkg.Message.oneof_name`, with a
+//!   variant per field, after all other fields
+//!
+//! All names are the names in the `.proto` files. Field numbers are not part
+//! of the schema, so renumbering a field is not a change for
+//! [`diff`](crate::diff::diff). Recursive messages have no schema.
+//!
+//! ```
+//! use irpc_schema::protobuf::Descriptors;
+//! use prost_types::{
+//!     field_descriptor_proto::{Label, Type},
+//!     DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+//! };
+//!
+//! // usually written by prost-build
+//! let descriptors = FileDescriptorSet {
+//!     file: vec![FileDescriptorProto {
+//!         package: Some("users".into()),
+//!         syntax: Some("proto3".into()),
+//!         message_type: vec![DescriptorProto {
+//!             name: Some("User".into()),
+//!             field: vec![FieldDescriptorProto {
+//!                 name: Some("name".into()),
+//!                 number: Some(1),
+//!                 label: Some(Label::Optional as i32),
+//!                 r#type: Some(Type::String as i32),
+//!                 ..Default::default()
+//!             }],
+//!             ..Default::default()
+//!         }],
+//!         ..Default::default()
+//!     }],
+//! };
+//! let descriptors = Descriptors::new(descriptors);
+//! assert_eq!(
+//!     descriptors.schema("users.User").unwrap().to_string(),
+//!     r#""users.User"=("name":"String",)"#
+//! );
+//! ```
+use std::{collections::BTreeMap, fmt};
+
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+};
+
+use crate::{HasSchema, Named, Schema};
+
+/// Errors when converting protobuf descriptors to schemas.
+#[derive(Debug)]
+pub enum ProstError {
+    /// The bytes are not an encoded `FileDescriptorSet`.
+    Decode(prost::DecodeError),
+    /// A message or enum is not in the descriptor set.
+    UnknownType(String),
+    /// A message contains itself, which a schema can not express.
+    Recursive(String),
+    /// An enum has a negative number, which is not a valid discriminant.
+    NegativeValue { name: String, value: i32 },
+}
+
+impl fmt::Display for ProstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProstError::Decode(e) => write!(f, "invalid file descriptor set: {}", e),
+            ProstError::UnknownType(name) => write!(f, "unknown protobuf type {}", name),
+            ProstError::Recursive(name) => write!(f, "message {} is recursive", name),
+            ProstError::NegativeValue { name, value } => {
+                write!(f, "enum value {} has negative number {}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProstError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProstError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The messages and enums of a protobuf file descriptor set, by full name.
+#[derive(Debug, Clone, Default)]
+pub struct Descriptors {
+    messages: BTreeMap<String, Message>,
+    enums: BTreeMap<String, EnumDescriptorProto>,
+}
+
+#[derive(Debug, Clone)]
+struct Message {
+    descriptor: DescriptorProto,
+    proto2: bool,
+}
+
+impl Descriptors {
+    /// Indexes the messages and enums of a descriptor set.
+    pub fn new(set: FileDescriptorSet) -> Self {
+        let mut res = Self::default();
+        for file in set.file {
+            let proto2 = file.syntax.as_deref() != Some("proto3");
+            let package = file.package.unwrap_or_default();
+            for message in file.message_type {
+                res.add_message(&package, message, proto2);
+            }
+            for item in file.enum_type {
+                res.add_enum(&package, item);
+            }
+        }
+        res
+    }
+
+    /// Decodes an encoded `FileDescriptorSet`, as written by prost-build.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProstError> {
+        let set =
+            <FileDescriptorSet as prost::Message>::decode(bytes).map_err(ProstError::Decode)?;
+        Ok(Self::new(set))
+    }
+
+    fn add_message(&mut self, scope: &str, mut descriptor: DescriptorProto, proto2: bool) {
+        let name = qualify(scope, descriptor.name());
+        for nested in std::mem::take(&mut descriptor.nested_type) {
+            self.add_message(&name, nested, proto2);
+        }
+        for item in std::mem::take(&mut descriptor.enum_type) {
+            self.add_enum(&name, item);
+        }
+        self.messages.insert(name, Message { descriptor, proto2 });
+    }
+
+    fn add_enum(&mut self, scope: &str, descriptor: EnumDescriptorProto) {
+        self.enums
+            .insert(qualify(scope, descriptor.name()), descriptor);
+    }
+
+    /// The full names of all messages, except the entries of map fields.
+    pub fn message_names(&self) -> impl Iterator<Item = &str> {
+        self.messages
+            .iter()
+            .filter(|(_, message)| !is_map_entry(&message.descriptor))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The schema of a message or enum, by full name, e.g. `pkg.Person`.
+    pub fn schema(&self, name: &str) -> Result<Schema, ProstError> {
+        self.type_schema(name.trim_start_matches('.'), &mut Vec::new())
+    }
+
+    /// The schemas of all messages, by full name, e.g. for
+    /// [`SchemaRegistry::register_many`](crate::registry::SchemaRegistry::register_many).
+    pub fn schemas(&self) -> Result<Vec<(String, Schema)>, ProstError> {
+        self.message_names()
+            .map(|name| Ok((name.to_string(), self.schema(name)?)))
+            .collect()
+    }
+
+    fn type_schema(&self, name: &str, stack: &mut Vec<String>) -> Result<Schema, ProstError> {
+        if let Some(item) = self.enums.get(name) {
+            return enum_schema(name, item);
+        }
+        let message = self
+            .messages
+            .get(name)
+            .ok_or_else(|| ProstError::UnknownType(name.to_string()))?;
+        if stack.iter().any(|n| n == name) {
+            return Err(ProstError::Recursive(name.to_string()));
+        }
+        stack.push(name.to_string());
+        let descriptor = &message.descriptor;
+        let mut fields = Vec::new();
+        let mut oneofs = vec![Vec::new(); descriptor.oneof_decl.len()];
+        for field in &descriptor.field {
+            let schema = self.field_schema(field, stack)?;
+            match field.oneof_index {
+                Some(index) if !field.proto3_optional() => {
+                    if let Some(cases) = oneofs.get_mut(index as usize) {
+                        cases.push(Named::new(field.name(), schema));
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            let schema = if is_optional(field, message.proto2) {
+                optional(schema)
+            } else {
+                schema
+            };
+            fields.push(Named::new(field.name(), schema));
+        }
+        for (decl, cases) in descriptor.oneof_decl.iter().zip(oneofs) {
+            // synthetic oneofs of proto3 optional fields have no other cases
+            if !cases.is_empty() {
+                let schema = Schema::named(qualify(name, decl.name()), Schema::Enum(cases));
+                fields.push(Named::new(decl.name(), optional(schema)));
+            }
+        }
+        stack.pop();
+        Ok(Schema::named(name, Schema::Struct(fields)))
+    }
+
+    /// The schema of a field, without the option for optional fields.
+    fn field_schema(
+        &self,
+        field: &FieldDescriptorProto,
+        stack: &mut Vec<String>,
+    ) -> Result<Schema, ProstError> {
+        if field.label() == Label::Repeated {
+            let entry = self
+                .messages
+                .get(field.type_name().trim_start_matches('.'))
+                .map(|message| &message.descriptor)
+                .filter(|descriptor| is_map_entry(descriptor));
+            if let Some(entry) = entry {
+                let [key, value] = [1, 2].map(|number| {
+                    entry
+                        .field
+                        .iter()
+                        .find(|field| field.number() == number)
+                        .ok_or_else(|| ProstError::UnknownType(field.type_name().to_string()))
+                });
+                let key = self.scalar_schema(key?, stack)?;
+                let value = self.scalar_schema(value?, stack)?;
+                return Ok(Schema::Map(Box::new(key), Box::new(value)));
+            }
+        }
+        let schema = self.scalar_schema(field, stack)?;
+        Ok(if field.label() == Label::Repeated {
+            Schema::Seq(Box::new(schema))
+        } else {
+            schema
+        })
+    }
+
+    /// The schema of a single value of a field.
+    fn scalar_schema(
+        &self,
+        field: &FieldDescriptorProto,
+        stack: &mut Vec<String>,
+    ) -> Result<Schema, ProstError> {
+        Ok(match field.r#type() {
+            Type::Double => f64::schema(),
+            Type::Float => f32::schema(),
+            Type::Int32 | Type::Sint32 | Type::Sfixed32 => i32::schema(),
+            Type::Int64 | Type::Sint64 | Type::Sfixed64 => i64::schema(),
+            Type::Uint32 | Type::Fixed32 => u32::schema(),
+            Type::Uint64 | Type::Fixed64 => u64::schema(),
+            Type::Bool => bool::schema(),
+            Type::String => String::schema(),
+            Type::Bytes => Vec::<u8>::schema(),
+            Type::Message | Type::Group | Type::Enum => {
+                self.type_schema(field.type_name().trim_start_matches('.'), stack)?
+            }
+        })
+    }
+}
+
+/// True if prost generates an `Option` for a field outside of a oneof.
+fn is_optional(field: &FieldDescriptorProto, proto2: bool) -> bool {
+    match field.label() {
+        Label::Repeated | Label::Required => false,
+        Label::Optional => {
+            proto2
+                || field.proto3_optional()
+                || matches!(field.r#type(), Type::Message | Type::Group)
+        }
+    }
+}
+
+fn is_map_entry(descriptor: &DescriptorProto) -> bool {
+    descriptor
+        .options
+        .as_ref()
+        .is_some_and(|options| options.map_entry())
+}
+
+fn enum_schema(name: &str, item: &EnumDescriptorProto) -> Result<Schema, ProstError> {
+    let cases = item
+        .value
+        .iter()
+        .map(|value| match u32::try_from(value.number()) {
+            Ok(number) => Ok((value.name().to_string(), number)),
+            Err(_) => Err(ProstError::NegativeValue {
+                name: qualify(name, value.name()),
+                value: value.number(),
+            }),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Schema::named(name, Schema::Enumeration(cases)))
+}
+
+fn optional(schema: Schema) -> Schema {
+    if cfg!(feature = "optional-schema") {
+        Schema::Optional(Box::new(schema))
+    } else {
+        Schema::Sum(vec![Schema::Unit, schema])
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+/// The full protobuf name of a prost message, e.g. `pkg.Person`.
+///
+/// Used by [`impl_has_schema_prost!`](crate::impl_has_schema_prost).
+pub fn full_name<T: prost::Name>() -> String {
+    T::full_name()
+}
+
+/// The schema of a message in an encoded descriptor set.
+///
+/// Used by [`impl_has_schema_prost!`](crate::impl_has_schema_prost).
+///
+/// # Panics
+///
+/// If the descriptor set is invalid or the message has no schema.
+pub fn message_schema(descriptors: &[u8], name: &str) -> Schema {
+    match Descriptors::decode(descriptors).and_then(|d| d.schema(name)) {
+        Ok(schema) => schema,
+        Err(e) => panic!("no schema for protobuf message {}: {}", name, e),
+    }
+}
+
+/// Implements [`HasSchema`](crate::HasSchema) for prost generated types.
+///
+/// The first argument is the encoded file descriptor set, followed by the
+/// types. Types without `prost::Name`, which prost-build generates with
+/// `enable_type_names`, need their full protobuf name:
+///
+/// ```ignore
+/// const DESCRIPTORS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/descriptors.bin"));
+///
+/// irpc_schema::impl_has_schema_prost!(DESCRIPTORS; users::User, users::Group = "users.Group");
+/// ```
+///
+/// The descriptor set is decoded whenever a schema is built, so prefer
+/// [`HasSchema::static_schema`](crate::HasSchema::static_schema), which
+/// caches the schema.
+#[macro_export]
+macro_rules! impl_has_schema_prost {
+    (@name $ty:ty) => {
+        $crate::protobuf::full_name::<$ty>()
+    };
+    (@name $ty:ty, $name:literal) => {
+        ::std::string::String::from($name)
+    };
+    ($descriptors:expr; $($ty:ty $(= $name:literal)?),* $(,)?) => {
+        $(
+            impl $crate::HasSchema for $ty {
+                fn schema() -> $crate::Schema {
+                    let name = $crate::impl_has_schema_prost!(@name $ty $(, $name)?);
+                    $crate::protobuf::message_schema($descriptors, &name)
+                }
+            }
+        )*
+    };
+}
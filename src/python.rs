@@ -0,0 +1,223 @@
+//! Bindings for Python, for release tooling that gates schema changes.
+//!
+//! [`irpc_schema`] is a pyo3 module with the classes `Schema`, `Manifest` and
+//! `Diff`, and the functions `diff` and `is_compatible`:
+//!
+//! ```python
+//! import irpc_schema
+//!
+//! old = irpc_schema.Schema.parse('("id":"u32",)')
+//! new = irpc_schema.Schema.parse('("id":"u64",)')
+//! assert irpc_schema.is_compatible(old, new)
+//! print(irpc_schema.diff(old, new).to_json())
+//! ```
+//!
+//! Schemas are parsed from the [`Display`](std::fmt::Display) form or the
+//! [canonical text format](crate::text), or loaded from JSON or a postcard
+//! encoded [`SchemaManifest`]. Errors are raised as `ValueError`.
+//!
+//! To build an extension module, depend on this crate with the `pyo3` feature
+//! from a `cdylib` crate and build that with maturin. The module is exported as
+//! `irpc_schema`.
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    changelog::Changelog,
+    diff::{self, Compat, SchemaDiff},
+    manifest::SchemaManifest,
+    Schema,
+};
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn json_error(e: serde_json::Error) -> PyErr {
+    value_error(format!("invalid json: {}", e))
+}
+
+/// A schema, see [`Schema`].
+#[pyclass(name = "Schema", module = "irpc_schema", frozen, eq)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PySchema(pub Schema);
+
+#[pymethods]
+impl PySchema {
+    /// Parses a schema from its text form.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        text.parse()
+            .map(Self)
+            .map_err(|e| value_error(format!("invalid schema: {}", e)))
+    }
+
+    /// Loads a schema from the JSON written by `to_json`.
+    #[staticmethod]
+    fn from_json(text: &str) -> PyResult<Self> {
+        serde_json::from_str(text).map(Self).map_err(json_error)
+    }
+
+    /// The schema as JSON, in the serde representation of [`Schema`].
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(json_error)
+    }
+
+    /// The schema in the canonical text format.
+    fn to_canonical_text(&self) -> String {
+        self.0.to_canonical_text()
+    }
+
+    /// The schema pretty printed over multiple lines.
+    fn pretty_print(&self) -> String {
+        self.0.pretty_print(0)
+    }
+
+    /// The stable hash of the schema, as hex.
+    #[getter]
+    fn hash(&self) -> String {
+        self.0.stable_hash().to_hex().to_string()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Schema.parse({:?})", self.0.to_string())
+    }
+}
+
+/// A manifest of the messages of a protocol version, see [`SchemaManifest`].
+#[pyclass(name = "Manifest", module = "irpc_schema", frozen)]
+#[derive(Debug, Clone)]
+pub struct PyManifest(pub SchemaManifest);
+
+#[pymethods]
+impl PyManifest {
+    /// Decodes a postcard encoded manifest.
+    #[staticmethod]
+    fn decode(bytes: &[u8]) -> PyResult<Self> {
+        postcard::from_bytes(bytes)
+            .map(Self)
+            .map_err(|e| value_error(format!("invalid manifest: {}", e)))
+    }
+
+    /// Name of the protocol.
+    #[getter]
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Version label of the protocol.
+    #[getter]
+    fn version(&self) -> &str {
+        &self.0.version
+    }
+
+    /// The messages as `(name, schema)` pairs, in declaration order.
+    fn messages(&self) -> Vec<(String, PySchema)> {
+        self.0
+            .messages
+            .iter()
+            .map(|entry| (entry.name.clone(), PySchema(entry.schema.clone())))
+            .collect()
+    }
+
+    /// The schema of a message, or `None` if there is no such message.
+    fn get(&self, name: &str) -> Option<PySchema> {
+        self.0.get(name).map(|entry| PySchema(entry.schema.clone()))
+    }
+
+    /// The changes from this manifest to `new`, as Markdown release notes.
+    fn changelog(&self, new: &PyManifest) -> String {
+        Changelog::new(&self.0, &new.0).to_markdown()
+    }
+}
+
+/// The changes between two schemas, see [`SchemaDiff`].
+#[pyclass(name = "Diff", module = "irpc_schema", frozen)]
+#[derive(Debug, Clone)]
+pub struct PyDiff(pub SchemaDiff);
+
+#[pymethods]
+impl PyDiff {
+    /// The classification of all changes: `"compatible"`, `"migratable"` or
+    /// `"breaking"`.
+    #[getter]
+    fn compat(&self) -> String {
+        self.0.compat().to_string()
+    }
+
+    /// The changes as `(path, change, compat)` triples.
+    fn changes(&self) -> Vec<(String, String, String)> {
+        self.0
+            .changes
+            .iter()
+            .map(|change| {
+                (
+                    change.path.to_string(),
+                    change.kind.to_string(),
+                    change.compat().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    /// The classification and changes as JSON.
+    fn to_json(&self) -> PyResult<String> {
+        let changes = self
+            .0
+            .changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({
+                    "path": change.path.to_string(),
+                    "change": change.kind.to_string(),
+                    "compat": change.compat().to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "compat": self.0.compat().to_string(),
+            "changes": changes,
+        });
+        serde_json::to_string(&json).map_err(json_error)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.changes.len()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// The changes from `old` to `new`.
+#[pyfunction(name = "diff")]
+fn diff_schemas(old: &PySchema, new: &PySchema) -> PyDiff {
+    PyDiff(diff::diff(&old.0, &new.0))
+}
+
+/// True if the changes from `old` to `new` are at most as severe as `accept`,
+/// one of `"compatible"`, `"migratable"` or `"breaking"`.
+#[pyfunction]
+#[pyo3(signature = (old, new, accept = "compatible"))]
+fn is_compatible(old: &PySchema, new: &PySchema, accept: &str) -> PyResult<bool> {
+    let accept = [Compat::Compatible, Compat::Migratable, Compat::Breaking]
+        .into_iter()
+        .find(|level| level.to_string() == accept)
+        .ok_or_else(|| value_error(format!("unknown compatibility level '{}'", accept)))?;
+    Ok(diff::diff(&old.0, &new.0).compat() <= accept)
+}
+
+/// The `irpc_schema` Python module.
+#[pymodule]
+pub fn irpc_schema(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySchema>()?;
+    m.add_class::<PyManifest>()?;
+    m.add_class::<PyDiff>()?;
+    m.add_function(wrap_pyfunction!(diff_schemas, m)?)?;
+    m.add_function(wrap_pyfunction!(is_compatible, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,281 @@
+//! A small path query language over dynamic values.
+//!
+//! Queries are dot separated steps, e.g. `items[*].id`:
+//!
+//! - `name` selects a struct field, an enum variant or a map entry with a
+//!   string key
+//! - `0` or `[0]` selects an element of a tuple, sequence or set, or a sum case
+//! - `*` or `[*]` selects all elements of a tuple, sequence, set or struct and
+//!   all values of a map
+//!
+//! Options are transparent, so `ttl` selects the value inside a `Some`, and
+//! nothing for `None`. Likewise, a variant step selects nothing if the value is
+//! a different variant.
+//!
+//! Given the schema of the value, [`Query::schema_of`] computes the schema of
+//! the results, which allows tooling to interpret them without guessing.
+use std::{fmt, str::FromStr};
+
+use crate::{
+    value::{option_inner, Value},
+    Schema,
+};
+
+/// Error when parsing or typing a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    /// The query.
+    pub query: String,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query {}: {}", self.query, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// One step of a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Step {
+    /// A struct field, enum variant or map entry with a string key.
+    Name(String),
+    /// An element by position.
+    Index(usize),
+    /// All elements.
+    Wildcard,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Name(name) => write!(f, "{}", name),
+            Step::Index(i) => write!(f, "[{}]", i),
+            Step::Wildcard => write!(f, "[*]"),
+        }
+    }
+}
+
+/// A parsed query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Query(pub Vec<Step>);
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i > 0 && matches!(step, Step::Name(_)) {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+/// The results of a query, together with their schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection<'a> {
+    /// The schema of all results.
+    pub schema: Schema,
+    /// The selected values.
+    pub values: Vec<&'a Value>,
+}
+
+fn parse_index(text: &str) -> Option<Step> {
+    match text {
+        "*" => Some(Step::Wildcard),
+        _ => text.parse().ok().map(Step::Index),
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let error = |message: &str| QueryError {
+            query: query.to_string(),
+            message: message.to_string(),
+        };
+        let mut steps = Vec::new();
+        if query.is_empty() {
+            return Ok(Query(steps));
+        }
+        for part in query.split('.') {
+            let (head, mut brackets) = match part.find('[') {
+                Some(i) => part.split_at(i),
+                None => (part, ""),
+            };
+            match head {
+                "" if brackets.is_empty() => return Err(error("empty step")),
+                "" => {}
+                head => {
+                    steps.push(parse_index(head).unwrap_or_else(|| Step::Name(head.to_string())))
+                }
+            }
+            while !brackets.is_empty() {
+                let end = brackets
+                    .find(']')
+                    .ok_or_else(|| error("unclosed bracket"))?;
+                let step = parse_index(&brackets[1..end])
+                    .ok_or_else(|| error("expected an index or * in brackets"))?;
+                steps.push(step);
+                brackets = &brackets[end + 1..];
+                if !brackets.is_empty() && !brackets.starts_with('[') {
+                    return Err(error("unexpected text after bracket"));
+                }
+            }
+        }
+        Ok(Query(steps))
+    }
+
+    /// Selects all values matching the query.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+        for step in &self.0 {
+            let mut next = Vec::new();
+            for value in current {
+                select_step(value, step, &mut next);
+            }
+            current = next;
+        }
+        // options are transparent for the results as well
+        current.into_iter().filter_map(unwrap_optional).collect()
+    }
+
+    /// Computes the schema of the results of this query on values of `schema`.
+    ///
+    /// Fails if the query does not match the schema, or if a wildcard selects
+    /// elements of different schemas.
+    pub fn schema_of(&self, schema: &Schema) -> Result<Schema, QueryError> {
+        let mut current = schema;
+        for step in &self.0 {
+            current = step_schema(current, step).map_err(|message| QueryError {
+                query: self.to_string(),
+                message,
+            })?;
+        }
+        Ok(strip(current).clone())
+    }
+}
+
+/// Removes named wrappers and options, which are transparent for queries.
+fn strip(schema: &Schema) -> &Schema {
+    match schema {
+        Schema::Named(named) => strip(&named.1),
+        _ => match option_inner(schema) {
+            Some(inner) => strip(inner),
+            None => schema,
+        },
+    }
+}
+
+fn unwrap_optional(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Optional(value) => unwrap_optional(value.as_deref()?),
+        value => Some(value),
+    }
+}
+
+fn select_step<'a>(value: &'a Value, step: &Step, out: &mut Vec<&'a Value>) {
+    match (value, step) {
+        (Value::Optional(Some(value)), _) => select_step(value, step, out),
+        (Value::Struct(fields), Step::Name(name)) => {
+            out.extend(fields.iter().filter(|f| &f.0 == name).map(|f| &f.1))
+        }
+        (Value::Struct(fields), Step::Wildcard) => out.extend(fields.iter().map(|f| &f.1)),
+        (
+            Value::Variant {
+                name: Some(n),
+                value,
+                ..
+            },
+            Step::Name(name),
+        ) if n == name => out.push(value),
+        (Value::Variant { index, value, .. }, Step::Index(i)) if *index as usize == *i => {
+            out.push(value)
+        }
+        (Value::Variant { value, .. }, Step::Wildcard) => out.push(value),
+        (Value::Tuple(items) | Value::Seq(items) | Value::Set(items), Step::Index(i)) => {
+            out.extend(items.get(*i))
+        }
+        (Value::Tuple(items) | Value::Seq(items) | Value::Set(items), Step::Wildcard) => {
+            out.extend(items.iter())
+        }
+        (Value::Map(entries), Step::Name(name)) => out.extend(
+            entries
+                .iter()
+                .filter(|(k, _)| matches!(k, Value::Str(k) if k == name))
+                .map(|(_, v)| v),
+        ),
+        (Value::Map(entries), Step::Wildcard) => out.extend(entries.iter().map(|(_, v)| v)),
+        _ => {}
+    }
+}
+
+/// The common schema of a set of alternatives, for wildcards.
+fn common<'a>(mut schemas: impl Iterator<Item = &'a Schema>) -> Result<&'a Schema, String> {
+    let first = schemas.next().ok_or("wildcard over no elements")?;
+    if schemas.all(|s| strip(s) == strip(first)) {
+        Ok(first)
+    } else {
+        Err("wildcard over elements of different schemas".to_string())
+    }
+}
+
+fn step_schema<'a>(schema: &'a Schema, step: &Step) -> Result<&'a Schema, String> {
+    let schema = strip(schema);
+    match (schema, step) {
+        (Schema::Struct(fields) | Schema::Enum(fields), Step::Name(name)) => {
+            match fields.iter().find(|f| &f.0 == name) {
+                Some(field) => Ok(&field.1),
+                None => Err(format!("no field or variant {}", name)),
+            }
+        }
+        (Schema::Struct(fields) | Schema::Enum(fields), Step::Wildcard) => {
+            common(fields.iter().map(|f| &f.1))
+        }
+        (Schema::Product(items) | Schema::Sum(items), Step::Index(i)) => {
+            items.get(*i).ok_or_else(|| format!("no element {}", i))
+        }
+        (Schema::Product(items) | Schema::Sum(items), Step::Wildcard) => common(items.iter()),
+        (
+            Schema::Seq(item) | Schema::Set(item) | Schema::UnorderedSet(item),
+            Step::Index(_) | Step::Wildcard,
+        ) => Ok(item),
+        (Schema::Map(key, value) | Schema::UnorderedMap(key, value), Step::Name(_)) => {
+            match strip(key) {
+                Schema::Atom(name) if name == "String" || name == "&str" => Ok(value),
+                _ => Err("map keys are not strings".to_string()),
+            }
+        }
+        (Schema::Map(_, value) | Schema::UnorderedMap(_, value), Step::Wildcard) => Ok(value),
+        (schema, step) => Err(format!("can not select {} in {}", step, schema)),
+    }
+}
+
+impl Value {
+    /// Selects all values matching a query, see [`crate::query`].
+    pub fn select(&self, query: &str) -> Result<Vec<&Value>, QueryError> {
+        Ok(Query::parse(query)?.select(self))
+    }
+
+    /// Selects all values matching a query, together with their schema.
+    ///
+    /// `schema` is the schema of this value.
+    pub fn select_typed(&self, schema: &Schema, query: &str) -> Result<Selection<'_>, QueryError> {
+        let query = Query::parse(query)?;
+        Ok(Selection {
+            schema: query.schema_of(schema)?,
+            values: query.select(self),
+        })
+    }
+}
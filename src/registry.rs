@@ -0,0 +1,459 @@
+//! Registries of message schemas, indexed by stable hash.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    diff::diff,
+    parallel,
+    telemetry::{report_unknown_hash, UnknownHash},
+    Schema, SchemaError,
+};
+
+/// A schema registered under a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryEntry {
+    /// The name the schema was registered under, e.g. the variant name.
+    pub name: String,
+    /// The schema.
+    pub schema: Schema,
+    /// The stable hash of the schema.
+    pub hash: [u8; 32],
+}
+
+/// A set of schemas, indexed by their stable hash.
+///
+/// This is typically populated from the `schemas()` function generated by
+/// `serialize_stable` or `serialize_service`.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    entries: BTreeMap<[u8; 32], RegistryEntry>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a schema under a name and returns its hash.
+    ///
+    /// If a schema with the same hash is already registered, the existing
+    /// entry is kept.
+    pub fn register(&mut self, name: impl Into<String>, schema: Schema) -> [u8; 32] {
+        let hash = *schema.stable_hash().as_bytes();
+        self.entries.entry(hash).or_insert_with(|| RegistryEntry {
+            name: name.into(),
+            schema,
+            hash,
+        });
+        hash
+    }
+
+    /// Registers all `(name, schema, hash)` triples, as returned by the
+    /// generated `schemas()` function.
+    pub fn register_all<'a>(
+        &mut self,
+        schemas: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+    ) {
+        for (name, schema, hash) in schemas {
+            self.entries.entry(hash).or_insert_with(|| RegistryEntry {
+                name: name.to_string(),
+                schema: schema.clone(),
+                hash,
+            });
+        }
+    }
+
+    /// Registers many schemas under their names.
+    ///
+    /// This is like calling [`register`](Self::register) for each schema, but
+    /// with the `rayon` feature, the hashes are computed in parallel.
+    pub fn register_many<N: Into<String>>(
+        &mut self,
+        schemas: impl IntoIterator<Item = (N, Schema)>,
+    ) {
+        let (names, schemas): (Vec<_>, Vec<_>) = schemas.into_iter().unzip();
+        let hashes = parallel::map(&schemas, |schema| *schema.stable_hash().as_bytes());
+        for ((name, schema), hash) in names.into_iter().zip(schemas).zip(hashes) {
+            self.entries.entry(hash).or_insert_with(|| RegistryEntry {
+                name: name.into(),
+                schema,
+                hash,
+            });
+        }
+    }
+
+    /// Looks up a schema by hash.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&RegistryEntry> {
+        self.entries.get(hash)
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Iterates over all entries, sorted by hash.
+    pub fn iter(&self) -> impl Iterator<Item = &RegistryEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the registered schema that is most similar to `schema`, together
+    /// with its diff distance.
+    pub fn nearest(&self, schema: &Schema) -> Option<(&RegistryEntry, usize)> {
+        nearest(self.iter(), schema)
+    }
+}
+
+fn nearest<'a>(
+    entries: impl Iterator<Item = &'a RegistryEntry>,
+    schema: &Schema,
+) -> Option<(&'a RegistryEntry, usize)> {
+    let entries = entries.collect::<Vec<_>>();
+    let distances = parallel::map(&entries, |entry| diff(&entry.schema, schema).distance());
+    entries
+        .into_iter()
+        .zip(distances)
+        .min_by_key(|(_, distance)| *distance)
+}
+
+/// The allowed schema closest to a rejected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nearest {
+    pub name: String,
+    pub hash: [u8; 32],
+    /// Diff distance between the rejected schema and this one.
+    pub distance: usize,
+}
+
+/// Errors returned by [`StrictRegistry`].
+#[derive(Debug)]
+pub enum StrictError {
+    /// The hash is not in the allowlist.
+    NotAllowed {
+        hash: [u8; 32],
+        /// The most similar allowed schema, if the rejected schema is known.
+        nearest: Option<Nearest>,
+    },
+    /// The message is too short to contain a hash, see
+    /// [`SchemaError::MissingHash`].
+    Schema(SchemaError),
+    /// The message has an allowed hash, but could not be decoded.
+    Decode(postcard::Error),
+}
+
+impl fmt::Display for StrictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrictError::NotAllowed { hash, nearest } => {
+                write!(f, "schema {} is not allowed", blake3::Hash::from(*hash))?;
+                if let Some(nearest) = nearest {
+                    write!(
+                        f,
+                        ", nearest allowed schema is {} ({}) at distance {}",
+                        nearest.name,
+                        blake3::Hash::from(nearest.hash),
+                        nearest.distance
+                    )?;
+                }
+                Ok(())
+            }
+            StrictError::Schema(e) => e.fmt(f),
+            StrictError::Decode(e) => write!(f, "decode error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StrictError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StrictError::Schema(e) => Some(e),
+            StrictError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A registry that only accepts an audited set of schema hashes.
+///
+/// The wrapped registry may contain schemas that are not in the allowlist,
+/// e.g. older versions of a protocol. They are never accepted, but are used to
+/// report the closest allowed schema when they are encountered.
+#[derive(Debug, Clone)]
+pub struct StrictRegistry {
+    registry: SchemaRegistry,
+    allowlist: BTreeSet<[u8; 32]>,
+}
+
+impl StrictRegistry {
+    /// Wraps a registry, pinning the set of allowed hashes.
+    pub fn new(registry: SchemaRegistry, allowlist: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        Self {
+            registry,
+            allowlist: allowlist.into_iter().collect(),
+        }
+    }
+
+    /// The underlying registry, including schemas that are not allowed.
+    pub fn registry(&self) -> &SchemaRegistry {
+        &self.registry
+    }
+
+    /// The set of allowed hashes.
+    pub fn allowlist(&self) -> &BTreeSet<[u8; 32]> {
+        &self.allowlist
+    }
+
+    /// Registers a schema, if its hash is in the allowlist.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        schema: Schema,
+    ) -> Result<[u8; 32], StrictError> {
+        let hash = *schema.stable_hash().as_bytes();
+        if !self.allowlist.contains(&hash) {
+            return Err(StrictError::NotAllowed {
+                hash,
+                nearest: self.nearest_allowed(&schema),
+            });
+        }
+        Ok(self.registry.register(name, schema))
+    }
+
+    /// Checks that a hash is in the allowlist.
+    pub fn check(&self, hash: &[u8; 32]) -> Result<(), StrictError> {
+        if self.allowlist.contains(hash) {
+            return Ok(());
+        }
+        let nearest = self
+            .registry
+            .get(hash)
+            .and_then(|entry| self.nearest_allowed(&entry.schema));
+        Err(StrictError::NotAllowed {
+            hash: *hash,
+            nearest,
+        })
+    }
+
+    /// Decodes a postcard encoded message in the format produced by
+    /// `serialize_stable`, after checking its hash against the allowlist.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, StrictError> {
+        let hash: [u8; 32] = bytes
+            .get(..32)
+            .ok_or(StrictError::Schema(SchemaError::MissingHash))?
+            .try_into()
+            .unwrap();
+        if let Err(e) = self.check(&hash) {
+            if let StrictError::NotAllowed { hash, nearest } = &e {
+                report_unknown_hash(UnknownHash {
+                    hash: *hash,
+                    nearest: nearest.clone(),
+                    len: Some(bytes.len()),
+                });
+            }
+            return Err(e);
+        }
+        postcard::from_bytes(bytes).map_err(StrictError::Decode)
+    }
+
+    /// Finds the allowed, registered schema that is most similar to `schema`.
+    pub fn nearest_allowed(&self, schema: &Schema) -> Option<Nearest> {
+        let allowed = self
+            .registry
+            .iter()
+            .filter(|entry| self.allowlist.contains(&entry.hash));
+        nearest(allowed, schema).map(|(entry, distance)| Nearest {
+            name: entry.name.clone(),
+            hash: entry.hash,
+            distance,
+        })
+    }
+}
+
+/// A conflict found when merging the registries of several services.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// Different schemas were registered under the same hash.
+    HashCollision {
+        hash: [u8; 32],
+        services: Vec<String>,
+    },
+    /// A nominal type name has different definitions across services.
+    NameConflict { name: String, services: Vec<String> },
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeConflict::HashCollision { hash, services } => write!(
+                f,
+                "hash {} refers to different schemas in services {}",
+                blake3::Hash::from(*hash),
+                services.join(", ")
+            ),
+            MergeConflict::NameConflict { name, services } => write!(
+                f,
+                "type name \"{}\" has different definitions in services {}",
+                name,
+                services.join(", ")
+            ),
+        }
+    }
+}
+
+/// Error returned by [`SchemaRegistry::merge`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeError {
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} conflicts when merging registries",
+            self.conflicts.len()
+        )?;
+        for conflict in &self.conflicts {
+            write!(f, "\n  {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// The combined registry of several services.
+///
+/// A message with the same schema can be part of more than one service, so a
+/// lookup by hash can return multiple services.
+#[derive(Debug, Clone, Default)]
+pub struct MergedRegistry {
+    registry: SchemaRegistry,
+    services: BTreeMap<[u8; 32], Vec<String>>,
+}
+
+impl MergedRegistry {
+    /// Looks up a schema by hash.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&RegistryEntry> {
+        self.registry.get(hash)
+    }
+
+    /// The services that contain the schema with the given hash.
+    pub fn services(&self, hash: &[u8; 32]) -> &[String] {
+        self.services
+            .get(hash)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The combined registry.
+    pub fn registry(&self) -> &SchemaRegistry {
+        &self.registry
+    }
+
+    pub fn into_registry(self) -> SchemaRegistry {
+        self.registry
+    }
+}
+
+impl SchemaRegistry {
+    /// Merges the registries of several services into one.
+    ///
+    /// Fails if the same hash refers to different schemas, or if a nominal type
+    /// name has different definitions in different services. A gateway routing
+    /// by hash or by type name could not tell these apart.
+    pub fn merge<'a>(
+        services: impl IntoIterator<Item = (&'a str, &'a SchemaRegistry)>,
+    ) -> Result<MergedRegistry, MergeError> {
+        let mut res = MergedRegistry::default();
+        let mut collisions = BTreeMap::<[u8; 32], Vec<String>>::new();
+        // name -> distinct definitions and the services using the name
+        let mut names = BTreeMap::<&str, (Vec<&Schema>, Vec<&str>)>::new();
+        for (service, registry) in services {
+            for entry in registry.iter() {
+                match res.registry.entries.get(&entry.hash) {
+                    Some(existing) if existing.schema != entry.schema => {
+                        collisions
+                            .entry(entry.hash)
+                            .or_insert_with(|| res.services[&entry.hash].clone())
+                            .push(service.to_string());
+                    }
+                    Some(_) => {}
+                    None => {
+                        res.registry.entries.insert(entry.hash, entry.clone());
+                    }
+                }
+                let owners = res.services.entry(entry.hash).or_default();
+                if !owners.iter().any(|s| s == service) {
+                    owners.push(service.to_string());
+                }
+                let mut named = Vec::new();
+                collect_named(&entry.schema, &mut named);
+                for (name, definition) in named {
+                    let (definitions, users) = names.entry(name).or_default();
+                    if !definitions.contains(&definition) {
+                        definitions.push(definition);
+                    }
+                    if !users.contains(&service) {
+                        users.push(service);
+                    }
+                }
+            }
+        }
+        let mut conflicts = collisions
+            .into_iter()
+            .map(|(hash, services)| MergeConflict::HashCollision { hash, services })
+            .collect::<Vec<_>>();
+        for (name, (definitions, users)) in names {
+            if definitions.len() > 1 && users.len() > 1 {
+                conflicts.push(MergeConflict::NameConflict {
+                    name: name.to_string(),
+                    services: users.into_iter().map(String::from).collect(),
+                });
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(res)
+        } else {
+            Err(MergeError { conflicts })
+        }
+    }
+}
+
+/// Collects all nominal type names in a schema, with their definitions.
+fn collect_named<'a>(schema: &'a Schema, out: &mut Vec<(&'a str, &'a Schema)>) {
+    match schema {
+        Schema::Unit | Schema::Bottom | Schema::Atom(_) | Schema::Enumeration(_) => {}
+        Schema::Product(items) | Schema::Sum(items) => {
+            items.iter().for_each(|item| collect_named(item, out))
+        }
+        Schema::Struct(fields) | Schema::Enum(fields) => {
+            fields.iter().for_each(|field| collect_named(&field.1, out))
+        }
+        Schema::Named(named) => {
+            out.push((&named.0, &named.1));
+            collect_named(&named.1, out);
+        }
+        Schema::Seq(item)
+        | Schema::Set(item)
+        | Schema::UnorderedSet(item)
+        | Schema::Optional(item)
+        | Schema::Tagged(_, item)
+        | Schema::Stream(item) => collect_named(item, out),
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            collect_named(key, out);
+            collect_named(value, out);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Support for routers.
+//!
+//! `#[serialize_service(MyService, router)]` generates a `MyServiceRouter<T>`
+//! that decodes messages in the `serialize_service` wire format and dispatches
+//! them to async handlers registered per variant, all producing a `T`:
+//!
+//! ```ignore
+//! let router = MyServiceRouter::new()
+//!     .on_get(|req| async move { lookup(req.key) })
+//!     .on_unknown(|hash, _payload| async move { Response::Unsupported });
+//! let response = router.handle(&bytes).await?;
+//! ```
+//!
+//! Messages with unknown hashes go to the unknown handler if there is one.
+//! Optionally, hashes can be checked against a [`StrictRegistry`] first. Both
+//! unknown and disallowed hashes are reported to the [telemetry](crate::telemetry)
+//! hook.
+use std::{fmt, future::Future, pin::Pin};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    registry::{Nearest, StrictError, StrictRegistry},
+    telemetry::{report_unknown_hash, UnknownHash},
+    SchemaError,
+};
+
+/// A boxed future, as returned by handlers.
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// A handler for a single variant.
+pub type Handler<Req, T> = Box<dyn Fn(Req) -> BoxFuture<T> + Send + Sync + 'static>;
+
+/// A handler for messages with unknown hashes, getting the hash and payload.
+pub type UnknownHandler<T> = Box<dyn Fn([u8; 32], Vec<u8>) -> BoxFuture<T> + Send + Sync + 'static>;
+
+/// Errors when routing a message.
+#[derive(Debug)]
+pub enum RouteError {
+    /// The message is too short to contain a hash, or the hash is not known
+    /// and there is no unknown handler.
+    Schema(SchemaError),
+    /// The hash is not allowed by the registry.
+    NotAllowed(StrictError),
+    /// The message has a known hash, but could not be decoded.
+    Decode(postcard::Error),
+    /// There is no handler for the variant.
+    NoHandler { name: &'static str },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::Schema(e) => e.fmt(f),
+            RouteError::NotAllowed(e) => write!(f, "{}", e),
+            RouteError::Decode(e) => write!(f, "decode error: {}", e),
+            RouteError::NoHandler { name } => write!(f, "no handler for {}", name),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RouteError::Schema(e) => Some(e),
+            RouteError::NotAllowed(e) => Some(e),
+            RouteError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Boxes an async handler function.
+pub fn handler<Req, T, F, Fut>(f: F) -> Handler<Req, T>
+where
+    F: Fn(Req) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    Box::new(move |req| Box::pin(f(req)))
+}
+
+/// Boxes an async handler function for unknown hashes.
+pub fn unknown_handler<T, F, Fut>(f: F) -> UnknownHandler<T>
+where
+    F: Fn([u8; 32], Vec<u8>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    Box::new(move |hash, payload| Box::pin(f(hash, payload)))
+}
+
+/// Reads the hash of a message, checking it against the registry if given.
+pub fn check_hash(bytes: &[u8], registry: Option<&StrictRegistry>) -> Result<[u8; 32], RouteError> {
+    let hash: [u8; 32] = bytes
+        .get(..32)
+        .ok_or(RouteError::Schema(SchemaError::MissingHash))?
+        .try_into()
+        .unwrap();
+    if let Some(registry) = registry {
+        if let Err(e) = registry.check(&hash) {
+            if let StrictError::NotAllowed { nearest, .. } = &e {
+                report(hash, nearest.clone(), bytes.len());
+            }
+            return Err(RouteError::NotAllowed(e));
+        }
+    }
+    Ok(hash)
+}
+
+/// Reports a message with an unknown hash to the telemetry hook.
+pub fn report_unknown(bytes: &[u8]) {
+    if let Ok(hash) = check_hash(bytes, None) {
+        report(hash, None, bytes.len());
+    }
+}
+
+fn report(hash: [u8; 32], nearest: Option<Nearest>, len: usize) {
+    report_unknown_hash(UnknownHash {
+        hash,
+        nearest,
+        len: Some(len),
+    });
+}
+
+/// Decodes a message with a known hash.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RouteError> {
+    postcard::from_bytes(bytes).map_err(RouteError::Decode)
+}
@@ -0,0 +1,347 @@
+//! Descriptions of whole irpc services.
+//!
+//! A [`ServiceDescriptor`] lists all methods of a service, i.e. the variants of
+//! a `serialize_service` enum, with the schemas of the request and of the
+//! channels. Its [hash](ServiceDescriptor::hash) identifies the entire API
+//! surface, so deployment tooling can pin a single value.
+//!
+//! Services can answer the standard [`Describe`] request with their
+//! descriptor, so generic clients and debuggers can introspect them.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hash_postcard,
+    manifest::{ManifestEntry, SchemaManifest},
+    HasSchema, Named, Schema,
+};
+
+/// The kind of an irpc channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChannelKind {
+    /// No channel, `NoSender` or `NoReceiver`.
+    None,
+    /// A oneshot channel carrying a single item.
+    Oneshot,
+    /// An mpsc channel carrying a stream of items.
+    Mpsc,
+}
+
+impl ChannelKind {
+    /// Recognizes the schema of an irpc channel, returning the kind and the
+    /// item schema.
+    ///
+    /// An mpsc channel is a [`Stream`](Schema::Stream). The named
+    /// `irpc::channel::mpsc` schemas of earlier versions are recognized too.
+    pub fn of(schema: &Schema) -> Option<(ChannelKind, Option<&Schema>)> {
+        match schema {
+            Schema::Atom(name) => match name.as_str() {
+                "irpc::channel::none::NoSender" | "irpc::channel::none::NoReceiver" => {
+                    Some((ChannelKind::None, None))
+                }
+                _ => None,
+            },
+            Schema::Named(named) => match named.0.as_str() {
+                "irpc::channel::oneshot::Sender" | "irpc::channel::oneshot::Receiver" => {
+                    Some((ChannelKind::Oneshot, Some(&named.1)))
+                }
+                "irpc::channel::mpsc::Sender" | "irpc::channel::mpsc::Receiver" => {
+                    Some((ChannelKind::Mpsc, Some(&named.1)))
+                }
+                _ => None,
+            },
+            Schema::Stream(item) => Some((ChannelKind::Mpsc, Some(item))),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelKind::None => write!(f, "none"),
+            ChannelKind::Oneshot => write!(f, "oneshot"),
+            ChannelKind::Mpsc => write!(f, "mpsc"),
+        }
+    }
+}
+
+/// Name of the combined schema of a `serialize_service` variant.
+///
+/// This layout was introduced with [hash scheme version
+/// 2](crate::hashing), and [`hash_v1`](crate::hashing::hash_v1) computes the
+/// hash of the anonymous `(msg, rx, tx)` product used before, so peers built
+/// before the change can still be recognized. The version suffix keeps a
+/// future layout from being confused with this one.
+pub const CHANNELS_SCHEMA_NAME: &str = "irpc_schema::Channels/v2";
+
+/// The combined schema of a message and its channels, as used by
+/// `ChannelsSchema`: a named struct with the parts `msg`, `rx` and `tx`.
+pub fn channels_schema(msg: Schema, rx: Schema, tx: Schema) -> Schema {
+    Schema::named(
+        CHANNELS_SCHEMA_NAME,
+        Schema::Struct(vec![
+            Named::new("msg", msg),
+            Named::new("rx", rx),
+            Named::new("tx", tx),
+        ]),
+    )
+}
+
+/// Splits a combined schema into message, rx and tx schemas.
+///
+/// Besides the current layout, this accepts the anonymous `(msg, rx, tx)`
+/// product used by earlier versions, so old manifests can still be read.
+pub fn split_channels_schema(schema: &Schema) -> Option<(&Schema, &Schema, &Schema)> {
+    match schema {
+        Schema::Named(named) if named.0 == CHANNELS_SCHEMA_NAME => match &named.1 {
+            Schema::Struct(fields) => match fields.as_slice() {
+                [msg, rx, tx] if msg.0 == "msg" && rx.0 == "rx" && tx.0 == "tx" => {
+                    Some((&msg.1, &rx.1, &tx.1))
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        Schema::Product(items) => match items.as_slice() {
+            [msg, rx, tx] => Some((msg, rx, tx)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The message part of a schema, which is the whole schema unless it is the
+/// combined schema of a `serialize_service` variant.
+pub(crate) fn request_schema(schema: &Schema) -> &Schema {
+    match split_channels_schema(schema) {
+        Some((msg, rx, tx)) if ChannelKind::of(rx).is_some() && ChannelKind::of(tx).is_some() => {
+            msg
+        }
+        _ => schema,
+    }
+}
+
+/// A single method of a service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodDescriptor {
+    /// The variant name.
+    pub name: String,
+    /// The schema of the request message.
+    pub request: Schema,
+    /// The schema of the channel the server receives updates on.
+    pub rx: Schema,
+    /// The schema of the channel the server sends responses on.
+    pub tx: Schema,
+    /// The stable hash of the request and channel schemas, as used on the wire.
+    pub hash: [u8; 32],
+}
+
+impl MethodDescriptor {
+    /// Creates a method from the combined schema of a `serialize_service`
+    /// variant, see [`split_channels_schema`].
+    ///
+    /// Returns `None` if the schema does not have this shape.
+    pub fn from_schema(name: impl Into<String>, schema: &Schema, hash: [u8; 32]) -> Option<Self> {
+        let (request, rx, tx) = split_channels_schema(schema)?;
+        Some(Self {
+            name: name.into(),
+            request: request.clone(),
+            rx: rx.clone(),
+            tx: tx.clone(),
+            hash,
+        })
+    }
+
+    /// The combined schema of request and channels, whose stable hash is
+    /// [`Self::hash`] for methods of the current layout.
+    pub fn schema(&self) -> Schema {
+        channels_schema(self.request.clone(), self.rx.clone(), self.tx.clone())
+    }
+
+    /// The schema of the response items the server sends, if any.
+    pub fn response(&self) -> Option<&Schema> {
+        self.tx_kind().and_then(|(_, item)| item)
+    }
+
+    /// The schema of the update items the client sends, if any.
+    pub fn updates(&self) -> Option<&Schema> {
+        self.rx_kind().and_then(|(_, item)| item)
+    }
+
+    /// The kind and item schema of the rx channel, if it is an irpc channel.
+    pub fn rx_kind(&self) -> Option<(ChannelKind, Option<&Schema>)> {
+        ChannelKind::of(&self.rx)
+    }
+
+    /// The kind and item schema of the tx channel, if it is an irpc channel.
+    pub fn tx_kind(&self) -> Option<(ChannelKind, Option<&Schema>)> {
+        ChannelKind::of(&self.tx)
+    }
+
+    /// True if the client or the server sends a stream of items, i.e. if one
+    /// of the channels is an mpsc channel.
+    pub fn is_streaming(&self) -> bool {
+        [self.rx_kind(), self.tx_kind()]
+            .into_iter()
+            .any(|kind| matches!(kind, Some((ChannelKind::Mpsc, _))))
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.request)?;
+        for (label, schema) in [("rx", &self.rx), ("tx", &self.tx)] {
+            match ChannelKind::of(schema) {
+                Some((ChannelKind::None, _)) => {}
+                Some((kind, Some(item))) => write!(f, " {} {} {}", label, kind, item)?,
+                _ => write!(f, " {} {}", label, schema)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All methods of a service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceDescriptor {
+    /// Name of the service.
+    pub name: String,
+    /// The methods, in declaration order.
+    pub methods: Vec<MethodDescriptor>,
+}
+
+impl ServiceDescriptor {
+    /// Creates a descriptor from `(name, schema, hash)` triples, as returned by
+    /// the `schemas()` function generated by `serialize_service`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a schema is not a combined schema of request, rx and tx, which
+    /// means that the triples are not from a `serialize_service` enum.
+    pub fn from_schemas<'a>(
+        name: impl Into<String>,
+        schemas: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+    ) -> Self {
+        let methods = schemas
+            .into_iter()
+            .map(|(name, schema, hash)| {
+                MethodDescriptor::from_schema(name, schema, hash)
+                    .unwrap_or_else(|| panic!("{} is not a service method schema", name))
+            })
+            .collect();
+        Self {
+            name: name.into(),
+            methods,
+        }
+    }
+
+    /// Looks up a method by name.
+    pub fn get(&self, name: &str) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// The methods with a stream of requests or responses, see
+    /// [`MethodDescriptor::is_streaming`].
+    pub fn streaming_methods(&self) -> impl Iterator<Item = &MethodDescriptor> {
+        self.methods.iter().filter(|method| method.is_streaming())
+    }
+
+    /// Looks up a method by hash.
+    pub fn get_by_hash(&self, hash: &[u8; 32]) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|method| &method.hash == hash)
+    }
+
+    /// The service hash, identifying the entire API surface.
+    ///
+    /// This covers the method names and hashes, but not the service name, just
+    /// like [`SchemaManifest::hash`](crate::manifest::SchemaManifest::hash).
+    pub fn hash(&self) -> [u8; 32] {
+        let parts = self
+            .methods
+            .iter()
+            .map(|method| (method.name.as_str(), method.hash))
+            .collect::<Vec<_>>();
+        *hash_postcard(&parts).as_bytes()
+    }
+
+    /// Creates a manifest with one message per method.
+    pub fn to_manifest(&self, version: impl Into<String>) -> SchemaManifest {
+        let mut res = SchemaManifest::new(self.name.clone(), version);
+        res.messages = self
+            .methods
+            .iter()
+            .map(|method| ManifestEntry {
+                name: method.name.clone(),
+                schema: method.schema(),
+                hash: method.hash,
+            })
+            .collect();
+        res
+    }
+}
+
+/// The schema of schemas is recursive, so descriptors are opaque atoms.
+impl HasSchema for ServiceDescriptor {
+    fn schema() -> Schema {
+        Schema::Atom("irpc_schema::service::ServiceDescriptor".to_string())
+    }
+}
+
+impl fmt::Display for ServiceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "service {} {}",
+            self.name,
+            blake3::Hash::from(self.hash())
+        )?;
+        for method in &self.methods {
+            writeln!(f, "  {}", method)?;
+        }
+        Ok(())
+    }
+}
+
+/// A request for the [`ServiceDescriptor`] of a service.
+///
+/// With the `irpc` feature, this is a valid request for any service, answered
+/// with a oneshot descriptor. Add it as a variant to a `serialize_service`
+/// enum, and answer it with [`respond_describe`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Describe;
+
+impl HasSchema for Describe {
+    fn schema() -> Schema {
+        Schema::named("Describe", Schema::Unit)
+    }
+}
+
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+impl<S: irpc::Service> irpc::Channels<S> for Describe {
+    type Rx = irpc::channel::none::NoReceiver;
+    type Tx = irpc::channel::oneshot::Sender<ServiceDescriptor>;
+}
+
+/// Requests the descriptor of a service.
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub async fn describe<S>(client: &irpc::Client<S>) -> irpc::Result<ServiceDescriptor>
+where
+    S: irpc::Service + From<Describe>,
+    S::Message: From<irpc::WithChannels<Describe, S>>,
+{
+    client.rpc(Describe).await
+}
+
+/// Answers a [`Describe`] request with the given descriptor.
+#[cfg(feature = "irpc")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "irpc")))]
+pub async fn respond_describe<S: irpc::Service>(
+    descriptor: &ServiceDescriptor,
+    request: irpc::WithChannels<Describe, S>,
+) -> Result<(), irpc::channel::SendError> {
+    request.tx.send(descriptor.clone()).await
+}
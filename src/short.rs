@@ -0,0 +1,117 @@
+//! Short discriminators for constrained links.
+//!
+//! By default, every message carries the full 32 byte schema hash. With
+//! `#[serialize_stable(short)]` or `#[serialize_service(Service, short)]`,
+//! only the first [`SHORT_LEN`] bytes are sent, saving 24 bytes per message,
+//! which matters for chatty protocols over radio or satellite links.
+//!
+//! 8 bytes are plenty to tell the messages of one protocol apart, but they
+//! no longer identify a schema globally. Collisions within a protocol are
+//! checked when its schemas are first built, and registries can be checked
+//! with [`ShortIndex::new`] before being used to interpret short hashes.
+//!
+//! `schemas()`, manifests and negotiation still use the full hashes. Tools
+//! that parse the hash from raw bytes, like routers, framing and mock servers,
+//! expect the full hash and can not be used with short discriminators. Unknown
+//! short hashes are reported to the [telemetry](crate::telemetry) hook padded
+//! with zeros.
+use std::{collections::BTreeMap, fmt};
+
+use crate::registry::SchemaRegistry;
+
+/// The number of hash bytes sent in the short wire mode.
+pub const SHORT_LEN: usize = 8;
+
+/// The short form of a schema hash.
+pub fn short_hash(hash: &[u8; 32]) -> [u8; SHORT_LEN] {
+    hash[..SHORT_LEN].try_into().unwrap()
+}
+
+/// A short hash padded with zeros, for reporting unknown short hashes.
+pub fn pad(short: &[u8; SHORT_LEN]) -> [u8; 32] {
+    let mut res = [0u8; 32];
+    res[..SHORT_LEN].copy_from_slice(short);
+    res
+}
+
+/// Two schemas with different hashes share the same short hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortCollision {
+    /// The short hash.
+    pub short: [u8; SHORT_LEN],
+    /// The name of the first schema.
+    pub first: String,
+    /// The name of the second schema.
+    pub second: String,
+}
+
+impl fmt::Display for ShortCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "short hash ")?;
+        for byte in self.short {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, " of {} collides with {}", self.second, self.first)
+    }
+}
+
+impl std::error::Error for ShortCollision {}
+
+/// Full hashes indexed by their short form.
+#[derive(Debug, Clone, Default)]
+pub struct ShortIndex {
+    entries: BTreeMap<[u8; SHORT_LEN], (String, [u8; 32])>,
+}
+
+impl ShortIndex {
+    /// Indexes `(name, hash)` pairs, failing if two different hashes share a
+    /// short hash.
+    ///
+    /// The same hash may occur several times, e.g. for two variants with the
+    /// same payload type.
+    pub fn new<'a>(
+        hashes: impl IntoIterator<Item = (&'a str, [u8; 32])>,
+    ) -> Result<Self, ShortCollision> {
+        let mut entries = BTreeMap::<[u8; SHORT_LEN], (String, [u8; 32])>::new();
+        for (name, hash) in hashes {
+            let short = short_hash(&hash);
+            match entries.get(&short) {
+                Some((first, existing)) if existing != &hash => {
+                    return Err(ShortCollision {
+                        short,
+                        first: first.clone(),
+                        second: name.to_string(),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    entries.insert(short, (name.to_string(), hash));
+                }
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// The full hash of a short hash.
+    pub fn get(&self, short: &[u8; SHORT_LEN]) -> Option<[u8; 32]> {
+        self.entries.get(short).map(|(_, hash)| *hash)
+    }
+
+    /// The number of distinct hashes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl SchemaRegistry {
+    /// Indexes the registered hashes by their short form, failing if two of
+    /// them collide.
+    pub fn short_index(&self) -> Result<ShortIndex, ShortCollision> {
+        ShortIndex::new(self.iter().map(|entry| (entry.name.as_str(), entry.hash)))
+    }
+}
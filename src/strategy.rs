@@ -0,0 +1,193 @@
+//! [proptest](https://docs.rs/proptest) strategies derived from schemas.
+//!
+//! [`arb_value`] generates dynamic values that conform to a schema, and
+//! [`arb_typed`] turns them into values of a concrete type by going through
+//! the postcard encoding. This allows round trip and compatibility properties
+//! to be tested against the declared schema, without writing strategies by
+//! hand.
+//!
+//! Cases of sums and enums that are uninhabited, such as the bottom type, are
+//! never generated. Opaque atoms have no known encoding, so they are
+//! generated as [`Value::Unit`].
+use std::fmt::Debug;
+
+use proptest::{
+    collection::vec,
+    num,
+    prelude::*,
+    strategy::{BoxedStrategy, Union},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    codec::encode_postcard,
+    value::{option_inner, Primitive, Value},
+    HasSchema, Schema,
+};
+
+/// Maximum number of elements of generated sequences, sets and maps.
+const MAX_LEN: usize = 8;
+
+/// True if there is at least one value of the schema.
+fn inhabited(schema: &Schema) -> bool {
+    match schema {
+        Schema::Bottom | Schema::Stream(_) => false,
+        Schema::Named(named) => inhabited(&named.1),
+        Schema::Tagged(_, inner) => inhabited(inner),
+        Schema::Product(items) => items.iter().all(inhabited),
+        Schema::Struct(fields) => fields.iter().all(|field| inhabited(&field.1)),
+        Schema::Sum(cases) => cases.iter().any(inhabited),
+        Schema::Enum(cases) => cases.iter().any(|case| inhabited(&case.1)),
+        Schema::Enumeration(cases) => !cases.is_empty(),
+        // empty sequences, sets and maps are always values
+        _ => true,
+    }
+}
+
+fn arb_f64() -> BoxedStrategy<f64> {
+    // no NaN or infinities, so generated values can be compared
+    (num::f64::NORMAL | num::f64::SUBNORMAL | num::f64::ZERO).boxed()
+}
+
+fn arb_primitive(primitive: Primitive) -> BoxedStrategy<Value> {
+    match primitive {
+        Primitive::Bool => any::<bool>().prop_map(Value::Bool).boxed(),
+        Primitive::Char => any::<char>().prop_map(Value::Char).boxed(),
+        Primitive::U8 => any::<u8>().prop_map(|v| Value::UInt(v as u128)).boxed(),
+        Primitive::U16 => any::<u16>().prop_map(|v| Value::UInt(v as u128)).boxed(),
+        Primitive::U32 => any::<u32>().prop_map(|v| Value::UInt(v as u128)).boxed(),
+        Primitive::U64 => any::<u64>().prop_map(|v| Value::UInt(v as u128)).boxed(),
+        Primitive::U128 => any::<u128>().prop_map(Value::UInt).boxed(),
+        Primitive::I8 => any::<i8>().prop_map(|v| Value::Int(v as i128)).boxed(),
+        Primitive::I16 => any::<i16>().prop_map(|v| Value::Int(v as i128)).boxed(),
+        Primitive::I32 => any::<i32>().prop_map(|v| Value::Int(v as i128)).boxed(),
+        Primitive::I64 => any::<i64>().prop_map(|v| Value::Int(v as i128)).boxed(),
+        Primitive::I128 => any::<i128>().prop_map(Value::Int).boxed(),
+        // go through f32 so the value is exactly representable
+        Primitive::F32 => arb_f64()
+            .prop_map(|v| Value::Float(v as f32 as f64))
+            .boxed(),
+        Primitive::F64 => arb_f64().prop_map(Value::Float).boxed(),
+        Primitive::Str => any::<String>().prop_map(Value::Str).boxed(),
+        Primitive::Bytes => vec(any::<u8>(), 0..=MAX_LEN * 4)
+            .prop_map(Value::Bytes)
+            .boxed(),
+    }
+}
+
+/// A strategy generating values that conform to `schema`.
+///
+/// # Panics
+///
+/// Panics if the schema is uninhabited, e.g. if it is or contains a required
+/// [`Schema::Bottom`].
+pub fn arb_value(schema: &Schema) -> BoxedStrategy<Value> {
+    assert!(inhabited(schema), "schema has no values");
+    match schema {
+        Schema::Named(named) => arb_value(&named.1),
+        Schema::Tagged(_, inner) => arb_value(inner),
+        Schema::Unit => Just(Value::Unit).boxed(),
+        Schema::Bottom | Schema::Stream(_) => unreachable!(),
+        Schema::Atom(name) => match Primitive::from_atom(name) {
+            Some(primitive) => arb_primitive(primitive),
+            None => Just(Value::Unit).boxed(),
+        },
+        Schema::Product(items) => items
+            .iter()
+            .map(arb_value)
+            .collect::<Vec<_>>()
+            .prop_map(Value::Tuple)
+            .boxed(),
+        Schema::Struct(fields) => {
+            let names = fields.iter().map(|f| f.0.clone()).collect::<Vec<_>>();
+            fields
+                .iter()
+                .map(|f| arb_value(&f.1))
+                .collect::<Vec<_>>()
+                .prop_map(move |values| Value::Struct(names.iter().cloned().zip(values).collect()))
+                .boxed()
+        }
+        Schema::Sum(_) | Schema::Optional(_) if option_inner(schema).is_some_and(inhabited) => {
+            proptest::option::of(arb_value(option_inner(schema).unwrap()))
+                .prop_map(|value| Value::Optional(value.map(Box::new)))
+                .boxed()
+        }
+        Schema::Sum(cases) => Union::new(
+            cases
+                .iter()
+                .enumerate()
+                .filter(|(_, case)| inhabited(case))
+                .map(|(index, case)| {
+                    arb_value(case)
+                        .prop_map(move |value| Value::Variant {
+                            index: index as u32,
+                            name: None,
+                            value: Box::new(value),
+                        })
+                        .boxed()
+                }),
+        )
+        .boxed(),
+        Schema::Enum(cases) => Union::new(
+            cases
+                .iter()
+                .enumerate()
+                .filter(|(_, case)| inhabited(&case.1))
+                .map(|(index, case)| {
+                    let name = case.0.clone();
+                    arb_value(&case.1)
+                        .prop_map(move |value| Value::Variant {
+                            index: index as u32,
+                            name: Some(name.clone()),
+                            value: Box::new(value),
+                        })
+                        .boxed()
+                }),
+        )
+        .boxed(),
+        Schema::Enumeration(cases) => Union::new(cases.iter().map(|(name, tag)| {
+            Just(Value::Variant {
+                index: *tag,
+                name: Some(name.clone()),
+                value: Box::new(Value::Unit),
+            })
+            .boxed()
+        }))
+        .boxed(),
+        Schema::Seq(item) if inhabited(item) => vec(arb_value(item), 0..=MAX_LEN)
+            .prop_map(Value::Seq)
+            .boxed(),
+        Schema::Set(item) | Schema::UnorderedSet(item) if inhabited(item) => {
+            vec(arb_value(item), 0..=MAX_LEN)
+                .prop_map(Value::Set)
+                .boxed()
+        }
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value)
+            if inhabited(key) && inhabited(value) =>
+        {
+            vec((arb_value(key), arb_value(value)), 0..=MAX_LEN)
+                .prop_map(Value::Map)
+                .boxed()
+        }
+        Schema::Seq(_) => Just(Value::Seq(Vec::new())).boxed(),
+        Schema::Set(_) | Schema::UnorderedSet(_) => Just(Value::Set(Vec::new())).boxed(),
+        Schema::Map(_, _) | Schema::UnorderedMap(_, _) => Just(Value::Map(Vec::new())).boxed(),
+        Schema::Optional(_) => Just(Value::Optional(None)).boxed(),
+    }
+}
+
+/// A strategy generating values of `T`, based on its schema.
+///
+/// Values are generated with [`arb_value`], encoded with the schema and then
+/// deserialized as `T`. Generated values that `T` rejects, e.g. because of
+/// additional invariants checked during deserialization, are filtered out.
+pub fn arb_typed<T>() -> impl Strategy<Value = T>
+where
+    T: HasSchema + DeserializeOwned + Debug,
+{
+    let schema = T::schema();
+    arb_value(&schema).prop_filter_map("value not accepted by the type", move |value| {
+        let bytes = encode_postcard(&schema, &value).ok()?;
+        postcard::from_bytes(&bytes).ok()
+    })
+}
@@ -0,0 +1,189 @@
+//! Visibility into messages with unknown schemas.
+//!
+//! A global hook can be set with [`set_unknown_hash_hook`]. It is called
+//! whenever a message with an unknown or disallowed schema hash is
+//! encountered, by the deserializers generated by `serialize_stable` and
+//! `serialize_service`, by [`StrictRegistry::decode`], by generated routers
+//! and by [`VersionBridge`]. Operators can use it to see which stale client
+//! versions are still around.
+//!
+//! Generated deserializers only know the hash of an unknown message. If the
+//! schemas of earlier versions are set with [`set_schema_history`], they also
+//! report the current message nearest to it. The `from_postcard` method
+//! generated for message enums reports the length of the message as well.
+//!
+//! With the `tracing` feature, the serializers and deserializers generated by
+//! `serialize_stable` and `serialize_service` also record the message name,
+//! schema hash and payload size of every message, see [`record_message`].
+//!
+//! [`StrictRegistry::decode`]: crate::registry::StrictRegistry::decode
+//! [`VersionBridge`]: crate::bridge::VersionBridge
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+use crate::{
+    registry::{Nearest, SchemaRegistry},
+    short, Schema,
+};
+
+/// A message with an unknown schema hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownHash {
+    /// The schema hash of the message.
+    pub hash: [u8; 32],
+    /// The most similar known schema, if the schema of the message is known,
+    /// e.g. because a registry contains it as an older version.
+    pub nearest: Option<Nearest>,
+    /// The length of the message in bytes, if known.
+    ///
+    /// Generated deserializers only know the length when called through the
+    /// generated `from_postcard`, since serde only shows them a part of the
+    /// input.
+    pub len: Option<usize>,
+}
+
+type Hook = Arc<dyn Fn(&UnknownHash) + Send + Sync + 'static>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Sets the global hook for messages with unknown hashes, replacing any
+/// previous hook.
+pub fn set_unknown_hash_hook(hook: impl Fn(&UnknownHash) + Send + Sync + 'static) {
+    *HOOK.write().unwrap() = Some(Arc::new(hook));
+}
+
+/// Removes the global hook.
+pub fn clear_unknown_hash_hook() {
+    *HOOK.write().unwrap() = None;
+}
+
+static HISTORY: RwLock<Option<Arc<SchemaRegistry>>> = RwLock::new(None);
+
+/// Sets the global schema history, replacing any previous history.
+///
+/// The history contains the schemas of earlier versions of messages, e.g.
+/// loaded from old manifests. It is used to find the nearest current message
+/// for unknown hashes reported by generated deserializers.
+pub fn set_schema_history(history: SchemaRegistry) {
+    *HISTORY.write().unwrap() = Some(Arc::new(history));
+}
+
+/// Removes the global schema history.
+pub fn clear_schema_history() {
+    *HISTORY.write().unwrap() = None;
+}
+
+/// Calls the global hook, if set.
+pub fn report_unknown_hash(event: UnknownHash) {
+    // clone the hook, so it can set or clear the hook itself
+    let hook = HOOK.read().unwrap().clone();
+    if let Some(hook) = hook {
+        hook(&event);
+    }
+}
+
+/// Calls the global hook for a message with an unknown hash, received by a
+/// decoder for the messages `known`.
+///
+/// If the hash is in the [schema history](set_schema_history), the event
+/// names the known message nearest to it. Short hashes padded with zeros are
+/// looked up by their short form. This is called by the code generated by
+/// `serialize_stable` and `serialize_service`.
+pub fn report_unknown_message<'a>(
+    hash: [u8; 32],
+    len: Option<usize>,
+    known: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+) {
+    if HOOK.read().unwrap().is_none() {
+        return;
+    }
+    let history = HISTORY.read().unwrap().clone();
+    let nearest = history.and_then(|history| {
+        let old = history.get(&hash).or_else(|| {
+            history
+                .iter()
+                .find(|entry| short::pad(&short::short_hash(&entry.hash)) == hash)
+        })?;
+        let mut current = SchemaRegistry::new();
+        current.register_all(known);
+        let (entry, distance) = current.nearest(&old.schema)?;
+        Some(Nearest {
+            name: entry.name.clone(),
+            hash: entry.hash,
+            distance,
+        })
+    });
+    report_unknown_hash(UnknownHash { hash, nearest, len });
+}
+
+/// Span field for the name of a message, see [`record_message`].
+pub const MESSAGE_FIELD: &str = "schema.message";
+/// Span field for the schema hash of a message, see [`record_message`].
+pub const HASH_FIELD: &str = "schema.hash";
+/// Span field for the payload size of a message, see [`record_message`].
+pub const SIZE_FIELD: &str = "schema.size";
+
+/// Whether a message was serialized or deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Serialize,
+    Deserialize,
+}
+
+/// Records a message in the current tracing span.
+///
+/// The name, hash and postcard size of the payload are recorded in the
+/// [`MESSAGE_FIELD`], [`HASH_FIELD`] and [`SIZE_FIELD`] fields of the current
+/// span, if it has them, e.g. because it was created with `message_span`. A
+/// trace event with the same fields is emitted as well, so the message can be
+/// attributed even if the span lacks the fields. If neither the span nor the
+/// event is enabled, the payload is not encoded to compute its size.
+///
+/// This is called by the code generated by `serialize_stable` and
+/// `serialize_service`, and does nothing without the `tracing` feature.
+#[inline]
+pub fn record_message<T: Serialize + ?Sized>(
+    direction: Direction,
+    name: &str,
+    hash: &[u8; 32],
+    payload: &T,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        // computing the size encodes the message, so skip it if nobody listens
+        if span.is_disabled() && !tracing::enabled!(target: "irpc_schema", tracing::Level::TRACE) {
+            return;
+        }
+        let hash = blake3::Hash::from(*hash);
+        let size = postcard::experimental::serialized_size(payload).ok();
+        span.record(MESSAGE_FIELD, name);
+        span.record(HASH_FIELD, tracing::field::display(&hash));
+        span.record(SIZE_FIELD, size);
+        tracing::event!(
+            target: "irpc_schema",
+            tracing::Level::TRACE,
+            schema.message = name,
+            schema.hash = %hash,
+            schema.size = size,
+            ?direction,
+            "message"
+        );
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (direction, name, hash, payload);
+}
+
+/// Creates a span with empty fields for [`record_message`].
+#[cfg(feature = "tracing")]
+#[cfg_attr(irpc_schema_docsrs, doc(cfg(feature = "tracing")))]
+pub fn message_span() -> tracing::Span {
+    tracing::info_span!(
+        target: "irpc_schema",
+        "message",
+        schema.message = tracing::field::Empty,
+        schema.hash = tracing::field::Empty,
+        schema.size = tracing::field::Empty,
+    )
+}
@@ -0,0 +1,677 @@
+//! Canonical text format for schemas.
+//!
+//! The canonical text format is meant for schema files that are committed to a
+//! repository. Formatting is strictly deterministic: one node per line, two
+//! spaces of indentation per level and a trailing newline, so that a change to
+//! a protocol shows up as a minimal diff.
+//!
+//! Children are never reordered, since field and variant order is significant
+//! for the wire format.
+//!
+//! ```text
+//! named "PutRequest" struct {
+//!   "key": "String"
+//!   "value": sum {
+//!     unit
+//!     "String"
+//!   }
+//! }
+//! ```
+//!
+//! Atoms are written as quoted strings. Composite nodes are written as a
+//! keyword followed by a block of children, and `named`, `seq`, `set`,
+//! `optional`, `stream` and `tagged` take their child on the same line. Tagged
+//! enums are written as `tagged internal "tag"`,
+//! `tagged adjacent "tag" "content"` or `tagged untagged`, followed by the
+//! enum. Sets and maps without a defined order are prefixed with `unordered`,
+//! e.g. `unordered set "u32"`.
+//! Enumerations are written as `enumeration` followed by a block of variant
+//! names with their discriminants, e.g. `"Red": 0`. The parser accepts
+//! arbitrary whitespace between tokens, so hand-written files don't need to be
+//! canonical.
+//!
+//! [`Schema`] and [`Named`] also implement [`FromStr`], which parses both the
+//! canonical text format and the compact single line form written by
+//! [`Display`](fmt::Display), e.g. `"Entry"=("id":"u32",)`, so that
+//! `schema.to_string().parse()` gives back the same schema.
+use std::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{Named, Schema, Tagging};
+
+/// The maximum nesting depth of a parsed schema.
+///
+/// Deeper input is rejected with a [`ParseError`] instead of overflowing the
+/// stack, so untrusted text can be parsed safely.
+pub const MAX_DEPTH: usize = 128;
+
+/// Error when parsing the canonical text format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line of the error.
+    pub line: usize,
+    /// 1-based column of the error, in characters.
+    pub column: usize,
+    /// Description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Schema {
+    /// Renders the schema in the canonical text format.
+    pub fn to_canonical_text(&self) -> String {
+        let mut res = String::new();
+        write_schema(&mut res, self, 0);
+        res.push('\n');
+        res
+    }
+
+    /// Parses a schema from the canonical text format.
+    pub fn from_canonical_text(text: &str) -> Result<Schema, ParseError> {
+        parse_all(text, Parser::schema)
+    }
+}
+
+/// Parses the [`Display`](fmt::Display) form of a schema, or the canonical
+/// text format.
+impl FromStr for Schema {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_all(text, Parser::schema)
+    }
+}
+
+/// Parses the [`Display`](fmt::Display) form `"name":schema` of a named
+/// schema. The schema can also be given in the canonical text format.
+impl FromStr for Named {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_all(text, Parser::named)
+    }
+}
+
+fn parse_all<'a, T>(
+    text: &'a str,
+    f: impl FnOnce(&mut Parser<'a>) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let mut parser = Parser::new(text);
+    let res = f(&mut parser)?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("expected end of input"));
+    }
+    Ok(res)
+}
+
+fn write_schema(out: &mut String, schema: &Schema, indent: usize) {
+    match schema {
+        Schema::Unit => out.push_str("unit"),
+        Schema::Bottom => out.push_str("bottom"),
+        Schema::Atom(name) => write_str(out, name),
+        Schema::Product(items) => {
+            out.push_str("product ");
+            write_block(out, items, indent, write_schema);
+        }
+        Schema::Sum(items) => {
+            out.push_str("sum ");
+            write_block(out, items, indent, write_schema);
+        }
+        Schema::Struct(fields) => {
+            out.push_str("struct ");
+            write_block(out, fields, indent, write_named);
+        }
+        Schema::Enum(cases) => {
+            out.push_str("enum ");
+            write_block(out, cases, indent, write_named);
+        }
+        Schema::Named(named) => {
+            out.push_str("named ");
+            write_str(out, &named.0);
+            out.push(' ');
+            write_schema(out, &named.1, indent);
+        }
+        Schema::Seq(item) => {
+            out.push_str("seq ");
+            write_schema(out, item, indent);
+        }
+        Schema::Set(item) | Schema::UnorderedSet(item) => {
+            if let Schema::UnorderedSet(_) = schema {
+                out.push_str("unordered ");
+            }
+            out.push_str("set ");
+            write_schema(out, item, indent);
+        }
+        Schema::Map(key, value) | Schema::UnorderedMap(key, value) => {
+            if let Schema::UnorderedMap(..) = schema {
+                out.push_str("unordered ");
+            }
+            out.push_str("map ");
+            write_block(out, [&**key, &**value], indent, write_schema);
+        }
+        Schema::Optional(item) => {
+            out.push_str("optional ");
+            write_schema(out, item, indent);
+        }
+        Schema::Tagged(tagging, item) => {
+            out.push_str("tagged ");
+            match tagging {
+                Tagging::Internal(tag) => {
+                    out.push_str("internal ");
+                    write_str(out, tag);
+                }
+                Tagging::Adjacent(tag, content) => {
+                    out.push_str("adjacent ");
+                    write_str(out, tag);
+                    out.push(' ');
+                    write_str(out, content);
+                }
+                Tagging::Untagged => out.push_str("untagged"),
+            }
+            out.push(' ');
+            write_schema(out, item, indent);
+        }
+        Schema::Enumeration(cases) => {
+            out.push_str("enumeration ");
+            write_block(out, cases, indent, write_case);
+        }
+        Schema::Stream(item) => {
+            out.push_str("stream ");
+            write_schema(out, item, indent);
+        }
+    }
+}
+
+fn write_case(out: &mut String, (name, tag): &(String, u32), _indent: usize) {
+    write_str(out, name);
+    write!(out, ": {}", tag).unwrap();
+}
+
+fn write_named(out: &mut String, named: &Named, indent: usize) {
+    write_str(out, &named.0);
+    out.push_str(": ");
+    write_schema(out, &named.1, indent);
+}
+
+fn write_block<I: IntoIterator>(
+    out: &mut String,
+    items: I,
+    indent: usize,
+    f: impl Fn(&mut String, I::Item, usize),
+) {
+    let mut items = items.into_iter().peekable();
+    if items.peek().is_none() {
+        out.push_str("{}");
+        return;
+    }
+    out.push_str("{\n");
+    for item in items {
+        push_indent(out, indent + 2);
+        f(out, item, indent + 2);
+        out.push('\n');
+    }
+    push_indent(out, indent);
+    out.push('}');
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    out.extend(std::iter::repeat_n(' ', indent));
+}
+
+fn write_str(out: &mut String, text: &str) {
+    write_quoted(out, text).unwrap();
+}
+
+/// Writes a string in quotes, escaping quotes, backslashes and control
+/// characters.
+pub(crate) fn write_quoted(f: &mut impl Write, text: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// An element of a list in the compact form.
+enum Item {
+    Schema(Schema),
+    /// A struct field or enum variant.
+    Field(Named),
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected '{}'", expected))),
+        }
+    }
+
+    /// Consumes `c` if it is the next non-whitespace character.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn keyword(&mut self) -> String {
+        let mut res = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+            res.push(c);
+            self.next();
+        }
+        res
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut res = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(res),
+                Some('\\') => match self.next() {
+                    Some('"') => res.push('"'),
+                    Some('\\') => res.push('\\'),
+                    Some('n') => res.push('\n'),
+                    Some('t') => res.push('\t'),
+                    Some('r') => res.push('\r'),
+                    Some('u') => res.push(self.unicode_escape()?),
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) => res.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, ParseError> {
+        if self.next() != Some('{') {
+            return Err(self.error("expected '{' in unicode escape"));
+        }
+        let mut digits = String::new();
+        loop {
+            match self.next() {
+                Some('}') => break,
+                Some(c) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                _ => return Err(self.error("invalid unicode escape")),
+            }
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    /// Parses the set or map after `unordered` or `~`.
+    fn unordered(&mut self) -> Result<Schema, ParseError> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        match self.schema()? {
+            Schema::Set(item) => Ok(Schema::UnorderedSet(item)),
+            Schema::Map(key, value) => Ok(Schema::UnorderedMap(key, value)),
+            _ => Err(ParseError {
+                line,
+                column,
+                message: "expected set or map".to_string(),
+            }),
+        }
+    }
+
+    fn block<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        self.expect('{')?;
+        let mut res = Vec::new();
+        while !self.eat('}') {
+            if self.peek().is_none() {
+                return Err(self.error("expected '}'"));
+            }
+            res.push(f(self)?);
+        }
+        Ok(res)
+    }
+
+    /// Parses the rest of a parenthesized list in the compact form, after the
+    /// opening parenthesis.
+    fn list(&mut self) -> Result<Schema, ParseError> {
+        let (line, column) = (self.line, self.column);
+        if self.eat(')') {
+            return Ok(Schema::Unit);
+        }
+        if self.eat(':') {
+            let separator = self.separator()?;
+            self.expect(')')?;
+            return Ok(if separator == ',' {
+                Schema::Struct(Vec::new())
+            } else {
+                Schema::Enum(Vec::new())
+            });
+        }
+        let mut items = Vec::new();
+        let mut separator = None;
+        let mut after_item = false;
+        while !self.eat(')') {
+            let empty = items.is_empty() && separator.is_none();
+            if after_item || (empty && matches!(self.peek(), Some(',' | '|'))) {
+                let c = self.separator()?;
+                if separator.is_some_and(|s| s != c) {
+                    return Err(self.error("can not mix ',' and '|' in one list"));
+                }
+                separator = Some(c);
+                after_item = false;
+            } else if self.peek().is_none() {
+                return Err(self.error("expected ')'"));
+            } else {
+                items.push(self.item()?);
+                after_item = true;
+            }
+        }
+        let Some(separator) = separator else {
+            return Err(ParseError {
+                line,
+                column,
+                message: "a single element must be followed by ',' or '|'".to_string(),
+            });
+        };
+        let mixed = || ParseError {
+            line,
+            column,
+            message: "can not mix fields and schemas in one list".to_string(),
+        };
+        if let Some(Item::Field(_)) = items.first() {
+            let fields = items
+                .into_iter()
+                .map(|item| match item {
+                    Item::Field(field) => Ok(field),
+                    Item::Schema(_) => Err(mixed()),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(match separator {
+                ',' => Schema::Struct(fields),
+                _ => Schema::Enum(fields),
+            })
+        } else {
+            let items = items
+                .into_iter()
+                .map(|item| match item {
+                    Item::Schema(schema) => Ok(schema),
+                    Item::Field(_) => Err(mixed()),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(match separator {
+                ',' => Schema::Product(items),
+                _ => Schema::Sum(items),
+            })
+        }
+    }
+
+    fn separator(&mut self) -> Result<char, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c @ (',' | '|')) => {
+                self.next();
+                Ok(c)
+            }
+            _ => Err(self.error("expected ',', '|' or ')'")),
+        }
+    }
+
+    /// An element of a list in the compact form.
+    fn item(&mut self) -> Result<Item, ParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some('"') {
+            return Ok(Item::Schema(self.schema()?));
+        }
+        let name = self.string()?;
+        Ok(if self.eat(':') {
+            Item::Field(Named(name, self.schema()?))
+        } else if self.eat('=') {
+            Item::Schema(Schema::named(name, self.schema()?))
+        } else {
+            Item::Schema(Schema::Atom(name))
+        })
+    }
+
+    /// A variant of an enumeration, `"name": discriminant`.
+    fn case(&mut self) -> Result<(String, u32), ParseError> {
+        let name = self.string()?;
+        self.expect(':')?;
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.next();
+        }
+        let tag = digits
+            .parse()
+            .map_err(|_| self.error("expected discriminant"))?;
+        Ok((name, tag))
+    }
+
+    /// Parses the rest of an enumeration in the compact form, after `#(`.
+    fn cases(&mut self) -> Result<Schema, ParseError> {
+        let mut cases = Vec::new();
+        if !self.eat(')') {
+            loop {
+                cases.push(self.case()?);
+                if self.eat(')') {
+                    break;
+                }
+                self.expect('|')?;
+            }
+        }
+        Ok(Schema::Enumeration(cases))
+    }
+
+    fn named(&mut self) -> Result<Named, ParseError> {
+        let name = self.string()?;
+        self.expect(':')?;
+        Ok(Named(name, self.schema()?))
+    }
+
+    fn schema(&mut self) -> Result<Schema, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(format!("schema nested deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let res = self.node();
+        self.depth -= 1;
+        res
+    }
+
+    fn node(&mut self) -> Result<Schema, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => {
+                let name = self.string()?;
+                if self.eat('=') {
+                    return Ok(Schema::named(name, self.schema()?));
+                }
+                return Ok(Schema::Atom(name));
+            }
+            Some('⊥' | '!') => {
+                self.next();
+                return Ok(Schema::Bottom);
+            }
+            Some('?') => {
+                self.next();
+                return Ok(Schema::Optional(Box::new(self.schema()?)));
+            }
+            Some('*') => {
+                self.next();
+                return Ok(Schema::Stream(Box::new(self.schema()?)));
+            }
+            Some('@') => {
+                self.next();
+                self.expect('(')?;
+                let tagging = if self.eat(')') {
+                    Tagging::Untagged
+                } else {
+                    let tag = self.string()?;
+                    let tagging = if self.eat(',') {
+                        Tagging::Adjacent(tag, self.string()?)
+                    } else {
+                        Tagging::Internal(tag)
+                    };
+                    self.expect(')')?;
+                    tagging
+                };
+                return Ok(Schema::Tagged(tagging, Box::new(self.schema()?)));
+            }
+            Some('~') => {
+                self.next();
+                return self.unordered();
+            }
+            Some('#') => {
+                self.next();
+                self.expect('(')?;
+                return self.cases();
+            }
+            Some('[') => {
+                self.next();
+                let item = self.schema()?;
+                self.expect(']')?;
+                return Ok(Schema::Seq(Box::new(item)));
+            }
+            Some('{') => {
+                self.next();
+                let key = self.schema()?;
+                if !self.eat(':') {
+                    self.expect('}')?;
+                    return Ok(Schema::Set(Box::new(key)));
+                }
+                let value = self.schema()?;
+                self.expect('}')?;
+                return Ok(Schema::Map(Box::new(key), Box::new(value)));
+            }
+            Some('(') => {
+                self.next();
+                return self.list();
+            }
+            _ => {}
+        }
+        let (line, column) = (self.line, self.column);
+        let keyword = self.keyword();
+        Ok(match keyword.as_str() {
+            "unit" => Schema::Unit,
+            "bottom" => Schema::Bottom,
+            "product" => Schema::Product(self.block(Self::schema)?),
+            "sum" => Schema::Sum(self.block(Self::schema)?),
+            "struct" => Schema::Struct(self.block(Self::named)?),
+            "enum" => Schema::Enum(self.block(Self::named)?),
+            "enumeration" => Schema::Enumeration(self.block(Self::case)?),
+            "named" => {
+                let name = self.string()?;
+                Schema::named(name, self.schema()?)
+            }
+            "seq" => Schema::Seq(Box::new(self.schema()?)),
+            "set" => Schema::Set(Box::new(self.schema()?)),
+            "optional" => Schema::Optional(Box::new(self.schema()?)),
+            "stream" => Schema::Stream(Box::new(self.schema()?)),
+            "unordered" => self.unordered()?,
+            "tagged" => {
+                self.skip_whitespace();
+                let tagging = match self.keyword().as_str() {
+                    "internal" => Tagging::Internal(self.string()?),
+                    "adjacent" => Tagging::Adjacent(self.string()?, self.string()?),
+                    "untagged" => Tagging::Untagged,
+                    _ => return Err(self.error("expected internal, adjacent or untagged")),
+                };
+                Schema::Tagged(tagging, Box::new(self.schema()?))
+            }
+            "map" => {
+                let mut parts = self.block(Self::schema)?;
+                if parts.len() != 2 {
+                    return Err(ParseError {
+                        line,
+                        column,
+                        message: "map requires exactly a key and a value".to_string(),
+                    });
+                }
+                let value = parts.pop().unwrap();
+                let key = parts.pop().unwrap();
+                Schema::Map(Box::new(key), Box::new(value))
+            }
+            "" => return Err(self.error("expected schema")),
+            other => {
+                return Err(ParseError {
+                    line,
+                    column,
+                    message: format!("unknown keyword '{}'", other),
+                })
+            }
+        })
+    }
+}
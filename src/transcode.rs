@@ -0,0 +1,84 @@
+//! Schema-checked transcoding between postcard and JSON.
+//!
+//! Transcoding goes through the dynamic [`Value`](crate::value::Value) layer,
+//! so the schema guarantees a faithful mapping between the two formats. This
+//! allows HTTP or debug frontends to speak JSON to services whose wire format
+//! is postcard. The JSON representation is described in [`crate::json`].
+use std::fmt;
+
+use crate::{
+    codec::{decode_postcard, encode_postcard, DecodeError, EncodeError},
+    json::{from_json, to_json, ValidationError},
+    Schema,
+};
+
+/// A serialization format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// The postcard encoding of the payload.
+    Postcard,
+    /// JSON, as produced by `serde_json` for the corresponding Rust types.
+    Json,
+}
+
+/// Error when transcoding between formats.
+#[derive(Debug)]
+pub enum TranscodeError {
+    /// The postcard input does not match the schema.
+    Decode(DecodeError),
+    /// The value can not be encoded as postcard.
+    Encode(EncodeError),
+    /// The JSON input is not valid JSON.
+    Syntax(serde_json::Error),
+    /// The JSON input does not match the schema, or the value can not be
+    /// represented as JSON.
+    Json(ValidationError),
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscodeError::Decode(e) => write!(f, "failed to decode postcard: {}", e),
+            TranscodeError::Encode(e) => write!(f, "failed to encode postcard: {}", e),
+            TranscodeError::Syntax(e) => write!(f, "invalid JSON: {}", e),
+            TranscodeError::Json(e) => write!(f, "JSON does not match the schema: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TranscodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TranscodeError::Decode(e) => Some(e),
+            TranscodeError::Encode(e) => Some(e),
+            TranscodeError::Syntax(e) => Some(e),
+            TranscodeError::Json(e) => Some(e),
+        }
+    }
+}
+
+/// Converts `bytes` of the given schema from one format to another.
+///
+/// JSON output is compact. Transcoding from a format to itself still checks
+/// the input against the schema.
+pub fn transcode(
+    schema: &Schema,
+    from: Format,
+    to: Format,
+    bytes: &[u8],
+) -> Result<Vec<u8>, TranscodeError> {
+    let value = match from {
+        Format::Postcard => decode_postcard(schema, bytes).map_err(TranscodeError::Decode)?,
+        Format::Json => {
+            let json = serde_json::from_slice(bytes).map_err(TranscodeError::Syntax)?;
+            from_json(schema, &json).map_err(TranscodeError::Json)?
+        }
+    };
+    match to {
+        Format::Postcard => encode_postcard(schema, &value).map_err(TranscodeError::Encode),
+        Format::Json => {
+            let json = to_json(schema, &value).map_err(TranscodeError::Json)?;
+            Ok(serde_json::to_vec(&json).expect("serializing JSON values can not fail"))
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Debug-build validation of irpc payloads.
+//!
+//! Serde attributes such as `skip_serializing_if`, `flatten` or `untagged` can
+//! make the encoding of a type differ from its schema, without any compile
+//! time error. [`validate`] wraps a request with its channels so that, in
+//! debug builds, the request and every item sent or received on its channels
+//! is encoded, decoded as a dynamic [`Value`](crate::value::Value) of the
+//! declared schema, and encoded again:
+//!
+//! ```ignore
+//! while let Some(msg) = rx.recv().await {
+//!     match msg {
+//!         Message::Get(msg) => {
+//!             let WithChannels { inner, tx, .. } = validate(msg);
+//!             tx.send(lookup(inner.key)).await.ok();
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A mismatch panics with a message describing where the encoding diverges
+//! from the schema. In release builds, [`validate`] returns the message
+//! unchanged, so it can stay in place.
+//!
+//! Schemas with opaque atoms can not be decoded dynamically and are not
+//! checked.
+use std::fmt;
+
+use irpc::{
+    channel::{
+        mpsc,
+        none::{NoReceiver, NoSender},
+        oneshot,
+    },
+    Channels, Service, WithChannels,
+};
+use serde::Serialize;
+
+use crate::{
+    codec::{decode_postcard, encode_postcard},
+    fuzz::is_transparent,
+    HasStaticSchema,
+};
+
+/// A value whose encoding does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The name of the type.
+    pub type_name: &'static str,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} does not match its schema: {}",
+            self.type_name, self.message
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks that the encoding of a value conforms to the schema of its type.
+///
+/// The value is encoded with postcard, decoded with the schema and encoded
+/// with the schema again, which must give the same bytes.
+pub fn check<T: HasStaticSchema + Serialize>(value: &T) -> Result<(), ValidationError> {
+    let error = |message: String| ValidationError {
+        type_name: std::any::type_name::<T>(),
+        message,
+    };
+    let bytes = postcard::to_allocvec(value).map_err(|e| error(e.to_string()))?;
+    let schema = T::schema_ref();
+    if !is_transparent(schema) {
+        return Ok(());
+    }
+    let decoded = decode_postcard(schema, &bytes).map_err(|e| error(e.to_string()))?;
+    let encoded = encode_postcard(schema, &decoded).map_err(|e| error(e.to_string()))?;
+    if encoded != bytes {
+        return Err(error(
+            "encoding with the schema differs from encoding with the type".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a value in debug builds, panicking if it does not match its schema.
+pub fn debug_check<T: HasStaticSchema + Serialize>(value: &T) {
+    if cfg!(debug_assertions) {
+        if let Err(e) = check(value) {
+            panic!("{}", e);
+        }
+    }
+}
+
+fn checked<T: HasStaticSchema + Serialize>(value: T) -> T {
+    debug_check(&value);
+    value
+}
+
+/// Channels whose items can be checked, see [`validate`].
+pub trait ValidateChannel {
+    /// Wraps the channel to check every item in debug builds.
+    fn validated(self) -> Self;
+}
+
+impl ValidateChannel for NoSender {
+    fn validated(self) -> Self {
+        self
+    }
+}
+
+impl ValidateChannel for NoReceiver {
+    fn validated(self) -> Self {
+        self
+    }
+}
+
+impl<T: HasStaticSchema + Serialize + Send + Sync + 'static> ValidateChannel
+    for oneshot::Sender<T>
+{
+    fn validated(self) -> Self {
+        self.with_map(checked)
+    }
+}
+
+impl<T: HasStaticSchema + Serialize + Send + Sync + 'static> ValidateChannel
+    for oneshot::Receiver<T>
+{
+    fn validated(self) -> Self {
+        oneshot::Receiver::from(move || async move { self.await.map(checked) })
+    }
+}
+
+impl<T: HasStaticSchema + Serialize + Send + Sync + 'static> ValidateChannel for mpsc::Sender<T> {
+    fn validated(self) -> Self {
+        self.with_map(checked)
+    }
+}
+
+impl<T: HasStaticSchema + Serialize + Send + Sync + 'static> ValidateChannel for mpsc::Receiver<T> {
+    fn validated(self) -> Self {
+        self.map(checked)
+    }
+}
+
+/// Checks a request and wraps its channels to check all items, in debug
+/// builds.
+///
+/// Returns the message unchanged in release builds.
+pub fn validate<I, S>(mut msg: WithChannels<I, S>) -> WithChannels<I, S>
+where
+    I: Channels<S> + HasStaticSchema + Serialize,
+    I::Tx: ValidateChannel,
+    I::Rx: ValidateChannel,
+    S: Service,
+{
+    if cfg!(debug_assertions) {
+        debug_check(&msg.inner);
+        msg.tx = msg.tx.validated();
+        msg.rx = msg.rx.validated();
+    }
+    msg
+}
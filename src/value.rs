@@ -0,0 +1,439 @@
+//! Dynamic values that mirror [`Schema`].
+//!
+//! A [`Value`] can hold data of any schema without the corresponding Rust type
+//! being compiled in, which is what generic tooling such as debuggers, proxies
+//! and test harnesses needs.
+//!
+//! Atoms for the std primitive types are mapped to the corresponding value
+//! kinds. Atoms with other names are opaque, so any value conforms to them.
+use std::fmt;
+
+use crate::{
+    diff::{Path, PathSegment},
+    Named, Schema,
+};
+
+/// A dynamically typed value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// The unit value, for [`Schema::Unit`].
+    Unit,
+    Bool(bool),
+    /// A signed integer.
+    Int(i128),
+    /// An unsigned integer.
+    UInt(u128),
+    Float(f64),
+    Char(char),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// The elements of a [`Schema::Product`].
+    Tuple(Vec<Value>),
+    /// The fields of a [`Schema::Struct`], in order.
+    Struct(Vec<(String, Value)>),
+    /// A case of a [`Schema::Sum`] or a variant of a [`Schema::Enum`].
+    ///
+    /// The name is optional, since sum cases don't have names.
+    Variant {
+        index: u32,
+        name: Option<String>,
+        value: Box<Value>,
+    },
+    /// A value of `Option<T>`.
+    Optional(Option<Box<Value>>),
+    Seq(Vec<Value>),
+    Set(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+/// A value does not conform to a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceError {
+    /// Where in the value the mismatch was found.
+    pub path: Path,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for ConformanceError {}
+
+/// The std types with a known encoding, identified by their atom name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Primitive {
+    Bool,
+    Char,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    Str,
+    Bytes,
+}
+
+impl Primitive {
+    pub(crate) fn from_atom(name: &str) -> Option<Self> {
+        Some(match name {
+            "bool" => Primitive::Bool,
+            "char" => Primitive::Char,
+            "u8" => Primitive::U8,
+            "u16" => Primitive::U16,
+            "u32" => Primitive::U32,
+            "u64" => Primitive::U64,
+            "u128" => Primitive::U128,
+            "i8" => Primitive::I8,
+            "i16" => Primitive::I16,
+            "i32" => Primitive::I32,
+            "i64" => Primitive::I64,
+            "i128" => Primitive::I128,
+            "f32" => Primitive::F32,
+            "f64" => Primitive::F64,
+            "String" | "&str" => Primitive::Str,
+            "&[u8]" | "bytes::Bytes" => Primitive::Bytes,
+            _ => return None,
+        })
+    }
+
+    /// The canonical atom name of this primitive.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Primitive::Bool => "bool",
+            Primitive::Char => "char",
+            Primitive::U8 => "u8",
+            Primitive::U16 => "u16",
+            Primitive::U32 => "u32",
+            Primitive::U64 => "u64",
+            Primitive::U128 => "u128",
+            Primitive::I8 => "i8",
+            Primitive::I16 => "i16",
+            Primitive::I32 => "i32",
+            Primitive::I64 => "i64",
+            Primitive::I128 => "i128",
+            Primitive::F32 => "f32",
+            Primitive::F64 => "f64",
+            Primitive::Str => "String",
+            Primitive::Bytes => "bytes",
+        }
+    }
+
+    /// The range of an integer type, as `(min, max)`.
+    pub(crate) fn int_range(self) -> Option<(i128, u128)> {
+        Some(match self {
+            Primitive::U8 => (0, u8::MAX as u128),
+            Primitive::U16 => (0, u16::MAX as u128),
+            Primitive::U32 => (0, u32::MAX as u128),
+            Primitive::U64 => (0, u64::MAX as u128),
+            Primitive::U128 => (0, u128::MAX),
+            Primitive::I8 => (i8::MIN as i128, i8::MAX as u128),
+            Primitive::I16 => (i16::MIN as i128, i16::MAX as u128),
+            Primitive::I32 => (i32::MIN as i128, i32::MAX as u128),
+            Primitive::I64 => (i64::MIN as i128, i64::MAX as u128),
+            Primitive::I128 => (i128::MIN, i128::MAX as u128),
+            _ => return None,
+        })
+    }
+
+    /// Checks that `value` is a valid value of this primitive.
+    pub(crate) fn check(self, value: &Value) -> Result<(), String> {
+        if let Some((min, max)) = self.int_range() {
+            let in_range = match value {
+                Value::Int(v) => *v >= min && (*v < 0 || *v as u128 <= max),
+                Value::UInt(v) => *v <= max,
+                _ => return Err(format!("expected integer, found {}", value.kind())),
+            };
+            return if in_range {
+                Ok(())
+            } else {
+                Err(format!("integer out of range for {}", self.name()))
+            };
+        }
+        let ok = matches!(
+            (self, value),
+            (Primitive::Bool, Value::Bool(_))
+                | (Primitive::Char, Value::Char(_))
+                | (Primitive::F32 | Primitive::F64, Value::Float(_))
+                | (Primitive::Str, Value::Str(_))
+                | (Primitive::Bytes, Value::Bytes(_))
+        );
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("expected {}, found {}", self.name(), value.kind()))
+        }
+    }
+}
+
+/// Returns the `T` of a schema of the shape of `Option<T>`, in either of its
+/// encodings.
+pub(crate) fn option_inner(schema: &Schema) -> Option<&Schema> {
+    match schema {
+        Schema::Sum(cases) if cases.len() == 2 && cases[0] == Schema::Unit => Some(&cases[1]),
+        Schema::Optional(item) => Some(item),
+        _ => None,
+    }
+}
+
+/// True for schemas whose only value carries no information, like unit
+/// variants.
+fn is_unit_like(schema: &Schema) -> bool {
+    match schema {
+        Schema::Unit => true,
+        Schema::Named(named) => is_unit_like(&named.1),
+        Schema::Product(items) => items.iter().all(is_unit_like),
+        Schema::Struct(fields) => fields.iter().all(|f| is_unit_like(&f.1)),
+        _ => false,
+    }
+}
+
+impl Value {
+    /// A short description of the kind of value, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Unit => "unit",
+            Value::Bool(_) => "bool",
+            Value::Int(_) | Value::UInt(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Char(_) => "char",
+            Value::Str(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::Tuple(_) => "tuple",
+            Value::Struct(_) => "struct",
+            Value::Variant { .. } => "variant",
+            Value::Optional(_) => "optional",
+            Value::Seq(_) => "sequence",
+            Value::Set(_) => "set",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// A canonical default value of the schema.
+    ///
+    /// Numbers are zero, strings and collections are empty and options are
+    /// `None`. For enums and sums, the first unit-like case is used, or the
+    /// first case with a default value if there is none.
+    ///
+    /// Returns `None` if the schema has no value with a known encoding, e.g.
+    /// because it contains the bottom type or an opaque atom.
+    pub fn default_for(schema: &Schema) -> Option<Value> {
+        Some(match schema {
+            Schema::Named(named) => return Value::default_for(&named.1),
+            Schema::Tagged(_, inner) => return Value::default_for(inner),
+            Schema::Unit => Value::Unit,
+            Schema::Bottom | Schema::Stream(_) => return None,
+            Schema::Atom(name) => match Primitive::from_atom(name)? {
+                Primitive::Bool => Value::Bool(false),
+                Primitive::Char => Value::Char('\0'),
+                Primitive::F32 | Primitive::F64 => Value::Float(0.0),
+                Primitive::Str => Value::Str(String::new()),
+                Primitive::Bytes => Value::Bytes(Vec::new()),
+                p if p.int_range().is_some_and(|(min, _)| min < 0) => Value::Int(0),
+                _ => Value::UInt(0),
+            },
+            Schema::Product(items) => Value::Tuple(
+                items
+                    .iter()
+                    .map(Value::default_for)
+                    .collect::<Option<_>>()?,
+            ),
+            Schema::Struct(fields) => Value::Struct(
+                fields
+                    .iter()
+                    .map(|Named(name, schema)| Some((name.clone(), Value::default_for(schema)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Schema::Sum(_) if option_inner(schema).is_some() => Value::Optional(None),
+            Schema::Sum(cases) => {
+                let (index, value) = default_case(cases.iter())?;
+                Value::Variant {
+                    index,
+                    name: None,
+                    value: Box::new(value),
+                }
+            }
+            Schema::Enum(cases) => {
+                let (index, value) = default_case(cases.iter().map(|case| &case.1))?;
+                Value::Variant {
+                    index,
+                    name: Some(cases[index as usize].0.clone()),
+                    value: Box::new(value),
+                }
+            }
+            Schema::Enumeration(cases) => {
+                let (name, tag) = cases.first()?;
+                Value::Variant {
+                    index: *tag,
+                    name: Some(name.clone()),
+                    value: Box::new(Value::Unit),
+                }
+            }
+            Schema::Seq(_) => Value::Seq(Vec::new()),
+            Schema::Set(_) | Schema::UnorderedSet(_) => Value::Set(Vec::new()),
+            Schema::Map(_, _) | Schema::UnorderedMap(_, _) => Value::Map(Vec::new()),
+            Schema::Optional(_) => Value::Optional(None),
+        })
+    }
+
+    /// True if this value is a valid value of `schema`.
+    pub fn conforms_to(&self, schema: &Schema) -> bool {
+        self.check_conforms(schema).is_ok()
+    }
+
+    /// Checks that this value is a valid value of `schema`, reporting the
+    /// location of the first mismatch.
+    pub fn check_conforms(&self, schema: &Schema) -> Result<(), ConformanceError> {
+        check(self, schema, &Path::default())
+    }
+}
+
+/// The index and default value of the first unit-like case, or of the first
+/// case with a default value.
+fn default_case<'a>(cases: impl Iterator<Item = &'a Schema> + Clone) -> Option<(u32, Value)> {
+    let mut cases = cases.enumerate();
+    let (index, case) = match cases.clone().find(|(_, case)| is_unit_like(case)) {
+        Some(found) => found,
+        None => cases.find(|(_, case)| Value::default_for(case).is_some())?,
+    };
+    Some((index as u32, Value::default_for(case)?))
+}
+
+fn check(value: &Value, schema: &Schema, path: &Path) -> Result<(), ConformanceError> {
+    let fail = |message: String| {
+        Err(ConformanceError {
+            path: path.clone(),
+            message,
+        })
+    };
+    let mismatch = |expected: &str| fail(format!("expected {}, found {}", expected, value.kind()));
+    match (schema, value) {
+        (Schema::Named(named), _) => check(
+            value,
+            &named.1,
+            &path.join(PathSegment::Named(named.0.clone())),
+        ),
+        (Schema::Tagged(_, inner), _) => check(value, inner, path),
+        (Schema::Bottom, _) => fail("no value conforms to the bottom type".to_string()),
+        (Schema::Stream(_), _) => fail("a stream has no value within a message".to_string()),
+        (Schema::Unit, Value::Unit) => Ok(()),
+        (Schema::Unit, _) => mismatch("unit"),
+        (Schema::Atom(name), _) => match Primitive::from_atom(name) {
+            Some(primitive) => primitive.check(value).or_else(fail),
+            None => Ok(()),
+        },
+        (Schema::Product(items), Value::Tuple(values)) => {
+            if items.len() != values.len() {
+                return fail(format!(
+                    "expected {} elements, found {}",
+                    items.len(),
+                    values.len()
+                ));
+            }
+            for (i, (item, value)) in items.iter().zip(values).enumerate() {
+                check(value, item, &path.join(PathSegment::Index(i)))?;
+            }
+            Ok(())
+        }
+        (Schema::Product(_), _) => mismatch("tuple"),
+        (Schema::Struct(fields), Value::Struct(values)) => {
+            if fields.len() != values.len() {
+                return fail(format!(
+                    "expected {} fields, found {}",
+                    fields.len(),
+                    values.len()
+                ));
+            }
+            for (Named(name, schema), (value_name, value)) in fields.iter().zip(values) {
+                if name != value_name {
+                    return fail(format!("expected field {}, found {}", name, value_name));
+                }
+                check(value, schema, &path.join(PathSegment::Field(name.clone())))?;
+            }
+            Ok(())
+        }
+        (Schema::Struct(_), _) => mismatch("struct"),
+        (Schema::Sum(_) | Schema::Optional(_), Value::Optional(value)) => {
+            let Some(inner) = option_inner(schema) else {
+                return mismatch("variant");
+            };
+            match value {
+                Some(value) => check(value, inner, &path.join(PathSegment::Index(1))),
+                None => Ok(()),
+            }
+        }
+        (Schema::Sum(cases), Value::Variant { index, value, .. }) => {
+            let Some(case) = cases.get(*index as usize) else {
+                return fail(format!("case index {} out of range", index));
+            };
+            check(value, case, &path.join(PathSegment::Index(*index as usize)))
+        }
+        (Schema::Sum(_), _) => mismatch("variant"),
+        (Schema::Enum(cases), Value::Variant { index, name, value }) => {
+            let Some(Named(case_name, case)) = cases.get(*index as usize) else {
+                return fail(format!("variant index {} out of range", index));
+            };
+            if let Some(name) = name.as_ref().filter(|name| *name != case_name) {
+                return fail(format!(
+                    "variant {} has index {}, found {}",
+                    case_name, index, name
+                ));
+            }
+            check(
+                value,
+                case,
+                &path.join(PathSegment::Variant(case_name.clone())),
+            )
+        }
+        (Schema::Enum(_), _) => mismatch("variant"),
+        (Schema::Enumeration(cases), Value::Variant { index, name, value }) => {
+            let Some((case_name, _)) = cases.iter().find(|case| case.1 == *index) else {
+                return fail(format!("unknown discriminant {}", index));
+            };
+            if let Some(name) = name.as_ref().filter(|name| *name != case_name) {
+                return fail(format!(
+                    "variant {} has discriminant {}, found {}",
+                    case_name, index, name
+                ));
+            }
+            check(
+                value,
+                &Schema::Unit,
+                &path.join(PathSegment::Variant(case_name.clone())),
+            )
+        }
+        (Schema::Enumeration(_), _) => mismatch("variant"),
+        (Schema::Seq(item), Value::Seq(values))
+        | (Schema::Set(item) | Schema::UnorderedSet(item), Value::Set(values)) => {
+            for (i, value) in values.iter().enumerate() {
+                check(value, item, &path.join(PathSegment::Index(i)))?;
+            }
+            Ok(())
+        }
+        (Schema::Seq(_), _) => mismatch("sequence"),
+        (Schema::Set(_) | Schema::UnorderedSet(_), _) => mismatch("set"),
+        (
+            Schema::Map(key, value_schema) | Schema::UnorderedMap(key, value_schema),
+            Value::Map(entries),
+        ) => {
+            for (i, (k, v)) in entries.iter().enumerate() {
+                let entry = path.join(PathSegment::Index(i));
+                check(k, key, &entry.join(PathSegment::Key))?;
+                check(v, value_schema, &entry.join(PathSegment::Value))?;
+            }
+            Ok(())
+        }
+        (Schema::Map(_, _) | Schema::UnorderedMap(_, _), _) => mismatch("map"),
+        (Schema::Optional(_), _) => mismatch("option"),
+    }
+}
@@ -0,0 +1,206 @@
+//! Test vectors for `serialize_stable` messages.
+//!
+//! A test vector is a sample value of a message together with its encoding on
+//! the wire, i.e. the 32 byte schema hash followed by the postcard encoded
+//! payload. Vectors are useful to pin wire bytes in golden tests, and for
+//! conformance suites of implementations in other languages.
+//!
+//! Sample values are the defaults given by [`Value::default_for`]. If the
+//! payload is an enum, there is one vector per variant.
+//!
+//! Plain types with a schema have test vectors as well, without the hash. See
+//! [`schema_roundtrip_tests`](crate::schema_roundtrip_tests) to turn vectors
+//! into round trip tests.
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{codec::encode_postcard, registry::SchemaRegistry, value::Value, HasSchema, Schema};
+
+/// An encoded sample of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestVector {
+    /// The message name, e.g. the variant name of the `serialize_stable` enum.
+    pub name: String,
+    /// The variant of the payload, if the payload is an enum.
+    pub variant: Option<String>,
+    /// The stable hash of the payload schema.
+    pub hash: [u8; 32],
+    /// The sample value.
+    pub value: Value,
+    /// The encoded message, including the hash for `serialize_stable`
+    /// messages.
+    pub bytes: Vec<u8>,
+}
+
+/// A test vector that does not round trip through a message type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorError {
+    pub name: String,
+    pub variant: Option<String>,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.variant {
+            Some(variant) => write!(f, "{}::{}: {}", self.name, variant, self.message),
+            None => write!(f, "{}: {}", self.name, self.message),
+        }
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+/// Sample values of a message payload, one per variant if it is an enum.
+fn samples(schema: &Schema) -> Vec<(Option<String>, Value)> {
+    match schema {
+        Schema::Named(named) => samples(&named.1),
+        Schema::Enum(cases) => cases
+            .iter()
+            .enumerate()
+            .filter_map(|(index, case)| {
+                let value = Value::Variant {
+                    index: index as u32,
+                    name: Some(case.0.clone()),
+                    value: Box::new(Value::default_for(&case.1)?),
+                };
+                Some((Some(case.0.clone()), value))
+            })
+            .collect(),
+        _ => Value::default_for(schema)
+            .map(|value| (None, value))
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Produces test vectors for all `(name, schema, hash)` triples, as returned
+/// by the generated `schemas()` function.
+///
+/// Messages or variants without a sample value with a known encoding, e.g.
+/// because they contain opaque atoms, are skipped.
+pub fn test_vectors<'a>(
+    schemas: impl IntoIterator<Item = (&'a str, &'a Schema, [u8; 32])>,
+) -> Vec<TestVector> {
+    let mut res = Vec::new();
+    for (name, schema, hash) in schemas {
+        push_vectors(&mut res, name, schema, hash, true);
+    }
+    res
+}
+
+fn push_vectors(
+    res: &mut Vec<TestVector>,
+    name: &str,
+    schema: &Schema,
+    hash: [u8; 32],
+    prefixed: bool,
+) {
+    for (variant, value) in samples(schema) {
+        let Ok(payload) = encode_postcard(schema, &value) else {
+            continue;
+        };
+        let mut bytes = if prefixed { hash.to_vec() } else { Vec::new() };
+        bytes.extend_from_slice(&payload);
+        res.push(TestVector {
+            name: name.to_string(),
+            variant,
+            hash,
+            value,
+            bytes,
+        });
+    }
+}
+
+/// Types that provide test vectors of their own encoding.
+///
+/// Implemented for all types with a schema, with the bytes being the plain
+/// postcard encoding, and by `serialize_stable` and `serialize_service` for
+/// message enums, with the bytes including the hash.
+pub trait HasTestVectors {
+    /// Returns the test vectors for this type.
+    fn test_vectors() -> Vec<TestVector>;
+}
+
+impl<T: HasSchema> HasTestVectors for T {
+    fn test_vectors() -> Vec<TestVector> {
+        let schema = T::schema();
+        let name = match &schema {
+            Schema::Named(named) => named.0.clone(),
+            _ => std::any::type_name::<T>().to_string(),
+        };
+        let hash = *schema.stable_hash().as_bytes();
+        let mut res = Vec::new();
+        push_vectors(&mut res, &name, &schema, hash, false);
+        res
+    }
+}
+
+/// Checks that all vectors decode as `T` and encode back to the same bytes.
+pub fn verify_test_vectors<T>(vectors: &[TestVector]) -> Result<(), VectorError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    for vector in vectors {
+        let error = |message: String| VectorError {
+            name: vector.name.clone(),
+            variant: vector.variant.clone(),
+            message,
+        };
+        let value: T = postcard::from_bytes(&vector.bytes)
+            .map_err(|e| error(format!("failed to decode: {}", e)))?;
+        let bytes =
+            postcard::to_allocvec(&value).map_err(|e| error(format!("failed to encode: {}", e)))?;
+        if bytes != vector.bytes {
+            return Err(error("encoding differs".to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl SchemaRegistry {
+    /// Produces test vectors for all registered schemas, see [`test_vectors`].
+    pub fn test_vectors(&self) -> Vec<TestVector> {
+        test_vectors(
+            self.iter()
+                .map(|entry| (entry.name.as_str(), &entry.schema, entry.hash)),
+        )
+    }
+}
+
+/// Generates round trip tests for types with test vectors.
+///
+/// For every type, a test named after the type checks that all its
+/// [test vectors](HasTestVectors) decode and encode back to the same bytes,
+/// including the hash prefix of `serialize_stable` messages. Types must
+/// implement `Serialize` and `DeserializeOwned`, and have at least one test
+/// vector.
+///
+/// The tests are placed in a module `schema_roundtrip_tests`, so the macro can
+/// be used once per module.
+///
+/// ```ignore
+/// schema_roundtrip_tests!(GetRequest, PutRequest, Proto);
+/// ```
+#[macro_export]
+macro_rules! schema_roundtrip_tests {
+    ($($t:ident),* $(,)?) => {
+        mod schema_roundtrip_tests {
+            use super::*;
+
+            $(
+                #[test]
+                #[allow(non_snake_case)]
+                fn $t() {
+                    let vectors = <$t as $crate::vectors::HasTestVectors>::test_vectors();
+                    assert!(!vectors.is_empty(), "no test vectors for {}", stringify!($t));
+                    if let Err(e) = $crate::vectors::verify_test_vectors::<$t>(&vectors) {
+                        panic!("{}", e);
+                    }
+                }
+            )*
+        }
+    };
+}
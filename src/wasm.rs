@@ -0,0 +1,61 @@
+//! Bindings for use from JavaScript, e.g. in a browser based protocol
+//! explorer.
+//!
+//! Schemas are passed in as text, in the [`Display`](std::fmt::Display) form
+//! or the [canonical text format](crate::text). Errors are reported as
+//! strings.
+//!
+//! The default features pull in irpc, which does not build for
+//! `wasm32-unknown-unknown`, so build with
+//! `--no-default-features --features wasm`.
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{diff, manifest::SchemaManifest, pretty::PrettyOptions, Schema};
+
+fn parse(schema: &str) -> Result<Schema, String> {
+    schema.parse().map_err(|e| format!("invalid schema: {}", e))
+}
+
+/// Decodes a postcard encoded [`SchemaManifest`].
+///
+/// The first line gives the name and version, followed by one line per
+/// message with its name, hash and schema.
+#[wasm_bindgen(js_name = parseManifest)]
+pub fn parse_manifest(bytes: &[u8]) -> Result<String, String> {
+    let manifest: SchemaManifest =
+        postcard::from_bytes(bytes).map_err(|e| format!("invalid manifest: {}", e))?;
+    let mut res = format!("{} {}\n", manifest.name, manifest.version);
+    for entry in &manifest.messages {
+        let hash = blake3::Hash::from(entry.hash);
+        res.push_str(&format!(
+            "{} {} {}\n",
+            entry.name,
+            hash.to_hex(),
+            entry.schema
+        ));
+    }
+    Ok(res)
+}
+
+/// The changes from `old` to `new`, one per line, see [`diff::diff`].
+#[wasm_bindgen]
+pub fn diff(old: &str, new: &str) -> Result<String, String> {
+    Ok(diff::diff(&parse(old)?, &parse(new)?).to_string())
+}
+
+/// Pretty prints a schema, writing nodes of up to `max_inline_width`
+/// characters on a single line.
+#[wasm_bindgen(js_name = prettyPrint)]
+pub fn pretty_print(schema: &str, max_inline_width: usize) -> Result<String, String> {
+    let options = PrettyOptions {
+        max_inline_width,
+        ..PrettyOptions::default()
+    };
+    Ok(parse(schema)?.pretty_print_with(0, &options))
+}
+
+/// The stable hash of a schema, as hex.
+#[wasm_bindgen]
+pub fn fingerprint(schema: &str) -> Result<String, String> {
+    Ok(parse(schema)?.stable_hash().to_hex().to_string())
+}
@@ -0,0 +1,28 @@
+//! Encoding of messages in the hash discriminated wire format.
+//!
+//! A message is the hash of its schema followed by the postcard encoded
+//! payload. Going through serde, postcard serializes the two as a tuple and
+//! grows its output buffer as it goes. [`to_vec`] instead measures the payload
+//! first and writes hash and payload into one buffer of the exact size, which
+//! the `to_postcard` method generated for message enums uses. [`from_bytes`]
+//! is its counterpart for the generated `from_postcard`.
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use postcard::Error;
+
+/// Encodes a message given by the hash on the wire and the payload.
+///
+/// The result is identical to serializing `(hash, payload)` with postcard, if
+/// `hash` has the length of a hash, i.e. 32 bytes or 8 for
+/// [short](crate::short) discriminators.
+pub fn to_vec<T: Serialize + ?Sized>(hash: &[u8], payload: &T) -> Result<Vec<u8>, Error> {
+    let len = postcard::experimental::serialized_size(payload)?;
+    let mut res = Vec::with_capacity(hash.len() + len);
+    res.extend_from_slice(hash);
+    postcard::to_extend(payload, res)
+}
+
+/// Decodes a message, like `postcard::from_bytes`.
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    postcard::from_bytes(bytes)
+}
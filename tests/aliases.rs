@@ -0,0 +1,51 @@
+use irpc_schema::{
+    aliases::AtomAliases,
+    diff::{diff, ChangeKind, Compat},
+    HasSchema, Named, Schema,
+};
+
+fn entry(id: &str) -> Schema {
+    Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", Schema::Atom(id.to_string())),
+            Named::new("name", String::schema()),
+        ]),
+    )
+}
+
+#[test]
+fn test_groups() {
+    let mut aliases = AtomAliases::new().with(["u64", "usize"]);
+    assert!(aliases.equivalent("usize", "u64"));
+    assert!(!aliases.equivalent("usize", "NonZeroU64"));
+    assert_eq!(aliases.canonical("usize"), "u64");
+    assert_eq!(aliases.canonical("u32"), "u32");
+    // groups sharing a name are merged, keeping the first canonical name
+    aliases.add(["NonZeroU64", "usize"]);
+    assert!(aliases.equivalent("NonZeroU64", "u64"));
+    assert_eq!(aliases.canonical("NonZeroU64"), "u64");
+    assert_eq!(aliases.canonical("usize"), "u64");
+}
+
+#[test]
+fn test_equal_and_diff() {
+    let aliases = AtomAliases::new().with(["u64", "usize"]);
+    let (old, new) = (entry("u64"), entry("usize"));
+    assert_ne!(old, new);
+    assert!(aliases.equal(&old, &new));
+    assert_eq!(aliases.normalize(&new), old);
+
+    let plain = diff(&old, &new);
+    assert!(matches!(
+        plain.changes[0].kind,
+        ChangeKind::AtomChanged { .. }
+    ));
+    assert_eq!(plain.compat(), Compat::Breaking);
+    assert!(aliases.diff(&old, &new).is_empty());
+    assert_eq!(aliases.diff(&old, &new).compat(), Compat::Compatible);
+
+    // other atoms are still compared by name
+    assert!(!aliases.equal(&old, &entry("i64")));
+    assert!(!aliases.diff(&old, &entry("i64")).is_empty());
+}
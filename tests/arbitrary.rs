@@ -0,0 +1,69 @@
+#![cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Unstructured};
+use irpc_schema::{arena::SchemaArena, diff::diff, Named, Schema};
+
+/// Deterministic pseudo random bytes, so the test is reproducible.
+fn bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_arbitrary_schemas() -> testresult::TestResult<()> {
+    for seed in 0..200 {
+        let data = bytes(seed, 512);
+        let mut u = Unstructured::new(&data);
+        let a = Schema::arbitrary(&mut u)?;
+        let b = Schema::arbitrary(&mut u)?;
+        let text = a.to_canonical_text();
+        assert_eq!(Schema::from_canonical_text(&text)?, a);
+        assert_eq!(a.to_string().parse::<Schema>()?, a);
+        assert!(diff(&a, &a).is_empty());
+        assert_eq!(diff(&a, &b).is_empty(), a == b);
+        assert_eq!(a.stable_hash() == b.stable_hash(), a == b);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_arbitrary_arena() -> testresult::TestResult<()> {
+    let mut arena = SchemaArena::new();
+    let mut ids = Vec::new();
+    for seed in 0..200 {
+        let data = bytes(seed, 512);
+        let schema = Schema::arbitrary(&mut Unstructured::new(&data))?;
+        ids.push((arena.insert(&schema), schema));
+    }
+    for (id, schema) in &ids {
+        assert_eq!(&arena.get(*id), schema);
+        assert_eq!(arena.stable_hash(*id), schema.stable_hash());
+        assert_eq!(
+            postcard::to_allocvec(&arena.serializable(*id))?,
+            postcard::to_allocvec(schema)?
+        );
+    }
+    for (a, x) in &ids {
+        for (b, y) in &ids {
+            assert_eq!(a == b, x == y);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_arbitrary_named_display() -> testresult::TestResult<()> {
+    for seed in 0..200 {
+        let data = bytes(seed, 512);
+        let mut u = Unstructured::new(&data);
+        let named = Named(String::arbitrary(&mut u)?, Schema::arbitrary(&mut u)?);
+        assert_eq!(named.to_string().parse::<Named>()?, named);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+
+use irpc_schema::{
+    arena::{Node, SchemaArena},
+    HasSchema, Named, Schema,
+};
+
+fn entry(name: &str) -> Schema {
+    Schema::named(
+        name,
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", <BTreeMap<String, Vec<u8>>>::schema()),
+            Named::new("kind", Schema::Sum(vec![Schema::Unit, Schema::Bottom])),
+        ]),
+    )
+}
+
+#[test]
+fn test_arena_roundtrip() {
+    let mut arena = SchemaArena::new();
+    let a = arena.insert(&entry("A"));
+    let len = arena.len();
+    // equal schemas get the same id, and shared subtrees are stored once
+    assert_eq!(arena.insert(&entry("A")), a);
+    assert_eq!(arena.len(), len);
+    let b = arena.insert(&entry("B"));
+    assert_ne!(a, b);
+    assert_eq!(arena.len(), len + 1);
+
+    for (id, schema) in [(a, entry("A")), (b, entry("B"))] {
+        assert_eq!(arena.get(id), schema);
+        assert_eq!(arena.find(&schema), Some(id));
+        assert_eq!(arena.stable_hash(id), schema.stable_hash());
+    }
+    assert_eq!(arena.find(&entry("C")), None);
+
+    let Node::Named(name, inner) = arena.node(a) else {
+        panic!("expected a named node");
+    };
+    assert_eq!(arena.name(name), "A");
+    let Node::Struct(span) = arena.node(inner) else {
+        panic!("expected a struct node");
+    };
+    assert_eq!(arena.fields(span).len(), 3);
+}
@@ -0,0 +1,60 @@
+use irpc_schema::{
+    atom::{AtomName, AtomOf},
+    schema, HasSchema, Named, Schema,
+};
+use serde::{Deserialize, Serialize};
+use testresult::TestResult;
+
+struct Uuid;
+
+impl AtomName for Uuid {
+    const NAME: &'static str = "Uuid";
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Inner {
+    a: u32,
+    b: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    id: AtomOf<[u8; 16], Uuid>,
+    name: String,
+}
+
+#[test]
+fn test_atom_schema() {
+    assert_eq!(
+        User::schema(),
+        Schema::named(
+            "User",
+            Schema::Struct(vec![
+                Named::new("id", Schema::Atom("Uuid".to_string())),
+                Named::new("name", String::schema()),
+            ])
+        )
+    );
+    // the schema of the wrapped type does not matter
+    assert_eq!(
+        <AtomOf<Vec<u8>, Uuid>>::schema(),
+        <AtomOf<Inner, Uuid>>::schema()
+    );
+}
+
+#[test]
+fn test_atom_serde() -> TestResult {
+    let inner = Inner {
+        a: 1,
+        b: "x".to_string(),
+    };
+    let bytes = postcard::to_allocvec(&inner)?;
+    let wrapped = AtomOf::<Inner, Uuid>::from(inner);
+    assert_eq!(postcard::to_allocvec(&wrapped)?, bytes);
+    let decoded: AtomOf<Inner, Uuid> = postcard::from_bytes(&bytes)?;
+    assert_eq!(decoded, wrapped);
+    assert_eq!(decoded.a, 1);
+    assert_eq!(decoded.into_inner().b, "x");
+    Ok(())
+}
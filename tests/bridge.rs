@@ -0,0 +1,112 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    bridge::{BridgeError, VersionBridge},
+    manifest::SchemaManifest,
+    SchemaError,
+};
+
+mod v1 {
+    use irpc_schema::{schema, serialize_stable};
+    use serde::{Deserialize, Serialize};
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct GetRequest {
+        pub key: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ClearRequest;
+
+    #[serialize_stable]
+    #[derive(Debug)]
+    pub enum Proto {
+        Get(GetRequest),
+        Put(PutRequest),
+        Clear(ClearRequest),
+    }
+}
+
+mod v2 {
+    use irpc_schema::{schema, serialize_stable};
+    use serde::{Deserialize, Serialize};
+
+    pub use super::v1::GetRequest;
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: Option<String>,
+        pub ttl: Option<u64>,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug)]
+    pub enum Proto {
+        Get(GetRequest),
+        Put(PutRequest),
+    }
+}
+
+fn bridge() -> VersionBridge {
+    VersionBridge::new(
+        SchemaManifest::from_schemas("kv", "1", v1::Proto::schemas()),
+        SchemaManifest::from_schemas("kv", "2", v2::Proto::schemas()),
+    )
+}
+
+#[test]
+fn test_bridge_migrates_old_messages() -> testresult::TestResult<()> {
+    let bridge = bridge();
+    let old = postcard::to_allocvec(&v1::Proto::Put(v1::PutRequest {
+        key: "k".into(),
+        value: "v".into(),
+    }))?;
+    let v2::Proto::Put(put) = bridge.decode::<v2::Proto>(&old)? else {
+        panic!("expected a put");
+    };
+    assert_eq!(
+        put,
+        v2::PutRequest {
+            key: "k".into(),
+            value: Some("v".into()),
+            ttl: None,
+        }
+    );
+    assert!(!bridge.diff(&old[..32].try_into()?).unwrap().is_empty());
+
+    // unchanged messages pass through
+    let get = postcard::to_allocvec(&v1::Proto::Get(v1::GetRequest { key: "k".into() }))?;
+    assert_eq!(&*bridge.translate(&get)?, &get[..]);
+    Ok(())
+}
+
+#[test]
+fn test_bridge_errors() -> testresult::TestResult<()> {
+    let bridge = bridge();
+    let clear = postcard::to_allocvec(&v1::Proto::Clear(v1::ClearRequest))?;
+    assert!(matches!(
+        bridge.translate(&clear),
+        Err(BridgeError::Removed { name }) if name == "Clear"
+    ));
+    let mut unknown = clear.clone();
+    unknown[0] ^= 1;
+    assert!(matches!(
+        bridge.translate(&unknown),
+        Err(BridgeError::Schema(SchemaError::UnknownHash { .. }))
+    ));
+    assert!(matches!(
+        bridge.translate(&clear[..3]),
+        Err(BridgeError::Schema(SchemaError::MissingHash))
+    ));
+    Ok(())
+}
@@ -0,0 +1,241 @@
+#![cfg(feature = "bundle")]
+use irpc_schema::{
+    bundle::{BundleBuilder, BundleError, BundleReader, CHUNK_LEN, MAX_DEPTH, MAX_NODES},
+    HasSchema, Schema,
+};
+use testresult::TestResult;
+
+#[test]
+fn test_bundle_roundtrip() -> TestResult<()> {
+    let schemas = [
+        ("a", <Vec<(u64, String)>>::schema()),
+        ("b", <Option<Vec<(u64, String)>>>::schema()),
+        (
+            "c",
+            Schema::named("Point", Schema::Product(vec![u32::schema(), u32::schema()])),
+        ),
+    ];
+    let mut builder = BundleBuilder::new();
+    for (name, schema) in &schemas {
+        builder.add(*name, schema)?;
+    }
+    let bytes = builder.finish()?;
+    let reader = BundleReader::new(&bytes)?;
+    assert_eq!(reader.len(), schemas.len());
+    for (name, schema) in &schemas {
+        let hash = *schema.stable_hash().as_bytes();
+        assert_eq!(reader.get(&hash)?.as_ref(), Some(schema));
+        assert_eq!(reader.name(&hash)?.as_deref(), Some(*name));
+    }
+    assert!(reader.get(&[0u8; 32])?.is_none());
+    let hashes = reader.hashes().collect::<Vec<_>>();
+    assert!(hashes.windows(2).all(|w| w[0] < w[1]));
+    Ok(())
+}
+
+#[test]
+fn test_bundle_dedup() -> TestResult<()> {
+    let inner = <Vec<(u64, String, Option<u32>)>>::schema();
+    let mut single = BundleBuilder::new();
+    single.add("x", &inner)?;
+    let single = single.finish()?;
+    let mut shared = BundleBuilder::new();
+    shared.add("x", &inner)?;
+    shared.add("y", &Schema::named("y", inner.clone()))?;
+    shared.add("z", &Schema::Seq(Box::new(inner.clone())))?;
+    let shared = shared.finish()?;
+    // each additional entry only adds an index entry and a single node
+    assert!(shared.len() < single.len() + 2 * 60);
+    Ok(())
+}
+
+#[test]
+fn test_bundle_errors() -> TestResult<()> {
+    assert!(matches!(
+        BundleReader::new(b"nope, not a bundle at all".as_slice()),
+        Err(BundleError::BadMagic)
+    ));
+    let mut builder = BundleBuilder::new();
+    builder.add("a", &u64::schema())?;
+    let bytes = builder.finish()?;
+    assert!(matches!(
+        BundleReader::new(&bytes[..bytes.len() - 1]),
+        Err(BundleError::Truncated)
+    ));
+    // a huge chunk length would make lookups allocate up to 4 GiB
+    let mut huge = bytes.clone();
+    huge[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        BundleReader::new(&huge),
+        Err(BundleError::Corrupt("chunk length too large"))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_bundle_compression() -> TestResult<()> {
+    let mut builder = BundleBuilder::new();
+    let mut names = 0;
+    for i in 0..100 {
+        let name = format!("com.example.inventory.warehouse.Message{}", i);
+        names += name.len();
+        builder.add(name.clone(), &Schema::named(name, u32::schema()))?;
+    }
+    let bytes = builder.finish()?;
+    // every name is stored twice, as entry name and nominal name, but only once
+    // in the bundle, and compressed on top of that
+    assert!(bytes.len() < 100 * 40 + names / 2);
+    let reader = BundleReader::new(&bytes)?;
+    for entry in reader.entries() {
+        let (name, schema, hash) = entry?;
+        assert_eq!(schema, Schema::named(name, u32::schema()));
+        assert_eq!(schema.stable_hash().as_bytes(), &hash);
+    }
+    Ok(())
+}
+
+/// Writes a bundle with a single entry for the last node, in chunks of 7 bytes.
+fn raw_bundle(hash: [u8; 32], nodes: &[Vec<u8>]) -> Vec<u8> {
+    use std::io::Write;
+    let mut data = b"x".to_vec();
+    for node in nodes {
+        data.extend_from_slice(node);
+    }
+    let chunks = data
+        .chunks(7)
+        .map(|chunk| {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(chunk).unwrap();
+            encoder.finish().unwrap()
+        })
+        .collect::<Vec<_>>();
+    let mut res = b"ISB\0".to_vec();
+    for value in [1, 1, 1, nodes.len() as u32, 7, chunks.len() as u32] {
+        res.extend_from_slice(&value.to_le_bytes());
+    }
+    res.extend_from_slice(&hash);
+    res.extend_from_slice(&0u32.to_le_bytes());
+    res.extend_from_slice(&(nodes.len() as u32 - 1).to_le_bytes());
+    let mut offsets = |lens: Vec<usize>| {
+        let mut offset = 0u32;
+        res.extend_from_slice(&offset.to_le_bytes());
+        for len in lens {
+            offset += len as u32;
+            res.extend_from_slice(&offset.to_le_bytes());
+        }
+    };
+    offsets(vec![1]);
+    offsets(nodes.iter().map(|n| n.len()).collect());
+    offsets(chunks.iter().map(|c| c.len()).collect());
+    res.extend(chunks.concat());
+    res
+}
+
+fn corrupt(bytes: &[u8], hash: &[u8; 32]) -> String {
+    match BundleReader::new(bytes).unwrap().get(hash) {
+        Err(BundleError::Corrupt(msg)) => msg.to_string(),
+        res => panic!("expected a corrupt bundle, got {:?}", res),
+    }
+}
+
+#[test]
+fn test_bundle_limits() {
+    let hash = [0u8; 32];
+    // node k is the product (k - 1, k - 1), which expands to 2^64 nodes
+    let mut nodes = vec![vec![0]];
+    for k in 1u8..64 {
+        nodes.push(vec![3, 2, k - 1, k - 1]);
+    }
+    assert_eq!(
+        corrupt(&raw_bundle(hash, &nodes), &hash),
+        "schema has too many nodes"
+    );
+    // node k is a sequence of node k - 1
+    let mut nodes = vec![vec![0]];
+    for k in 1u32..1000 {
+        let mut node = vec![8];
+        node.extend_from_slice(&postcard::to_allocvec(&(k - 1)).unwrap());
+        nodes.push(node);
+    }
+    assert_eq!(
+        corrupt(&raw_bundle(hash, &nodes), &hash),
+        "schema nested too deeply"
+    );
+    // a valid schema under the wrong hash
+    assert_eq!(
+        corrupt(&raw_bundle(hash, &[vec![0]]), &hash),
+        "schema does not match its hash"
+    );
+    let unit = *Schema::Unit.stable_hash().as_bytes();
+    let reader = BundleReader::new(raw_bundle(unit, &[vec![0]])).unwrap();
+    assert_eq!(reader.get(&unit).unwrap(), Some(Schema::Unit));
+}
+
+#[test]
+fn test_bundle_writer_limits() -> TestResult<()> {
+    let nested = |depth: usize| (1..depth).fold(Schema::Unit, |s, _| Schema::Seq(Box::new(s)));
+    let mut builder = BundleBuilder::new();
+    let hash = builder.add("deep", &nested(MAX_DEPTH))?;
+    assert!(matches!(
+        builder.add("deeper", &nested(MAX_DEPTH + 1)),
+        Err(BundleError::TooLarge("schema nested too deeply"))
+    ));
+    assert!(matches!(
+        builder.add("wide", &Schema::Product(vec![Schema::Unit; MAX_NODES])),
+        Err(BundleError::TooLarge("schema has too many nodes"))
+    ));
+    // rejected schemas leave nothing behind
+    assert_eq!(builder.len(), 1);
+    let reader = BundleReader::new(builder.finish()?)?;
+    assert_eq!(reader.get(&hash)?, Some(nested(MAX_DEPTH)));
+    Ok(())
+}
+
+#[test]
+fn test_bundle_chunks() -> TestResult<()> {
+    // the node data of the large products spans several chunks
+    let schemas = (1..300)
+        .map(|i| Schema::Product(vec![u32::schema(); i]))
+        .collect::<Vec<_>>();
+    let mut builder = BundleBuilder::new();
+    for (i, schema) in schemas.iter().enumerate() {
+        builder.add(format!("p{}", i), schema)?;
+    }
+    let mut bytes = builder.finish()?;
+    let read = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let chunk_count = read(24) as usize;
+    assert!(chunk_count >= 3);
+    assert_eq!(read(20) as usize, CHUNK_LEN);
+    // make the last chunk an invalid deflate block
+    let chunks_table = 28 + 40 * read(8) as usize + 4 * (read(12) + read(16)) as usize + 8;
+    let last_chunk =
+        chunks_table + 4 * (chunk_count + 1) + read(chunks_table + 4 * (chunk_count - 1)) as usize;
+    bytes[last_chunk] = 0xff;
+    let reader = BundleReader::new(&bytes)?;
+    // lookups that don't read the last chunk still work
+    let first = schemas[0].stable_hash();
+    assert_eq!(reader.get(first.as_bytes())?.as_ref(), Some(&schemas[0]));
+    assert_eq!(reader.name(first.as_bytes())?.as_deref(), Some("p0"));
+    let last = schemas[schemas.len() - 1].stable_hash();
+    assert!(matches!(
+        reader.get(last.as_bytes()),
+        Err(BundleError::Corrupt("invalid compressed data"))
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_bundle_mmap() -> TestResult<()> {
+    let mut builder = BundleBuilder::new();
+    let hash = builder.add("a", &u64::schema())?;
+    let path = std::env::temp_dir().join(format!("irpc-schema-{}.isb", std::process::id()));
+    std::fs::write(&path, builder.finish()?)?;
+    // SAFETY: the file is private to this test and not modified while mapped
+    let reader = unsafe { BundleReader::open_mmap(&path)? };
+    assert_eq!(reader.get(&hash)?, Some(u64::schema()));
+    drop(reader);
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    changelog::Changelog,
+    diff::Compat,
+    manifest::SchemaManifest,
+    policy::{EvolutionPolicy, Rule},
+    schema, serialize_stable,
+};
+use serde::{Deserialize, Serialize};
+
+mod v1 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct GetRequest {
+        pub key: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeleteRequest {
+        pub key: String,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug)]
+    pub enum Proto {
+        Get(GetRequest),
+        Put(PutRequest),
+        Delete(DeleteRequest),
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: Option<String>,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ListRequest {
+        pub prefix: String,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug)]
+    pub enum Proto {
+        Fetch(v1::GetRequest),
+        Put(PutRequest),
+        List(ListRequest),
+    }
+}
+
+#[test]
+fn test_changelog() {
+    let old = SchemaManifest::from_schemas("kv", "1.0", v1::Proto::schemas());
+    let new = SchemaManifest::from_schemas("kv", "2.0", v2::Proto::schemas());
+    let log = Changelog::new(&old, &new);
+    assert_eq!(log.added.len(), 1);
+    assert_eq!(log.added[0].name, "List");
+    assert_eq!(log.removed.len(), 1);
+    assert_eq!(log.removed[0].name, "Delete");
+    assert_eq!(log.renamed.len(), 1);
+    assert_eq!(log.renamed[0].new_name, "Fetch");
+    assert_eq!(log.changed.len(), 1);
+    assert_eq!(log.changed[0].diff.compat(), Compat::Migratable);
+    assert_eq!(log.compat(), Compat::Breaking);
+    let md = log.to_markdown();
+    println!("{}", md);
+    assert!(md.starts_with("# Changes from kv 1.0 to kv 2.0\n"));
+    assert!(md.contains("| `PutRequest.value` | became optional | migratable |"));
+    assert!(Changelog::new(&old, &old).is_empty());
+}
+
+#[test]
+fn test_changelog_policy() {
+    let old = SchemaManifest::from_schemas("kv", "1.0", v1::Proto::schemas());
+    let new = SchemaManifest::from_schemas("kv", "2.0", v2::Proto::schemas());
+    let log = Changelog::new(&old, &new);
+    let violation = log.check(&EvolutionPolicy::new()).unwrap_err();
+    assert_eq!(violation.changes.len(), 1);
+    let policy = EvolutionPolicy::new().allow(Rule::MakeOptional);
+    assert!(log.check(&policy).is_ok());
+}
+
+#[test]
+fn test_manifest_hash() {
+    let a = SchemaManifest::from_schemas("kv", "1.0", v1::Proto::schemas());
+    let b = SchemaManifest::from_schemas("kv-renamed", "1.1", v1::Proto::schemas());
+    let c = SchemaManifest::from_schemas("kv", "2.0", v2::Proto::schemas());
+    assert_eq!(a.hash(), b.hash());
+    assert_ne!(a.hash(), c.hash());
+    assert_eq!(a.to_registry().len(), 3);
+}
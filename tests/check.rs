@@ -0,0 +1,144 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    check::{is_valid_name, sanitize_name, InvalidSchema, Problem},
+    diff::{Path, PathSegment},
+    schema, HasSchema, Named, Schema, SchemaError,
+};
+
+#[schema(Nominal)]
+enum Request {
+    Put { key: String, value: Vec<u8> },
+    Delete(String),
+}
+
+#[test]
+fn test_checked_constructors() {
+    let fields = vec![
+        Named::new("a", u32::schema()),
+        Named::new("b", u32::schema()),
+    ];
+    assert_eq!(
+        Schema::struct_checked(fields.clone()).unwrap(),
+        Schema::Struct(fields.clone())
+    );
+    assert_eq!(
+        Schema::enum_checked(fields.clone()).unwrap(),
+        Schema::Enum(fields)
+    );
+    let duplicate = vec![
+        Named::new("a", u32::schema()),
+        Named::new("a", String::schema()),
+    ];
+    let Err(SchemaError::Invalid(err)) = Schema::struct_checked(duplicate.clone()) else {
+        panic!("expected an invalid schema");
+    };
+    assert_eq!(
+        err,
+        InvalidSchema {
+            path: Path::default(),
+            problem: Problem::DuplicateField("a".to_string()),
+        }
+    );
+    let Err(SchemaError::Invalid(err)) = Schema::enum_checked(duplicate) else {
+        panic!("expected an invalid schema");
+    };
+    assert_eq!(err.problem, Problem::DuplicateVariant("a".to_string()));
+}
+
+#[test]
+fn test_validate() {
+    assert!(Request::schema().validate().is_ok());
+    let schema = Schema::named(
+        "Outer",
+        Schema::Struct(vec![Named::new(
+            "inner",
+            Schema::Seq(Box::new(Schema::Enum(vec![
+                Named::new("A", Schema::Unit),
+                Named::new("A", Schema::Unit),
+            ]))),
+        )]),
+    );
+    let Err(SchemaError::Invalid(err)) = schema.validate() else {
+        panic!("expected an invalid schema");
+    };
+    assert_eq!(
+        err.path,
+        Path(vec![
+            PathSegment::Named("Outer".to_string()),
+            PathSegment::Field("inner".to_string()),
+            PathSegment::Item,
+        ])
+    );
+    assert_eq!(err.to_string(), "Outer.inner.[]: duplicate variant A");
+    assert_eq!(
+        SchemaError::from(err).to_string(),
+        "invalid schema: Outer.inner.[]: duplicate variant A"
+    );
+}
+
+#[test]
+fn test_names() {
+    for name in ["u8", "&[u8]", "[u8; 16]", "acme::users::User@v1", "Größe"] {
+        assert!(is_valid_name(name), "{:?}", name);
+    }
+    for name in ["", " ", " u8", "u8\n", "a\tb", "a\u{0}b", "a\"b", "a\\b"] {
+        assert!(!is_valid_name(name), "{:?}", name);
+    }
+    assert_eq!(
+        sanitize_name("  my\n\ttype\u{7}\"x\" ").as_deref(),
+        Some("my type x")
+    );
+    assert_eq!(sanitize_name("u8").as_deref(), Some("u8"));
+    assert_eq!(sanitize_name(" \"\n"), None);
+}
+
+#[test]
+fn test_checked_names() {
+    assert_eq!(
+        Schema::atom_checked("u8").unwrap(),
+        Schema::Atom("u8".to_string())
+    );
+    assert_eq!(
+        Schema::named_checked("Foo", Schema::Unit).unwrap(),
+        Schema::named("Foo", Schema::Unit)
+    );
+    let Err(SchemaError::Invalid(e)) = Schema::atom_checked("") else {
+        panic!("expected an invalid schema error");
+    };
+    assert_eq!(e.problem, Problem::InvalidName(String::new()));
+    assert!(Schema::named_checked("Foo\n", Schema::Unit).is_err());
+
+    let schema = Schema::named(
+        "Outer",
+        Schema::Struct(vec![Named::new("x", Schema::Atom("bad\"atom".to_string()))]),
+    );
+    let Err(SchemaError::Invalid(e)) = schema.validate() else {
+        panic!("expected an invalid schema error");
+    };
+    assert_eq!(
+        e,
+        InvalidSchema {
+            path: Path(vec![
+                PathSegment::Named("Outer".to_string()),
+                PathSegment::Field("x".to_string()),
+            ]),
+            problem: Problem::InvalidName("bad\"atom".to_string()),
+        }
+    );
+    assert_eq!(e.to_string(), "Outer.x: invalid name \"bad\\\"atom\"");
+}
+
+#[test]
+fn test_schema_error_conversions() {
+    fn parse_valid(text: &str) -> Result<Schema, SchemaError> {
+        let schema: Schema = text.parse()?;
+        schema.validate()?;
+        Ok(schema)
+    }
+    assert_eq!(parse_valid("\"u32\"").unwrap(), u32::schema());
+    assert!(matches!(parse_valid("{"), Err(SchemaError::Parse(_))));
+    assert!(matches!(
+        parse_valid("struct { \"a\": \"u32\" \"a\": \"u32\" }"),
+        Err(SchemaError::Invalid(_))
+    ));
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "irpc")]
+use irpc::{
+    channel::{mpsc, oneshot},
+    rpc_requests, Client,
+};
+use irpc_schema::{schema, serialize_service};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct List {
+    count: u32,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Sum;
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Log {
+    line: String,
+}
+
+#[rpc_requests(message = KvMessage, no_rpc, no_spans)]
+#[derive(Debug, Serialize, Deserialize)]
+enum KvProtocol {
+    #[rpc(tx = oneshot::Sender<Option<String>>)]
+    Get(Get),
+    #[rpc(tx = mpsc::Sender<u32>)]
+    List(List),
+    #[rpc(tx = oneshot::Sender<u64>, rx = mpsc::Receiver<u32>)]
+    Sum(Sum),
+    #[rpc(tx = irpc::channel::none::NoSender)]
+    Log(Log),
+}
+
+#[serialize_service(KvProtocol, client)]
+#[derive(Debug)]
+enum Proto {
+    Get(Get),
+    List(List),
+    Sum(Sum),
+    Log(Log),
+}
+
+async fn serve(mut rx: tokio::sync::mpsc::Receiver<KvMessage>) {
+    while let Some(msg) = rx.recv().await {
+        match msg {
+            KvMessage::Get(msg) => {
+                msg.tx.send(Some(msg.inner.key.to_uppercase())).await.ok();
+            }
+            KvMessage::List(msg) => {
+                for i in 0..msg.inner.count {
+                    msg.tx.send(i).await.ok();
+                }
+            }
+            KvMessage::Sum(mut msg) => {
+                let mut sum = 0u64;
+                while let Ok(Some(x)) = msg.rx.recv().await {
+                    sum += x as u64;
+                }
+                msg.tx.send(sum).await.ok();
+            }
+            KvMessage::Log(_) => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_generated_client() -> testresult::TestResult<()> {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let server = tokio::spawn(serve(rx));
+    let client = KvProtocolClient::new(Client::local(tx)).with_local_capacity(4);
+
+    let value = client.get(Get { key: "a".into() }).await?;
+    assert_eq!(value, Some("A".to_string()));
+
+    let mut items = client.list(List { count: 3 }).await?;
+    let mut received = Vec::new();
+    while let Some(item) = items.recv().await? {
+        received.push(item);
+    }
+    assert_eq!(received, [0, 1, 2]);
+
+    let (updates, res) = client.sum(Sum).await?;
+    for x in [1, 2, 3] {
+        updates.send(x).await?;
+    }
+    drop(updates);
+    assert_eq!(res.await?, 6);
+
+    client.log(Log { line: "hi".into() }).await?;
+    assert_eq!(Proto::schemas().count(), 4);
+
+    drop(client);
+    server.await?;
+    Ok(())
+}
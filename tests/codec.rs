@@ -0,0 +1,141 @@
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{
+    codec::{decode_postcard, encode_postcard},
+    schema,
+    value::Value,
+    HasSchema,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+struct PutRequest {
+    key: String,
+    value: Option<Vec<u8>>,
+    ttl: i64,
+}
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Clear,
+}
+
+#[test]
+fn test_decode_postcard() -> testresult::TestResult<()> {
+    let request = Request::Put(PutRequest {
+        key: "a".to_string(),
+        value: Some(vec![1, 2]),
+        ttl: -300,
+    });
+    let bytes = postcard::to_allocvec(&request)?;
+    let value = decode_postcard(&Request::schema(), &bytes)?;
+    let expected = Value::Variant {
+        index: 0,
+        name: Some("Put".to_string()),
+        value: Box::new(Value::Tuple(vec![Value::Struct(vec![
+            ("key".to_string(), Value::Str("a".to_string())),
+            (
+                "value".to_string(),
+                Value::Optional(Some(Box::new(Value::Seq(vec![
+                    Value::UInt(1),
+                    Value::UInt(2),
+                ])))),
+            ),
+            ("ttl".to_string(), Value::Int(-300)),
+        ])])),
+    };
+    assert_eq!(value, expected);
+    assert!(value.conforms_to(&Request::schema()));
+
+    let map = BTreeMap::from([(1u64, 'x'), (300, 'ü')]);
+    let bytes = postcard::to_allocvec(&map)?;
+    let value = decode_postcard(&<BTreeMap<u64, char>>::schema(), &bytes)?;
+    assert_eq!(
+        value,
+        Value::Map(vec![
+            (Value::UInt(1), Value::Char('x')),
+            (Value::UInt(300), Value::Char('ü')),
+        ])
+    );
+    Ok(())
+}
+
+#[test]
+fn test_decode_errors() -> testresult::TestResult<()> {
+    let schema = PutRequest::schema();
+    let bytes = postcard::to_allocvec(&PutRequest {
+        key: "a".to_string(),
+        value: None,
+        ttl: 1,
+    })?;
+    let err = decode_postcard(&schema, &bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.path.to_string(), "PutRequest.ttl");
+    assert_eq!(err.offset, bytes.len() - 1);
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(decode_postcard(&schema, &trailing).is_err());
+    let err = decode_postcard(&Request::schema(), &[2]).unwrap_err();
+    assert_eq!(err.message, "unknown discriminant 2");
+    let err = decode_postcard(&u16::schema(), &[0xff, 0xff, 0x04]).unwrap_err();
+    assert_eq!(err.message, "varint out of range");
+    Ok(())
+}
+
+#[test]
+fn test_huge_lengths() -> testresult::TestResult<()> {
+    use irpc_schema::{codec::MAX_EMPTY_ITEMS, extract::extract, Schema};
+
+    // a length of 2^63 - 1 in 10 bytes
+    let huge = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0x00];
+    let units = Schema::Seq(Box::new(Schema::Unit));
+    let err = decode_postcard(&units, &huge[..9]).unwrap_err();
+    assert_eq!(err.message, "length 9223372036854775807 exceeds the input");
+    assert_eq!(err.offset, 0);
+    let empty = Schema::Map(Box::new(Schema::Product(vec![])), Box::new(Schema::Unit));
+    assert!(decode_postcard(&empty, &huge[..9]).is_err());
+    let err = decode_postcard(&<Vec<u32>>::schema(), &[100, 1, 2, 3]).unwrap_err();
+    assert_eq!(err.message, "length 100 exceeds the input");
+    // skipping over the items fails the same way
+    let schema = Schema::Product(vec![units.clone(), u8::schema()]);
+    assert!(extract(&schema, &huge, "1").is_err());
+    // zero sized items up to the limit are fine
+    let bytes = postcard::to_allocvec(&vec![(); MAX_EMPTY_ITEMS])?;
+    assert_eq!(
+        decode_postcard(&units, &bytes)?,
+        Value::Seq(vec![Value::Unit; MAX_EMPTY_ITEMS])
+    );
+    let bytes = postcard::to_allocvec(&(vec![(); MAX_EMPTY_ITEMS], 7u8))?;
+    assert_eq!(extract(&schema, &bytes, "1")?, Some(Value::UInt(7)));
+    let bytes = postcard::to_allocvec(&vec![(); MAX_EMPTY_ITEMS + 1])?;
+    assert!(decode_postcard(&units, &bytes).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_encode_postcard() -> testresult::TestResult<()> {
+    let request = Request::Put(PutRequest {
+        key: "key".to_string(),
+        value: None,
+        ttl: i64::MIN,
+    });
+    let bytes = postcard::to_allocvec(&request)?;
+    let value = decode_postcard(&Request::schema(), &bytes)?;
+    assert_eq!(encode_postcard(&Request::schema(), &value)?, bytes);
+
+    let value = Value::Tuple(vec![Value::Float(1.5), Value::Int(-1), Value::Int(7)]);
+    type T = (f32, i8, u128);
+    let expected: T = (1.5, -1, 7);
+    let bytes = encode_postcard(&T::schema(), &value)?;
+    assert_eq!(bytes, postcard::to_allocvec(&expected)?);
+    assert_eq!(postcard::from_bytes::<T>(&bytes)?, expected);
+
+    let err = encode_postcard(&PutRequest::schema(), &Value::Unit).unwrap_err();
+    assert_eq!(err.path.to_string(), "PutRequest");
+    let opaque = irpc_schema::Schema::Atom("Opaque".to_string());
+    assert!(encode_postcard(&opaque, &Value::Unit).is_err());
+    Ok(())
+}
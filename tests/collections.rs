@@ -0,0 +1,77 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use irpc_schema::{
+    codec::{decode_postcard, encode_postcard},
+    diff::{diff, ChangeKind, Compat},
+    HasSchema, Named, Schema,
+};
+
+fn u32_schema() -> Box<Schema> {
+    Box::new(u32::schema())
+}
+
+#[test]
+fn test_std_schemas() {
+    assert_eq!(<BTreeSet<u32>>::schema(), Schema::Set(u32_schema()));
+    assert_eq!(<HashSet<u32>>::schema(), Schema::UnorderedSet(u32_schema()));
+    assert_eq!(
+        <BTreeMap<u32, u32>>::schema(),
+        Schema::Map(u32_schema(), u32_schema())
+    );
+    assert_eq!(
+        <HashMap<u32, u32>>::schema(),
+        Schema::UnorderedMap(u32_schema(), u32_schema())
+    );
+}
+
+#[test]
+fn test_has_unordered() {
+    let entry = |schema| Schema::named("Entry", Schema::Struct(vec![Named::new("x", schema)]));
+    assert!(!entry(<BTreeMap<u32, Vec<u32>>>::schema()).has_unordered());
+    assert!(entry(<Vec<HashSet<u32>>>::schema()).has_unordered());
+    assert_eq!(
+        entry(<Vec<HashMap<u32, u32>>>::schema()).erase_unordered(),
+        entry(<Vec<BTreeMap<u32, u32>>>::schema())
+    );
+}
+
+#[test]
+fn test_text_round_trip() {
+    let schema = Schema::Product(vec![
+        <HashSet<u32>>::schema(),
+        <HashMap<String, BTreeSet<u8>>>::schema(),
+    ]);
+    let text = schema.to_string();
+    assert_eq!(text, r#"(~{"u32"},~{"String":{"u8"}})"#);
+    assert_eq!(text.parse::<Schema>().unwrap(), schema);
+    let canonical = schema.to_canonical_text();
+    assert!(canonical.contains("unordered set \"u32\""), "{}", canonical);
+    assert_eq!(canonical.parse::<Schema>().unwrap(), schema);
+    assert!("~[\"u32\"]".parse::<Schema>().is_err());
+}
+
+#[test]
+fn test_diff() {
+    let changes = diff(
+        &<BTreeMap<u32, u32>>::schema(),
+        &<HashMap<u32, u64>>::schema(),
+    )
+    .changes;
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].kind, ChangeKind::BecameUnordered);
+    assert_eq!(changes[0].compat(), Compat::Compatible);
+    assert!(matches!(changes[1].kind, ChangeKind::AtomChanged { .. }));
+    let changes = diff(&<HashSet<u32>>::schema(), &<BTreeSet<u32>>::schema()).changes;
+    assert_eq!(changes[0].kind, ChangeKind::BecameOrdered);
+    assert_eq!(changes[0].kind.to_string(), "became ordered");
+}
+
+#[test]
+fn test_codec() -> testresult::TestResult<()> {
+    let map = HashMap::from([(1u32, 2u32), (3, 4)]);
+    let bytes = postcard::to_allocvec(&map)?;
+    let schema = <HashMap<u32, u32>>::schema();
+    let value = decode_postcard(&schema, &bytes)?;
+    assert_eq!(encode_postcard(&schema, &value)?, bytes);
+    Ok(())
+}
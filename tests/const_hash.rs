@@ -0,0 +1,92 @@
+use irpc_schema::{
+    const_hash::{check, pin, ConstSchemaHash},
+    schema, serialize_stable, HasSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal(hash = "bca2e44f570a839bf86604fe8fc6fd1788787ba19395533b34eecfa80f2a68a3"))]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Put {
+    key: String,
+    value: String,
+}
+
+/// The pinned hash does not match the schema.
+#[schema(Nominal(hash = "0000000000000000000000000000000000000000000000000000000000000000"))]
+#[allow(dead_code)]
+struct Stale {
+    key: String,
+}
+
+#[serialize_stable]
+#[derive(Debug, PartialEq)]
+enum Proto {
+    Get(Get),
+    Put(Put),
+}
+
+// usable in const contexts
+const _: () = assert!(Get::SCHEMA_HASH[0] == 0xbc);
+
+fn kind(bytes: &[u8]) -> &'static str {
+    match bytes[..32].try_into().unwrap() {
+        Get::SCHEMA_HASH => "get",
+        _ => "other",
+    }
+}
+
+#[test]
+fn test_const_hash() -> testresult::TestResult<()> {
+    assert!(check::<Get>().is_ok());
+    assert_eq!(&Get::SCHEMA_HASH, Get::schema().stable_hash().as_bytes());
+    assert_eq!(Proto::schemas().next().unwrap().2, Get::SCHEMA_HASH);
+
+    let get = postcard::to_allocvec(&Proto::Get(Get { key: "a".into() }))?;
+    let put = postcard::to_allocvec(&Proto::Put(Put {
+        key: "a".into(),
+        value: "b".into(),
+    }))?;
+    assert_eq!(kind(&get), "get");
+    assert_eq!(kind(&put), "other");
+    Ok(())
+}
+
+/// A manual implementation with a wrong hash.
+struct Manual;
+
+impl HasSchema for Manual {
+    fn schema() -> irpc_schema::Schema {
+        u32::schema()
+    }
+}
+
+impl ConstSchemaHash for Manual {
+    const SCHEMA_HASH: [u8; 32] = [0; 32];
+}
+
+#[test]
+fn test_const_hash_mismatch() {
+    let e = check::<Manual>().unwrap_err();
+    assert_eq!(e.expected, [0; 32]);
+    assert_eq!(&e.actual, u32::schema().stable_hash().as_bytes());
+}
+
+#[test]
+#[should_panic(expected = "pin it with hash = ")]
+fn test_const_hash_stale() {
+    let _ = Stale::schema();
+}
+
+#[test]
+fn test_pin() {
+    assert_eq!(
+        pin(&Get::schema()),
+        "hash = \"bca2e44f570a839bf86604fe8fc6fd1788787ba19395533b34eecfa80f2a68a3\""
+    );
+}
@@ -0,0 +1,59 @@
+#![allow(dead_code)]
+use irpc_schema::{debug::explain, schema, HasSchema};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+struct PutRequest {
+    key: String,
+    value: Vec<u16>,
+}
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Clear,
+}
+
+#[test]
+fn test_explain() -> testresult::TestResult<()> {
+    let bytes = postcard::to_allocvec(&Request::Put(PutRequest {
+        key: "ab".to_string(),
+        value: vec![1, 300],
+    }))?;
+    let text = explain(&Request::schema(), &bytes);
+    let expected = r#"offset   len  node                        bytes
+     0     8  <root> (Request): enum      00 02 61 62 02 01 ac 02
+     0     1    discriminant = 0 (Put)    00
+     1     7    Put: tuple                02 61 62 02 01 ac 02
+     1     7      0 (PutRequest): struct  02 61 62 02 01 ac 02
+     1     3        key = "ab"            02 61 62
+     4     4        value: seq of 2       02 01 ac 02
+     5     1          [0] = 1             01
+     6     2          [1] = 300           ac 02
+"#;
+    assert_eq!(text, expected);
+    Ok(())
+}
+
+#[test]
+fn test_explain_errors() -> testresult::TestResult<()> {
+    let text = explain(&Request::schema(), &[5]);
+    assert!(text.contains("unknown discriminant 5"));
+    let bytes = postcard::to_allocvec(&Request::Put(PutRequest {
+        key: "abc".to_string(),
+        value: vec![],
+    }))?;
+    let text = explain(&Request::schema(), &bytes[..3]);
+    assert!(
+        text.contains("error at offset 2 (Request.Put.0.PutRequest.key): unexpected end of input")
+    );
+    let text = explain(&Request::schema(), &[1, 0]);
+    assert!(text.contains("1 trailing bytes at offset 1"));
+    // a huge length of zero sized items
+    let units = irpc_schema::Schema::Seq(Box::new(irpc_schema::Schema::Unit));
+    let text = explain(&units, &[0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert!(text.contains("length 4294967295 exceeds the input"));
+    Ok(())
+}
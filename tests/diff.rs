@@ -0,0 +1,113 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    assert_schemas_incompatible,
+    diff::{assert_incompatible, diff, ChangeKind, Compat},
+    schema, HasSchema,
+};
+
+mod v1 {
+    use super::*;
+
+    #[schema(Nominal)]
+    pub struct Request {
+        pub key: String,
+        pub value: String,
+        pub flags: u8,
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[schema(Nominal(name = "RequestV2"))]
+    pub struct Request {
+        pub key: String,
+        pub flags: u8,
+        pub value: Option<String>,
+        pub ttl: u64,
+    }
+}
+
+#[test]
+fn test_diff() {
+    let d = diff(&v1::Request::schema(), &v2::Request::schema());
+    let changes = d
+        .changes
+        .iter()
+        .map(|c| (c.path.to_string(), c.kind.clone()))
+        .collect::<Vec<_>>();
+    assert!(changes.contains(&(
+        "<root>".to_string(),
+        ChangeKind::Renamed {
+            old: "Request".to_string(),
+            new: "RequestV2".to_string()
+        }
+    )));
+    assert!(changes.contains(&("RequestV2.value".to_string(), ChangeKind::BecameOptional)));
+    assert!(changes.iter().any(|(_, c)| matches!(
+        c,
+        ChangeKind::FieldAdded { name, index: 3, .. } if name == "ttl"
+    )));
+    assert!(changes.iter().any(|(_, c)| matches!(
+        c,
+        ChangeKind::FieldMoved { name, old_index: 1, new_index: 2 } if name == "value"
+    )));
+    assert!(diff(&v1::Request::schema(), &v1::Request::schema()).is_empty());
+}
+
+#[test]
+fn test_diff_renames() {
+    let old = <Result<u32, String>>::schema();
+    let new = irpc_schema::Schema::Enum(vec![
+        irpc_schema::Named::new("Success", u32::schema()),
+        irpc_schema::Named::new("Err", String::schema()),
+    ]);
+    let d = diff(&old, &new);
+    assert_eq!(d.changes.len(), 1);
+    assert!(matches!(
+        &d.changes[0].kind,
+        ChangeKind::VariantRenamed { old, new, index: 0 } if old == "Ok" && new == "Success"
+    ));
+    assert_eq!(d.distance(), 1);
+}
+
+#[test]
+fn test_diff_compat() {
+    use irpc_schema::diff::Compat;
+    let d = diff(&v1::Request::schema(), &v2::Request::schema());
+    assert_eq!(d.compat(), Compat::Migratable);
+    assert_eq!(
+        diff(&u16::schema(), &u64::schema()).compat(),
+        Compat::Compatible
+    );
+    assert_eq!(
+        diff(&u64::schema(), &u16::schema()).compat(),
+        Compat::Breaking
+    );
+    assert_eq!(
+        diff(&<Vec<u8>>::schema(), &String::schema()).compat(),
+        Compat::Breaking
+    );
+}
+
+#[test]
+fn test_assert_schemas_incompatible() {
+    assert_schemas_incompatible!(v1::Request, v2::Request);
+    assert_schemas_incompatible!(v2::Request, v1::Request, Compat::Migratable);
+}
+
+#[test]
+#[should_panic(expected = "schemas are migratable, which is accepted by a migratable policy")]
+fn test_assert_schemas_incompatible_accepted() {
+    assert_schemas_incompatible!(v1::Request, v2::Request, Compat::Migratable);
+}
+
+#[test]
+#[should_panic(expected = "schemas have the same hash")]
+fn test_assert_schemas_incompatible_same_hash() {
+    assert_incompatible(
+        &v1::Request::schema(),
+        &v1::Request::schema(),
+        Compat::Compatible,
+    );
+}
@@ -0,0 +1,15 @@
+use irpc_schema::dispatch::DispatchTable;
+
+#[test]
+fn test_dispatch_table() {
+    let hashes = [[3u8; 32], [1u8; 32], [2u8; 32], [1u8; 32]];
+    let table = DispatchTable::new(hashes);
+    assert_eq!(table.len(), 4);
+    assert_eq!(table.get(&[3u8; 32]), Some(0));
+    assert_eq!(table.get(&[2u8; 32]), Some(2));
+    // duplicates resolve to the first variant
+    assert_eq!(table.get(&[1u8; 32]), Some(1));
+    assert!(!table.contains(&[0u8; 32]));
+    assert!(!table.contains(&[4u8; 32]));
+    assert!(DispatchTable::<[u8; 32]>::new([]).is_empty());
+}
@@ -0,0 +1,128 @@
+#![allow(deprecated, dead_code)]
+use irpc_schema::{
+    docs::{Deprecation, DocTable, DocTree},
+    schema, HasSchema,
+};
+
+/// An identifier.
+#[schema(Atom)]
+struct Id([u8; 16]);
+
+/// A user.
+///
+/// Users can log in.
+#[schema(Nominal)]
+struct User {
+    /// The id of the user.
+    id: Id,
+    #[deprecated(since = "0.2.0", note = "use `display_name`")]
+    name: String,
+    /// The name to show.
+    display_name: String,
+}
+
+/// A request.
+#[schema(Nominal(name = "Req"))]
+enum Request {
+    /// Adds users.
+    Add(Vec<User>),
+    /// Removes a user.
+    Remove {
+        /// The user to remove.
+        id: Id,
+        #[deprecated]
+        force: bool,
+    },
+    #[deprecated = "no longer supported"]
+    Clear,
+}
+
+#[schema(Structural)]
+struct Batch(Vec<Request>, u64);
+
+fn member<'a>(tree: &'a DocTree, name: &str) -> &'a DocTree {
+    &tree
+        .members
+        .iter()
+        .find(|member| member.name == name)
+        .unwrap()
+        .tree
+}
+
+fn doc<'a>(tree: &'a DocTree, name: &str) -> Option<&'a str> {
+    let member = tree.members.iter().find(|member| member.name == name);
+    member.unwrap().docs.doc.as_deref()
+}
+
+#[test]
+fn test_doc_table() {
+    let table = DocTable::of::<Batch>();
+    assert_eq!(table.names().collect::<Vec<_>>(), ["Id", "Req", "User"]);
+    let user = table.get("User").unwrap();
+    assert_eq!(
+        user.docs.doc.as_deref(),
+        Some("A user.\n\nUsers can log in.")
+    );
+    assert_eq!(
+        user.members["name"].docs.deprecated,
+        Some(Deprecation {
+            since: Some("0.2.0".to_string()),
+            note: Some("use `display_name`".to_string()),
+        })
+    );
+    let remove = &table.get("Req").unwrap().members["Remove"];
+    assert_eq!(
+        remove.members["force"].docs.deprecated,
+        Some(Deprecation::default())
+    );
+    assert_eq!(table.get("Id").unwrap().members.len(), 0);
+}
+
+#[test]
+fn test_doc_tree() {
+    let tree = Batch::schema().docs(&DocTable::of::<Batch>());
+    assert_eq!(tree.type_name, None);
+    assert!(tree.docs.is_empty());
+
+    // sequences are transparent
+    let request = member(&tree, "0");
+    assert_eq!(request.type_name.as_deref(), Some("Req"));
+    assert_eq!(request.docs.doc.as_deref(), Some("A request."));
+    assert_eq!(doc(request, "Add"), Some("Adds users."));
+    let clear = request.members.iter().find(|m| m.name == "Clear").unwrap();
+    assert_eq!(
+        clear.docs.deprecated.as_ref().unwrap().note.as_deref(),
+        Some("no longer supported")
+    );
+
+    let remove = member(request, "Remove");
+    assert_eq!(doc(remove, "id"), Some("The user to remove."));
+    assert_eq!(
+        member(remove, "id").docs.doc.as_deref(),
+        Some("An identifier.")
+    );
+
+    let user = member(member(request, "Add"), "0");
+    assert_eq!(user.type_name.as_deref(), Some("User"));
+    assert_eq!(
+        user.members
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>(),
+        ["id", "name", "display_name"]
+    );
+    assert_eq!(doc(user, "display_name"), Some("The name to show."));
+    assert_eq!(member(user, "name").type_name.as_deref(), Some("String"));
+
+    let count = member(&tree, "1");
+    assert_eq!(count.type_name.as_deref(), Some("u64"));
+    assert!(count.docs.is_empty());
+}
+
+#[test]
+fn test_doc_tree_without_table() {
+    let tree = User::schema().docs(&DocTable::new());
+    assert_eq!(tree.type_name.as_deref(), Some("User"));
+    assert!(tree.docs.is_empty());
+    assert!(tree.members.iter().all(|member| member.docs.is_empty()));
+}
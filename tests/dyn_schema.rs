@@ -0,0 +1,73 @@
+#![allow(dead_code)]
+use std::{rc::Rc, sync::Arc};
+
+use irpc_schema::{
+    diff::{diff, ChangeKind},
+    impl_has_schema_dyn, schema, HasSchema, Named, Schema,
+};
+
+mod plugins {
+    pub trait Plugin {}
+
+    pub trait Codec {}
+}
+
+#[schema(Nominal)]
+struct Echo {
+    text: String,
+}
+
+#[schema(Nominal)]
+struct Count(u64);
+
+impl_has_schema_dyn!(dyn plugins::Plugin as Enum { Echo: Echo, Count: Count });
+impl_has_schema_dyn!(dyn plugins::Codec + Send + Sync + 'static as Atom);
+
+#[schema(Nominal)]
+struct Request {
+    id: u64,
+    plugin: Box<dyn plugins::Plugin>,
+    codec: Arc<dyn plugins::Codec + Send + Sync>,
+}
+
+fn plugin() -> Schema {
+    Schema::named(
+        "Plugin",
+        Schema::Enum(vec![
+            Named::new("Echo", Echo::schema()),
+            Named::new("Count", Count::schema()),
+        ]),
+    )
+}
+
+#[test]
+fn test_dyn_schema() {
+    assert_eq!(<dyn plugins::Plugin>::schema(), plugin());
+    assert_eq!(<Box<dyn plugins::Plugin>>::schema(), plugin());
+    assert_eq!(<Rc<dyn plugins::Plugin>>::schema(), plugin());
+    assert_eq!(
+        Request::schema(),
+        Schema::named(
+            "Request",
+            Schema::Struct(vec![
+                Named::new("id", u64::schema()),
+                Named::new("plugin", plugin()),
+                Named::new("codec", Schema::Atom("Codec".to_string())),
+            ])
+        )
+    );
+}
+
+#[test]
+fn test_dyn_schema_new_implementation() {
+    let old = Schema::named(
+        "Plugin",
+        Schema::Enum(vec![Named::new("Echo", Echo::schema())]),
+    );
+    let changes = diff(&old, &plugin()).changes;
+    assert_eq!(changes.len(), 1);
+    assert!(matches!(
+        &changes[0].kind,
+        ChangeKind::VariantAdded { name, .. } if name == "Count"
+    ));
+}
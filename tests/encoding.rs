@@ -0,0 +1,32 @@
+use irpc_schema::{
+    encoding::{reference_corpus, verify_encoding_stability, EncodingChanged, REFERENCE_HASH},
+    hash_postcard, Schema,
+};
+
+#[test]
+fn test_encoding_stability() {
+    verify_encoding_stability().unwrap();
+}
+
+#[test]
+fn test_reference_corpus() {
+    let corpus = reference_corpus();
+    // every variant is covered
+    let indices = corpus
+        .iter()
+        .map(|schema| postcard::to_allocvec(schema).unwrap()[0])
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(indices.len(), 17);
+    // any change to a schema in the corpus changes the hash
+    let mut changed = corpus.clone();
+    changed[2] = Schema::Atom("u64".to_string());
+    let actual = *hash_postcard(&changed).as_bytes();
+    assert_ne!(actual, REFERENCE_HASH);
+    let err = EncodingChanged {
+        expected: REFERENCE_HASH,
+        actual,
+    };
+    assert!(err
+        .to_string()
+        .starts_with("schema encoding version 1 changed"));
+}
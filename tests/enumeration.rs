@@ -0,0 +1,173 @@
+use irpc_schema::{
+    check::Problem,
+    codec::{decode_postcard, encode_postcard},
+    diff::{diff, ChangeKind, Compat},
+    migrate::migrate_value,
+    schema,
+    value::Value,
+    HasSchema, Schema, SchemaError,
+};
+use serde::{Deserialize, Serialize};
+
+mod v1 {
+    use super::*;
+
+    #[schema(Nominal(enumeration))]
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum Status {
+        Ok,
+        NotFound,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum Mode {
+        Read,
+        Write,
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[schema(Nominal(enumeration))]
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum Status {
+        Ok,
+        NotFound,
+        Forbidden,
+    }
+
+    #[schema(Nominal(enumeration))]
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum Mode {
+        Read,
+        Write,
+    }
+}
+
+fn cases(cases: &[(&str, u32)]) -> Schema {
+    Schema::Enumeration(
+        cases
+            .iter()
+            .map(|(name, tag)| (name.to_string(), *tag))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_derive() {
+    assert_eq!(
+        v1::Status::schema(),
+        Schema::named("Status", cases(&[("Ok", 0), ("NotFound", 1)]))
+    );
+    assert_eq!(
+        v1::Status::schema().to_string(),
+        r#""Status"=#("Ok":0|"NotFound":1)"#
+    );
+}
+
+#[test]
+fn test_codec() {
+    let schema = v2::Status::schema();
+    let bytes = postcard::to_allocvec(&v2::Status::Forbidden).unwrap();
+    let value = decode_postcard(&schema, &bytes).unwrap();
+    assert_eq!(
+        value,
+        Value::Variant {
+            index: 2,
+            name: Some("Forbidden".to_string()),
+            value: Box::new(Value::Unit),
+        }
+    );
+    assert!(value.conforms_to(&schema));
+    assert_eq!(encode_postcard(&schema, &value).unwrap(), bytes);
+    // the encoding is the same as for an enum of unit variants
+    let bytes = postcard::to_allocvec(&v1::Mode::Write).unwrap();
+    assert_eq!(bytes, postcard::to_allocvec(&v2::Mode::Write).unwrap());
+    assert!(decode_postcard(&v2::Mode::schema(), &bytes).is_ok());
+    // unknown discriminants are rejected
+    assert!(decode_postcard(&schema, &[3]).is_err());
+}
+
+#[test]
+fn test_variant_added_at_end() {
+    let changes = diff(&v1::Status::schema(), &v2::Status::schema());
+    assert_eq!(changes.changes.len(), 1);
+    assert!(matches!(
+        &changes.changes[0].kind,
+        ChangeKind::VariantAdded { name, index: 2, .. } if name == "Forbidden"
+    ));
+    assert_eq!(changes.compat(), Compat::Compatible);
+}
+
+#[test]
+fn test_diff_by_discriminant() {
+    let old = cases(&[("A", 0), ("B", 1)]);
+    // a new variant in the middle of the list with an unused discriminant
+    let added = cases(&[("A", 0), ("C", 2), ("B", 1)]);
+    assert_eq!(diff(&old, &added).compat(), Compat::Compatible);
+    // reordering without changing discriminants is compatible
+    let reordered = cases(&[("B", 1), ("A", 0)]);
+    let changes = diff(&old, &reordered);
+    assert_eq!(changes.changes[0].kind, ChangeKind::VariantsReordered);
+    assert_eq!(changes.compat(), Compat::Compatible);
+    // a variant with the discriminant of a removed one is a rename
+    let renamed = cases(&[("A", 0), ("C", 1)]);
+    assert!(matches!(
+        diff(&old, &renamed).changes[0].kind,
+        ChangeKind::VariantRenamed { index: 1, .. }
+    ));
+    // changing a discriminant moves the variant
+    let moved = cases(&[("A", 0), ("B", 5)]);
+    assert_eq!(diff(&old, &moved).compat(), Compat::Migratable);
+    let removed = cases(&[("A", 0)]);
+    assert_eq!(diff(&old, &removed).compat(), Compat::Breaking);
+}
+
+#[test]
+fn test_became_enumeration() {
+    let changes = diff(&v1::Mode::schema(), &v2::Mode::schema());
+    assert_eq!(changes.changes.len(), 1);
+    assert_eq!(changes.changes[0].kind, ChangeKind::BecameEnumeration);
+    assert_eq!(changes.compat(), Compat::Compatible);
+    let changes = diff(&v2::Mode::schema(), &v1::Mode::schema());
+    assert_eq!(changes.changes[0].kind, ChangeKind::BecameEnum);
+    let value = Value::Variant {
+        index: 1,
+        name: Some("Write".to_string()),
+        value: Box::new(Value::Unit),
+    };
+    let migrated = migrate_value(
+        value.clone(),
+        &diff(&v1::Mode::schema(), &v2::Mode::schema()),
+    );
+    assert_eq!(migrated.unwrap(), value);
+}
+
+#[test]
+fn test_validate() {
+    assert!(v2::Status::schema().validate().is_ok());
+    let err =
+        Schema::enumeration_checked(vec![("A".to_string(), 0), ("B".to_string(), 0)]).unwrap_err();
+    let SchemaError::Invalid(err) = err else {
+        panic!("expected an invalid schema");
+    };
+    assert_eq!(err.problem, Problem::DuplicateDiscriminant(0));
+}
+
+#[test]
+fn test_text_roundtrip() {
+    let schema = v2::Status::schema();
+    let text = schema.to_canonical_text();
+    assert_eq!(
+        text,
+        "named \"Status\" enumeration {\n  \"Ok\": 0\n  \"NotFound\": 1\n  \"Forbidden\": 2\n}\n"
+    );
+    assert_eq!(Schema::from_canonical_text(&text).unwrap(), schema);
+    assert_eq!(schema.to_string().parse::<Schema>().unwrap(), schema);
+    assert_eq!(
+        "#()".parse::<Schema>().unwrap(),
+        Schema::Enumeration(vec![])
+    );
+}
@@ -0,0 +1,78 @@
+use irpc_schema::{
+    envelope::{self, Envelope, EnvelopeError},
+    schema, HasSchema, SchemaError,
+};
+use serde::{Deserialize, Serialize};
+use testresult::TestResult;
+
+#[schema(Nominal)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct UserCreated {
+    name: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct UserDeleted {
+    id: u64,
+}
+
+#[test]
+fn test_envelope_roundtrip() -> TestResult {
+    let event = Envelope::new(UserCreated {
+        name: "alice".to_string(),
+    });
+    let bytes = event.to_postcard()?;
+    assert_eq!(bytes, postcard::to_allocvec(&event)?);
+    assert_eq!(
+        Envelope::<UserCreated>::hash(),
+        *UserCreated::schema().stable_hash().as_bytes()
+    );
+
+    let (hash, payload) = envelope::split(&bytes).unwrap();
+    assert_eq!(hash, Envelope::<UserCreated>::hash());
+    assert_eq!(payload, postcard::to_allocvec(&event.0)?);
+
+    assert_eq!(Envelope::<UserCreated>::from_postcard(&bytes)?, event);
+    let decoded: Envelope<UserCreated> = postcard::from_bytes(&bytes)?;
+    assert_eq!(decoded.name, "alice");
+    Ok(())
+}
+
+#[test]
+fn test_envelope_heterogeneous() -> TestResult {
+    let log = [
+        Envelope::new(UserCreated {
+            name: "bob".to_string(),
+        })
+        .to_postcard()?,
+        Envelope::new(UserDeleted { id: 7 }).to_postcard()?,
+    ];
+    let deleted = log
+        .iter()
+        .filter(|bytes| Envelope::<UserDeleted>::matches(bytes))
+        .map(|bytes| Envelope::<UserDeleted>::from_postcard(bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(deleted, [Envelope::new(UserDeleted { id: 7 })]);
+    Ok(())
+}
+
+#[test]
+fn test_envelope_errors() -> TestResult {
+    let bytes = Envelope::new(UserDeleted { id: 7 }).to_postcard()?;
+    assert!(matches!(
+        Envelope::<UserCreated>::from_postcard(&bytes),
+        Err(EnvelopeError::HashMismatch { .. })
+    ));
+    assert!(postcard::from_bytes::<Envelope<UserCreated>>(&bytes).is_err());
+    assert!(matches!(
+        Envelope::<UserDeleted>::from_postcard(&bytes[..10]),
+        Err(EnvelopeError::Schema(SchemaError::MissingHash))
+    ));
+    assert!(matches!(
+        Envelope::<UserDeleted>::from_postcard(&bytes[..32]),
+        Err(EnvelopeError::Decode(_))
+    ));
+    assert_eq!(envelope::peek_hash(&bytes[..31]), None);
+    Ok(())
+}
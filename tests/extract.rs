@@ -0,0 +1,62 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    extract::{extract, ExtractError},
+    schema,
+    value::Value,
+    HasSchema,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+struct PutRequest {
+    payload: Vec<u8>,
+    tags: Vec<(u32, String)>,
+    key: String,
+}
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Clear,
+}
+
+#[test]
+fn test_extract() -> testresult::TestResult<()> {
+    let schema = Request::schema();
+    let bytes = postcard::to_allocvec(&Request::Put(PutRequest {
+        payload: vec![7; 100_000],
+        tags: vec![(1, "a".to_string()), (2, "b".to_string())],
+        key: "k".to_string(),
+    }))?;
+    let key = extract(&schema, &bytes, "Request.Put.0.PutRequest.key")?;
+    assert_eq!(key, Some(Value::Str("k".to_string())));
+    // names of named types can be omitted
+    let key = extract(&schema, &bytes, "Put.0.key")?;
+    assert_eq!(key, Some(Value::Str("k".to_string())));
+    let tag = extract(&schema, &bytes, "Put.0.tags.1.1")?;
+    assert_eq!(tag, Some(Value::Str("b".to_string())));
+    assert_eq!(extract(&schema, &bytes, "Put.0.tags.2")?, None);
+    // other variants are not present
+    assert_eq!(extract(&schema, &bytes, "Clear")?, None);
+    // bytes after the extracted node are not needed
+    let truncated = &bytes[..bytes.len() - 2];
+    let tag = extract(&schema, truncated, "Put.0.tags.0.0")?;
+    assert_eq!(tag, Some(Value::UInt(1)));
+    assert!(matches!(
+        extract(&schema, truncated, "Put.0.key"),
+        Err(ExtractError::Decode(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_extract_invalid_path() {
+    let schema = Request::schema();
+    let err = extract(&schema, &[], "Put.0.value").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid path Put.0.value: no field value at Request.Put.0.PutRequest"
+    );
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "ffi")]
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use irpc_schema::{ffi::*, manifest::SchemaManifest, Schema};
+
+fn parse(text: &str) -> *mut IrpcSchema {
+    let text = CString::new(text).unwrap();
+    unsafe { irpc_schema_parse(text.as_ptr()) }
+}
+
+#[test]
+fn test_hash_matches_stable_hash() {
+    let schema = Schema::Seq(Box::new(Schema::Atom("u8".into())));
+    let handle = parse(&schema.to_string());
+    assert!(!handle.is_null());
+    let mut out = [0u8; 32];
+    assert_eq!(unsafe { irpc_schema_hash(handle, out.as_mut_ptr()) }, 0);
+    assert_eq!(&out, schema.stable_hash().as_bytes());
+    unsafe { irpc_schema_free(handle) };
+    assert!(parse("[\"u8\"").is_null());
+}
+
+#[test]
+fn test_pretty_print_truncates() {
+    let handle = parse("(\"a\":\"u32\",\"b\":\"u8\",)");
+    let full = "(\"a\": \"u32\", \"b\": \"u8\")";
+    let len = unsafe { irpc_schema_pretty_print(handle, 60, ptr::null_mut(), 0) };
+    assert_eq!(len, full.len() as isize);
+    let mut buf = [1 as c_char; 8];
+    let len = unsafe { irpc_schema_pretty_print(handle, 60, buf.as_mut_ptr(), buf.len()) };
+    assert_eq!(len, full.len() as isize);
+    let text = unsafe { CStr::from_ptr(buf.as_ptr()) };
+    assert_eq!(text.to_str().unwrap(), &full[..7]);
+    unsafe { irpc_schema_free(handle) };
+}
+
+#[test]
+fn test_diff_and_compat() {
+    let old = parse("\"u32\"");
+    let new = parse("\"u64\"");
+    let bytes = "(\"u8\",)";
+    let other = parse(bytes);
+    unsafe {
+        assert_eq!(irpc_schema_compat(old, old), 0);
+        assert_eq!(irpc_schema_compat(old, new), 0);
+        assert_eq!(irpc_schema_compat(old, other), 2);
+        assert_eq!(irpc_schema_compat(old, ptr::null()), -1);
+        assert_eq!(irpc_schema_diff(old, old, ptr::null_mut(), 0), 0);
+        assert!(irpc_schema_diff(old, new, ptr::null_mut(), 0) > 0);
+        irpc_schema_free(old);
+        irpc_schema_free(new);
+        irpc_schema_free(other);
+    }
+}
+
+#[test]
+fn test_manifest_lookup() {
+    let mut manifest = SchemaManifest::new("echo", "1.0");
+    let hash = manifest.push("Echo", Schema::Atom("String".into()));
+    let bytes = postcard::to_allocvec(&manifest).unwrap();
+    unsafe {
+        let handle = irpc_manifest_decode(bytes.as_ptr(), bytes.len());
+        assert!(!handle.is_null());
+        let mut out = [0u8; 32];
+        assert_eq!(irpc_manifest_hash(handle, out.as_mut_ptr()), 0);
+        assert_eq!(out, manifest.hash());
+        let mut buf = [0 as c_char; 16];
+        let len = irpc_manifest_lookup(handle, hash.as_ptr(), buf.as_mut_ptr(), buf.len());
+        assert_eq!(len, 4);
+        assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "Echo");
+        assert_eq!(
+            irpc_manifest_lookup(handle, [0u8; 32].as_ptr(), ptr::null_mut(), 0),
+            -1
+        );
+        irpc_manifest_free(handle);
+        assert!(irpc_manifest_decode([0xffu8].as_ptr(), 1).is_null());
+    }
+}
+
+#[test]
+fn test_header_declares_all_functions() {
+    let header = include_str!("../include/irpc_schema.h");
+    let source = include_str!("../src/ffi.rs");
+    let functions = source
+        .split("pub unsafe extern \"C\" fn ")
+        .skip(1)
+        .map(|rest| &rest[..rest.find('(').unwrap()])
+        .collect::<Vec<_>>();
+    assert_eq!(functions.len(), 10);
+    for name in functions {
+        assert!(header.contains(&format!("{name}(")), "{name} missing");
+    }
+}
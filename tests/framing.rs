@@ -0,0 +1,101 @@
+use irpc_schema::{
+    framing::{self, FramingError, MAX_FRAME_LEN},
+    schema, serialize_stable, SchemaError,
+};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Put {
+    key: String,
+    value: Vec<u8>,
+}
+
+#[serialize_stable]
+#[derive(Debug, PartialEq)]
+enum Proto {
+    Get(Get),
+    Put(Put),
+}
+
+fn put(len: usize) -> Proto {
+    Proto::Put(Put {
+        key: "a".into(),
+        value: vec![7; len],
+    })
+}
+
+#[test]
+fn test_framing() -> testresult::TestResult<()> {
+    let get = Proto::Get(Get { key: "a".into() });
+    let message = postcard::to_allocvec(&get)?;
+    let frame = framing::encode(&get)?;
+    assert_eq!(frame[0] as usize, message.len());
+    assert_eq!(frame[1..], message[..]);
+    assert_eq!(
+        framing::encode_frame(message[..32].try_into()?, &message[32..]),
+        frame
+    );
+
+    // a stream of frames, with a multi byte length prefix
+    let mut stream = frame.clone();
+    stream.extend(framing::encode(&put(300))?);
+    let (decoded, len) = framing::decode::<Proto>(&stream, MAX_FRAME_LEN)?.unwrap();
+    assert_eq!(decoded, get);
+    assert_eq!(len, frame.len());
+    let (frame2, len2) = framing::decode_frame(&stream[len..], MAX_FRAME_LEN)?.unwrap();
+    assert_eq!(len + len2, stream.len());
+    assert_eq!(
+        postcard::from_bytes::<Proto>(&frame2.to_message())?,
+        put(300)
+    );
+
+    // incomplete frames
+    for end in [0, 1, 2, 40, len2 - 1] {
+        assert!(framing::decode_frame(&stream[len..][..end], MAX_FRAME_LEN)?.is_none());
+    }
+    assert!(matches!(
+        framing::decode_frame(&stream[len..], 100),
+        Err(FramingError::TooLarge { max: 100, .. })
+    ));
+    assert!(matches!(
+        framing::decode_frame(&[3, 1, 2, 3], MAX_FRAME_LEN),
+        Err(FramingError::Schema(SchemaError::MissingHash))
+    ));
+    assert!(matches!(
+        framing::decode_frame(&[0xff; 11], MAX_FRAME_LEN),
+        Err(FramingError::InvalidLength)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_framing_io() -> testresult::TestResult<()> {
+    let mut stream = Vec::new();
+    framing::write_frame(&mut stream, &put(1))?;
+    framing::write_frame(&mut stream, &put(1000))?;
+    let mut reader = stream.as_slice();
+    assert_eq!(
+        framing::read::<_, Proto>(&mut reader, MAX_FRAME_LEN)?,
+        Some(put(1))
+    );
+    assert_eq!(
+        framing::read::<_, Proto>(&mut reader, MAX_FRAME_LEN)?,
+        Some(put(1000))
+    );
+    assert_eq!(framing::read::<_, Proto>(&mut reader, MAX_FRAME_LEN)?, None);
+
+    let mut truncated = &stream[..stream.len() - 1];
+    framing::read_frame(&mut truncated, MAX_FRAME_LEN)?;
+    assert!(matches!(
+        framing::read_frame(&mut truncated, MAX_FRAME_LEN),
+        Err(FramingError::UnexpectedEof)
+    ));
+    Ok(())
+}
@@ -0,0 +1,59 @@
+#![allow(dead_code)]
+use irpc_schema::{fuzz::fuzz_roundtrip, schema, HasSchema, Schema};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Serialize, Deserialize)]
+struct PutRequest {
+    key: String,
+    ttl: Option<u64>,
+    tags: Vec<(u8, bool)>,
+}
+
+#[schema(Nominal)]
+#[derive(Serialize, Deserialize)]
+enum Command {
+    Clear,
+    Delete(i32),
+    Put(PutRequest),
+}
+
+/// A type that claims to be a string, but is encoded as a byte.
+#[derive(Serialize, Deserialize)]
+struct Lying(u8);
+
+impl HasSchema for Lying {
+    fn schema() -> Schema {
+        String::schema()
+    }
+}
+
+/// Deterministic pseudo random bytes, so the test is reproducible.
+fn bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            // small bytes, so lengths and discriminants are often valid
+            (state >> 61) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_fuzz_roundtrip() {
+    for seed in 0..1000 {
+        let data = bytes(seed, 16);
+        fuzz_roundtrip::<PutRequest>(&data);
+        fuzz_roundtrip::<Command>(&data);
+        fuzz_roundtrip::<Vec<Option<u16>>>(&data);
+    }
+}
+
+#[test]
+#[should_panic(expected = "accepted bytes do not conform to the schema")]
+fn test_fuzz_roundtrip_mismatch() {
+    fuzz_roundtrip::<Lying>(&[3]);
+}
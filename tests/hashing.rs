@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashMap};
+
+use irpc_schema::{
+    hashing::{
+        all_hashes, hash, UnknownHashScheme, HASH_SCHEME_VERSION, LEGACY_HASH_SCHEME_VERSION,
+    },
+    HasSchema, Schema, SchemaAndHash, SchemaError,
+};
+
+#[test]
+fn test_hash_versions() {
+    let schema = Schema::Optional(Box::new(u32::schema()));
+    let current = hash(&schema, HASH_SCHEME_VERSION).unwrap();
+    let legacy = hash(&schema, LEGACY_HASH_SCHEME_VERSION).unwrap();
+    assert_eq!(current, schema.stable_hash());
+    assert_eq!(legacy, schema.legacy_hash());
+    assert_ne!(current, legacy);
+    assert_eq!(
+        all_hashes(&schema),
+        vec![
+            (LEGACY_HASH_SCHEME_VERSION, legacy),
+            (2, current),
+            (3, current),
+            (HASH_SCHEME_VERSION, current)
+        ]
+    );
+    // schemas without options, unordered collections or streams hash the
+    // same in all versions
+    let schema = String::schema();
+    assert_eq!(hash(&schema, 1).unwrap(), hash(&schema, 4).unwrap());
+}
+
+#[test]
+fn test_unordered_versions() {
+    let schema = <HashMap<String, u32>>::schema();
+    let ordered = <BTreeMap<String, u32>>::schema();
+    assert_eq!(hash(&schema, 4).unwrap(), schema.stable_hash());
+    assert_eq!(hash(&schema, 3).unwrap(), schema.stable_hash());
+    assert_ne!(schema.stable_hash(), ordered.stable_hash());
+    assert_eq!(hash(&schema, 2).unwrap(), ordered.stable_hash());
+    assert_eq!(hash(&schema, 1).unwrap(), ordered.legacy_hash());
+}
+
+#[test]
+fn test_rehash() {
+    let current = SchemaAndHash::from(Schema::Optional(Box::new(u32::schema())));
+    let legacy = current.rehash(LEGACY_HASH_SCHEME_VERSION).unwrap();
+    assert_eq!(legacy.schema, current.schema);
+    assert_eq!(legacy.hash, *current.schema.legacy_hash().as_bytes());
+    assert_eq!(legacy.rehash(HASH_SCHEME_VERSION).unwrap(), current);
+
+    let err = current.rehash(5).unwrap_err();
+    assert_eq!(err, UnknownHashScheme(5));
+    assert_eq!(
+        SchemaError::from(err).to_string(),
+        "unknown hash scheme version 5, supported are 1 to 4"
+    );
+}
+
+#[cfg(feature = "irpc")]
+mod baseline {
+    use std::collections::{HashMap, HashSet};
+
+    use irpc::channel::{mpsc, none::NoReceiver, oneshot};
+    use irpc_schema::{hashing::hash_v1, schema, ChannelsSchema, HasSchema};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct KvService;
+
+    impl irpc::Service for KvService {
+        type Message = ();
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Get {
+        key: String,
+        tags: Option<HashSet<String>>,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Sync {
+        counts: HashMap<String, u64>,
+    }
+
+    impl irpc::Channels<KvService> for Get {
+        type Rx = NoReceiver;
+        type Tx = oneshot::Sender<Option<String>>;
+    }
+
+    impl irpc::Channels<KvService> for Sync {
+        type Rx = mpsc::Receiver<Shape>;
+        type Tx = mpsc::Sender<Event>;
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    enum Shape {
+        Point { x: u32, y: u32 },
+        Pair(u32, u32),
+        Circle(u32),
+        Empty,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    enum Single {
+        Only { a: u8, b: u8 },
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "type")]
+    enum Event {
+        Added { key: String },
+        Removed { key: String },
+    }
+
+    #[schema(Nominal(enumeration))]
+    #[derive(Debug, Serialize, Deserialize)]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    fn v1(schema: irpc_schema::Schema) -> String {
+        hash_v1(&schema).to_string()
+    }
+
+    /// The hashes of these schemas were computed by the crate before hash
+    /// schemes were versioned.
+    #[test]
+    fn test_baseline_hashes() {
+        assert_eq!(
+            v1(<Get as ChannelsSchema<KvService>>::schema()),
+            "fd1692c5067cd3621a8db5b1094a76c36db10b456f480ac541cb48ff79348f55"
+        );
+        assert_eq!(
+            v1(<Sync as ChannelsSchema<KvService>>::schema()),
+            "ac9b1cea396e19e567877f42bc04aae617712da0b5718917b15aa75bb3cf2a2e"
+        );
+        assert_eq!(
+            v1(Shape::schema()),
+            "f11cc76ab0df9e0facf45440ca2e24f6cb328ab248e1702e123b1d4f00f4a910"
+        );
+        assert_eq!(
+            v1(Single::schema()),
+            "56e5c72da700a3813489f71bc364807f645df271a2a1c21ddd5c74a049975127"
+        );
+        assert_eq!(
+            v1(Mode::schema()),
+            "0219df5374cb7d0254181769bde964405c661f639baeb18ef6c6b223231375c7"
+        );
+    }
+}
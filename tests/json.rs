@@ -0,0 +1,151 @@
+#![cfg(feature = "json")]
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{
+    json::{from_json, to_json, validate_json},
+    schema,
+    value::Value,
+    HasSchema,
+};
+use serde::Serialize;
+use serde_json::json;
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+struct PutRequest {
+    key: String,
+    value: Option<Vec<u8>>,
+    ttl: u32,
+}
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+struct Id(u64);
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+enum Request {
+    Put(PutRequest),
+    Get(Id),
+    Clear,
+}
+
+#[test]
+fn test_validate_serde_json_output() -> testresult::TestResult<()> {
+    let values = [
+        Request::Put(PutRequest {
+            key: "a".to_string(),
+            value: Some(vec![1, 2, 3]),
+            ttl: 5,
+        }),
+        Request::Get(Id(7)),
+        Request::Clear,
+    ];
+    for value in values {
+        let json = serde_json::to_value(&value)?;
+        assert_eq!(validate_json(&Request::schema(), &json), Ok(()));
+    }
+    let map = BTreeMap::from([(1u32, "a".to_string())]);
+    let json = serde_json::to_value(&map)?;
+    assert_eq!(
+        validate_json(&<BTreeMap<u32, String>>::schema(), &json),
+        Ok(())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_validate_errors() {
+    let json = json!({"Put": {"key": 1, "ttl": -1, "extra": true}});
+    let errors = validate_json(&Request::schema(), &json).unwrap_err();
+    let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "Request.Put.PutRequest.key: expected String, found number",
+            "Request.Put.PutRequest.ttl: integer out of range for u32",
+            "Request.Put.PutRequest: unknown field extra",
+        ]
+    );
+    assert!(validate_json(&Request::schema(), &json!("Put")).is_err());
+    assert!(validate_json(&Request::schema(), &json!({"Nope": null})).is_err());
+    assert!(validate_json(&<BTreeMap<u32, String>>::schema(), &json!({"x": "a"})).is_err());
+}
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Internal {
+    Circle { r: f64 },
+    Point,
+}
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+#[serde(tag = "t", content = "c")]
+enum Adjacent {
+    Circle { r: f64 },
+    Scaled(u32),
+    Point,
+}
+
+#[schema(Nominal)]
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Untagged {
+    Circle { r: f64 },
+    Point,
+}
+
+#[test]
+fn test_tagged_enums() -> testresult::TestResult<()> {
+    let documents = [
+        (
+            Internal::schema(),
+            serde_json::to_value(Internal::Circle { r: 1.5 })?,
+        ),
+        (Internal::schema(), serde_json::to_value(Internal::Point)?),
+        (
+            Adjacent::schema(),
+            serde_json::to_value(Adjacent::Circle { r: 1.5 })?,
+        ),
+        (
+            Adjacent::schema(),
+            serde_json::to_value(Adjacent::Scaled(2))?,
+        ),
+        (Adjacent::schema(), serde_json::to_value(Adjacent::Point)?),
+        (
+            Untagged::schema(),
+            serde_json::to_value(Untagged::Circle { r: 1.5 })?,
+        ),
+        (Untagged::schema(), serde_json::to_value(Untagged::Point)?),
+    ];
+    for (schema, json) in documents {
+        assert_eq!(validate_json(&schema, &json), Ok(()), "{}", json);
+        let value = from_json(&schema, &json)?;
+        assert_eq!(to_json(&schema, &value)?, json);
+    }
+    assert!(validate_json(&Internal::schema(), &json!({"Point": null})).is_err());
+    assert!(validate_json(&Internal::schema(), &json!({"type": "Nope"})).is_err());
+    assert!(validate_json(&Adjacent::schema(), &json!({"t": "Scaled", "c": "x"})).is_err());
+    assert!(validate_json(&Untagged::schema(), &json!("Point")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_from_json_integer_variant() -> testresult::TestResult<()> {
+    // signed types give Int and unsigned types UInt, whatever the sign of the number
+    assert_eq!(from_json(&i32::schema(), &json!(5))?, Value::Int(5));
+    assert_eq!(from_json(&i64::schema(), &json!(-5))?, Value::Int(-5));
+    assert_eq!(
+        from_json(&i128::schema(), &json!(u64::MAX))?,
+        Value::Int(u64::MAX as i128)
+    );
+    assert_eq!(from_json(&u8::schema(), &json!(5))?, Value::UInt(5));
+    assert_eq!(
+        from_json(&u64::schema(), &json!(u64::MAX))?,
+        Value::UInt(u64::MAX as u128)
+    );
+    Ok(())
+}
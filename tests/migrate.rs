@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    codec::{decode_postcard, encode_postcard},
+    diff::diff,
+    migrate::{decode_with_schema, migrate_value, UpgradeError},
+    schema, HasSchema,
+};
+use serde::{Deserialize, Serialize};
+use testresult::TestResult;
+
+mod v1 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Profile {
+        pub name: String,
+        pub tags: Vec<String>,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Event {
+        Updated(Profile),
+        Deleted,
+        Banned,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: String,
+        pub flags: (u8, u16),
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Request {
+        Put(PutRequest),
+        Clear,
+        Remove(String),
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Profile {
+        pub name: String,
+        pub email: Option<String>,
+        pub tags: Vec<String>,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Event {
+        Deleted,
+        Updated(Profile),
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub id: u64,
+        pub value: Option<String>,
+        pub key: String,
+        pub flags: (u8, u16, bool),
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Request {
+        Get(String),
+        Reset,
+        Put(PutRequest),
+    }
+}
+
+fn migrate(request: &v1::Request) -> anyhow::Result<v2::Request> {
+    let old = v1::Request::schema();
+    let new = v2::Request::schema();
+    let value = decode_postcard(&old, &postcard::to_allocvec(request)?)?;
+    let value = migrate_value(value, &diff(&old, &new))?;
+    Ok(postcard::from_bytes(&encode_postcard(&new, &value)?)?)
+}
+
+#[test]
+fn test_migrate_value() -> TestResult<()> {
+    let request = v1::Request::Put(v1::PutRequest {
+        key: "k".to_string(),
+        value: "v".to_string(),
+        flags: (3, 4),
+    });
+    assert_eq!(
+        migrate(&request)?,
+        v2::Request::Put(v2::PutRequest {
+            id: 0,
+            value: Some("v".to_string()),
+            key: "k".to_string(),
+            flags: (3, 4, false),
+        })
+    );
+    assert_eq!(migrate(&v1::Request::Clear)?, v2::Request::Reset);
+    Ok(())
+}
+
+#[test]
+fn test_migrate_breaking() {
+    let err = migrate(&v1::Request::Remove("k".to_string())).unwrap_err();
+    assert!(err.to_string().contains("variant Remove was removed"));
+}
+
+#[test]
+fn test_decode_with_schema() -> TestResult<()> {
+    let old = v1::Event::schema();
+    let event = v1::Event::Updated(v1::Profile {
+        name: "alice".to_string(),
+        tags: vec!["admin".to_string()],
+    });
+    let bytes = postcard::to_allocvec(&event)?;
+    // the new optional field becomes None, the variant is matched by name
+    let decoded: v2::Event = decode_with_schema(&bytes, &old)?;
+    assert_eq!(
+        decoded,
+        v2::Event::Updated(v2::Profile {
+            name: "alice".to_string(),
+            email: None,
+            tags: vec!["admin".to_string()],
+        })
+    );
+    let bytes = postcard::to_allocvec(&v1::Event::Deleted)?;
+    assert_eq!(
+        decode_with_schema::<v2::Event>(&bytes, &old)?,
+        v2::Event::Deleted
+    );
+    // identical schemas decode directly
+    let bytes = postcard::to_allocvec(&v2::Event::Deleted)?;
+    assert_eq!(
+        decode_with_schema::<v2::Event>(&bytes, &v2::Event::schema())?,
+        v2::Event::Deleted
+    );
+    Ok(())
+}
+
+#[test]
+fn test_decode_with_schema_errors() -> TestResult<()> {
+    let old = v1::Event::schema();
+    // a removed variant can not be upgraded
+    let bytes = postcard::to_allocvec(&v1::Event::Banned)?;
+    let err = decode_with_schema::<v2::Event>(&bytes, &old).unwrap_err();
+    assert!(matches!(err, UpgradeError::Migrate(_)));
+    assert!(err.to_string().contains("variant Banned was removed"));
+    // the payload must match the old schema
+    let err = decode_with_schema::<v2::Event>(&[7], &old).unwrap_err();
+    assert!(matches!(err, UpgradeError::Decode(_)));
+    Ok(())
+}
@@ -0,0 +1,139 @@
+#![cfg(feature = "irpc")]
+use irpc::channel::{
+    mpsc,
+    none::{NoReceiver, NoSender},
+    oneshot,
+};
+use irpc_schema::{
+    mock::{MockError, MockServer},
+    schema, serialize_service,
+    service::ServiceDescriptor,
+    value::Value,
+    SchemaError,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KvService;
+
+impl irpc::Service for KvService {
+    type Message = Proto;
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct List {
+    prefix: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Clear;
+
+impl irpc::Channels<KvService> for Get {
+    type Rx = NoReceiver;
+    type Tx = oneshot::Sender<Option<String>>;
+}
+
+impl irpc::Channels<KvService> for List {
+    type Rx = NoReceiver;
+    type Tx = mpsc::Sender<String>;
+}
+
+impl irpc::Channels<KvService> for Clear {
+    type Rx = NoReceiver;
+    type Tx = NoSender;
+}
+
+#[serialize_service(KvService)]
+#[derive(Debug)]
+enum Proto {
+    Get(Get),
+    List(List),
+    Clear(Clear),
+}
+
+fn mock() -> MockServer {
+    MockServer::new(ServiceDescriptor::from_schemas(
+        "KvService",
+        Proto::schemas(),
+    ))
+}
+
+fn get(key: &str) -> Proto {
+    Proto::Get(Get { key: key.into() })
+}
+
+#[test]
+fn test_mock_defaults() -> testresult::TestResult<()> {
+    let mock = mock();
+    assert_eq!(mock.call::<_, Option<String>>(&get("a"))?, [None]);
+    assert_eq!(
+        mock.call::<_, String>(&Proto::List(List { prefix: "a".into() }))?,
+        [""]
+    );
+    assert!(mock.call::<_, ()>(&Proto::Clear(Clear))?.is_empty());
+
+    let calls = mock.calls();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[0].method, "Get");
+    assert_eq!(
+        calls[0].request,
+        Value::Struct(vec![("key".into(), Value::Str("a".into()))])
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mock_scripted() -> testresult::TestResult<()> {
+    let mock = mock()
+        .respond_with("Get", |request| {
+            let key = request.select("key").unwrap()[0].clone();
+            vec![Value::Optional(Some(Box::new(key)))]
+        })
+        .respond("List", ["a", "b", "c"].map(|s| Value::Str(s.to_string())));
+    assert_eq!(
+        mock.call::<_, Option<String>>(&get("x"))?,
+        [Some("x".to_string())]
+    );
+    assert_eq!(
+        mock.call::<_, String>(&Proto::List(List { prefix: "a".into() }))?,
+        ["a", "b", "c"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mock_errors() -> testresult::TestResult<()> {
+    let mock = mock().respond("Get", []).respond("List", [Value::UInt(1)]);
+    assert!(matches!(
+        mock.call::<_, Option<String>>(&get("a")),
+        Err(MockError::InvalidResponseCount { count: 0, .. })
+    ));
+    assert!(matches!(
+        mock.call::<_, String>(&Proto::List(List { prefix: "a".into() })),
+        Err(MockError::InvalidResponse { .. })
+    ));
+
+    let mut bytes = postcard::to_allocvec(&get("a"))?;
+    assert!(matches!(
+        mock.handle(&bytes[..bytes.len() - 1]),
+        Err(MockError::InvalidRequest { .. })
+    ));
+    bytes[0] ^= 1;
+    assert!(matches!(
+        mock.handle(&bytes),
+        Err(MockError::Schema(SchemaError::UnknownHash { .. }))
+    ));
+    assert!(matches!(
+        mock.handle(&[]),
+        Err(MockError::Schema(SchemaError::MissingHash))
+    ));
+    Ok(())
+}
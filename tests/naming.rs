@@ -0,0 +1,86 @@
+#![allow(dead_code, non_camel_case_types, non_snake_case)]
+use irpc_schema::{
+    check::Problem,
+    naming::{self, Case, NamingPattern, NamingRules},
+    schema, HasSchema, SchemaError,
+};
+
+#[schema(Nominal(name = "acme::users::User@v2", naming = "acme::**::{Type}@v{N}"))]
+struct User {
+    name: String,
+    homeDir: String,
+}
+
+#[schema(Atom(name = "acme::Id@v1", naming = "acme::{Type}@v{N}"))]
+struct Id([u8; 16]);
+
+#[schema(Nominal)]
+enum Request {
+    Get(User),
+    put_all(Vec<User>),
+}
+
+#[test]
+fn test_patterns() {
+    let pattern = NamingPattern::new("acme::*::{Type}@v{N}").unwrap();
+    assert!(pattern.matches("acme::users::User@v1"));
+    assert!(pattern.matches("acme::users::UserV2@v10"));
+    assert!(!pattern.matches("acme::users::user@v1"));
+    assert!(!pattern.matches("acme::users::User@v"));
+    assert!(!pattern.matches("acme::users::admin::User@v1"));
+    assert!(!pattern.matches("other::users::User@v1"));
+
+    let deep = NamingPattern::new("acme::**::{Type}").unwrap();
+    assert!(deep.matches("acme::users::admin::User"));
+    assert!(!deep.matches("acme::User"));
+
+    let snake = NamingPattern::new("{name}::{Type}").unwrap();
+    assert!(snake.matches("my_crate2::Type"));
+    assert!(!snake.matches("MyCrate::Type"));
+
+    assert!(NamingPattern::new("acme::{Kind}").is_err());
+    assert!(NamingPattern::new("acme::{Type").is_err());
+    assert!(!naming::matches("acme::{Type", "acme::{Type"));
+}
+
+#[test]
+fn test_cases() {
+    assert!(Case::Snake.matches("home_dir2"));
+    assert!(!Case::Snake.matches("homeDir"));
+    assert!(!Case::Snake.matches("_private"));
+    assert!(Case::UpperCamel.matches("PutAll"));
+    assert!(!Case::UpperCamel.matches("put_all"));
+    assert!(!Case::UpperCamel.matches(""));
+}
+
+#[test]
+fn test_lint() {
+    let rules = NamingRules::new()
+        .types(NamingPattern::new("acme::**::{Type}@v{N}").unwrap())
+        .fields(Case::Snake)
+        .variants(Case::UpperCamel);
+    let problems = rules
+        .lint(&Request::schema())
+        .into_iter()
+        .map(|invalid| invalid.problem)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        [
+            Problem::TypeNaming("Request".to_string()),
+            Problem::VariantNaming("put_all".to_string()),
+            Problem::FieldNaming("homeDir".to_string()),
+            Problem::FieldNaming("homeDir".to_string()),
+        ]
+    );
+    assert!(NamingRules::new().lint(&Request::schema()).is_empty());
+    assert!(rules.lint(&Id::schema()).is_empty());
+
+    let Err(SchemaError::Invalid(invalid)) = User::schema().validate_naming(&rules) else {
+        panic!("expected a naming violation");
+    };
+    assert_eq!(
+        invalid.to_string(),
+        "acme::users::User@v2: field name homeDir violates the naming rules"
+    );
+}
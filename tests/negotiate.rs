@@ -0,0 +1,75 @@
+#![cfg(feature = "irpc")]
+use irpc::{channel::oneshot, rpc_requests, Client};
+use irpc_schema::{
+    manifest::SchemaManifest,
+    negotiate::{negotiate, respond, Hello, NegotiatedProtocol, NegotiationError},
+    HasSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[rpc_requests(message = HandshakeMessage, no_rpc, no_spans)]
+#[derive(Debug, Serialize, Deserialize)]
+enum Handshake {
+    #[rpc(tx = oneshot::Sender<Hello>)]
+    Hello(Hello),
+}
+
+fn manifest(messages: &[(&str, irpc_schema::Schema)]) -> SchemaManifest {
+    let mut res = SchemaManifest::new("test", "1");
+    for (name, schema) in messages {
+        res.push(*name, schema.clone());
+    }
+    res
+}
+
+#[test]
+fn test_negotiated_protocol() {
+    let v1 = manifest(&[
+        ("Get", String::schema()),
+        ("Put", <(String, u64)>::schema()),
+    ]);
+    let v2 = manifest(&[
+        ("Get", String::schema()),
+        ("Put", <(String, u32)>::schema()),
+    ]);
+    let get = v1.messages[0].hash;
+    let put = v1.messages[1].hash;
+
+    let same = NegotiatedProtocol::new(&Hello::from_manifest(&v1), &Hello::from_manifest(&v1));
+    assert!(same.unwrap().identical());
+
+    let mixed =
+        NegotiatedProtocol::new(&Hello::from_manifest(&v1), &Hello::from_manifest(&v2)).unwrap();
+    assert!(!mixed.identical());
+    assert_eq!(mixed.remote_manifest(), v2.hash());
+    assert!(mixed.supports(&get));
+    assert!(matches!(
+        mixed.check(&put),
+        Err(NegotiationError::Unsupported { hash }) if hash == put
+    ));
+
+    let other = manifest(&[("Ping", u8::schema())]);
+    let none = NegotiatedProtocol::new(&Hello::from_manifest(&v1), &Hello::from_manifest(&other));
+    assert!(matches!(none, Err(NegotiationError::NoCommonMessages)));
+}
+
+#[tokio::test]
+async fn test_negotiate_over_irpc() -> testresult::TestResult<()> {
+    let client_manifest = manifest(&[("Get", String::schema()), ("Del", u64::schema())]);
+    let server_manifest = manifest(&[("Get", String::schema())]);
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let server = tokio::spawn(async move {
+        let Some(HandshakeMessage::Hello(request)) = rx.recv().await else {
+            panic!("expected a hello");
+        };
+        respond(&Hello::from_manifest(&server_manifest), request).await
+    });
+    let client = Client::<Handshake>::local(tx);
+    let negotiated = negotiate(&client, Hello::from_manifest(&client_manifest)).await?;
+    let server_side = server.await??;
+    assert_eq!(negotiated.common(), server_side.common());
+    assert_eq!(server_side.remote_manifest(), client_manifest.hash());
+    assert_eq!(negotiated.common().len(), 1);
+    assert!(negotiated.supports(&client_manifest.messages[0].hash));
+    Ok(())
+}
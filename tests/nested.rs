@@ -0,0 +1,148 @@
+use irpc_schema::{
+    manifest::SchemaManifest, negotiate::Hello, nested::Protocol, router::RouteError,
+    serialize_nested, HasSchema,
+};
+
+mod kv {
+    use irpc_schema::{schema, serialize_stable};
+    use serde::{Deserialize, Serialize};
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Get {
+        pub key: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Put {
+        pub key: String,
+        pub value: String,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Get(Get),
+        Put(Put),
+    }
+}
+
+mod auth {
+    use irpc_schema::{schema, serialize_stable};
+    use serde::{Deserialize, Serialize};
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Login {
+        pub user: String,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Login(Login),
+    }
+}
+
+#[serialize_nested(router)]
+#[derive(Debug, PartialEq)]
+enum Root {
+    Kv(kv::Proto),
+    Auth(auth::Proto),
+    // the same child protocol a second time
+    Cache(kv::Proto),
+}
+
+#[serialize_nested]
+#[derive(Debug, PartialEq)]
+enum Outer {
+    Root(Root),
+}
+
+fn get(key: &str) -> kv::Proto {
+    kv::Proto::Get(kv::Get { key: key.into() })
+}
+
+#[test]
+fn test_nested_schemas() {
+    let names = Root::schemas().map(|(name, _, _)| name).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["Kv.Get", "Kv.Put", "Auth.Login", "Cache.Get", "Cache.Put"]
+    );
+    let (_, schema, hash) = Root::schemas().next().unwrap();
+    let (_, child_schema, child_hash) = kv::Proto::schemas().next().unwrap();
+    assert_eq!(schema, &irpc_schema::Schema::named("Kv", kv::Get::schema()));
+    assert_eq!(schema.stable_hash().as_bytes(), &hash);
+    assert_eq!(child_schema, &kv::Get::schema());
+    assert_ne!(hash, child_hash);
+    // the same child under different variants gets different hashes
+    assert_ne!(hash, Root::schemas().nth(3).unwrap().2);
+
+    let names = Outer::schemas()
+        .map(|(name, _, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names[0], "Root.Kv.Get");
+}
+
+#[test]
+fn test_nested_roundtrip() -> testresult::TestResult<()> {
+    let child = postcard::to_allocvec(&get("a"))?;
+    for msg in [Root::Kv(get("a")), Root::Cache(get("a"))] {
+        let bytes = postcard::to_allocvec(&msg)?;
+        // the parent hash replaces the child hash
+        assert_eq!(bytes[..32], msg.message_hash());
+        assert_ne!(bytes[..32], child[..32]);
+        assert_eq!(bytes[32..], child[32..]);
+        assert_eq!(postcard::from_bytes::<Root>(&bytes)?, msg);
+    }
+
+    let msg = Outer::Root(Root::Auth(auth::Proto::Login(auth::Login {
+        user: "b".into(),
+    })));
+    let bytes = postcard::to_allocvec(&msg)?;
+    assert_eq!(bytes[..32], Outer::schemas().nth(2).unwrap().2);
+    assert_eq!(postcard::from_bytes::<Outer>(&bytes)?, msg);
+
+    // child messages are not messages of the parent
+    assert!(postcard::from_bytes::<Root>(&child).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_nested_manifest() {
+    let manifest = SchemaManifest::from_schemas("root", "1", Root::schemas());
+    assert_eq!(manifest.children(), ["Kv", "Auth", "Cache"]);
+    let kv = manifest.child("Kv").unwrap();
+    assert_eq!(kv.name, "root.Kv");
+    assert_eq!(
+        kv.messages,
+        SchemaManifest::from_schemas("kv", "1", kv::Proto::schemas()).messages
+    );
+    assert_eq!(manifest.child("Cache").unwrap().messages, kv.messages);
+    assert!(manifest.child("Missing").is_none());
+
+    // a peer that only knows the kv service can negotiate it
+    let local = Hello::from_manifest(&kv);
+    let remote = Hello::from_schemas(kv::Proto::schemas());
+    assert_eq!(local.hashes, remote.hashes);
+}
+
+#[tokio::test]
+async fn test_nested_router() -> testresult::TestResult<()> {
+    let router = RootRouter::new().on_kv(|msg| async move {
+        match msg {
+            kv::Proto::Get(get) => format!("get {}", get.key),
+            kv::Proto::Put(put) => format!("put {}", put.key),
+        }
+    });
+    let bytes = postcard::to_allocvec(&Root::Kv(get("a")))?;
+    assert_eq!(router.handle(&bytes).await?, "get a");
+    let bytes = postcard::to_allocvec(&Root::Cache(get("a")))?;
+    assert!(matches!(
+        router.handle(&bytes).await,
+        Err(RouteError::NoHandler { name: "Cache" })
+    ));
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use irpc_schema::{
+    codec::{decode_postcard, encode_postcard},
+    diff::{diff, ChangeKind},
+    value::Value,
+    HasSchema, Named, Schema,
+};
+
+fn optional(schema: Schema) -> Schema {
+    Schema::Optional(Box::new(schema))
+}
+
+fn legacy(schema: Schema) -> Schema {
+    Schema::Sum(vec![Schema::Unit, schema])
+}
+
+#[test]
+fn test_option_schema() {
+    let expected = if cfg!(feature = "optional-schema") {
+        optional(u32::schema())
+    } else {
+        legacy(u32::schema())
+    };
+    assert_eq!(<Option<u32>>::schema(), expected);
+    assert_eq!(
+        <Option<u32>>::schema().legacy_hash(),
+        legacy(u32::schema()).stable_hash()
+    );
+}
+
+#[test]
+fn test_legacy_hash() {
+    let schema = Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", Schema::Seq(Box::new(optional(String::schema())))),
+        ]),
+    );
+    let expected = Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", Schema::Seq(Box::new(legacy(String::schema())))),
+        ]),
+    );
+    assert_eq!(schema.to_legacy(), expected);
+    assert_eq!(schema.legacy_hash(), expected.stable_hash());
+    assert_ne!(schema.stable_hash(), expected.stable_hash());
+    // schemas without optionals keep their hash
+    assert_eq!(expected.legacy_hash(), expected.stable_hash());
+}
+
+#[test]
+fn test_optional_text() -> testresult::TestResult<()> {
+    let schema = Schema::Struct(vec![Named::new("value", optional(u32::schema()))]);
+    assert_eq!(schema.to_string(), r#"("value":?"u32",)"#);
+    let text = schema.to_canonical_text();
+    assert_eq!(text, "struct {\n  \"value\": optional \"u32\"\n}\n");
+    assert_eq!(Schema::from_canonical_text(&text)?, schema);
+    Ok(())
+}
+
+#[test]
+fn test_optional_codec() -> testresult::TestResult<()> {
+    // both encodings of an option are the same on the wire
+    for schema in [optional(u32::schema()), legacy(u32::schema())] {
+        for value in [None, Some(7u32)] {
+            let bytes = postcard::to_allocvec(&value)?;
+            let decoded = decode_postcard(&schema, &bytes)?;
+            assert_eq!(
+                decoded,
+                Value::Optional(value.map(|v| Box::new(Value::UInt(v as u128))))
+            );
+            assert_eq!(encode_postcard(&schema, &decoded)?, bytes);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_optional_diff() {
+    // switching the encoding is not a change on the wire
+    let changes = diff(&legacy(u32::schema()), &optional(u32::schema()));
+    assert!(changes.is_empty());
+    let changes = diff(&u32::schema(), &optional(u32::schema()));
+    assert_eq!(changes.changes.len(), 1);
+    assert_eq!(changes.changes[0].kind, ChangeKind::BecameOptional);
+}
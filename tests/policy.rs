@@ -0,0 +1,110 @@
+use irpc_schema::{
+    diff::{diff, Compat},
+    policy::{EvolutionPolicy, Rule},
+    HasSchema, Named, Schema,
+};
+
+fn user(fields: Vec<Named>) -> Schema {
+    Schema::named("User", Schema::Struct(fields))
+}
+
+fn id(atom: &str) -> Named {
+    Named::new("id", Schema::Atom(atom.to_string()))
+}
+
+#[test]
+fn test_default_policy() {
+    let policy = EvolutionPolicy::new();
+    let old = user(vec![id("u32")]);
+    // widening a varint is compatible
+    policy.assert_accepts(&old, &user(vec![id("u64")]));
+    // adding a field is migratable
+    let new = user(vec![id("u32"), Named::new("name", String::schema())]);
+    let violation = policy.check_schemas(&old, &new).unwrap_err();
+    assert_eq!(violation.changes.len(), 1);
+    assert!(violation.to_string().contains("add-field"));
+    // accepting migratable changes
+    policy
+        .clone()
+        .accept(Compat::Migratable)
+        .assert_accepts(&old, &new);
+}
+
+#[test]
+fn test_allow_and_forbid() {
+    let policy = EvolutionPolicy::new()
+        .allow(Rule::AddOptionalField)
+        .allow(Rule::WidenInt)
+        .forbid(Rule::ChangeAtom);
+    let old = user(vec![id("u8")]);
+    // u8 is not a varint, so this changes the encoding and is not widening
+    let widened = user(vec![id("u16")]);
+    assert_eq!(diff(&old, &widened).compat(), Compat::Breaking);
+    assert!(policy.check_schemas(&old, &widened).is_err());
+    // neither is widening into a zigzag encoded signed integer
+    let signed = user(vec![id("i64")]);
+    assert!(policy
+        .check_schemas(&user(vec![id("u32")]), &signed)
+        .is_err());
+    // widening a varint is, and so is rejected once forbidden
+    policy.assert_accepts(&widened, &user(vec![id("u64")]));
+    let strict = policy.clone().forbid(Rule::WidenInt);
+    assert!(strict
+        .check_schemas(&widened, &user(vec![id("u64")]))
+        .is_err());
+    // narrowing is not widening
+    assert!(policy.check_schemas(&widened, &old).is_err());
+    let optional = user(vec![
+        id("u8"),
+        Named::new("name", Option::<String>::schema()),
+    ]);
+    policy.assert_accepts(&old, &optional);
+    // forbidding wins over the classification
+    let strings = Named::new("name", Schema::Atom("String".to_string()));
+    let strs = Named::new("name", Schema::Atom("&str".to_string()));
+    let (a, b) = (user(vec![strings]), user(vec![strs]));
+    assert_eq!(diff(&a, &b).compat(), Compat::Compatible);
+    assert!(policy.check_schemas(&a, &b).is_err());
+    // and over allowing
+    let policy = policy.forbid(Rule::AddOptionalField);
+    assert!(policy.check_schemas(&old, &optional).is_err());
+}
+
+#[test]
+fn test_rule_names() {
+    for rule in Rule::ALL {
+        assert_eq!(rule.name().parse::<Rule>().unwrap(), rule);
+    }
+    assert!("remove-everything".parse::<Rule>().is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_from_json() {
+    let policy = EvolutionPolicy::from_json(
+        r#"{"accept": "migratable", "allow": ["widen-int"], "forbid": ["remove-field"]}"#,
+    )
+    .unwrap();
+    let expected = EvolutionPolicy::new()
+        .accept(Compat::Migratable)
+        .allow(Rule::WidenInt)
+        .forbid(Rule::RemoveField);
+    assert_eq!(policy, expected);
+    assert!(EvolutionPolicy::from_json(r#"{"allow": ["nope"]}"#).is_err());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_from_toml() {
+    let policy = EvolutionPolicy::from_toml(
+        r#"
+        allow = ["add-optional-field", "add-variant", "widen-int"]
+        forbid = ["remove-field", "change-atom"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(policy.accept, Compat::Compatible);
+    assert!(policy.allow.contains(&Rule::AddOptionalField));
+    assert!(policy.forbid.contains(&Rule::ChangeAtom));
+    assert!(EvolutionPolicy::from_toml("deny = []").is_err());
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use irpc_schema::{pretty::PrettyOptions, HasSchema, Named, Schema};
+
+fn entry() -> Schema {
+    Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", <BTreeMap<String, Vec<u8>>>::schema()),
+            Named::new("kind", Schema::Sum(vec![Schema::Unit, Schema::Bottom])),
+        ]),
+    )
+}
+
+#[test]
+fn test_default_options() {
+    let schema = entry();
+    assert_eq!(
+        schema.pretty_print_with(2, &PrettyOptions::default()),
+        schema.pretty_print(2)
+    );
+}
+
+#[test]
+fn test_compact() {
+    let options = PrettyOptions {
+        max_inline_width: 30,
+        ..PrettyOptions::default()
+    };
+    let expected = r#""Entry": (
+  "id": "u32",
+  "tags": {"String": ["u8"]},
+  "kind": (() | ⊥)
+)"#;
+    assert_eq!(entry().pretty_print_with(0, &options), expected);
+    // small schemas end up on a single line
+    assert_eq!(
+        entry().pretty_print_with(0, &PrettyOptions::compact()),
+        r#""Entry": ("id": "u32", "tags": {"String": ["u8"]}, "kind": (() | ⊥))"#
+    );
+}
+
+#[test]
+fn test_indent_and_ascii() {
+    let options = PrettyOptions {
+        indent_width: 4,
+        unicode: false,
+        ..PrettyOptions::default()
+    };
+    let expected = r#""Entry": (
+    "id": "u32",
+    "tags": {
+        "String": [
+            "u8"
+        ]
+    },
+    "kind": (
+        () |
+        !
+    )
+)"#;
+    assert_eq!(entry().pretty_print_with(0, &options), expected);
+}
+
+#[test]
+fn test_show_hashes() {
+    let options = PrettyOptions {
+        show_hashes: true,
+        ..PrettyOptions::compact()
+    };
+    let schema = entry();
+    let hash = schema.stable_hash().to_hex();
+    let text = schema.pretty_print_with(0, &options);
+    assert!(text.starts_with(&format!("\"Entry\" [{}]: (", &hash[..16])));
+}
+
+#[test]
+fn test_alternate_display() {
+    let schema = entry();
+    assert_eq!(format!("{:#}", schema), schema.pretty_print(0));
+    let options = PrettyOptions {
+        max_inline_width: 30,
+        ..PrettyOptions::default()
+    };
+    assert_eq!(
+        format!("{:#30}", schema),
+        schema.pretty_print_with(0, &options)
+    );
+    let Schema::Named(named) = &schema else {
+        unreachable!()
+    };
+    assert_eq!(format!("{:#}", named), named.pretty_print(0));
+    // the compact form is unchanged
+    assert!(!format!("{}", schema).contains('\n'));
+}
@@ -0,0 +1,270 @@
+#![cfg(feature = "prost")]
+use irpc_schema::{
+    impl_has_schema_prost,
+    protobuf::{Descriptors, ProstError},
+    registry::SchemaRegistry,
+    HasSchema, Named, Schema,
+};
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+    FileDescriptorProto, FileDescriptorSet, MessageOptions, OneofDescriptorProto,
+};
+
+fn field(name: &str, number: i32, label: Label, ty: Type) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        label: Some(label as i32),
+        r#type: Some(ty as i32),
+        ..Default::default()
+    }
+}
+
+fn typed(name: &str, number: i32, label: Label, ty: Type, type_name: &str) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        type_name: Some(type_name.to_string()),
+        ..field(name, number, label, ty)
+    }
+}
+
+fn message(name: &str, field: Vec<FieldDescriptorProto>) -> DescriptorProto {
+    DescriptorProto {
+        name: Some(name.to_string()),
+        field,
+        ..Default::default()
+    }
+}
+
+/// The descriptors of
+///
+/// ```proto
+/// syntax = "proto3";
+/// package users;
+///
+/// enum Role { GUEST = 0; ADMIN = 5; }
+///
+/// message User {
+///   string name = 1;
+///   optional uint32 age = 2;
+///   repeated Role roles = 3;
+///   map<string, bytes> attributes = 4;
+///   Address address = 5;
+///   oneof contact {
+///     string email = 6;
+///     uint64 phone = 7;
+///   }
+///   message Address { string city = 1; }
+/// }
+/// ```
+fn descriptors() -> FileDescriptorSet {
+    let mut user = message(
+        "User",
+        vec![
+            field("name", 1, Label::Optional, Type::String),
+            FieldDescriptorProto {
+                proto3_optional: Some(true),
+                oneof_index: Some(0),
+                ..field("age", 2, Label::Optional, Type::Uint32)
+            },
+            typed("roles", 3, Label::Repeated, Type::Enum, ".users.Role"),
+            typed(
+                "attributes",
+                4,
+                Label::Repeated,
+                Type::Message,
+                ".users.User.AttributesEntry",
+            ),
+            typed(
+                "address",
+                5,
+                Label::Optional,
+                Type::Message,
+                ".users.User.Address",
+            ),
+            FieldDescriptorProto {
+                oneof_index: Some(1),
+                ..field("email", 6, Label::Optional, Type::String)
+            },
+            FieldDescriptorProto {
+                oneof_index: Some(1),
+                ..field("phone", 7, Label::Optional, Type::Uint64)
+            },
+        ],
+    );
+    user.oneof_decl = ["_age", "contact"]
+        .map(|name| OneofDescriptorProto {
+            name: Some(name.to_string()),
+            ..Default::default()
+        })
+        .to_vec();
+    user.nested_type = vec![
+        message(
+            "Address",
+            vec![field("city", 1, Label::Optional, Type::String)],
+        ),
+        DescriptorProto {
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..message(
+                "AttributesEntry",
+                vec![
+                    field("key", 1, Label::Optional, Type::String),
+                    field("value", 2, Label::Optional, Type::Bytes),
+                ],
+            )
+        },
+    ];
+    let role = EnumDescriptorProto {
+        name: Some("Role".to_string()),
+        value: [("GUEST", 0), ("ADMIN", 5)]
+            .map(|(name, number)| EnumValueDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                ..Default::default()
+            })
+            .to_vec(),
+        ..Default::default()
+    };
+    FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("users.proto".to_string()),
+            package: Some("users".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![user],
+            enum_type: vec![role],
+            ..Default::default()
+        }],
+    }
+}
+
+fn address() -> Schema {
+    Schema::named(
+        "users.User.Address",
+        Schema::Struct(vec![Named::new("city", String::schema())]),
+    )
+}
+
+#[test]
+fn test_message_schema() -> Result<(), ProstError> {
+    let descriptors = Descriptors::new(descriptors());
+    let role = Schema::named(
+        "users.Role",
+        Schema::Enumeration(vec![("GUEST".to_string(), 0), ("ADMIN".to_string(), 5)]),
+    );
+    let contact = Schema::named(
+        "users.User.contact",
+        Schema::Enum(vec![
+            Named::new("email", String::schema()),
+            Named::new("phone", u64::schema()),
+        ]),
+    );
+    let expected = Schema::named(
+        "users.User",
+        Schema::Struct(vec![
+            Named::new("name", String::schema()),
+            Named::new("age", Option::<u32>::schema()),
+            Named::new("roles", Schema::Seq(Box::new(role.clone()))),
+            Named::new(
+                "attributes",
+                Schema::Map(Box::new(String::schema()), Box::new(Vec::<u8>::schema())),
+            ),
+            Named::new("address", Schema::Sum(vec![Schema::Unit, address()])),
+            Named::new("contact", Schema::Sum(vec![Schema::Unit, contact])),
+        ]),
+    );
+    let schema = descriptors.schema("users.User")?;
+    // options are sums without the optional-schema feature
+    assert_eq!(schema.to_legacy(), expected.to_legacy());
+    assert!(schema.validate().is_ok());
+    assert_eq!(descriptors.schema(".users.Role")?, role);
+    assert!(matches!(
+        descriptors.schema("users.Missing"),
+        Err(ProstError::UnknownType(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_registry() -> Result<(), ProstError> {
+    let descriptors = Descriptors::decode(&descriptors().encode_to_vec())?;
+    // map entries are not messages of their own
+    assert_eq!(
+        descriptors.message_names().collect::<Vec<_>>(),
+        ["users.User", "users.User.Address"]
+    );
+    let mut registry = SchemaRegistry::new();
+    registry.register_many(descriptors.schemas()?);
+    assert_eq!(registry.len(), 2);
+    assert!(registry.contains(address().stable_hash().as_bytes()));
+    assert!(matches!(
+        Descriptors::decode(&[0xff]),
+        Err(ProstError::Decode(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_descriptors() {
+    let node = message(
+        "Node",
+        vec![typed("next", 1, Label::Optional, Type::Message, ".Node")],
+    );
+    let negative = EnumDescriptorProto {
+        name: Some("Sign".to_string()),
+        value: vec![EnumValueDescriptorProto {
+            name: Some("MINUS".to_string()),
+            number: Some(-1),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let descriptors = Descriptors::new(FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            message_type: vec![node],
+            enum_type: vec![negative],
+            ..Default::default()
+        }],
+    });
+    assert!(matches!(
+        descriptors.schema("Node"),
+        Err(ProstError::Recursive(name)) if name == "Node"
+    ));
+    let err = descriptors.schema("Sign").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "enum value Sign.MINUS has negative number -1"
+    );
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Address {
+    #[prost(string, tag = "1")]
+    pub city: String,
+}
+
+impl prost::Name for Address {
+    const NAME: &'static str = "User.Address";
+    const PACKAGE: &'static str = "users";
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Location {
+    #[prost(string, tag = "1")]
+    pub city: String,
+}
+
+static DESCRIPTORS: std::sync::LazyLock<Vec<u8>> =
+    std::sync::LazyLock::new(|| descriptors().encode_to_vec());
+
+impl_has_schema_prost!(&DESCRIPTORS; Address, Location = "users.User.Address");
+
+#[test]
+fn test_impl_has_schema() {
+    assert_eq!(Address::schema(), address());
+    assert_eq!(Location::schema(), address());
+    assert_eq!(Address::static_schema(), &address());
+}
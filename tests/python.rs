@@ -0,0 +1,102 @@
+#![cfg(feature = "pyo3")]
+use std::ffi::CStr;
+
+use irpc_schema::{manifest::SchemaManifest, python, HasSchema, Named, Schema};
+use pyo3::{prelude::*, types::PyDict};
+
+/// Runs Python code with the module imported as `irpc_schema`.
+fn run(code: &CStr, globals: &[(&str, Vec<u8>)]) {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = pyo3::wrap_pymodule!(python::irpc_schema)(py);
+        let locals = PyDict::new(py);
+        locals.set_item("irpc_schema", module).unwrap();
+        for (name, bytes) in globals {
+            locals.set_item(name, bytes.as_slice()).unwrap();
+        }
+        if let Err(e) = py.run(code, None, Some(&locals)) {
+            e.display(py);
+            panic!("python code failed: {}", e);
+        }
+    })
+}
+
+#[test]
+fn test_schema() {
+    run(
+        cr#"
+Schema = irpc_schema.Schema
+schema = Schema.parse('("id":"u32",)')
+assert str(schema) == '("id":"u32",)'
+assert Schema.parse(schema.to_canonical_text()) == schema
+assert Schema.from_json(schema.to_json()) == schema
+assert len(schema.hash) == 64
+assert repr(schema) == 'Schema.parse("(\\"id\\":\\"u32\\",)")'
+try:
+    Schema.parse('["u8"')
+    assert False
+except ValueError as e:
+    assert str(e).startswith("invalid schema")
+"#,
+        &[],
+    );
+}
+
+#[test]
+fn test_diff() {
+    run(
+        cr#"
+Schema = irpc_schema.Schema
+old = Schema.parse('("id":"u32",)')
+new = Schema.parse('("id":"u32","name":"String",)')
+diff = irpc_schema.diff(old, new)
+assert diff.compat == "migratable"
+assert len(diff) == 1
+path, change, compat = diff.changes()[0]
+assert compat == "migratable"
+import json
+assert json.loads(diff.to_json())["changes"][0]["compat"] == "migratable"
+assert not irpc_schema.is_compatible(old, new)
+assert irpc_schema.is_compatible(old, new, accept="migratable")
+assert len(irpc_schema.diff(old, old)) == 0
+try:
+    irpc_schema.is_compatible(old, new, accept="fine")
+    assert False
+except ValueError:
+    pass
+"#,
+        &[],
+    );
+}
+
+#[test]
+fn test_manifest() {
+    let mut old = SchemaManifest::new("kv", "1.0");
+    old.push("Get", String::schema());
+    let mut new = SchemaManifest::new("kv", "2.0");
+    new.push("Get", String::schema());
+    new.push(
+        "Put",
+        Schema::Struct(vec![Named::new("key", String::schema())]),
+    );
+    let old = postcard::to_allocvec(&old).unwrap();
+    let new = postcard::to_allocvec(&new).unwrap();
+    run(
+        cr#"
+Manifest = irpc_schema.Manifest
+old = Manifest.decode(old_bytes)
+new = Manifest.decode(new_bytes)
+assert (new.name, new.version) == ("kv", "2.0")
+assert [name for name, _ in new.messages()] == ["Get", "Put"]
+assert new.get("Get") == old.get("Get")
+assert old.get("Put") is None
+assert "Put" in old.changelog(new)
+try:
+    Manifest.decode(b"\xff")
+    assert False
+except ValueError:
+    pass
+"#,
+        &[("old_bytes", old), ("new_bytes", new)],
+    );
+}
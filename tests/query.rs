@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{codec::decode_postcard, query::Query, schema, value::Value, HasSchema};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+struct Item {
+    id: u64,
+    label: Option<String>,
+}
+
+#[derive(Serialize)]
+#[schema(Nominal)]
+struct Order {
+    items: Vec<Item>,
+    meta: BTreeMap<String, i32>,
+}
+
+fn order() -> testresult::TestResult<Value> {
+    let order = Order {
+        items: vec![
+            Item {
+                id: 1,
+                label: Some("a".to_string()),
+            },
+            Item { id: 2, label: None },
+        ],
+        meta: BTreeMap::from([("x".to_string(), -1)]),
+    };
+    Ok(decode_postcard(
+        &Order::schema(),
+        &postcard::to_allocvec(&order)?,
+    )?)
+}
+
+#[test]
+fn test_select() -> testresult::TestResult<()> {
+    let order = order()?;
+    assert_eq!(
+        order.select("items[*].id")?,
+        [&Value::UInt(1), &Value::UInt(2)]
+    );
+    assert_eq!(order.select("items.1.id")?, [&Value::UInt(2)]);
+    assert_eq!(
+        order.select("items[*].label")?,
+        [&Value::Str("a".to_string())]
+    );
+    assert_eq!(order.select("meta.x")?, [&Value::Int(-1)]);
+    assert!(order.select("nope")?.is_empty());
+    assert!(order.select("items[").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_select_typed() -> testresult::TestResult<()> {
+    let order = order()?;
+    let selection = order.select_typed(&Order::schema(), "items[*].label")?;
+    assert_eq!(selection.schema, String::schema());
+    assert_eq!(selection.values.len(), 1);
+    let err = Query::parse("items[*].price")?
+        .schema_of(&Order::schema())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid query items[*].price: no field or variant price"
+    );
+    assert_eq!(Query::parse("items[*].id")?.to_string(), "items[*].id");
+    Ok(())
+}
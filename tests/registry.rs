@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    registry::{SchemaRegistry, StrictError, StrictRegistry},
+    schema, serialize_stable, HasSchema, SchemaError,
+};
+use serde::{Deserialize, Serialize};
+use testresult::TestResult;
+
+mod v1 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: String,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum Proto {
+        Put(PutRequest),
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct GetRequest {
+        pub key: String,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PutRequest {
+        pub key: String,
+        pub value: Option<String>,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum Proto {
+        Get(GetRequest),
+        Put(PutRequest),
+    }
+}
+
+#[test]
+fn test_registry() {
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(v2::Proto::schemas());
+    assert_eq!(registry.len(), 2);
+    let hash = *v2::GetRequest::schema().stable_hash().as_bytes();
+    assert_eq!(registry.get(&hash).unwrap().name, "Get");
+    let (nearest, distance) = registry.nearest(&v1::PutRequest::schema()).unwrap();
+    assert_eq!(nearest.name, "Put");
+    assert_eq!(distance, 1);
+}
+
+#[test]
+fn test_strict_registry() -> TestResult<()> {
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(v1::Proto::schemas());
+    registry.register_all(v2::Proto::schemas());
+    let allowed = v2::Proto::schemas().map(|(_, _, hash)| hash);
+    let mut strict = StrictRegistry::new(registry, allowed);
+
+    // allowed messages decode
+    let bytes = postcard::to_allocvec(&v2::Proto::Get(v2::GetRequest {
+        key: "a".to_string(),
+    }))?;
+    let msg: v2::Proto = strict.decode(&bytes)?;
+    assert!(matches!(msg, v2::Proto::Get(_)));
+
+    // known but not allowed messages are rejected, reporting the nearest allowed schema
+    let bytes = postcard::to_allocvec(&v1::Proto::Put(v1::PutRequest {
+        key: "a".to_string(),
+        value: "b".to_string(),
+    }))?;
+    let Err(StrictError::NotAllowed { nearest, .. }) = strict.decode::<v1::Proto>(&bytes) else {
+        panic!("expected rejection");
+    };
+    assert_eq!(nearest.unwrap().name, "Put");
+
+    // registration outside the allowlist is rejected
+    assert!(strict.register("x", u64::schema()).is_err());
+    assert!(strict.register("Get", v2::GetRequest::schema()).is_ok());
+    assert!(matches!(
+        strict.decode::<v2::Proto>(&[0u8; 4]),
+        Err(StrictError::Schema(SchemaError::MissingHash))
+    ));
+    Ok(())
+}
+
+mod other {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct GetRequest {
+        pub id: u64,
+    }
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum Proto {
+        Get(GetRequest),
+        Put(v2::PutRequest),
+    }
+}
+
+#[test]
+fn test_merge() -> TestResult<()> {
+    use irpc_schema::registry::MergeConflict;
+    let mut kv = SchemaRegistry::new();
+    kv.register_all(v2::Proto::schemas());
+    let mut store = SchemaRegistry::new();
+    store.register("Store", v2::PutRequest::schema());
+    let merged = SchemaRegistry::merge([("kv", &kv), ("store", &store)])?;
+    assert_eq!(merged.registry().len(), 2);
+    let put = *v2::PutRequest::schema().stable_hash().as_bytes();
+    assert_eq!(merged.services(&put), ["kv", "store"]);
+    assert_eq!(merged.get(&put).unwrap().name, "Put");
+
+    let mut other = SchemaRegistry::new();
+    other.register_all(other::Proto::schemas());
+    let err = SchemaRegistry::merge([("kv", &kv), ("other", &other)]).unwrap_err();
+    assert_eq!(
+        err.conflicts,
+        vec![MergeConflict::NameConflict {
+            name: "GetRequest".to_string(),
+            services: vec!["kv".to_string(), "other".to_string()],
+        }]
+    );
+    Ok(())
+}
+
+/// Enough schemas to be processed in parallel with the `rayon` feature.
+fn many(version: u32) -> Vec<(String, irpc_schema::Schema)> {
+    (0..200)
+        .map(|i| {
+            let fields = (0..i % 7 + version)
+                .map(|j| irpc_schema::Named::new(format!("f{}", j), u32::schema()))
+                .collect();
+            (
+                format!("M{}", i),
+                irpc_schema::Schema::named(format!("M{}", i), irpc_schema::Schema::Struct(fields)),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_register_many() {
+    let mut a = SchemaRegistry::new();
+    a.register_many(many(1));
+    let mut b = SchemaRegistry::new();
+    for (name, schema) in many(1) {
+        b.register(name, schema);
+    }
+    assert_eq!(a.len(), 200);
+    assert!(a.iter().eq(b.iter()));
+
+    let (_, target) = &many(1)[10];
+    let (entry, distance) = a.nearest(target).unwrap();
+    assert_eq!(entry.name, "M10");
+    assert_eq!(distance, 0);
+
+    let old = irpc_schema::manifest::SchemaManifest::from_schemas(
+        "many",
+        "1",
+        a.iter().map(|e| (e.name.as_str(), &e.schema, e.hash)),
+    );
+    let mut new = irpc_schema::manifest::SchemaManifest::new("many", "2");
+    for (name, schema) in many(2) {
+        new.push(name, schema);
+    }
+    let changelog = irpc_schema::changelog::Changelog::new(&old, &new);
+    assert_eq!(changelog.changed.len(), 200);
+    let names = old.messages.iter().map(|e| e.name.as_str());
+    assert!(changelog.changed.iter().map(|c| c.name.as_str()).eq(names));
+}
@@ -0,0 +1,79 @@
+#![allow(dead_code)]
+use irpc_schema::{impl_has_schema_remote, schema, HasSchema, Named, Schema};
+
+mod other_crate {
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    pub struct Id(pub [u8; 16]);
+}
+
+impl_has_schema_remote!(other_crate::Point as Nominal { x: f64, y: f64 } => PointDef);
+impl_has_schema_remote!(other_crate::Id as Atom => pub IdDef);
+
+#[schema(Nominal)]
+struct Shape {
+    #[schema(with = "PointDef")]
+    origin: other_crate::Point,
+    #[schema(with = "IdDef")]
+    id: other_crate::Id,
+}
+
+#[schema(Nominal)]
+enum Event {
+    Moved(#[schema(with = "PointDef")] other_crate::Point),
+    Removed {
+        #[schema(with = "IdDef")]
+        id: other_crate::Id,
+    },
+}
+
+#[schema(Structural)]
+struct Pair(
+    #[schema(with = "PointDef")] other_crate::Point,
+    #[schema(with = "PointDef")] other_crate::Point,
+);
+
+fn point() -> Schema {
+    Schema::named(
+        "Point",
+        Schema::Struct(vec![
+            Named::new("x", f64::schema()),
+            Named::new("y", f64::schema()),
+        ]),
+    )
+}
+
+fn id() -> Schema {
+    Schema::Atom("Id".to_string())
+}
+
+#[test]
+fn test_remote_schemas() {
+    assert_eq!(PointDef::schema(), point());
+    assert_eq!(IdDef::schema(), id());
+}
+
+#[test]
+fn test_with_attribute() {
+    assert_eq!(
+        Shape::schema(),
+        Schema::named(
+            "Shape",
+            Schema::Struct(vec![Named::new("origin", point()), Named::new("id", id())])
+        )
+    );
+    assert_eq!(
+        Event::schema(),
+        Schema::named(
+            "Event",
+            Schema::Enum(vec![
+                Named::new("Moved", Schema::Product(vec![point()])),
+                Named::new("Removed", Schema::Struct(vec![Named::new("id", id())])),
+            ])
+        )
+    );
+    assert_eq!(Pair::schema(), Schema::Product(vec![point(), point()]));
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "irpc")]
+use irpc::channel::{none::NoReceiver, oneshot};
+use irpc_schema::{
+    registry::{SchemaRegistry, StrictRegistry},
+    router::RouteError,
+    schema, serialize_service, SchemaError,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KvService;
+
+impl irpc::Service for KvService {
+    type Message = Proto;
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Put {
+    key: String,
+    value: String,
+}
+
+impl irpc::Channels<KvService> for Get {
+    type Rx = NoReceiver;
+    type Tx = oneshot::Sender<Option<String>>;
+}
+
+impl irpc::Channels<KvService> for Put {
+    type Rx = NoReceiver;
+    type Tx = oneshot::Sender<()>;
+}
+
+#[serialize_service(KvService, router)]
+#[derive(Debug)]
+enum Proto {
+    Get(Get),
+    Put(Put),
+}
+
+#[tokio::test]
+async fn test_router() -> testresult::TestResult<()> {
+    let router = KvServiceRouter::new()
+        .on_get(|get| async move { format!("get {}", get.key) })
+        .on_unknown(|_hash, payload| async move { format!("unknown, {} bytes", payload.len()) });
+
+    let get = postcard::to_allocvec(&Proto::Get(Get { key: "a".into() }))?;
+    assert_eq!(router.handle(&get).await?, "get a");
+
+    let put = postcard::to_allocvec(&Proto::Put(Put {
+        key: "a".into(),
+        value: "b".into(),
+    }))?;
+    assert!(matches!(
+        router.handle(&put).await,
+        Err(RouteError::NoHandler { name: "Put" })
+    ));
+
+    let mut unknown = get.clone();
+    unknown[0] ^= 1;
+    assert_eq!(router.handle(&unknown).await?, "unknown, 2 bytes");
+    assert!(matches!(
+        router.handle(&get[..10]).await,
+        Err(RouteError::Schema(SchemaError::MissingHash))
+    ));
+
+    // only allow put
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(Proto::schemas());
+    let put_hash = Proto::schemas().nth(1).unwrap().2;
+    let router = KvServiceRouter::new()
+        .on_get(|_| async { "get" })
+        .with_registry(StrictRegistry::new(registry, [put_hash]));
+    assert!(matches!(
+        router.handle(&get).await,
+        Err(RouteError::NotAllowed(_))
+    ));
+    Ok(())
+}
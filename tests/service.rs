@@ -0,0 +1,243 @@
+#![cfg(feature = "irpc")]
+#![allow(dead_code)]
+use irpc::channel::{
+    mpsc,
+    none::{NoReceiver, NoSender},
+    oneshot,
+};
+use irpc_schema::{
+    capabilities::Capabilities,
+    hashing, schema, serialize_service,
+    service::{split_channels_schema, ChannelKind, MethodDescriptor, ServiceDescriptor},
+    HasSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KvService;
+
+impl irpc::Service for KvService {
+    type Message = Proto;
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct List {
+    prefix: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Clear;
+
+impl irpc::Channels<KvService> for Get {
+    type Rx = NoReceiver;
+    type Tx = oneshot::Sender<Option<String>>;
+}
+
+impl irpc::Channels<KvService> for List {
+    type Rx = NoReceiver;
+    type Tx = mpsc::Sender<String>;
+}
+
+impl irpc::Channels<KvService> for Clear {
+    type Rx = NoReceiver;
+    type Tx = NoSender;
+}
+
+#[serialize_service(KvService)]
+#[derive(Debug)]
+enum Proto {
+    Get(Get),
+    List(List),
+    Clear(Clear),
+}
+
+#[test]
+fn test_service_descriptor() -> testresult::TestResult<()> {
+    let descriptor = ServiceDescriptor::from_schemas("KvService", Proto::schemas());
+    let names = descriptor
+        .methods
+        .iter()
+        .map(|m| m.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Get", "List", "Clear"]);
+    let get = descriptor.get("Get").unwrap();
+    assert_eq!(get.request, Get::schema());
+    assert_eq!(
+        get.tx_kind(),
+        Some((ChannelKind::Oneshot, Some(&Option::<String>::schema())))
+    );
+    assert_eq!(get.schema().stable_hash().as_bytes(), &get.hash);
+    assert_eq!(
+        descriptor.get("List").unwrap().tx_kind().unwrap().0,
+        ChannelKind::Mpsc
+    );
+    assert_eq!(descriptor.get_by_hash(&get.hash), Some(get));
+    let streaming = descriptor
+        .streaming_methods()
+        .map(|m| m.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(streaming, ["List"]);
+
+    let bytes = postcard::to_allocvec(&descriptor)?;
+    let decoded: ServiceDescriptor = postcard::from_bytes(&bytes)?;
+    assert_eq!(decoded, descriptor);
+    assert_eq!(decoded.hash(), descriptor.hash());
+
+    let mut renamed = descriptor.clone();
+    renamed.name = "Other".to_string();
+    assert_eq!(renamed.hash(), descriptor.hash());
+    renamed.methods.pop();
+    assert_ne!(renamed.hash(), descriptor.hash());
+
+    let text = descriptor.to_string();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert!(lines[0].starts_with("service KvService "));
+    // options are written differently with the optional-schema feature
+    assert_eq!(
+        lines[1],
+        format!(
+            "  Get \"Get\"=(\"key\":\"String\",) tx oneshot {}",
+            Option::<String>::schema()
+        )
+    );
+    assert_eq!(lines[3], "  Clear \"Clear\"=()");
+    Ok(())
+}
+
+#[test]
+fn test_channels_schema_parts() {
+    let (_, schema, _) = Proto::schemas().next().unwrap();
+    let (msg, rx, tx) = split_channels_schema(schema).unwrap();
+    assert_eq!(msg, &Get::schema());
+    assert_eq!(rx, &NoReceiver::schema());
+    assert_eq!(tx, &oneshot::Sender::<Option<String>>::schema());
+
+    let descriptor = ServiceDescriptor::from_schemas("KvService", Proto::schemas());
+    let list = descriptor.get("List").unwrap();
+    assert_eq!(list.response(), Some(&String::schema()));
+    assert_eq!(list.updates(), None);
+
+    // the anonymous product of earlier versions is still understood
+    let legacy = <(Get, NoReceiver, oneshot::Sender<Option<String>>)>::schema();
+    assert_eq!(hashing::hash_v1(schema), legacy.legacy_hash());
+    let method = MethodDescriptor::from_schema("Get", &legacy, [0; 32]).unwrap();
+    assert_eq!(
+        &method,
+        &MethodDescriptor {
+            hash: [0; 32],
+            ..descriptor.methods[0].clone()
+        }
+    );
+}
+
+#[test]
+fn test_capabilities() -> testresult::TestResult<()> {
+    let descriptor = ServiceDescriptor::from_schemas("KvService", Proto::schemas());
+    let all = Capabilities::from_descriptor(&descriptor);
+    assert_eq!(all.len(), 3);
+    assert!(descriptor.methods.iter().all(|m| all.supports(&m.hash)));
+    assert!(!all.supports(&[0; 32]));
+
+    let some = Capabilities::from_hashes([&descriptor.methods[2].hash]);
+    assert!(!some.supports(&descriptor.methods[0].hash));
+    assert!(some.supports(&descriptor.methods[2].hash));
+
+    let bytes = all.to_bytes();
+    assert_eq!(bytes.len(), 1 + 3 * 8);
+    assert_eq!(Capabilities::from_bytes(&bytes)?, all);
+    Ok(())
+}
+
+#[test]
+fn test_channel_schemas() {
+    let item = u32::schema();
+    let cases = [
+        (NoSender::schema(), ChannelKind::None),
+        (NoReceiver::schema(), ChannelKind::None),
+        (oneshot::Sender::<u32>::schema(), ChannelKind::Oneshot),
+        (oneshot::Receiver::<u32>::schema(), ChannelKind::Oneshot),
+        (mpsc::Sender::<u32>::schema(), ChannelKind::Mpsc),
+        (mpsc::Receiver::<u32>::schema(), ChannelKind::Mpsc),
+    ];
+    for (schema, kind) in cases {
+        let expected = (kind != ChannelKind::None).then_some(&item);
+        assert_eq!(ChannelKind::of(&schema), Some((kind, expected)));
+    }
+}
+
+mod describe {
+    use irpc::{channel::oneshot, rpc_requests, Client, WithChannels};
+    use irpc_schema::{
+        serialize_service,
+        service::{describe, respond_describe, Describe, ServiceDescriptor},
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::Get;
+
+    #[rpc_requests(message = KvMessage, no_rpc, no_spans)]
+    #[derive(Debug, Serialize, Deserialize)]
+    enum KvProtocol {
+        #[rpc(tx = oneshot::Sender<Option<String>>)]
+        Get(Get),
+        // channels are provided by irpc-schema
+        Describe(Describe),
+    }
+
+    impl From<Describe> for KvProtocol {
+        fn from(value: Describe) -> Self {
+            KvProtocol::Describe(value)
+        }
+    }
+
+    impl From<WithChannels<Describe, KvProtocol>> for KvMessage {
+        fn from(value: WithChannels<Describe, KvProtocol>) -> Self {
+            KvMessage::Describe(value)
+        }
+    }
+
+    #[serialize_service(KvProtocol)]
+    #[derive(Debug)]
+    enum Proto {
+        Get(Get),
+        Describe(Describe),
+    }
+
+    #[tokio::test]
+    async fn test_describe() -> testresult::TestResult<()> {
+        let descriptor = ServiceDescriptor::from_schemas("Kv", Proto::schemas());
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let server = {
+            let descriptor = descriptor.clone();
+            tokio::spawn(async move {
+                while let Some(msg) = rx.recv().await {
+                    match msg {
+                        KvMessage::Get(get) => get.tx.send(None).await.ok(),
+                        KvMessage::Describe(msg) => respond_describe(&descriptor, msg).await.ok(),
+                    };
+                }
+            })
+        };
+        let client = Client::<KvProtocol>::local(tx);
+        assert_eq!(describe(&client).await?, descriptor);
+        drop(client);
+        server.await?;
+
+        let manifest = descriptor.to_manifest("1.0");
+        assert_eq!(manifest.name, "Kv");
+        assert_eq!(manifest.messages.len(), 2);
+        assert_eq!(
+            manifest.get("Describe").unwrap().hash,
+            descriptor.methods[1].hash
+        );
+        Ok(())
+    }
+}
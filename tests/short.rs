@@ -0,0 +1,128 @@
+use irpc_schema::{
+    registry::SchemaRegistry,
+    short::{short_hash, ShortIndex, SHORT_LEN},
+    HasSchema, Schema,
+};
+
+mod short {
+    use irpc_schema::{schema, serialize_stable};
+    use serde::{Deserialize, Serialize};
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Ping {
+        pub seq: u32,
+    }
+
+    #[schema(Nominal)]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Pong {
+        pub seq: u32,
+    }
+
+    #[serialize_stable(short)]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Ping(Ping),
+        Pong(Pong),
+    }
+}
+
+mod full {
+    use irpc_schema::serialize_stable;
+
+    use super::short::{Ping, Pong};
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Ping(Ping),
+        Pong(Pong),
+    }
+}
+
+#[test]
+fn test_short_roundtrip() -> testresult::TestResult<()> {
+    let msg = short::Proto::Pong(short::Pong { seq: 7 });
+    let bytes = postcard::to_allocvec(&msg)?;
+    let full = postcard::to_allocvec(&full::Proto::Pong(short::Pong { seq: 7 }))?;
+    // 24 bytes less than the full hash, same payload
+    assert_eq!(bytes.len() + 32 - SHORT_LEN, full.len());
+    assert_eq!(
+        bytes[..SHORT_LEN],
+        short_hash(&short::Proto::schemas().nth(1).unwrap().2)
+    );
+    assert_eq!(bytes[SHORT_LEN..], full[32..]);
+    assert_eq!(postcard::from_bytes::<short::Proto>(&bytes)?, msg);
+
+    // schemas still have the full hashes
+    let hashes = |schemas: &mut dyn Iterator<Item = (&str, &Schema, [u8; 32])>| {
+        schemas.map(|(_, _, hash)| hash).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        hashes(&mut short::Proto::schemas()),
+        hashes(&mut full::Proto::schemas())
+    );
+
+    // the modes are not compatible
+    assert!(postcard::from_bytes::<short::Proto>(&[0u8; SHORT_LEN + 1]).is_err());
+    assert!(postcard::from_bytes::<full::Proto>(&bytes).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_short_index() {
+    let ping = *short::Ping::schema().stable_hash().as_bytes();
+    let index =
+        ShortIndex::new(short::Proto::schemas().map(|(name, _, hash)| (name, hash))).unwrap();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.get(&short_hash(&ping)), Some(ping));
+
+    // the same hash twice is fine, different hashes with the same prefix are not
+    let mut other = ping;
+    other[31] ^= 1;
+    assert!(ShortIndex::new([("A", ping), ("B", ping)]).is_ok());
+    let e = ShortIndex::new([("A", ping), ("B", other)]).unwrap_err();
+    assert_eq!((e.first.as_str(), e.second.as_str()), ("A", "B"));
+    assert_eq!(e.short, short_hash(&ping));
+
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(short::Proto::schemas());
+    assert_eq!(registry.short_index().unwrap().len(), 2);
+}
+
+#[cfg(feature = "irpc")]
+mod service {
+    use irpc::channel::{none::NoReceiver, oneshot};
+    use irpc_schema::serialize_service;
+    use serde::{Deserialize, Serialize};
+
+    use super::short::Ping;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PingService;
+
+    impl irpc::Service for PingService {
+        type Message = Proto;
+    }
+
+    impl irpc::Channels<PingService> for Ping {
+        type Rx = NoReceiver;
+        type Tx = oneshot::Sender<u32>;
+    }
+
+    #[serialize_service(PingService, short)]
+    #[derive(Debug, PartialEq)]
+    enum Proto {
+        Ping(Ping),
+    }
+
+    #[test]
+    fn test_short_service() -> testresult::TestResult<()> {
+        let msg = Proto::Ping(Ping { seq: 1 });
+        let bytes = postcard::to_allocvec(&msg)?;
+        assert_eq!(bytes.len(), irpc_schema::short::SHORT_LEN + 1);
+        assert_eq!(postcard::from_bytes::<Proto>(&bytes)?, msg);
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 use std::collections::{BTreeMap, BTreeSet};
 
-use irpc_schema::{HasSchema, Named, Schema};
+use irpc_schema::{hash_postcard, schema_of, HasSchema, HasStaticSchema, Named, Schema};
 use irpc_schema_derive::{schema, serialize_stable};
 use testresult::TestResult;
 
@@ -72,6 +72,31 @@ fn test_pretty_print() {
     println!("{}", NominalEnum::schema().pretty_print(0));
 }
 
+#[test]
+fn test_static_schema() {
+    let schema = NominalEnum::static_schema();
+    assert!(std::ptr::eq(schema, NominalEnum::static_schema()));
+    assert_eq!(schema, &NominalEnum::schema());
+    // types without a derive use the global cache
+    let schema = <Vec<NominalStruct>>::static_schema();
+    assert!(std::ptr::eq(schema, <Vec<NominalStruct>>::static_schema()));
+    assert_eq!(schema, &<Vec<NominalStruct>>::schema());
+    assert_ne!(schema, <Vec<UnitStruct>>::static_schema());
+}
+
+#[test]
+fn test_schema_ref() {
+    fn same_schema<A: HasStaticSchema, B: HasStaticSchema>() -> bool {
+        A::schema_ref() == B::schema_ref()
+    }
+    assert!(std::ptr::eq(
+        NominalEnum::schema_ref(),
+        NominalEnum::static_schema()
+    ));
+    assert!(same_schema::<Vec<u8>, Vec<u8>>());
+    assert!(!same_schema::<Vec<u8>, Vec<u16>>());
+}
+
 #[test]
 fn test_unit_struct_schema() {
     assert_eq!(
@@ -154,3 +179,177 @@ fn test_serialize_schema() -> TestResult<()> {
     assert_eq!(v, v_out);
     Ok(())
 }
+
+#[test]
+fn test_pretty_print_output() {
+    let schema = Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", <BTreeMap<String, Vec<u8>>>::schema()),
+            Named::new("kind", Schema::Sum(vec![Schema::Unit, Schema::Bottom])),
+        ]),
+    );
+    let mut text = String::new();
+    schema.pretty_fmt(&mut text, 2).unwrap();
+    assert_eq!(text, schema.pretty_print(2));
+    let expected = r#"  "Entry": (
+    "id": "u32",
+    "tags": {
+      "String": [
+        "u8"
+      ]
+    },
+    "kind": (
+      () |
+      ⊥
+    )
+  )"#;
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn test_display_output() {
+    let schema = Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", <BTreeMap<String, Vec<u8>>>::schema()),
+            Named::new(
+                "kind",
+                Schema::Sum(vec![Schema::Unit, Schema::Product(vec![Schema::Bottom])]),
+            ),
+        ]),
+    );
+    assert_eq!(
+        schema.to_string(),
+        r#""Entry"=("id":"u32","tags":{"String":["u8"]},"kind":(()|(⊥,)))"#
+    );
+    assert_eq!(schema.to_string().parse::<Schema>().unwrap(), schema);
+}
+
+#[test]
+fn test_stable_hash_golden() {
+    let schema = Schema::named(
+        "Entry",
+        Schema::Struct(vec![
+            Named::new("id", u32::schema()),
+            Named::new("tags", <BTreeMap<String, Vec<u8>>>::schema()),
+        ]),
+    );
+    // hashes must not change, no matter how the encoding is produced
+    assert_eq!(
+        schema.stable_hash().to_hex().as_str(),
+        "a0634f3e9c5b8cf42f98912b67d1c4343fc148b2e132638bc07dabda0d99ad98"
+    );
+    assert_eq!(hash_postcard(&schema), schema.stable_hash());
+}
+
+/// Many fields of the same type share one lookup of the field schema.
+#[schema(Nominal)]
+struct Wide {
+    a: [u8; 32],
+    b: [u8; 32],
+    c: u32,
+    d: [u8; 32],
+}
+
+#[test]
+fn test_repeated_field_types() {
+    let hash = <[u8; 32]>::schema();
+    assert_eq!(
+        Wide::schema(),
+        Schema::named(
+            "Wide",
+            Schema::Struct(vec![
+                Named::new("a", hash.clone()),
+                Named::new("b", hash.clone()),
+                Named::new("c", u32::schema()),
+                Named::new("d", hash),
+            ])
+        )
+    );
+}
+
+#[schema(Nominal)]
+enum OneCase {
+    Point { x: u32, y: u32 },
+}
+
+#[schema(Nominal(legacy_enum))]
+enum LegacyOneCase {
+    Point { x: u32, y: u32 },
+}
+
+#[schema(Nominal(legacy_enum))]
+enum LegacyTuple {
+    Pair(u32, u32),
+    Unit,
+}
+
+#[test]
+fn test_enum_mapping() {
+    let fields = vec![
+        Named::new("x", u32::schema()),
+        Named::new("y", u32::schema()),
+    ];
+    assert_eq!(
+        OneCase::schema(),
+        Schema::named(
+            "OneCase",
+            Schema::Enum(vec![Named::new("Point", Schema::Struct(fields.clone()))])
+        )
+    );
+    assert_eq!(
+        LegacyOneCase::schema(),
+        Schema::named(
+            "LegacyOneCase",
+            Schema::Struct(vec![Named::new("Point", Schema::Enum(fields))])
+        )
+    );
+    let Schema::Named(named) = NominalEnum::schema() else {
+        panic!("Expected Named");
+    };
+    let Schema::Enum(cases) = &named.1 else {
+        panic!("Expected Enum");
+    };
+    assert_eq!(
+        cases[0],
+        Named::new(
+            "Tuple",
+            Schema::Product(vec![i32::schema(), String::schema()])
+        )
+    );
+    assert_eq!(
+        LegacyTuple::schema(),
+        Schema::named(
+            "LegacyTuple",
+            Schema::Enum(vec![
+                Named::new("Pair", Schema::Sum(vec![u32::schema(), u32::schema()])),
+                Named::new("Unit", Schema::Unit),
+            ])
+        )
+    );
+}
+
+#[test]
+fn test_try_stable_hash() {
+    let schema = NominalEnum::schema();
+    assert_eq!(schema.try_stable_hash().unwrap(), schema.stable_hash());
+}
+
+#[test]
+fn test_schema_of() {
+    assert_eq!(schema_of!(NominalStruct), NominalStruct::schema());
+    assert_eq!(
+        schema_of!(BTreeMap<String, Vec<NominalStruct>>),
+        Schema::Map(
+            Box::new(String::schema()),
+            Box::new(Schema::Seq(Box::new(NominalStruct::schema())))
+        )
+    );
+    assert_eq!(
+        schema_of!((u32, BTreeSet<u8>)),
+        <(u32, BTreeSet<u8>)>::schema()
+    );
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "proptest")]
+use std::collections::BTreeMap;
+
+use irpc_schema::{
+    codec::{decode_postcard, encode_postcard},
+    schema,
+    strategy::{arb_typed, arb_value},
+    HasSchema,
+};
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[schema(Nominal)]
+struct PutRequest {
+    key: String,
+    value: Option<Vec<u8>>,
+    ttl: i64,
+    weight: f32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Tags(BTreeMap<u16, (char, bool)>),
+    Clear,
+}
+
+proptest! {
+    #[test]
+    fn test_values_conform(value in arb_value(&Request::schema())) {
+        prop_assert!(value.conforms_to(&Request::schema()));
+        let bytes = encode_postcard(&Request::schema(), &value).unwrap();
+        prop_assert_eq!(decode_postcard(&Request::schema(), &bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_typed_roundtrip(request in arb_typed::<Request>()) {
+        let bytes = postcard::to_allocvec(&request).unwrap();
+        prop_assert_eq!(postcard::from_bytes::<Request>(&bytes).unwrap(), request);
+    }
+}
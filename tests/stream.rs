@@ -0,0 +1,70 @@
+#![cfg(feature = "irpc")]
+use irpc::channel::{mpsc, none::NoReceiver, oneshot};
+use irpc_schema::{
+    codec::decode_postcard,
+    hashing,
+    service::{channels_schema, ChannelKind, MethodDescriptor},
+    HasSchema, Schema,
+};
+
+fn stream() -> Schema {
+    Schema::Stream(Box::new(u32::schema()))
+}
+
+#[test]
+fn test_channel_schemas() {
+    assert_eq!(mpsc::Sender::<u32>::schema(), stream());
+    assert_eq!(mpsc::Receiver::<u32>::schema(), stream());
+    assert_eq!(
+        ChannelKind::of(&stream()),
+        Some((ChannelKind::Mpsc, Some(&u32::schema())))
+    );
+    // the named channels of earlier versions are still recognized
+    let legacy = Schema::named("irpc::channel::mpsc::Sender", u32::schema());
+    assert_eq!(
+        ChannelKind::of(&legacy),
+        Some((ChannelKind::Mpsc, Some(&u32::schema())))
+    );
+}
+
+#[test]
+fn test_text() {
+    let schema = stream();
+    assert_eq!(schema.to_string(), r#"*"u32""#);
+    assert_eq!(schema.to_string().parse::<Schema>().unwrap(), schema);
+    let text = schema.to_canonical_text();
+    assert_eq!(text, "stream \"u32\"\n");
+    assert_eq!(Schema::from_canonical_text(&text).unwrap(), schema);
+}
+
+#[test]
+fn test_erase_streams() {
+    let schema = channels_schema(Schema::Unit, stream(), stream());
+    let erased = channels_schema(
+        Schema::Unit,
+        Schema::named("irpc::channel::mpsc::Receiver", u32::schema()),
+        Schema::named("irpc::channel::mpsc::Sender", u32::schema()),
+    );
+    assert_eq!(schema.erase_streams(), erased);
+    assert_eq!(hashing::hash_v3(&schema), erased.stable_hash());
+    assert_ne!(hashing::hash_v4(&schema), hashing::hash_v3(&schema));
+    // schemas without streams hash the same in both versions
+    let plain = oneshot::Sender::<u32>::schema();
+    assert_eq!(hashing::hash_v4(&plain), hashing::hash_v3(&plain));
+}
+
+#[test]
+fn test_no_value() {
+    assert!(decode_postcard(&stream(), &[1]).is_err());
+    assert!(stream().validate().is_ok());
+}
+
+#[test]
+fn test_is_streaming() {
+    let method = |tx: Schema| {
+        let schema = channels_schema(u32::schema(), NoReceiver::schema(), tx);
+        MethodDescriptor::from_schema("Method", &schema, [0; 32]).unwrap()
+    };
+    assert!(method(mpsc::Sender::<u32>::schema()).is_streaming());
+    assert!(!method(oneshot::Sender::<u32>::schema()).is_streaming());
+}
@@ -0,0 +1,142 @@
+use irpc_schema::{
+    diff::{diff, ChangeKind},
+    schema, HasSchema, Named, Schema, Tagging,
+};
+use serde::{Deserialize, Serialize};
+
+mod external {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Serialize, Deserialize)]
+    pub enum Shape {
+        Circle { r: f64 },
+        Point,
+    }
+}
+
+mod internal {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "type")]
+    pub enum Shape {
+        Circle { r: f64 },
+        Point,
+    }
+}
+
+mod adjacent {
+    use super::*;
+
+    #[schema(Nominal)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "t", content = "c")]
+    pub enum Shape {
+        Circle { r: f64 },
+        Point,
+    }
+}
+
+mod untagged {
+    use super::*;
+
+    #[schema(Structural)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Shape {
+        Circle(f64),
+        Point,
+    }
+}
+
+fn shape() -> Schema {
+    Schema::Enum(vec![
+        Named::new(
+            "Circle",
+            Schema::Struct(vec![Named::new("r", Schema::Atom("f64".into()))]),
+        ),
+        Named::new("Point", Schema::Unit),
+    ])
+}
+
+#[test]
+fn test_derive_records_tagging() {
+    assert_eq!(external::Shape::schema(), Schema::named("Shape", shape()));
+    assert_eq!(
+        internal::Shape::schema(),
+        Schema::named(
+            "Shape",
+            Schema::Tagged(Tagging::Internal("type".into()), Box::new(shape()))
+        )
+    );
+    assert_eq!(
+        adjacent::Shape::schema(),
+        Schema::named(
+            "Shape",
+            Schema::Tagged(Tagging::Adjacent("t".into(), "c".into()), Box::new(shape()))
+        )
+    );
+    assert!(matches!(
+        untagged::Shape::schema(),
+        Schema::Tagged(Tagging::Untagged, _)
+    ));
+}
+
+#[test]
+fn test_tagging_changes_hash() {
+    let hashes = [
+        external::Shape::schema().stable_hash(),
+        internal::Shape::schema().stable_hash(),
+        adjacent::Shape::schema().stable_hash(),
+    ];
+    assert_ne!(hashes[0], hashes[1]);
+    assert_ne!(hashes[0], hashes[2]);
+    assert_ne!(hashes[1], hashes[2]);
+}
+
+#[test]
+fn test_text_round_trip() {
+    for schema in [
+        internal::Shape::schema(),
+        adjacent::Shape::schema(),
+        untagged::Shape::schema(),
+    ] {
+        let text = schema.to_string();
+        assert_eq!(text.parse::<Schema>().unwrap(), schema, "{}", text);
+        let canonical = schema.to_canonical_text();
+        assert_eq!(
+            canonical.parse::<Schema>().unwrap(),
+            schema,
+            "{}",
+            canonical
+        );
+    }
+    assert_eq!(
+        internal::Shape::schema().to_string(),
+        r#""Shape"=@("type")("Circle":("r":"f64",)|"Point":())"#
+    );
+    assert_eq!(
+        Schema::Tagged(Tagging::Untagged, Box::new(Schema::Atom("u8".into()))).to_string(),
+        r#"@()"u8""#
+    );
+}
+
+#[test]
+fn test_diff_reports_tagging_change() {
+    let changes = diff(&external::Shape::schema(), &internal::Shape::schema()).changes;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(
+        changes[0].kind,
+        ChangeKind::TaggingChanged {
+            old: None,
+            new: Some(Tagging::Internal("type".into())),
+        }
+    );
+    assert_eq!(
+        changes[0].kind.to_string(),
+        "changed representation from externally tagged to internally tagged with `type`"
+    );
+    assert!(diff(&internal::Shape::schema(), &internal::Shape::schema()).is_empty());
+}
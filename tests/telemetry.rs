@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+
+use irpc_schema::{
+    registry::{SchemaRegistry, StrictRegistry},
+    schema, serialize_stable,
+    telemetry::{
+        clear_schema_history, clear_unknown_hash_hook, set_schema_history, set_unknown_hash_hook,
+        UnknownHash,
+    },
+    HasSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct PutRequest {
+    key: String,
+}
+
+#[serialize_stable]
+#[derive(Debug)]
+enum Proto {
+    Put(PutRequest),
+}
+
+/// A single test, since the hook and the history are global.
+#[test]
+fn test_unknown_hash_hook() -> testresult::TestResult<()> {
+    let events = Arc::new(Mutex::new(Vec::<UnknownHash>::new()));
+    let sink = events.clone();
+    set_unknown_hash_hook(move |event| sink.lock().unwrap().push(event.clone()));
+
+    // an old version of the message, known to the registry but not allowed
+    let old = <(String, u64)>::schema();
+    let old_hash = *old.stable_hash().as_bytes();
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(Proto::schemas());
+    registry.register("Put", old.clone());
+    let allowed = Proto::schemas().map(|(_, _, hash)| hash);
+    let registry = StrictRegistry::new(registry, allowed);
+
+    let mut bytes = old_hash.to_vec();
+    bytes.extend_from_slice(&postcard::to_allocvec(&("k", 1u64))?);
+    assert!(registry.decode::<Proto>(&bytes).is_err());
+    assert!(Proto::from_postcard(&bytes).is_err());
+    assert!(postcard::from_bytes::<Proto>(&bytes).is_err());
+
+    // with the old version in the history, generated decoders find the nearest message
+    let mut history = SchemaRegistry::new();
+    history.register("Put", old);
+    set_schema_history(history);
+    assert!(postcard::from_bytes::<Proto>(&bytes).is_err());
+    clear_schema_history();
+
+    clear_unknown_hash_hook();
+    assert!(postcard::from_bytes::<Proto>(&bytes).is_err());
+    assert!(Proto::from_postcard(&bytes).is_err());
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[0].hash, old_hash);
+    assert_eq!(events[0].len, Some(bytes.len()));
+    assert_eq!(events[0].nearest.as_ref().unwrap().name, "Put");
+    assert_eq!(events[1].hash, old_hash);
+    assert_eq!(events[1].nearest, None);
+    assert_eq!(events[1].len, Some(bytes.len()));
+    assert_eq!(events[2].nearest, None);
+    assert_eq!(events[2].len, None);
+    let nearest = events[3].nearest.as_ref().unwrap();
+    assert_eq!(nearest.name, "Put");
+    assert_eq!(nearest.hash, Proto::schemas().next().unwrap().2);
+    assert_eq!(events[3].len, None);
+    // known messages decode as before
+    let bytes = Proto::Put(PutRequest { key: "k".into() }).to_postcard()?;
+    assert!(matches!(Proto::from_postcard(&bytes)?, Proto::Put(PutRequest { key }) if key == "k"));
+    Ok(())
+}
@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{schema, HasSchema, Named, Schema};
+use testresult::TestResult;
+
+#[schema(Nominal)]
+struct PutRequest {
+    key: String,
+    value: Option<String>,
+}
+
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Clear,
+    Scan(BTreeMap<String, Vec<u8>>, (u64, u64)),
+}
+
+#[test]
+fn test_canonical_text() {
+    let text = PutRequest::schema().to_canonical_text();
+    let expected = if cfg!(feature = "optional-schema") {
+        r#"named "PutRequest" struct {
+  "key": "String"
+  "value": optional "String"
+}
+"#
+    } else {
+        r#"named "PutRequest" struct {
+  "key": "String"
+  "value": sum {
+    unit
+    "String"
+  }
+}
+"#
+    };
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn test_canonical_text_roundtrip() -> TestResult<()> {
+    let schemas = [
+        Request::schema(),
+        Schema::Product(vec![]),
+        Schema::Bottom,
+        Schema::Set(Box::new(Schema::Atom("we\"ird\\ \n\u{1} name".to_string()))),
+    ];
+    for schema in schemas {
+        let text = schema.to_canonical_text();
+        let parsed = Schema::from_canonical_text(&text)?;
+        assert_eq!(parsed, schema);
+        assert_eq!(parsed.to_canonical_text(), text);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_canonical_text_lenient_parse() -> TestResult<()> {
+    let parsed = Schema::from_canonical_text(r#"map{"String" seq   "u8"}"#)?;
+    assert_eq!(parsed, <BTreeMap<String, Vec<u8>>>::schema());
+    Ok(())
+}
+
+#[test]
+fn test_canonical_text_errors() {
+    let err = Schema::from_canonical_text("struct {\n  \"a\": frob\n}").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 8));
+    assert!(Schema::from_canonical_text("unit unit").is_err());
+    assert!(Schema::from_canonical_text("map { unit }").is_err());
+    assert!(Schema::from_canonical_text("product {").is_err());
+    assert!(Schema::from_canonical_text("\"abc").is_err());
+}
+
+#[test]
+fn test_nesting_limit() -> TestResult<()> {
+    let depth = irpc_schema::text::MAX_DEPTH;
+    let text = format!("{}unit", "seq ".repeat(depth - 1));
+    let parsed = Schema::from_canonical_text(&text)?;
+    assert_eq!(parsed.to_canonical_text(), format!("{}\n", text));
+    let err = Schema::from_canonical_text(&format!("seq {}", text)).unwrap_err();
+    assert_eq!(err.message, "schema nested deeper than 128 levels");
+    // far too deep input fails instead of overflowing the stack
+    assert!("[".repeat(1_000_000).parse::<Schema>().is_err());
+    assert!(Schema::from_canonical_text(&"product {".repeat(100_000)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_display_roundtrip() -> TestResult<()> {
+    let named = |name: &str, schema| Named::new(name, schema);
+    let schemas = vec![
+        Request::schema(),
+        Schema::Unit,
+        Schema::Bottom,
+        Schema::Product(vec![]),
+        Schema::Sum(vec![]),
+        Schema::Struct(vec![]),
+        Schema::Enum(vec![]),
+        Schema::Product(vec![Schema::named("A", Schema::Unit)]),
+        Schema::Struct(vec![named("A", Schema::Unit)]),
+        Schema::Sum(vec![Schema::Unit]),
+        Schema::Enum(vec![named("A", Schema::Unit)]),
+        Schema::Set(Box::new(Schema::named("K", Schema::Unit))),
+        Schema::Map(
+            Box::new(Schema::Atom("K".to_string())),
+            Box::new(Schema::Unit),
+        ),
+        Schema::Optional(Box::new(Schema::named("A", Schema::Unit))),
+        Schema::named("A", Schema::Optional(Box::new(Schema::Unit))),
+        Schema::Atom("we\"ird\\ \n\u{1} name".to_string()),
+    ];
+    for schema in schemas {
+        let text = schema.to_string();
+        assert_eq!(text.parse::<Schema>()?, schema, "{}", text);
+    }
+    let field = named("key", String::schema());
+    assert_eq!(field.to_string(), r#""key":"String""#);
+    assert_eq!(field.to_string().parse::<Named>()?, field);
+    Ok(())
+}
+
+#[test]
+fn test_from_str() -> TestResult<()> {
+    // whitespace and the canonical text format are accepted
+    let parsed = r#"{ "String" : [ "u8" ] }"#.parse::<Schema>()?;
+    assert_eq!(parsed, <BTreeMap<String, Vec<u8>>>::schema());
+    let parsed = "map { \"String\" seq \"u8\" }".parse::<Schema>()?;
+    assert_eq!(parsed, <BTreeMap<String, Vec<u8>>>::schema());
+    let parsed = r#""key": seq "u8""#.parse::<Named>()?;
+    assert_eq!(parsed, Named::new("key", <Vec<u8>>::schema()));
+    // a single element must say whether it is a product or a sum
+    assert!("(\"u8\")".parse::<Schema>().is_err());
+    assert!("(\"u8\",\"u8\"|\"u8\")".parse::<Schema>().is_err());
+    assert!("(\"a\":\"u8\",\"u8\")".parse::<Schema>().is_err());
+    assert!("(,,)".parse::<Schema>().is_err());
+    assert!("[\"u8\"".parse::<Schema>().is_err());
+    Ok(())
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "tracing")]
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use irpc_schema::{schema, serialize_stable, telemetry::message_span};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[serialize_stable]
+#[derive(Debug)]
+enum Proto {
+    Get(Get),
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tracing() -> testresult::TestResult<()> {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let hash = hex::encode(Proto::schemas().next().unwrap().2);
+    tracing::subscriber::with_default(subscriber, || -> testresult::TestResult<()> {
+        let _guard = message_span().entered();
+        let bytes = postcard::to_allocvec(&Proto::Get(Get { key: "a".into() }))?;
+        postcard::from_bytes::<Proto>(&bytes)?;
+        Ok(())
+    })?;
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    for (line, direction) in lines.iter().zip(["Serialize", "Deserialize"]) {
+        assert!(line.contains("schema.message=\"Get\""), "{}", line);
+        assert!(line.contains(&format!("schema.hash={}", hash)), "{}", line);
+        assert!(line.contains("schema.size=2"), "{}", line);
+        assert!(
+            line.contains(&format!("direction={}", direction)),
+            "{}",
+            line
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "json")]
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{
+    schema,
+    transcode::{transcode, Format, TranscodeError},
+    HasSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PutRequest {
+    key: String,
+    value: Option<Vec<u8>>,
+    tags: BTreeMap<u32, char>,
+    ratio: f64,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Id(i64);
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Request {
+    Put(PutRequest),
+    Get(Id),
+    Clear,
+}
+
+#[test]
+fn test_transcode() -> testresult::TestResult<()> {
+    let values = [
+        Request::Put(PutRequest {
+            key: "a".to_string(),
+            value: Some(vec![1, 2, 3]),
+            tags: BTreeMap::from([(1, 'x'), (20, 'y')]),
+            ratio: 0.5,
+        }),
+        Request::Get(Id(-7)),
+        Request::Clear,
+    ];
+    let schema = Request::schema();
+    for value in values {
+        let bytes = postcard::to_allocvec(&value)?;
+        let json = transcode(&schema, Format::Postcard, Format::Json, &bytes)?;
+        let expected: serde_json::Value = serde_json::to_value(&value)?;
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&json)?,
+            expected
+        );
+        let back = transcode(&schema, Format::Json, Format::Postcard, &json)?;
+        assert_eq!(postcard::from_bytes::<Request>(&back)?, value);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_transcode_errors() {
+    let schema = Request::schema();
+    let err = transcode(&schema, Format::Json, Format::Postcard, b"{\"Nope\": 1}").unwrap_err();
+    assert!(matches!(err, TranscodeError::Json(_)));
+    let err = transcode(&schema, Format::Json, Format::Postcard, b"{").unwrap_err();
+    assert!(matches!(err, TranscodeError::Syntax(_)));
+    let err = transcode(&schema, Format::Postcard, Format::Json, &[9]).unwrap_err();
+    assert!(matches!(err, TranscodeError::Decode(_)));
+}
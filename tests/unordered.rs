@@ -0,0 +1,63 @@
+use irpc_schema::{
+    diff::{diff, ChangeKind},
+    Named, Schema,
+};
+
+fn atom(name: &str) -> Schema {
+    Schema::Atom(name.to_string())
+}
+
+fn point(fields: [&str; 2], variants: [&str; 2]) -> Schema {
+    Schema::named(
+        "Point",
+        Schema::Struct(
+            fields
+                .iter()
+                .map(|name| {
+                    let kind = Schema::Enum(
+                        variants
+                            .iter()
+                            .map(|variant| Named::new(*variant, Schema::Unit))
+                            .collect(),
+                    );
+                    Named::new(*name, Schema::Product(vec![atom("f64"), kind]))
+                })
+                .collect(),
+        ),
+    )
+}
+
+#[test]
+fn test_to_unordered() {
+    let schema = point(["y", "x"], ["B", "A"]);
+    assert_eq!(schema.to_unordered(), point(["x", "y"], ["A", "B"]));
+    assert_eq!(schema.to_unordered().to_unordered(), schema.to_unordered());
+}
+
+#[test]
+fn test_unordered_comparison() {
+    let a = point(["x", "y"], ["A", "B"]);
+    let b = point(["y", "x"], ["B", "A"]);
+    assert_ne!(a, b);
+    assert_ne!(a.stable_hash(), b.stable_hash());
+    assert!(a.eq_unordered(&b));
+    assert_eq!(a.unordered_hash(), b.unordered_hash());
+    // renaming a field still matters
+    let c = point(["x", "z"], ["A", "B"]);
+    assert!(!a.eq_unordered(&c));
+    assert_ne!(a.unordered_hash(), c.unordered_hash());
+}
+
+#[test]
+fn test_diff_ignore_order() {
+    let a = point(["x", "y"], ["A", "B"]);
+    let b = point(["y", "x"], ["A", "B"]);
+    let changes = diff(&a, &b);
+    assert!(changes
+        .changes
+        .iter()
+        .any(|change| matches!(change.kind, ChangeKind::FieldMoved { .. })));
+    assert!(changes.ignore_order().is_empty());
+    let c = point(["y", "z"], ["A", "B"]);
+    assert!(!diff(&a, &c).ignore_order().is_empty());
+}
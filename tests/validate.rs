@@ -0,0 +1,102 @@
+#![cfg(feature = "irpc")]
+use irpc::{
+    channel::{mpsc, oneshot},
+    rpc_requests, WithChannels,
+};
+use irpc_schema::{
+    schema,
+    validate::{check, validate},
+};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Get {
+    key: String,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Watch {
+    key: String,
+}
+
+/// The serde attribute is not reflected in the schema.
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u32>,
+}
+
+#[rpc_requests(message = KvMessage, no_rpc, no_spans)]
+#[derive(Debug, Serialize, Deserialize)]
+enum KvProtocol {
+    #[rpc(tx = oneshot::Sender<Entry>)]
+    Get(Get),
+    #[rpc(tx = mpsc::Sender<Entry>)]
+    Watch(Watch),
+}
+
+fn entry(ttl: Option<u32>) -> Entry {
+    Entry {
+        value: "a".to_string(),
+        ttl,
+    }
+}
+
+#[test]
+fn test_check() {
+    assert!(check(&Get {
+        key: "a".to_string()
+    })
+    .is_ok());
+    assert!(check(&entry(Some(1))).is_ok());
+    let e = check(&entry(None)).unwrap_err();
+    assert!(e.type_name.ends_with("Entry"));
+}
+
+#[tokio::test]
+async fn test_validate() {
+    let (tx, rx) = oneshot::channel();
+    let msg: WithChannels<Get, KvProtocol> = (
+        Get {
+            key: "a".to_string(),
+        },
+        tx,
+    )
+        .into();
+    validate(msg).tx.send(entry(Some(1))).await.unwrap();
+    assert_eq!(rx.await.unwrap(), entry(Some(1)));
+}
+
+#[tokio::test]
+#[should_panic(expected = "does not match its schema")]
+async fn test_validate_oneshot_mismatch() {
+    let (tx, _rx) = oneshot::channel();
+    let msg: WithChannels<Get, KvProtocol> = (
+        Get {
+            key: "a".to_string(),
+        },
+        tx,
+    )
+        .into();
+    validate(msg).tx.send(entry(None)).await.ok();
+}
+
+#[tokio::test]
+#[should_panic(expected = "does not match its schema")]
+async fn test_validate_mpsc_mismatch() {
+    let (tx, _rx) = mpsc::channel(4);
+    let msg: WithChannels<Watch, KvProtocol> = (
+        Watch {
+            key: "a".to_string(),
+        },
+        tx,
+    )
+        .into();
+    let msg = validate(msg);
+    msg.tx.send(entry(Some(1))).await.unwrap();
+    msg.tx.send(entry(None)).await.ok();
+}
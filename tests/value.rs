@@ -0,0 +1,104 @@
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+
+use irpc_schema::{schema, value::Value, HasSchema};
+
+#[schema(Nominal)]
+struct PutRequest {
+    key: String,
+    value: Option<Vec<u8>>,
+    ttl: u32,
+}
+
+#[schema(Nominal)]
+enum Request {
+    Put(PutRequest),
+    Clear,
+}
+
+fn put(ttl: Value) -> Value {
+    Value::Struct(vec![
+        ("key".to_string(), Value::Str("a".to_string())),
+        (
+            "value".to_string(),
+            Value::Optional(Some(Box::new(Value::Seq(vec![Value::UInt(1)])))),
+        ),
+        ("ttl".to_string(), ttl),
+    ])
+}
+
+#[test]
+fn test_conforms_to() {
+    assert!(put(Value::UInt(10)).conforms_to(&PutRequest::schema()));
+    assert!(put(Value::Int(10)).conforms_to(&PutRequest::schema()));
+    let request = Value::Variant {
+        index: 0,
+        name: Some("Put".to_string()),
+        value: Box::new(Value::Tuple(vec![put(Value::UInt(1))])),
+    };
+    assert!(request.conforms_to(&Request::schema()));
+    let clear = Value::Variant {
+        index: 1,
+        name: None,
+        value: Box::new(Value::Unit),
+    };
+    assert!(clear.conforms_to(&Request::schema()));
+    let map = Value::Map(vec![(Value::Str("x".to_string()), Value::Bool(true))]);
+    assert!(map.conforms_to(&<BTreeMap<String, bool>>::schema()));
+    assert!(Value::Bool(true).conforms_to(&irpc_schema::Schema::Atom("Opaque".to_string())));
+}
+
+#[test]
+fn test_conformance_errors() {
+    let err = put(Value::UInt(1 << 40))
+        .check_conforms(&PutRequest::schema())
+        .unwrap_err();
+    assert_eq!(err.path.to_string(), "PutRequest.ttl");
+    let err = put(Value::Int(-1))
+        .check_conforms(&PutRequest::schema())
+        .unwrap_err();
+    assert_eq!(err.path.to_string(), "PutRequest.ttl");
+    let wrong_name = Value::Variant {
+        index: 1,
+        name: Some("Put".to_string()),
+        value: Box::new(Value::Unit),
+    };
+    assert!(!wrong_name.conforms_to(&Request::schema()));
+    assert!(!Value::Unit.conforms_to(&irpc_schema::Schema::Bottom));
+    assert!(!Value::Str("x".to_string()).conforms_to(&u8::schema()));
+}
+
+#[schema(Nominal)]
+enum Mode {
+    Fixed(u32),
+    Auto,
+}
+
+#[test]
+fn test_default_for() {
+    let value = Value::default_for(&PutRequest::schema()).unwrap();
+    assert_eq!(
+        value,
+        Value::Struct(vec![
+            ("key".to_string(), Value::Str(String::new())),
+            ("value".to_string(), Value::Optional(None)),
+            ("ttl".to_string(), Value::UInt(0)),
+        ])
+    );
+    // the first unit-like variant is preferred
+    let value = Value::default_for(&Mode::schema()).unwrap();
+    assert_eq!(
+        value,
+        Value::Variant {
+            index: 1,
+            name: Some("Auto".to_string()),
+            value: Box::new(Value::Unit),
+        }
+    );
+    assert!(value.conforms_to(&Mode::schema()));
+    assert_eq!(Value::default_for(&i64::schema()), Some(Value::Int(0)));
+    assert_eq!(
+        Value::default_for(&irpc_schema::Schema::Atom("Opaque".to_string())),
+        None
+    );
+}
@@ -0,0 +1,86 @@
+#![allow(dead_code)]
+use irpc_schema::{
+    registry::SchemaRegistry,
+    schema, serialize_stable,
+    value::Value,
+    vectors::{test_vectors, verify_test_vectors},
+};
+use serde::{Deserialize, Serialize};
+use testresult::TestResult;
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+struct PutRequest {
+    key: String,
+    ttl: Option<u64>,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, Serialize, Deserialize)]
+enum Command {
+    Clear,
+    Delete(String),
+}
+
+#[serialize_stable]
+#[derive(Debug)]
+enum Proto {
+    Put(PutRequest),
+    Command(Command),
+}
+
+#[test]
+fn test_vectors_per_variant() -> TestResult<()> {
+    let vectors = test_vectors(Proto::schemas());
+    let names = vectors
+        .iter()
+        .map(|v| (v.name.as_str(), v.variant.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("Put", None),
+            ("Command", Some("Clear")),
+            ("Command", Some("Delete")),
+        ]
+    );
+    let put = postcard::to_allocvec(&Proto::Put(PutRequest {
+        key: String::new(),
+        ttl: None,
+    }))?;
+    assert_eq!(vectors[0].bytes, put);
+    assert_eq!(
+        vectors[2].value,
+        Value::Variant {
+            index: 1,
+            name: Some("Delete".to_string()),
+            value: Box::new(Value::Tuple(vec![Value::Str(String::new())])),
+        }
+    );
+    verify_test_vectors::<Proto>(&vectors)?;
+
+    let mut registry = SchemaRegistry::new();
+    registry.register_all(Proto::schemas());
+    assert_eq!(registry.test_vectors().len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_verify_fails_for_other_type() {
+    let mut vectors = test_vectors(Proto::schemas());
+    vectors[0].bytes[0] ^= 1;
+    let err = verify_test_vectors::<Proto>(&vectors).unwrap_err();
+    assert_eq!(err.name, "Put");
+}
+
+irpc_schema::schema_roundtrip_tests!(PutRequest, Command, Proto);
+
+#[test]
+fn test_plain_type_vectors() {
+    use irpc_schema::vectors::HasTestVectors;
+    let vectors = PutRequest::test_vectors();
+    assert_eq!(vectors.len(), 1);
+    assert_eq!(vectors[0].name, "PutRequest");
+    // plain types are encoded without the hash
+    assert_eq!(vectors[0].bytes, [0, 0]);
+}
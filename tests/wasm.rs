@@ -0,0 +1,37 @@
+#![cfg(feature = "wasm")]
+use irpc_schema::{manifest::SchemaManifest, wasm, Schema};
+
+#[test]
+fn test_fingerprint_matches_stable_hash() {
+    let schema = Schema::Seq(Box::new(Schema::Atom("u8".into())));
+    let hex = wasm::fingerprint(&schema.to_string()).unwrap();
+    assert_eq!(hex, schema.stable_hash().to_hex().to_string());
+    assert!(wasm::fingerprint("[\"u8\"")
+        .unwrap_err()
+        .starts_with("invalid schema"));
+}
+
+#[test]
+fn test_pretty_print_respects_width() {
+    let text = "(\"a\":\"u32\",\"b\":[\"u8\"],)";
+    assert_eq!(wasm::pretty_print(text, 0).unwrap().lines().count(), 6);
+    assert_eq!(wasm::pretty_print(text, 60).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_diff_reports_changes() {
+    assert_eq!(wasm::diff("\"u32\"", "\"u32\"").unwrap(), "");
+    assert!(!wasm::diff("\"u32\"", "\"u64\"").unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_manifest_lists_messages() {
+    let mut manifest = SchemaManifest::new("echo", "1.0");
+    let schema = Schema::Atom("String".into());
+    let hash = manifest.push("Echo", schema.clone());
+    let bytes = postcard::to_allocvec(&manifest).unwrap();
+    let text = wasm::parse_manifest(&bytes).unwrap();
+    let hash = blake3::Hash::from(hash).to_hex();
+    assert_eq!(text, format!("echo 1.0\nEcho {} {}\n", hash, schema));
+    assert!(wasm::parse_manifest(&[0xff]).is_err());
+}
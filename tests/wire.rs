@@ -0,0 +1,96 @@
+use irpc_schema::{schema, serialize_nested, wire};
+use serde::{Deserialize, Serialize};
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Put {
+    key: String,
+    value: Vec<u8>,
+}
+
+#[schema(Nominal)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Delete {
+    key: String,
+}
+
+mod full {
+    use irpc_schema::serialize_stable;
+
+    use super::{Delete, Put};
+
+    #[serialize_stable]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Put(Put),
+        Delete(Delete),
+    }
+}
+
+mod short {
+    use irpc_schema::serialize_stable;
+
+    use super::{Delete, Put};
+
+    #[serialize_stable(short)]
+    #[derive(Debug, PartialEq)]
+    pub enum Proto {
+        Put(Put),
+        Delete(Delete),
+    }
+}
+
+#[serialize_nested]
+#[derive(Debug, PartialEq)]
+enum Root {
+    Store(full::Proto),
+}
+
+fn put() -> Put {
+    Put {
+        key: "a".into(),
+        value: vec![1; 300],
+    }
+}
+
+#[test]
+fn test_to_postcard() -> testresult::TestResult<()> {
+    let msg = full::Proto::Put(put());
+    let bytes = msg.to_postcard()?;
+    assert_eq!(bytes, postcard::to_allocvec(&msg)?);
+    assert_eq!(bytes.capacity(), bytes.len());
+
+    let msg = full::Proto::Delete(Delete { key: "a".into() });
+    assert_eq!(msg.to_postcard()?, postcard::to_allocvec(&msg)?);
+
+    let msg = short::Proto::Put(put());
+    assert_eq!(msg.to_postcard()?, postcard::to_allocvec(&msg)?);
+
+    let msg = Root::Store(full::Proto::Put(put()));
+    assert_eq!(msg.to_postcard()?, postcard::to_allocvec(&msg)?);
+
+    assert_eq!(
+        wire::to_vec(&[7; 32], &1u8)?,
+        postcard::to_allocvec(&([7u8; 32], 1u8))?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_from_postcard() -> testresult::TestResult<()> {
+    let msg = full::Proto::Put(put());
+    assert_eq!(full::Proto::from_postcard(&msg.to_postcard()?)?, msg);
+
+    let msg = short::Proto::Delete(Delete { key: "a".into() });
+    assert_eq!(short::Proto::from_postcard(&msg.to_postcard()?)?, msg);
+
+    let msg = Root::Store(full::Proto::Put(put()));
+    assert_eq!(Root::from_postcard(&msg.to_postcard()?)?, msg);
+
+    // unknown hashes are rejected without decoding the payload
+    let bytes = wire::to_vec(&[7; 32], &1u8)?;
+    assert!(full::Proto::from_postcard(&bytes).is_err());
+    assert!(short::Proto::from_postcard(&bytes).is_err());
+    assert!(Root::from_postcard(&bytes).is_err());
+    Ok(())
+}